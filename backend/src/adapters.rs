@@ -3,8 +3,14 @@
 //! permitted** interaction with the external crates. Adapters map resulting errors to a
 //! `DBoResult`, leading to consistency and brevity within the codebase.
 
+pub mod captcha;
+pub mod circuit_breaker;
+pub mod dns;
 pub mod email;
 pub mod hashing;
 pub mod jwt;
+pub mod metrics;
 pub mod mongo;
+pub mod rate_limit;
 pub mod repositories;
+pub mod storage;
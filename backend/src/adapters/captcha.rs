@@ -0,0 +1,152 @@
+//! This module is an adapter over an external CAPTCHA verification service (such as hCaptcha or
+//! reCAPTCHA), used to prevent bots from registering player accounts.
+//!
+//! Verification is only performed when `ENV.captcha_secret` is set. In development environments,
+//! it is usually left unset, and all CAPTCHA checks are skipped.
+
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::{
+    config::environment::ENV,
+    errors::{DBoError, DBoResult},
+};
+
+/// A trait for any service which can verify a CAPTCHA token provided by the client.
+pub trait CaptchaVerifier: Send + Sync {
+    /// Verify a CAPTCHA token with the verification service.
+    ///
+    /// ### Arguments
+    /// - `token`: The token provided by the client after solving the CAPTCHA
+    ///
+    /// ### Returns
+    /// A boolean indicating whether or not the token was accepted
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the verification service cannot be reached
+    async fn verify(&self, token: &str) -> DBoResult<bool>;
+}
+
+/// The relevant part of the response body returned by hCaptcha and reCAPTCHA, which share the same
+/// `siteverify` API shape.
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+/// Verifies CAPTCHA tokens over HTTP, using the `siteverify` API shared by hCaptcha and reCAPTCHA.
+pub struct HttpCaptchaVerifier {
+    /// The HTTP client used to reach the verification service.
+    client: Client,
+    /// The secret key shared with the verification service.
+    secret: String,
+    /// The `siteverify` URL of the verification service.
+    verify_url: String,
+}
+
+impl HttpCaptchaVerifier {
+    /// Construct a new HttpCaptchaVerifier
+    ///
+    /// ### Arguments
+    /// - `secret`: The secret key shared with the verification service
+    /// - `verify_url`: The `siteverify` URL of the verification service
+    pub fn new(secret: &str, verify_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            secret: String::from(secret),
+            verify_url: String::from(verify_url),
+        }
+    }
+}
+
+impl CaptchaVerifier for HttpCaptchaVerifier {
+    async fn verify(&self, token: &str) -> DBoResult<bool> {
+        let response: SiteVerifyResponse = self
+            .client
+            .post(&self.verify_url)
+            .form(&[("secret", self.secret.as_str()), ("response", token)])
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        Ok(response.success)
+    }
+}
+
+/// The CAPTCHA verifier used by the application, or None if CAPTCHA verification is disabled.
+pub static CAPTCHA: Lazy<Option<HttpCaptchaVerifier>> = Lazy::new(|| {
+    ENV.captcha_secret
+        .as_ref()
+        .map(|secret| HttpCaptchaVerifier::new(secret, &ENV.captcha_verify_url))
+});
+
+/// Ensure that a client has solved a CAPTCHA. If no verifier is configured, the check is skipped.
+///
+/// ### Arguments
+/// - `verifier`: The configured CAPTCHA verifier, if any
+/// - `token`: The CAPTCHA token provided by the client, if any
+///
+/// ### Errors
+/// - `CaptchaFailure` if a verifier is configured, and the token is either missing or rejected
+/// - `AdapterError` if the verification service cannot be reached
+pub async fn check_captcha<V: CaptchaVerifier>(
+    verifier: Option<&V>,
+    token: Option<&str>,
+) -> DBoResult<()> {
+    let verifier = match verifier {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    let token = match token {
+        Some(t) => t,
+        None => return Err(DBoError::CaptchaFailure),
+    };
+
+    if verifier.verify(token).await? {
+        Ok(())
+    } else {
+        Err(DBoError::CaptchaFailure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts only the token "solved".
+    struct MockVerifier;
+
+    impl CaptchaVerifier for MockVerifier {
+        async fn verify(&self, token: &str) -> DBoResult<bool> {
+            Ok(token == "solved")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_captcha() {
+        assert!(check_captcha(None::<&MockVerifier>, None).await.is_ok());
+        assert!(
+            check_captcha(None::<&MockVerifier>, Some("x"))
+                .await
+                .is_ok()
+        );
+
+        assert!(
+            check_captcha(Some(&MockVerifier), Some("solved"))
+                .await
+                .is_ok()
+        );
+
+        assert!(matches!(
+            check_captcha(Some(&MockVerifier), Some("wrong")).await,
+            Err(DBoError::CaptchaFailure)
+        ));
+        assert!(matches!(
+            check_captcha(Some(&MockVerifier), None).await,
+            Err(DBoError::CaptchaFailure)
+        ));
+    }
+}
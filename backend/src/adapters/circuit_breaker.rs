@@ -0,0 +1,239 @@
+//! This module provides a circuit breaker, which stops the application from hammering the database
+//! while it is unreachable.
+//!
+//! The breaker begins **closed**, letting every operation through. Once enough operations fail in
+//! a row, it **opens**, and every operation is rejected immediately for a cooldown period. After
+//! the cooldown, it is **half-open**: a single trial operation is let through, and its outcome
+//! decides whether the breaker closes again or re-opens for another cooldown.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// The state of a circuit breaker, as reported by `/health`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum BreakerStatus {
+    /// Operations are let through.
+    #[serde(rename = "closed")]
+    Closed,
+    /// Operations are rejected until the cooldown ends.
+    #[serde(rename = "open")]
+    Open,
+    /// A single trial operation is let through, to test whether the downstream has recovered.
+    #[serde(rename = "half_open")]
+    HalfOpen,
+}
+
+/// The internal state of a circuit breaker.
+enum BreakerState {
+    /// Counts how many operations have failed in a row.
+    Closed { failures: u32 },
+    /// Rejects operations until the provided time.
+    Open { until: Instant },
+    /// A trial operation began at the provided time, and has not yet finished.
+    HalfOpen { since: Instant },
+}
+
+/// Tracks the failures of a downstream service, rejecting operations while it seems unreachable.
+pub struct CircuitBreaker {
+    /// The current state of the breaker.
+    state: Mutex<BreakerState>,
+    /// The number of consecutive failures which open the breaker. When 0, it never opens.
+    threshold: u32,
+    /// How long the breaker stays open before a trial operation is let through.
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    /// Construct a new, closed CircuitBreaker
+    ///
+    /// ### Arguments
+    /// - `threshold`: The number of consecutive failures which open the breaker; 0 disables it
+    /// - `cooldown`: How long the breaker stays open before a trial operation is let through
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+            threshold,
+            cooldown,
+        }
+    }
+
+    /// Determine whether an operation may be attempted. Once the cooldown has ended, the first
+    /// caller becomes the trial operation; if that trial never reports its outcome (for example,
+    /// because its request was abandoned), another trial is let through after a further cooldown.
+    ///
+    /// ### Arguments
+    /// - `now`: The current time
+    ///
+    /// ### Errors
+    /// - How long the caller should wait before trying again, if the operation is rejected
+    pub fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+
+        match *state {
+            BreakerState::Closed { .. } => Ok(()),
+            BreakerState::Open { until } if now < until => Err(until - now),
+            BreakerState::HalfOpen { since } if now < since + self.cooldown => {
+                Err(since + self.cooldown - now)
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                *state = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    /// Record that an operation reached the downstream successfully, closing the breaker.
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::Closed { failures: 0 };
+    }
+
+    /// Record that an operation failed because the downstream could not be reached. The breaker
+    /// opens once the threshold is reached, or immediately if the failure was the trial operation.
+    ///
+    /// ### Arguments
+    /// - `now`: The current time
+    pub fn record_failure(&self, now: Instant) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        *state = match *state {
+            BreakerState::Closed { failures } if failures + 1 < self.threshold => {
+                BreakerState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            BreakerState::Closed { .. } => {
+                eprintln!(
+                    "The database seems to be unreachable; rejecting operations for {:?}.",
+                    self.cooldown
+                );
+                BreakerState::Open {
+                    until: now + self.cooldown,
+                }
+            }
+            BreakerState::Open { until } => BreakerState::Open { until },
+            BreakerState::HalfOpen { .. } => BreakerState::Open {
+                until: now + self.cooldown,
+            },
+        };
+    }
+
+    /// Return how long operations will keep being rejected, if the breaker is open and its cooldown
+    /// has not yet ended, or if a trial operation is still in flight. Unlike `try_acquire`, this
+    /// never changes the state of the breaker.
+    ///
+    /// ### Arguments
+    /// - `now`: The current time
+    pub fn retry_after(&self, now: Instant) -> Option<Duration> {
+        match *self.state.lock().unwrap() {
+            BreakerState::Open { until } if now < until => Some(until - now),
+            BreakerState::HalfOpen { since } if now < since + self.cooldown => {
+                Some(since + self.cooldown - now)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the current state of the breaker. An open breaker whose cooldown has ended is
+    /// reported as half-open, since the next operation will be let through as a trial.
+    ///
+    /// ### Arguments
+    /// - `now`: The current time
+    pub fn status(&self, now: Instant) -> BreakerStatus {
+        match *self.state.lock().unwrap() {
+            BreakerState::Closed { .. } => BreakerStatus::Closed,
+            BreakerState::Open { until } if now < until => BreakerStatus::Open,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => BreakerStatus::HalfOpen,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_breaker_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        let now = Instant::now();
+
+        breaker.record_failure(now);
+        breaker.record_failure(now);
+        assert_eq!(breaker.status(now), BreakerStatus::Closed);
+        assert!(breaker.try_acquire(now).is_ok());
+
+        // A success resets the count of consecutive failures.
+        breaker.record_success();
+        breaker.record_failure(now);
+        breaker.record_failure(now);
+        assert_eq!(breaker.status(now), BreakerStatus::Closed);
+
+        breaker.record_failure(now);
+        assert_eq!(breaker.status(now), BreakerStatus::Open);
+        assert_eq!(breaker.try_acquire(now), Err(COOLDOWN));
+        assert_eq!(
+            breaker.retry_after(now + Duration::from_secs(10)),
+            Some(COOLDOWN - Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_breaker_half_open_trial() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record_failure(start);
+
+        // Once the cooldown ends, only a single trial is let through.
+        let later = start + COOLDOWN;
+        assert_eq!(breaker.status(later), BreakerStatus::HalfOpen);
+        assert_eq!(breaker.retry_after(later), None);
+        assert!(breaker.try_acquire(later).is_ok());
+        assert!(breaker.try_acquire(later).is_err());
+        assert_eq!(breaker.retry_after(later), Some(COOLDOWN));
+
+        // A failed trial re-opens the breaker for another cooldown.
+        breaker.record_failure(later);
+        assert_eq!(breaker.status(later), BreakerStatus::Open);
+        assert_eq!(breaker.try_acquire(later), Err(COOLDOWN));
+
+        // A successful trial closes it.
+        let even_later = later + COOLDOWN;
+        assert!(breaker.try_acquire(even_later).is_ok());
+        breaker.record_success();
+        assert_eq!(breaker.status(even_later), BreakerStatus::Closed);
+        assert!(breaker.try_acquire(even_later).is_ok());
+    }
+
+    #[test]
+    fn test_breaker_abandoned_trial() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record_failure(start);
+        assert!(breaker.try_acquire(start + COOLDOWN).is_ok());
+
+        // The trial never reports back, so another is let through after a further cooldown.
+        assert!(breaker.try_acquire(start + COOLDOWN * 2).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_breaker_never_opens() {
+        let breaker = CircuitBreaker::new(0, COOLDOWN);
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            breaker.record_failure(now);
+        }
+
+        assert_eq!(breaker.status(now), BreakerStatus::Closed);
+        assert!(breaker.try_acquire(now).is_ok());
+    }
+}
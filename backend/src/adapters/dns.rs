@@ -0,0 +1,160 @@
+//! This module is an adapter over a DNS resolver, used to make sure that the domain of a
+//! registering player's email address can actually receive emails.
+//!
+//! Verification is only performed when `ENV.verify_email_mx` is enabled. It is disabled by
+//! default, so that registration does not depend on the network in development and testing.
+
+use std::time::Duration;
+
+use hickory_resolver::TokioResolver;
+use once_cell::sync::Lazy;
+
+use crate::{
+    config::environment::ENV,
+    errors::{DBoError, DBoResult},
+    handlers::responses::PlayerInvalidFieldsResponse,
+    models::player_validation::{ProblemCode, ValidationProblem},
+};
+
+/// The time to wait for a DNS response before treating the lookup as inconclusive.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A trait for any service which can find out whether a domain has MX records.
+pub trait MxLookup: Send + Sync {
+    /// Look up the MX records of a domain.
+    ///
+    /// ### Arguments
+    /// - `domain`: The domain to look up
+    ///
+    /// ### Returns
+    /// - `Some`: A boolean indicating whether or not the domain has MX records
+    /// - `None`: The lookup was inconclusive (i.e. it timed out)
+    async fn has_mx_records(&self, domain: &str) -> Option<bool>;
+}
+
+/// Looks up MX records using the DNS servers configured on the host.
+pub struct DnsMxLookup {
+    /// The resolver used to perform the lookups.
+    resolver: TokioResolver,
+}
+
+impl DnsMxLookup {
+    /// Construct a new DnsMxLookup from the host's DNS configuration
+    ///
+    /// ### Returns
+    /// - `Some`: The constructed lookup
+    /// - `None`: The host's DNS configuration could not be read
+    pub fn new() -> Option<Self> {
+        let mut builder = match TokioResolver::builder_tokio() {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("Could not read the DNS configuration: {}", e);
+                return None;
+            }
+        };
+        builder.options_mut().timeout = LOOKUP_TIMEOUT;
+        builder.options_mut().attempts = 1;
+
+        match builder.build() {
+            Ok(resolver) => Some(Self { resolver }),
+            Err(e) => {
+                eprintln!("Could not build the DNS resolver: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl MxLookup for DnsMxLookup {
+    async fn has_mx_records(&self, domain: &str) -> Option<bool> {
+        // A trailing dot marks the domain as fully qualified, skipping the search domains.
+        match self.resolver.mx_lookup(format!("{}.", domain)).await {
+            Ok(lookup) => Some(!lookup.answers().is_empty()),
+            Err(e) if e.is_no_records_found() => Some(false),
+            Err(e) => {
+                eprintln!("MX lookup for {} was inconclusive: {}", domain, e);
+                None
+            }
+        }
+    }
+}
+
+/// The MX lookup used by the application, or None if MX verification is disabled.
+pub static MX_LOOKUP: Lazy<Option<DnsMxLookup>> = Lazy::new(|| match ENV.verify_email_mx {
+    true => DnsMxLookup::new(),
+    false => None,
+});
+
+/// Ensure that the domain of an email address has MX records. If no lookup is configured, the check
+/// is skipped. An inconclusive lookup is treated as a success, so that a slow DNS server does not
+/// block registration.
+///
+/// ### Arguments
+/// - `lookup`: The configured MX lookup, if any
+/// - `email`: The email address to check, which has already passed syntax validation
+///
+/// ### Errors
+/// - `InvalidPlayerInfo` if a lookup is configured, and the domain has no MX records
+pub async fn check_email_mx<L: MxLookup>(lookup: Option<&L>, email: &str) -> DBoResult<()> {
+    let (lookup, domain) = match (lookup, email.rsplit_once('@')) {
+        (Some(l), Some((_, d))) => (l, d),
+        _ => return Ok(()),
+    };
+
+    match lookup.has_mx_records(domain).await {
+        Some(false) => Err(DBoError::invalid_player_info(
+            PlayerInvalidFieldsResponse::new(
+                None,
+                None,
+                Some(vec![ValidationProblem::new(ProblemCode::EmailDomainNoMx)]),
+                None,
+                None,
+                None,
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finds MX records only for "example.com", and times out for "slow.example".
+    struct MockLookup;
+
+    impl MxLookup for MockLookup {
+        async fn has_mx_records(&self, domain: &str) -> Option<bool> {
+            match domain {
+                "example.com" => Some(true),
+                "slow.example" => None,
+                _ => Some(false),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_email_mx() {
+        assert!(
+            check_email_mx(None::<&MockLookup>, "alice@gmial.com")
+                .await
+                .is_ok()
+        );
+
+        assert!(
+            check_email_mx(Some(&MockLookup), "alice@example.com")
+                .await
+                .is_ok()
+        );
+        assert!(
+            check_email_mx(Some(&MockLookup), "alice@slow.example")
+                .await
+                .is_ok()
+        );
+
+        assert!(matches!(
+            check_email_mx(Some(&MockLookup), "alice@gmial.com").await,
+            Err(DBoError::InvalidPlayerInfo(_))
+        ));
+    }
+}
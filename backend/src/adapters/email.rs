@@ -1,23 +1,51 @@
 //! This module is an adapter over the `lettre` crate, allowing for the sending of various types of
 //! emails necessary within the application.
+//!
+//! Once the background email worker has been spawned, emails are not sent within the request which
+//! triggers them. Each one is stored in the durable **outbox** and placed on a bounded queue
+//! instead, and the worker sends them (retrying failures) without delaying the response. Emails
+//! left pending when the server stops are sent once it starts again.
+//!
+//! Emails are never sent to an address on the **suppression list**: those configured in
+//! `ENV.email_suppression_list`, and those which the mail server has permanently rejected.
 
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bson::DateTime as BsonDateTime;
+use chrono::Duration as ChronoDuration;
 use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
 use chrono_tz::Tz;
 use lettre::{
-    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    address::Envelope,
     message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType},
     transport::smtp::authentication::Credentials,
 };
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use regex::Regex;
+use tokio::{
+    select,
+    sync::{
+        Semaphore,
+        mpsc::{Sender, channel, error::TrySendError},
+    },
+    time::interval,
+};
 
 use crate::{
+    adapters::{metrics::record_email_send, repositories::Repository},
     config::{
         assets::{ASSETS, EmailLocalizationVariants},
-        environment::ENV,
+        environment::{ENV, EmailMode},
     },
     errors::{DBoError, DBoResult},
-    models::submodels::{Gender, LanguagePreference},
+    models::{
+        Identifiable, OutboxEmail, outbox_retry_delay,
+        submodels::{Gender, LanguagePreference},
+    },
 };
 
 /// The mailer used to send all emails from the official D-Bo email address.
@@ -26,9 +54,379 @@ static MAILER: Lazy<AsyncSmtpTransport<Tokio1Executor>> = Lazy::new(|| {
     AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&ENV.smtp_host)
         .unwrap()
         .credentials(credentials)
+        .timeout(Some(Duration::from_secs(ENV.email_send_timeout_secs)))
         .build()
 });
 
+// /////////// //
+// EMAIL QUEUE //
+// /////////// //
+
+/// The durable outbox and the in-memory queue in front of it, which are only set once the
+/// background worker is spawned.
+struct EmailOutbox {
+    /// The repository in which every email is stored before it is attempted.
+    repository: Repository<OutboxEmail>,
+    /// The queue of emails which the worker should attempt right away.
+    queue: Sender<OutboxEmail>,
+}
+
+static EMAIL_OUTBOX: OnceCell<EmailOutbox> = OnceCell::new();
+
+/// The maximum number of due emails attempted by each sweep of the outbox.
+const SWEEP_LIMIT: i64 = 50;
+
+/// Spawn the background worker which sends every queued email. Each email is attempted as soon as
+/// it is queued; the outbox is also swept every `ENV.email_outbox_sweep_secs` seconds, for emails
+/// which are due to be retried, or which were left pending when the server last stopped. Up to
+/// `ENV.email_concurrency` emails are attempted at once, each in its own task, so that a slow mail
+/// server does not hold up the rest of the queue.
+///
+/// ### Arguments
+/// - `repository`: The email outbox repository
+///
+/// ### Panics
+/// If the worker has already been spawned.
+pub fn spawn_email_worker(repository: Repository<OutboxEmail>) {
+    let (queue, mut receiver) = channel::<OutboxEmail>(ENV.email_queue_capacity);
+
+    let outbox = EmailOutbox {
+        repository: repository.clone(),
+        queue,
+    };
+    if EMAIL_OUTBOX.set(outbox).is_err() {
+        panic!("The email worker has already been spawned!");
+    }
+
+    tokio::spawn(async move {
+        let permits = Arc::new(Semaphore::new(ENV.email_concurrency));
+        let mut sweep = interval(Duration::from_secs(ENV.email_outbox_sweep_secs));
+
+        loop {
+            select! {
+                Some(email) = receiver.recv() => spawn_attempt(&repository, &permits, email).await,
+                _ = sweep.tick() => sweep_outbox(&repository, &permits).await,
+            }
+        }
+    });
+}
+
+/// Wait until fewer than `ENV.email_concurrency` emails are being attempted, then attempt an email
+/// in its own task.
+///
+/// ### Arguments
+/// - `repository`: The email outbox repository
+/// - `permits`: The semaphore limiting the number of concurrent attempts
+/// - `email`: The email to attempt
+async fn spawn_attempt(
+    repository: &Repository<OutboxEmail>,
+    permits: &Arc<Semaphore>,
+    email: OutboxEmail,
+) {
+    let permit = permits
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("The email semaphore is never closed!");
+    let repository = repository.clone();
+
+    tokio::spawn(async move {
+        attempt(&repository, &email).await;
+        drop(permit);
+    });
+}
+
+/// Attempt every email in the outbox which is due.
+///
+/// ### Arguments
+/// - `repository`: The email outbox repository
+/// - `permits`: The semaphore limiting the number of concurrent attempts
+async fn sweep_outbox(repository: &Repository<OutboxEmail>, permits: &Arc<Semaphore>) {
+    match repository.find_due(&BsonDateTime::now(), SWEEP_LIMIT).await {
+        Ok(emails) => {
+            for email in emails {
+                spawn_attempt(repository, permits, email).await;
+            }
+        }
+        Err(e) => {
+            eprintln!("The email outbox could not be swept!");
+            eprintln!("{:?}", e);
+        }
+    }
+}
+
+/// Claim an email and attempt to send it once, recording the outcome in the outbox. Emails which
+/// cannot be claimed (because they were already sent, or are being attempted elsewhere) are
+/// skipped, and emails to a suppressed address are marked as suppressed without being sent.
+///
+/// ### Arguments
+/// - `repository`: The email outbox repository
+/// - `email`: The email to attempt
+async fn attempt(repository: &Repository<OutboxEmail>, email: &OutboxEmail) {
+    let now = Utc::now();
+    // The claim outlasts the SMTP timeout, so that it cannot lapse while the email is being sent.
+    let claimed_until = BsonDateTime::from_chrono(
+        now + ChronoDuration::seconds(2 * ENV.email_send_timeout_secs as i64),
+    );
+
+    let email = match repository
+        .claim(email.id(), &BsonDateTime::from_chrono(now), &claimed_until)
+        .await
+    {
+        Ok(Some(e)) => e,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("A queued email could not be claimed!");
+            eprintln!("{:?}", e);
+            return;
+        }
+    };
+
+    let suppressed = match on_suppression_list(email.to(), &ENV.email_suppression_list) {
+        true => Ok(true),
+        false => repository.is_suppressed(email.to()).await,
+    };
+
+    let outcome = match suppressed {
+        Ok(true) => {
+            println!("An email to a suppressed address has been skipped.");
+            repository.mark_suppressed(email.id()).await
+        }
+        Ok(false) => match transmit(&email).await {
+            Ok(()) => repository.mark_sent(email.id(), &BsonDateTime::now()).await,
+            Err(failure) => record_failure(repository, &email, &failure).await,
+        },
+        // The claim lapses, so the email is attempted again by a later sweep.
+        Err(_) => {
+            eprintln!("The suppression list could not be checked; an email has been postponed.");
+            return;
+        }
+    };
+
+    if let Err(e) = outcome {
+        eprintln!("The outcome of an email could not be recorded in the outbox!");
+        eprintln!("{:?}", e);
+    }
+}
+
+/// Record a failed attempt to send an email in the outbox.
+///
+/// ### Arguments
+/// - `repository`: The email outbox repository
+/// - `email`: The email which failed to send
+/// - `failure`: The reason it failed
+///
+/// ### Errors
+/// - `AdapterError` if the query fails
+async fn record_failure(
+    repository: &Repository<OutboxEmail>,
+    email: &OutboxEmail,
+    failure: &TransmitFailure,
+) -> DBoResult<()> {
+    let description = failure.description();
+    let base = ChronoDuration::milliseconds(ENV.email_retry_base_delay_ms as i64);
+
+    match failure_outcome(failure, email.attempts() + 1, ENV.email_retries, base) {
+        FailureOutcome::Retry(delay) => {
+            let retry_at = BsonDateTime::from_chrono(Utc::now() + delay);
+            repository
+                .record_failure(email.id(), description, Some(&retry_at))
+                .await
+        }
+        FailureOutcome::Fail => {
+            eprintln!("An email has failed to send too many times, and will not be retried!");
+            repository
+                .record_failure(email.id(), description, None)
+                .await
+        }
+        FailureOutcome::Reject => {
+            eprintln!("An email was permanently rejected; its recipients are now suppressed!");
+            repository.record_rejection(email.id(), description).await
+        }
+    }
+}
+
+/// What becomes of an email after a failed attempt to send it.
+#[derive(Debug, PartialEq)]
+enum FailureOutcome {
+    /// The email is attempted again after the given delay.
+    Retry(ChronoDuration),
+    /// The email has run out of retries, and is marked as failed.
+    Fail,
+    /// The email was permanently rejected, and is not attempted again.
+    Reject,
+}
+
+/// Decide what becomes of an email after a failed attempt to send it. Temporary failures are
+/// retried with an exponential backoff until the retries run out; permanent rejections are never
+/// retried.
+///
+/// ### Arguments
+/// - `failure`: The reason the attempt failed
+/// - `failures`: The number of failed attempts so far, including this one
+/// - `retries`: The number of times an email may be retried
+/// - `base`: The delay before the first retry
+fn failure_outcome(
+    failure: &TransmitFailure,
+    failures: u32,
+    retries: u32,
+    base: ChronoDuration,
+) -> FailureOutcome {
+    match failure {
+        TransmitFailure::Permanent(_) => FailureOutcome::Reject,
+        TransmitFailure::Transient(_) if failures <= retries => {
+            FailureOutcome::Retry(outbox_retry_delay(base, failures))
+        }
+        TransmitFailure::Transient(_) => FailureOutcome::Fail,
+    }
+}
+
+/// Determine whether any of the recipients is on a configured suppression list of lowercase
+/// addresses.
+///
+/// ### Arguments
+/// - `to`: The recipients of an email
+/// - `list`: The suppressed addresses
+fn on_suppression_list(to: &[String], list: &[String]) -> bool {
+    to.iter()
+        .any(|address| list.contains(&address.to_lowercase()))
+}
+
+/// Convert a message into an email which can be stored in the outbox.
+///
+/// ### Arguments
+/// - `message`: The message to convert
+fn outbox_email(message: &Message) -> OutboxEmail {
+    let envelope = message.envelope();
+
+    OutboxEmail::new(
+        envelope.from().map(|a| a.to_string()),
+        envelope.to().iter().map(|a| a.to_string()).collect(),
+        message.headers().get_raw("Subject").unwrap_or_default(),
+        String::from_utf8_lossy(&message.formatted()).into_owned(),
+    )
+}
+
+/// Deliver a message. If the email worker is running, the message is stored in the outbox and
+/// queued to be sent in the background; otherwise, it is sent immediately.
+///
+/// If the queue is full, the message is left in the outbox, and is sent by the next sweep instead.
+///
+/// ### Arguments
+/// - `message`: The message to deliver
+///
+/// ### Errors
+/// - `AdapterError` if the message cannot be stored in the outbox, or cannot be sent immediately
+async fn deliver(message: Message) -> DBoResult<()> {
+    let email = outbox_email(&message);
+
+    let outbox = match EMAIL_OUTBOX.get() {
+        Some(o) => o,
+        None if on_suppression_list(email.to(), &ENV.email_suppression_list) => {
+            println!("An email to a suppressed address has been skipped.");
+            return Ok(());
+        }
+        None => {
+            return transmit(&email).await.map_err(|failure| {
+                eprintln!("An email could not be sent!");
+                eprintln!("{}", failure.description());
+                DBoError::AdapterError
+            });
+        }
+    };
+
+    outbox.repository.insert(&email).await?;
+
+    match outbox.queue.try_send(email) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            eprintln!("The email queue is full; an email will be sent by the next outbox sweep.");
+        }
+        Err(TrySendError::Closed(_)) => {
+            eprintln!("The email worker has stopped; an email has been left in the outbox!");
+        }
+    }
+
+    Ok(())
+}
+
+/// The reason an email could not be transmitted.
+enum TransmitFailure {
+    /// The failure may be temporary, so the email may be retried.
+    Transient(String),
+    /// The email can never be sent, either because its envelope is invalid or because the mail
+    /// server rejected it permanently.
+    Permanent(String),
+}
+
+impl TransmitFailure {
+    /// A description of the failure, as reported by the mail server.
+    fn description(&self) -> &str {
+        match self {
+            Self::Transient(description) | Self::Permanent(description) => description,
+        }
+    }
+}
+
+/// Transmit an email according to the configured email mode. When emails are stubbed, the email
+/// is only logged, rather than sent. The duration of each SMTP attempt is recorded.
+///
+/// ### Arguments
+/// - `email`: The email to transmit
+///
+/// ### Errors
+/// - `Permanent` if the email's envelope is invalid, or the SMTP server rejects it permanently
+/// - `Transient` if the email cannot be sent over SMTP for any other reason
+async fn transmit(email: &OutboxEmail) -> Result<(), TransmitFailure> {
+    match ENV.email_mode {
+        EmailMode::Smtp => {
+            let envelope =
+                envelope(email).map_err(|e| TransmitFailure::Permanent(format!("{:?}", e)))?;
+            let start = Instant::now();
+            let result = MAILER.send_raw(&envelope, email.raw().as_bytes()).await;
+            record_email_send(start.elapsed(), result.is_ok());
+
+            if let Err(e) = result {
+                let description = format!("{:?}", e);
+                return Err(match e.is_permanent() {
+                    true => TransmitFailure::Permanent(description),
+                    false => TransmitFailure::Transient(description),
+                });
+            }
+        }
+        EmailMode::Stub => {
+            println!("Stubbed email to {:?}: {}", email.to(), email.subject());
+        }
+    }
+
+    Ok(())
+}
+
+/// Rebuild the envelope of an email stored in the outbox.
+///
+/// ### Arguments
+/// - `email`: The stored email
+///
+/// ### Errors
+/// - `InvalidEmailAddress` if any of its addresses cannot be parsed
+/// - `AdapterError` if the envelope has no recipients
+fn envelope(email: &OutboxEmail) -> DBoResult<Envelope> {
+    let parse = |address: &String| {
+        address
+            .parse::<Address>()
+            .map_err(|_| DBoError::InvalidEmailAddress)
+    };
+
+    let from = email.from().as_ref().map(parse).transpose()?;
+    let to = email
+        .to()
+        .iter()
+        .map(parse)
+        .collect::<DBoResult<Vec<_>>>()?;
+
+    Ok(Envelope::new(from, to)?)
+}
+
 /// The "from" address for messages.
 static MAILBOX: Lazy<Mailbox> = Lazy::new(|| "d-bo@bigdevdog.com".parse().unwrap());
 
@@ -43,7 +441,7 @@ struct PlaceholderHelper {
 impl PlaceholderHelper {
     pub fn new(placeholder: &'static str, value: &str) -> Self {
         Self {
-            placeholder: placeholder,
+            placeholder,
             value: String::from(value),
         }
     }
@@ -79,6 +477,10 @@ impl PlaceholderHelper {
     pub fn frontend_url() -> Self {
         Self::new("{{FRONTEND_URL}}", &ENV.frontend_url)
     }
+
+    pub fn support_url() -> Self {
+        Self::new("{{SUPPORT_URL}}", &ENV.support_url)
+    }
 }
 
 /// Replace all value placeholders in a template with their proper values. Make sure to include all
@@ -121,14 +523,39 @@ fn fill_gendered_template(template: &str, gender: &Gender) -> String {
         .to_string()
 }
 
+/// Format a UTC time for display in an email, in the player's language and local time. If the
+/// stored time zone cannot be parsed, a warning is logged and UTC is used instead, so that a bad
+/// stored value never prevents an email (such as a lockout notice) from being sent. Time zones are
+/// validated by `validate_time_zone` before they are stored, so only older values can be bad.
+///
+/// ### Arguments
+/// - `utc_time`: The time to format
+/// - `language`: The language to format the time in
+/// - `time_zone_str`: The IANA name of the player's time zone
 pub fn format_date_time(
     utc_time: &DateTime<Utc>,
     language: &LanguagePreference,
     time_zone_str: &str,
-) -> DBoResult<String> {
-    // TODO: Finish this function, and test it with multiple values, in English and Spanish.
-    let tz: Tz = time_zone_str.parse()?;
+) -> String {
+    let tz = time_zone_str.parse::<Tz>().unwrap_or_else(|_| {
+        eprintln!(
+            "The time zone {:?} could not be parsed; formatting the time in UTC instead.",
+            time_zone_str
+        );
+        Tz::UTC
+    });
 
+    format_date_time_in(utc_time, language, tz)
+}
+
+/// Format a UTC time in the provided language and time zone.
+///
+/// ### Arguments
+/// - `utc_time`: The time to format
+/// - `language`: The language to format the time in
+/// - `tz`: The time zone to convert the time to
+fn format_date_time_in(utc_time: &DateTime<Utc>, language: &LanguagePreference, tz: Tz) -> String {
+    // TODO: Finish this function, and test it with multiple values, in English and Spanish.
     let local = utc_time.with_timezone(&tz);
 
     let weekday = match (language, local.weekday()) {
@@ -159,7 +586,7 @@ pub fn format_date_time(
 
     let formatted_date_time = local.format(formatter).to_string();
 
-    Ok(format!("{}, {}", weekday, formatted_date_time))
+    format!("{}, {}", weekday, formatted_date_time)
 }
 
 /// Build a branded message from an email template. This function takes all information that may be
@@ -169,7 +596,8 @@ pub fn format_date_time(
 /// being the plaintext message, and the other part containing the HTML message, alongside both the
 /// D-Bo logo and the BigDevDog logo.
 ///
-/// The function will automatically add the PlaceholderHelper for the CIDs within the HTML template.
+/// The function will automatically add the PlaceholderHelpers for the CIDs within the HTML
+/// template, as well as the `{{SUPPORT_URL}}` placeholder, which is available to every template.
 /// **Do not** include these within the `helpers` argument, as it will just slow the function down.
 ///
 /// ### Arguments
@@ -177,7 +605,8 @@ pub fn format_date_time(
 /// - `templates`: The type of email to be sent.
 /// - `language`: The language that the email will be sent in.
 /// - `helpers`: The value placeholders that should be replaced in the templates. Again, this should
-///   **not** include the placeholders for the D-Bo logo and BigDevDog logo CIDs.
+///   **not** include the placeholders for the D-Bo logo and BigDevDog logo CIDs, or the support
+///   URL.
 /// - `gender`: The gender of the player receiving this message. This is **always** ignored for
 ///   messages sent in English. If the value is None for Spanish messages, the gendered placeholders
 ///   will **not** be replaced. This is preferred for messages not including gendered placeholders,
@@ -193,6 +622,8 @@ fn build_branded_message(
     helpers: &mut Vec<PlaceholderHelper>,
     gender: &Option<Gender>,
 ) -> DBoResult<Message> {
+    helpers.push(PlaceholderHelper::support_url());
+
     let message_info = templates.language(language);
     let txt = match language {
         LanguagePreference::English => replace_placeholders(&message_info.txt, helpers),
@@ -285,7 +716,7 @@ fn build_branded_message(
 ///
 /// ### Errors
 /// - `InvalidEmailAddress` if the **player_email** argument cannot be parsed into a Mailbox.
-/// - `AdapterError` if the email cannot be constructed or sent.
+/// - `AdapterError` if the email cannot be constructed or queued.
 pub async fn send_registration_email(
     player_email: &str,
     username: &str,
@@ -309,9 +740,7 @@ pub async fn send_registration_email(
         &Some(pronoun.clone()),
     )?;
 
-    MAILER.send(message).await?;
-
-    Ok(())
+    deliver(message).await
 }
 
 /// Send a lockout email, informing a player that their account has been locked from logging in due
@@ -322,11 +751,12 @@ pub async fn send_registration_email(
 /// - `username`: The player's username
 /// - `failed_logins`: The number of failed logins resulting in this lockout
 /// - `end_lockout`: The time at which their lockout will be over
+/// - `time_zone_str`: The IANA name of the player's time zone; UTC is used if it cannot be parsed
 /// - `language`: The language to send the email in
 ///
 /// ### Errors
 /// - `InvalidEmailAddress` if the player_email cannot be parsed into a Mailbox.
-/// - `AdapterError` if the message cannot be constructed or sent.
+/// - `AdapterError` if the message cannot be constructed or queued.
 pub async fn send_lockout_email(
     player_email: &str,
     username: &str,
@@ -340,7 +770,7 @@ pub async fn send_lockout_email(
         PlaceholderHelper::new("{{FAILED_LOGINS}}", &format!("{}", failed_logins)),
         PlaceholderHelper::new(
             "{{END_LOCKOUT}}",
-            &format_date_time(end_lockout, language, time_zone_str)?,
+            &format_date_time(end_lockout, language, time_zone_str),
         ),
     ];
 
@@ -352,9 +782,7 @@ pub async fn send_lockout_email(
         &None,
     )?;
 
-    MAILER.send(message).await?;
-
-    Ok(())
+    deliver(message).await
 }
 
 /// Send an email to the player's newly proposed email address, providing them with a link to
@@ -373,7 +801,8 @@ pub async fn send_lockout_email(
 ///
 /// ### Errors
 /// - `InvalidEmailAddress` if the new email cannot be parsed into a mailbox
-/// - `AdapterError` if the email cannot be constructed or sent due to a server-side error
+/// - `AdapterError` if the email cannot be constructed or queued due to a server-side error
+#[allow(clippy::too_many_arguments)]
 pub async fn send_change_email_confirmation_email(
     username: &str,
     old_email: &str,
@@ -402,9 +831,7 @@ pub async fn send_change_email_confirmation_email(
         &Some(pronoun.clone()),
     )?;
 
-    MAILER.send(message).await?;
-
-    Ok(())
+    deliver(message).await
 }
 
 /// Send a warning email to a player's current confirmed email address, informing them that a
@@ -421,7 +848,7 @@ pub async fn send_change_email_confirmation_email(
 ///
 /// ### Errors
 /// - `InvalidEmailAddress` if the old email cannot be parsed into a Mailbox
-/// - `AdapterError` if the message cannot be constructed or sent due to a server-side error
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
 pub async fn send_change_email_warning_email(
     username: &str,
     old_email: &str,
@@ -447,9 +874,7 @@ pub async fn send_change_email_warning_email(
         &None,
     )?;
 
-    MAILER.send(message).await?;
-
-    Ok(())
+    deliver(message).await
 }
 
 /// Send an email to the player informing them that their password has been changed. This email
@@ -465,7 +890,7 @@ pub async fn send_change_email_warning_email(
 ///
 /// ### Errors
 /// - `InvalidEmailAddress` if the player's email address cannot be parsed into a Mailbox
-/// - `AdapterError` if the message cannot be constructed or sent due to a server-side error
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
 pub async fn send_change_password_email(
     player_email: &str,
     username: &str,
@@ -489,9 +914,7 @@ pub async fn send_change_password_email(
         &Some(pronoun.clone()),
     )?;
 
-    MAILER.send(message).await?;
-
-    Ok(())
+    deliver(message).await
 }
 
 /// Send an email to a player informing them that their username has been changed.
@@ -505,7 +928,7 @@ pub async fn send_change_password_email(
 ///
 /// ### Errors
 /// - `InvalidEmailAddress` if the player email cannot be parsed into a Mailbox
-/// - `AdapterError` if the message cannot be constructed or sent due to a server-side error
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
 pub async fn send_change_username_email(
     player_email: &str,
     old_username: &str,
@@ -526,7 +949,207 @@ pub async fn send_change_username_email(
         &Some(pronoun.clone()),
     )?;
 
-    MAILER.send(message).await?;
+    deliver(message).await
+}
 
-    Ok(())
+/// Send an email to a player informing them that their account has been scheduled for deletion.
+/// This email provides them with a link to cancel the deletion during the grace period.
+///
+/// ### Arguments
+/// - `player_email`: The player's email address
+/// - `username`: The player's username
+/// - `player_id`: The player's unique identifier
+/// - `undo_token_id`: The undo token's unique identifier
+/// - `language`: The language to send the email in
+///
+/// ### Errors
+/// - `InvalidEmailAddress` if the player's email address cannot be parsed into a Mailbox
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
+pub async fn send_account_deletion_email(
+    player_email: &str,
+    username: &str,
+    player_id: &str,
+    undo_token_id: &str,
+    language: &LanguagePreference,
+) -> DBoResult<()> {
+    let mut helpers = vec![
+        PlaceholderHelper::username(username),
+        PlaceholderHelper::frontend_url(),
+        PlaceholderHelper::player_id(player_id),
+        PlaceholderHelper::undo_token_id(undo_token_id),
+        PlaceholderHelper::new("{{GRACE_DAYS}}", &ENV.deletion_grace_days.to_string()),
+    ];
+
+    let message = build_branded_message(
+        player_email,
+        &ASSETS.templates.account_deletion,
+        language,
+        &mut helpers,
+        &None,
+    )?;
+
+    deliver(message).await
+}
+
+/// Send an email to a player who has forgotten their password, providing them with a link to
+/// choose a new one.
+///
+/// ### Arguments
+/// - `player_email`: The email address to send the message to
+/// - `username`: The player's username
+/// - `player_id`: The player's unique identifier
+/// - `undo_token_id`: The password reset token's unique identifier
+/// - `language`: The language to send the email in
+///
+/// ### Errors
+/// - `InvalidEmailAddress` if the player email cannot be parsed into a Mailbox
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
+pub async fn send_password_reset_email(
+    player_email: &str,
+    username: &str,
+    player_id: &str,
+    undo_token_id: &str,
+    language: &LanguagePreference,
+) -> DBoResult<()> {
+    let mut helpers = vec![
+        PlaceholderHelper::username(username),
+        PlaceholderHelper::frontend_url(),
+        PlaceholderHelper::player_id(player_id),
+        PlaceholderHelper::undo_token_id(undo_token_id),
+    ];
+
+    let message = build_branded_message(
+        player_email,
+        &ASSETS.templates.password_reset,
+        language,
+        &mut helpers,
+        &None,
+    )?;
+
+    deliver(message).await
+}
+
+/// Send an email to a player reminding them of their username.
+///
+/// ### Arguments
+/// - `player_email`: The email address to send the message to
+/// - `username`: The player's username
+/// - `language`: The language to send the email in
+///
+/// ### Errors
+/// - `InvalidEmailAddress` if the player email cannot be parsed into a Mailbox
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
+pub async fn send_username_reminder_email(
+    player_email: &str,
+    username: &str,
+    language: &LanguagePreference,
+) -> DBoResult<()> {
+    let mut helpers = vec![
+        PlaceholderHelper::username(username),
+        PlaceholderHelper::frontend_url(),
+    ];
+
+    let message = build_branded_message(
+        player_email,
+        &ASSETS.templates.username_reminder,
+        language,
+        &mut helpers,
+        &None,
+    )?;
+
+    deliver(message).await
+}
+
+/// Send a welcome email to a player whose account has just been confirmed, introducing them to
+/// getting started with the application.
+///
+/// ### Arguments
+/// - `player_email`: The email address to send the message to
+/// - `username`: The player's username
+/// - `language`: The language to send the email in
+/// - `pronoun`: Specifies gender-specific language in the Spanish version of the email
+///
+/// ### Errors
+/// - `InvalidEmailAddress` if the player email cannot be parsed into a Mailbox
+/// - `AdapterError` if the message cannot be constructed or queued due to a server-side error
+pub async fn send_welcome_email(
+    player_email: &str,
+    username: &str,
+    language: &LanguagePreference,
+    pronoun: &Gender,
+) -> DBoResult<()> {
+    let mut helpers = vec![
+        PlaceholderHelper::username(username),
+        PlaceholderHelper::frontend_url(),
+    ];
+
+    let message = build_branded_message(
+        player_email,
+        &ASSETS.templates.welcome,
+        language,
+        &mut helpers,
+        &Some(pronoun.clone()),
+    )?;
+
+    deliver(message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_format_date_time_falls_back_to_utc() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 5, 13, 4, 5).unwrap();
+        let english = LanguagePreference::English;
+
+        assert_eq!(
+            format_date_time(&time, &english, "Not/A_Zone"),
+            "Sunday, 01/05/2025 at 01:04:05 pm"
+        );
+        assert_eq!(
+            format_date_time(&time, &english, "Not/A_Zone"),
+            format_date_time(&time, &english, "UTC")
+        );
+        assert_eq!(
+            format_date_time(&time, &english, "America/Los_Angeles"),
+            "Sunday, 01/05/2025 at 05:04:05 am"
+        );
+    }
+
+    #[test]
+    fn test_failure_outcome() {
+        let base = ChronoDuration::seconds(30);
+        let transient = TransmitFailure::Transient(String::from("timed out"));
+        let permanent = TransmitFailure::Permanent(String::from("550 no such user"));
+
+        assert_eq!(
+            failure_outcome(&transient, 1, 3, base),
+            FailureOutcome::Retry(base)
+        );
+        assert_eq!(
+            failure_outcome(&transient, 3, 3, base),
+            FailureOutcome::Retry(ChronoDuration::seconds(120))
+        );
+        assert_eq!(
+            failure_outcome(&transient, 4, 3, base),
+            FailureOutcome::Fail
+        );
+        assert_eq!(
+            failure_outcome(&permanent, 1, 3, base),
+            FailureOutcome::Reject
+        );
+    }
+
+    #[test]
+    fn test_on_suppression_list() {
+        let list = vec![String::from("bounced@example.com")];
+        let to = |address: &str| vec![String::from("player@example.com"), String::from(address)];
+
+        assert!(on_suppression_list(&to("Bounced@Example.com"), &list));
+        assert!(!on_suppression_list(&to("other@example.com"), &list));
+        assert!(!on_suppression_list(&to("bounced@example.com"), &[]));
+    }
 }
@@ -1,7 +1,14 @@
 //! This module is an adapter over the `argon2` crate, handling secret hashing and verification.
+//!
+//! When `ENV.password_pepper` is set, it is used as the Argon2 secret key for every new hash, and
+//! the hash is marked as peppered by a key id in its parameters. Unmarked hashes, created before
+//! the pepper was introduced, are still verified without it, so that a pepper can be rolled out
+//! without invalidating existing passwords; `needs_rehash` identifies them, so that they can be
+//! replaced.
 
 use argon2::{
-    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
     password_hash::{
         SaltString,
         rand_core::{OsRng, RngCore},
@@ -9,7 +16,54 @@ use argon2::{
 };
 use base64::{Engine, engine::general_purpose};
 
-use crate::errors::DBoError;
+use crate::{config::environment::ENV, errors::DBoError};
+
+/// The key id recorded in the parameters of every peppered hash, marking it as such.
+const PEPPER_KEY_ID: &[u8] = b"pepper";
+
+/// Return the Argon2 context used to hash and verify secrets. When a pepper is given, new hashes
+/// record `PEPPER_KEY_ID` as their key id.
+///
+/// ### Arguments
+/// - `pepper`: The server-side secret key mixed into the hash, if any
+///
+/// ### Errors
+/// - `AdapterError` if the pepper is too long to be used as a secret key
+fn argon2(pepper: Option<&[u8]>) -> Result<Argon2<'_>, DBoError> {
+    let p = match pepper {
+        Some(p) => p,
+        None => return Ok(Argon2::default()),
+    };
+
+    KeyId::new(PEPPER_KEY_ID)
+        .and_then(|keyid| ParamsBuilder::new().keyid(keyid).build())
+        .and_then(|params| {
+            Argon2::new_with_secret(p, Algorithm::default(), Version::default(), params)
+        })
+        .map_err(|e| {
+            eprintln!("The password pepper cannot be used as an Argon2 secret!");
+            eprintln!("{:?}", e);
+            DBoError::AdapterError
+        })
+}
+
+/// Determine whether a hash is marked as peppered.
+///
+/// ### Arguments
+/// - `hash`: The parsed hash
+fn is_peppered(hash: &PasswordHash) -> bool {
+    Params::try_from(hash).is_ok_and(|params| params.keyid() == PEPPER_KEY_ID)
+}
+
+/// Return the configured pepper, if any.
+fn pepper() -> Option<&'static [u8]> {
+    // Unit tests run without an environment; those testing the pepper pass it explicitly.
+    if cfg!(test) {
+        return None;
+    }
+
+    ENV.password_pepper.as_deref().map(str::as_bytes)
+}
 
 /// Hash a user provided secret to securely store it in the database.
 ///
@@ -22,9 +76,18 @@ use crate::errors::DBoError;
 /// ### Errors
 /// - `AdapterError` indicating that the provided secret cannot be hashed.
 pub fn hash_secret(secret: &str) -> Result<String, DBoError> {
+    hash_secret_with(secret, pepper())
+}
+
+/// Hash a secret, using a specific pepper.
+///
+/// ### Arguments
+/// - `secret`: The raw text secret to be hashed
+/// - `pepper`: The server-side secret key mixed into the hash, if any
+fn hash_secret_with(secret: &str, pepper: Option<&[u8]>) -> Result<String, DBoError> {
     let salt = SaltString::generate(&mut OsRng);
 
-    Ok(Argon2::default()
+    Ok(argon2(pepper)?
         .hash_password(secret.as_bytes(), &salt)?
         .to_string())
 }
@@ -42,19 +105,138 @@ pub fn hash_secret(secret: &str) -> Result<String, DBoError> {
 /// - `AdapterError` indicating that the provided hash could not be parsed. This could
 ///   indicate a fatal error within our database!
 pub fn verify_secret(secret: &str, hash: &str) -> Result<bool, DBoError> {
+    verify_secret_with(secret, hash, pepper())
+}
+
+/// Verify a secret against a hash, using a specific pepper. A hash marked as peppered is only
+/// checked with the pepper. An unmarked hash is checked without it, as it may predate the pepper,
+/// and then with it, as hashes were once peppered without being marked.
+///
+/// ### Arguments
+/// - `secret`: The user provided, raw-text secret
+/// - `hash`: The secure hash from the database
+/// - `pepper`: The server-side secret key mixed into new hashes, if any
+fn verify_secret_with(secret: &str, hash: &str, pepper: Option<&[u8]>) -> Result<bool, DBoError> {
     let parsed_hash = PasswordHash::new(hash)?;
+    let verify = |pepper| -> Result<bool, DBoError> {
+        Ok(argon2(pepper)?
+            .verify_password(secret.as_bytes(), &parsed_hash)
+            .is_ok())
+    };
+
+    if is_peppered(&parsed_hash) {
+        return verify(pepper);
+    }
+
+    Ok(verify(None)? || (pepper.is_some() && verify(pepper)?))
+}
 
-    Ok(
-        match Argon2::default().verify_password(secret.as_bytes(), &parsed_hash) {
-            Ok(()) => true,
-            Err(_) => false,
-        },
-    )
+/// Determine whether a stored hash should be replaced with a new one, because a pepper is
+/// configured but the hash is not marked as peppered.
+///
+/// ### Arguments
+/// - `hash`: The secure hash from the database
+pub fn needs_rehash(hash: &str) -> bool {
+    needs_rehash_with(hash, pepper())
 }
 
-/// Generate a random secret string.
+/// Determine whether a stored hash should be replaced, using a specific pepper.
+///
+/// ### Arguments
+/// - `hash`: The secure hash from the database
+/// - `pepper`: The server-side secret key mixed into new hashes, if any
+fn needs_rehash_with(hash: &str, pepper: Option<&[u8]>) -> bool {
+    pepper.is_some() && PasswordHash::new(hash).is_ok_and(|h| !is_peppered(&h))
+}
+
+/// Generate a random secret string, containing `ENV.refresh_secret_bytes` bytes drawn from the
+/// operating system's CSPRNG. The secret is URL-safe base64 without padding, so it never contains
+/// the `:` separating a refresh token's id from its secret.
 pub fn generate_secret() -> String {
-    let mut bytes = [0u8; 32];
-    OsRng.fill_bytes(&mut bytes);
-    general_purpose::URL_SAFE_NO_PAD.encode(&bytes)
+    // Unit tests run without an environment; those testing the length pass it explicitly.
+    let bytes = if cfg!(test) {
+        32
+    } else {
+        ENV.refresh_secret_bytes
+    };
+
+    generate_secret_with(bytes)
+}
+
+/// Generate a random secret string of a specific length.
+///
+/// ### Arguments
+/// - `bytes`: The number of random bytes in the secret, before encoding
+fn generate_secret_with(bytes: usize) -> String {
+    let mut secret = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut secret);
+    general_purpose::URL_SAFE_NO_PAD.encode(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_without_pepper() {
+        let hash = hash_secret_with("thr0wTH3b4ll!", None).unwrap();
+
+        assert!(verify_secret_with("thr0wTH3b4ll!", &hash, None).unwrap());
+        assert!(!verify_secret_with("wrong", &hash, None).unwrap());
+    }
+
+    #[test]
+    fn test_verify_with_pepper() {
+        let pepper = Some("pepper".as_bytes());
+        let hash = hash_secret_with("thr0wTH3b4ll!", pepper).unwrap();
+
+        assert!(verify_secret_with("thr0wTH3b4ll!", &hash, pepper).unwrap());
+        assert!(!verify_secret_with("wrong", &hash, pepper).unwrap());
+
+        // A peppered hash cannot be verified without the pepper, or with a different one.
+        assert!(!verify_secret_with("thr0wTH3b4ll!", &hash, None).unwrap());
+        assert!(!verify_secret_with("thr0wTH3b4ll!", &hash, Some("salt".as_bytes())).unwrap());
+    }
+
+    #[test]
+    fn test_verify_hash_predating_pepper() {
+        let hash = hash_secret_with("thr0wTH3b4ll!", None).unwrap();
+        let pepper = Some("pepper".as_bytes());
+
+        assert!(verify_secret_with("thr0wTH3b4ll!", &hash, pepper).unwrap());
+        assert!(!verify_secret_with("wrong", &hash, pepper).unwrap());
+    }
+
+    #[test]
+    fn test_peppered_hashes_are_marked() {
+        let pepper = Some("pepper".as_bytes());
+        let peppered = hash_secret_with("thr0wTH3b4ll!", pepper).unwrap();
+        let unpeppered = hash_secret_with("thr0wTH3b4ll!", None).unwrap();
+
+        assert!(is_peppered(&PasswordHash::new(&peppered).unwrap()));
+        assert!(!is_peppered(&PasswordHash::new(&unpeppered).unwrap()));
+
+        assert!(needs_rehash_with(&unpeppered, pepper));
+        assert!(!needs_rehash_with(&peppered, pepper));
+        assert!(!needs_rehash_with(&unpeppered, None));
+    }
+
+    #[test]
+    fn test_generated_secret_length() {
+        for bytes in [16, 32, 64] {
+            let secret = generate_secret_with(bytes);
+
+            let decoded = general_purpose::URL_SAFE_NO_PAD.decode(&secret).unwrap();
+            assert_eq!(decoded.len(), bytes);
+            assert!(
+                secret
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                "{} is not URL-safe",
+                secret
+            );
+        }
+
+        assert_ne!(generate_secret_with(32), generate_secret_with(32));
+    }
 }
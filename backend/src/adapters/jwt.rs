@@ -75,10 +75,86 @@ pub fn generate_access_token(player_id: &str) -> DBoResult<String> {
 /// - `InvalidToken` if the token cannot be decoded because it is bad
 /// - `AdapterError` if the token cannot be decoded due to a server-side error
 pub fn decode_access_token(token: &str) -> DBoResult<AccessTokenPayload> {
+    decode_access_token_with(token, &ENV.authn_token_secret, ENV.jwt_leeway_secs)
+}
+
+/// Decode an access token, signed with a specific secret.
+///
+/// ### Arguments
+/// - `token`: The access token
+/// - `secret`: The secret with which the token was signed
+/// - `leeway`: The number of seconds of clock skew tolerated while validating its expiry
+///
+/// ### Errors
+/// - `TokenExpired` if the token expired more than `leeway` seconds ago
+/// - `InvalidToken` if the token cannot be decoded because it is bad, or has no expiry
+/// - `AdapterError` if the token cannot be decoded due to a server-side error
+fn decode_access_token_with(
+    token: &str,
+    secret: &str,
+    leeway: u64,
+) -> DBoResult<AccessTokenPayload> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = leeway;
+    validation.set_required_spec_claims(&["exp"]);
+
     Ok(decode::<AccessTokenPayload>(
         token,
-        &DecodingKey::from_secret(ENV.authn_token_secret.as_bytes()),
-        &Validation::new(Algorithm::HS256),
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
     )?
     .claims)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::DBoError;
+
+    const SECRET: &str = "test-secret";
+
+    /// Encode a token which expired a number of seconds ago.
+    fn expired_token(seconds_ago: i64) -> String {
+        let now = Utc::now();
+        let payload = AccessTokenPayload {
+            sub: String::from("player"),
+            exp: (now - Duration::seconds(seconds_ago)).timestamp() as usize,
+            iat: (now - Duration::minutes(15)).timestamp() as usize,
+        };
+
+        encode(
+            &Header::default(),
+            &payload,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_expired_token_within_leeway() {
+        let token = expired_token(10);
+
+        let payload = decode_access_token_with(&token, SECRET, 30).unwrap();
+        assert_eq!(payload.sub(), "player");
+
+        assert!(matches!(
+            decode_access_token_with(&token, SECRET, 0),
+            Err(DBoError::TokenExpired)
+        ));
+    }
+
+    #[test]
+    fn test_token_without_expiry_is_invalid() {
+        let token = encode(
+            &Header::default(),
+            &serde_json::json!({ "sub": "player", "iat": Utc::now().timestamp() }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            decode_access_token_with(&token, SECRET, 60),
+            Err(DBoError::InvalidToken)
+        ));
+    }
+}
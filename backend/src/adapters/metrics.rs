@@ -0,0 +1,154 @@
+//! This module is an adapter over the `metrics` crate and its Prometheus exporter, recording how
+//! long requests, database operations, and email deliveries take.
+//!
+//! Metrics are only recorded when `ENV.metrics_allowlist` is set; otherwise no recorder is
+//! installed, and every measurement is discarded. The recorded histograms are served in the
+//! Prometheus text format by `GET /metrics`, to the addresses on the allow-list only.
+
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics::histogram;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use mongodb::event::command::CommandEvent;
+use once_cell::sync::Lazy;
+use tokio::time::interval;
+
+use crate::config::environment::ENV;
+
+/// The upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// The interval at which recorded measurements are folded into their histograms.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The installed Prometheus recorder, or None if metrics are disabled.
+pub static METRICS: Lazy<Option<PrometheusHandle>> = Lazy::new(|| {
+    if ENV.metrics_allowlist.is_empty() {
+        return None;
+    }
+
+    let handle = PrometheusBuilder::new()
+        .set_buckets(&BUCKETS)
+        .expect("The metrics histogram buckets are invalid!")
+        .install_recorder()
+        .expect("Failed to install the metrics recorder!");
+
+    Some(handle)
+});
+
+/// Periodically fold recorded measurements into their histograms, so that they do not accumulate
+/// between scrapes. Does nothing if metrics are disabled.
+pub fn spawn_metrics_upkeep() {
+    let handle = match METRICS.as_ref() {
+        Some(h) => h,
+        None => return,
+    };
+
+    tokio::spawn(async move {
+        let mut ticker = interval(UPKEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            handle.run_upkeep();
+        }
+    });
+}
+
+/// Determine whether a peer may read the metrics.
+///
+/// ### Arguments
+/// - `peer`: The address of the connecting peer
+/// - `allowlist`: The addresses which may read the metrics
+///
+/// ### Returns
+/// Whether the peer is on the allow-list; IPv4 peers connecting over IPv6 are compared by their
+/// IPv4 address
+pub fn peer_allowed(peer: IpAddr, allowlist: &[IpAddr]) -> bool {
+    allowlist.contains(&peer.to_canonical())
+}
+
+/// A middleware recording the latency of each request, labelled by its method, the route which
+/// handled it, and the status of its response.
+///
+/// ### Arguments
+/// - `request`: The incoming request
+/// - `next`: The rest of the middleware stack, and the handler
+pub async fn track_request_latency(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| String::from("unmatched"));
+
+    let response = next.run(request).await;
+
+    histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "route" => route,
+        "status" => response.status().as_u16().to_string(),
+    )
+    .record(start.elapsed());
+
+    response
+}
+
+/// Record the duration of a completed MongoDB command, labelled by the name of the command and
+/// whether it succeeded. Used as the command event handler of the MongoDB client.
+///
+/// ### Arguments
+/// - `event`: The command event reported by the MongoDB driver
+pub fn record_mongo_command(event: CommandEvent) {
+    let (command, outcome, duration) = match event {
+        CommandEvent::Succeeded(e) => (e.command_name, "success", e.duration),
+        CommandEvent::Failed(e) => (e.command_name, "failure", e.duration),
+        _ => return,
+    };
+
+    histogram!(
+        "mongodb_command_duration_seconds",
+        "command" => command,
+        "outcome" => outcome,
+    )
+    .record(duration);
+}
+
+/// Record the duration of an attempt to send an email over SMTP.
+///
+/// ### Arguments
+/// - `duration`: How long the attempt took
+/// - `succeeded`: Whether the email was accepted by the SMTP server
+pub fn record_email_send(duration: Duration, succeeded: bool) {
+    let outcome = if succeeded { "success" } else { "failure" };
+
+    histogram!("email_send_duration_seconds", "outcome" => outcome).record(duration);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_allowed() {
+        let allowlist: Vec<IpAddr> = vec!["10.0.0.5".parse().unwrap(), "::1".parse().unwrap()];
+
+        assert!(peer_allowed("10.0.0.5".parse().unwrap(), &allowlist));
+        assert!(peer_allowed("::ffff:10.0.0.5".parse().unwrap(), &allowlist));
+        assert!(peer_allowed("::1".parse().unwrap(), &allowlist));
+
+        assert!(!peer_allowed("10.0.0.6".parse().unwrap(), &allowlist));
+        assert!(!peer_allowed("127.0.0.1".parse().unwrap(), &allowlist));
+        assert!(!peer_allowed("10.0.0.5".parse().unwrap(), &[]));
+    }
+}
@@ -1,12 +1,59 @@
 //! This module handles the configuration of the MongoDB database used by the application.
+//!
+//! It also provides `with_retry`, allowing repositories to retry operations which fail due to
+//! transient network problems, rather than immediately failing the request,
+//! `is_duplicate_key`, allowing repositories to recognize uniqueness index violations, and
+//! `analytics_selection_criteria`, allowing analytics-style reads to be served by a secondary.
+//!
+//! Every repository operation is run through either `with_retry` or, if it is not safe to repeat,
+//! `with_breaker`, so that all of them are guarded by `DATABASE_BREAKER`. While the database is
+//! unreachable, they are rejected immediately, and `reject_while_database_unavailable` answers
+//! requests with `503 SERVICE UNAVAILABLE` rather than letting them hang.
 
+use std::{
+    future::IntoFuture,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
 use mongodb::{
     Client, Database,
-    options::{Collation, CollationStrength},
+    error::{
+        Error as MongoError, ErrorKind, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR,
+        WriteFailure,
+    },
+    event::EventHandler,
+    options::{ClientOptions, Collation, CollationStrength, ReadPreference, SelectionCriteria},
 };
+use once_cell::sync::Lazy;
+use tokio::time::sleep;
 use urlencoding::encode;
 
-use crate::config::environment::ENV;
+use crate::{
+    adapters::{
+        circuit_breaker::CircuitBreaker,
+        metrics::{METRICS, record_mongo_command},
+    },
+    config::environment::ENV,
+};
+
+/// The circuit breaker guarding every database operation run through `with_retry` or
+/// `with_breaker`.
+pub static DATABASE_BREAKER: Lazy<CircuitBreaker> = Lazy::new(|| {
+    CircuitBreaker::new(
+        ENV.mongo_breaker_threshold,
+        Duration::from_secs(ENV.mongo_breaker_cooldown_secs),
+    )
+});
+
+/// The error returned in place of a database operation which was rejected by `DATABASE_BREAKER`.
+#[derive(Debug)]
+pub struct DatabaseUnavailable;
 
 /// Returns a standard case-insensitive collation, for use while creating database indices, as well
 /// as performing search queries which do not rely on case.
@@ -24,6 +71,10 @@ pub fn case_insensitive_collation() -> Collation {
 /// ### Returns
 /// A MongoDB Database
 ///
+/// The connection pool and timeouts are configured by the environment, so that requests fail fast
+/// rather than hanging when the database cannot be reached. While metrics are enabled, the
+/// duration of every database command is recorded.
+///
 /// ### Panics
 /// If the database connection string is invalid, or if the database indices could not be created.
 pub async fn database() -> Database {
@@ -34,12 +85,330 @@ pub async fn database() -> Database {
         ENV.mongo_server
     );
 
-    let mongo_client = Client::with_uri_str(mongo_uri)
+    let mut options = ClientOptions::parse(mongo_uri)
         .await
         .expect("The mongo_uri string is malformed.");
 
-    // This is what we will return from the function to be used as an axum state.
-    let mongo_database = mongo_client.database(&ENV.mongo_dbname);
+    options.max_pool_size = Some(ENV.mongo_max_pool_size);
+    options.min_pool_size = Some(ENV.mongo_min_pool_size);
+    options.connect_timeout = Some(Duration::from_millis(ENV.mongo_connect_timeout_ms));
+    options.server_selection_timeout =
+        Some(Duration::from_millis(ENV.mongo_server_selection_timeout_ms));
+    if METRICS.is_some() {
+        options.command_event_handler = Some(EventHandler::callback(record_mongo_command));
+    }
+
+    let mongo_client =
+        Client::with_options(options).expect("The MongoDB client options are invalid.");
+
+    mongo_client.database(&ENV.mongo_dbname)
+}
+
+/// Returns the selection criteria for **analytics-style** reads (such as admin searches), which
+/// can tolerate slightly stale data. When `ENV.analytics_read_secondary` is enabled, they are
+/// routed to a secondary replica if one is available, so that they do not compete with the
+/// transactional reads and writes on the primary. All other queries always use the primary.
+pub fn analytics_selection_criteria() -> SelectionCriteria {
+    analytics_read_preference(ENV.analytics_read_secondary).into()
+}
+
+/// Returns the read preference for analytics-style reads.
+///
+/// ### Arguments
+/// - `read_secondary`: Whether analytics-style reads should prefer a secondary replica
+fn analytics_read_preference(read_secondary: bool) -> ReadPreference {
+    if read_secondary {
+        ReadPreference::SecondaryPreferred { options: None }
+    } else {
+        ReadPreference::Primary
+    }
+}
+
+/// The server error code returned when a write violates a unique index.
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// Determine whether a MongoDB error was caused by a write violating a unique index. This allows
+/// repositories to report races between two requests claiming the same unique value as a
+/// `UniquenessViolation`, rather than as an unexpected adapter failure.
+pub fn is_duplicate_key(error: &MongoError) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY_CODE,
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
+/// The server error code returned when a command names a collection which does not exist.
+const NAMESPACE_NOT_FOUND_CODE: i32 = 26;
+
+/// The server error code returned when a command names an index which does not exist.
+const INDEX_NOT_FOUND_CODE: i32 = 27;
+
+/// Determine whether a MongoDB error was caused by a command naming a collection or an index which
+/// does not exist. This allows index maintenance to run against a fresh database.
+pub fn is_missing_namespace_or_index(error: &MongoError) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Command(e) => {
+            e.code == NAMESPACE_NOT_FOUND_CODE || e.code == INDEX_NOT_FOUND_CODE
+        }
+        _ => false,
+    }
+}
+
+/// Describes how many times, and how patiently, a failed database operation should be retried.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    /// The maximum number of retries following the initial attempt.
+    pub retries: u32,
+    /// The delay before the first retry. Each subsequent retry waits twice as long as the last.
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Return the retry policy configured by the environment.
+    pub fn configured() -> Self {
+        Self {
+            retries: ENV.mongo_retries,
+            base_delay: Duration::from_millis(ENV.mongo_retry_base_delay_ms),
+        }
+    }
+}
+
+/// Determine whether a MongoDB error is **transient** - that is, whether the same operation could
+/// reasonably succeed if it were attempted again. This includes network errors, timeouts, and
+/// server selection failures. Errors such as duplicate keys are never retryable.
+fn is_retryable(error: &MongoError) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Io(_)
+            | ErrorKind::ConnectionPoolCleared { .. }
+            | ErrorKind::ServerSelection { .. }
+    ) || error.contains_label(RETRYABLE_WRITE_ERROR)
+        || error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+}
+
+/// Run a database operation, retrying it with exponential backoff according to the environment's
+/// `RetryPolicy` if it fails due to a transient error. Non-retryable errors are returned
+/// immediately.
+///
+/// The outcome is reported to `DATABASE_BREAKER`: running out of retries on a transient error
+/// counts as a failure, while any other outcome shows that the database is reachable. While the
+/// breaker is open, the operation is not attempted at all.
+///
+/// **Note**: Only wrap operations which are safe to repeat (reads and idempotent updates); use
+/// `with_breaker` for the rest.
+///
+/// ### Arguments
+/// - `operation`: A function producing the database operation to run
+///
+/// ### Errors
+/// - A custom `MongoError` holding `DatabaseUnavailable`, if the breaker rejected the operation
+/// - The last `MongoError` encountered, if the operation fails with a non-retryable error or runs
+///   out of retries
+pub async fn with_retry<T, F, O>(operation: F) -> Result<T, MongoError>
+where
+    F: FnMut() -> O,
+    O: IntoFuture<Output = Result<T, MongoError>>,
+{
+    with_breaker(with_retry_policy(RetryPolicy::configured(), operation)).await
+}
+
+/// Run a database operation once, without retrying it, reporting its outcome to
+/// `DATABASE_BREAKER` in the same way as `with_retry`. This guards the operations which are not
+/// safe to repeat: inserts, increments, find-and-modify operations, and whole transactions.
+///
+/// ### Arguments
+/// - `operation`: The database operation to run
+///
+/// ### Errors
+/// - A custom `MongoError` holding `DatabaseUnavailable`, if the breaker rejected the operation
+/// - The `MongoError` encountered, if the operation fails
+pub async fn with_breaker<T, O>(operation: O) -> Result<T, MongoError>
+where
+    O: IntoFuture<Output = Result<T, MongoError>>,
+{
+    if DATABASE_BREAKER.try_acquire(Instant::now()).is_err() {
+        return Err(MongoError::custom(DatabaseUnavailable));
+    }
+
+    let result = operation.await;
+
+    match &result {
+        Err(e) if is_retryable(e) => DATABASE_BREAKER.record_failure(Instant::now()),
+        _ => DATABASE_BREAKER.record_success(),
+    }
+
+    result
+}
+
+/// A middleware answering requests with `503 SERVICE UNAVAILABLE` and a `Retry-After` header while
+/// `DATABASE_BREAKER` is open, rather than running handlers which depend on the database. A
+/// `500 INTERNAL SERVER ERROR` produced while the breaker is open is replaced in the same way, so
+/// that requests caught by the breaker opening are also told when to retry.
+///
+/// ### Arguments
+/// - `request`: The incoming request
+/// - `next`: The rest of the middleware stack, and the handler
+pub async fn reject_while_database_unavailable(request: Request, next: Next) -> Response {
+    if let Some(retry_after) = DATABASE_BREAKER.retry_after(Instant::now()) {
+        return service_unavailable(retry_after);
+    }
+
+    let response = next.run(request).await;
+
+    if response.status() == StatusCode::INTERNAL_SERVER_ERROR
+        && let Some(retry_after) = DATABASE_BREAKER.retry_after(Instant::now())
+    {
+        return service_unavailable(retry_after);
+    }
+
+    response
+}
+
+/// Return a `503 SERVICE UNAVAILABLE` response, asking the client to retry after a delay.
+///
+/// ### Arguments
+/// - `retry_after`: How long the client should wait, rounded up to whole seconds
+fn service_unavailable(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(RETRY_AFTER, HeaderValue::from(seconds.max(1)))],
+    )
+        .into_response()
+}
+
+/// Run a database operation, retrying it with exponential backoff according to the provided
+/// `RetryPolicy` if it fails due to a transient error.
+///
+/// ### Arguments
+/// - `policy`: The retry policy to follow
+/// - `operation`: A function producing the database operation to run
+///
+/// ### Errors
+/// - The last `MongoError` encountered, if the operation fails with a non-retryable error or runs
+///   out of retries
+pub async fn with_retry_policy<T, F, O>(
+    policy: RetryPolicy,
+    mut operation: F,
+) -> Result<T, MongoError>
+where
+    F: FnMut() -> O,
+    O: IntoFuture<Output = Result<T, MongoError>>,
+{
+    let mut delay = policy.base_delay;
+    let mut attempt = 0;
+
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.retries && is_retryable(&e) => {
+                eprintln!(
+                    "A transient MongoDB error occurred; retrying in {:?} ({}/{}).",
+                    delay,
+                    attempt + 1,
+                    policy.retries
+                );
+                sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::ready,
+        io::{Error as IoError, ErrorKind as IoErrorKind},
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use bson::doc;
+
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        retries: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    fn network_error() -> MongoError {
+        MongoError::from(IoError::from(IoErrorKind::ConnectionReset))
+    }
+
+    #[tokio::test]
+    async fn test_retry_recovers_from_transient_errors() {
+        let attempts = AtomicU32::new(0);
+
+        let result = with_retry_policy(POLICY, || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            ready(if attempt < 2 {
+                Err(network_error())
+            } else {
+                Ok(attempt)
+            })
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_policy_retries() {
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), MongoError> = with_retry_policy(POLICY, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            ready(Err(network_error()))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), POLICY.retries + 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_passes_through_non_retryable_errors() {
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), MongoError> = with_retry_policy(POLICY, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            ready(Err(MongoError::custom("duplicate key")))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_is_duplicate_key() {
+        let write_error = |code: i32| {
+            MongoError::from(ErrorKind::Write(WriteFailure::WriteError(
+                bson::from_document(doc! { "code": code }).unwrap(),
+            )))
+        };
+
+        assert!(is_duplicate_key(&write_error(DUPLICATE_KEY_CODE)));
+        assert!(!is_duplicate_key(&write_error(121)));
+        assert!(!is_duplicate_key(&network_error()));
+        assert!(!is_duplicate_key(&MongoError::custom("not a write error")));
+    }
 
-    mongo_database
+    #[test]
+    fn test_analytics_read_preference() {
+        assert!(matches!(
+            analytics_read_preference(true),
+            ReadPreference::SecondaryPreferred { options: None }
+        ));
+        assert!(matches!(
+            analytics_read_preference(false),
+            ReadPreference::Primary
+        ));
+    }
 }
@@ -0,0 +1,167 @@
+//! This module provides a simple in-memory rate limiter, which limits how often a single client IP
+//! address may attempt to log in, or pre-validate a registration.
+//!
+//! The limiters count attempts in fixed one-minute windows, beginning with each address's first
+//! attempt. Counts are only kept within this instance of the application, so the effective limit
+//! grows with the number of instances. Addresses within `ENV.rate_limit_allowlist` are never
+//! limited, and each limiter is disabled entirely while its configured limit is 0.
+
+use std::{
+    net::IpAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use moka::sync::Cache;
+use once_cell::sync::Lazy;
+
+use crate::config::environment::ENV;
+
+/// The length of a single rate limiting window.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// The maximum number of addresses tracked at once.
+const CAPACITY: u64 = 100_000;
+
+/// The attempts made by a single address within its current window.
+struct Window {
+    /// The time of the first attempt, at which the window began.
+    started: Instant,
+    /// The number of attempts made within the window.
+    attempts: AtomicU32,
+}
+
+/// Limits how many attempts each client address may make within a window.
+pub struct RateLimiter {
+    /// The current window of each address.
+    windows: Cache<IpAddr, Arc<Window>>,
+    /// The maximum number of attempts allowed within a window.
+    limit: u32,
+    /// The length of a window.
+    window: Duration,
+}
+
+impl RateLimiter {
+    /// Construct a new RateLimiter
+    ///
+    /// ### Arguments
+    /// - `limit`: The maximum number of attempts allowed within a window
+    /// - `window`: The length of a window
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            windows: Cache::builder()
+                .max_capacity(CAPACITY)
+                .time_to_live(window)
+                .build(),
+            limit,
+            window,
+        }
+    }
+
+    /// Record an attempt by a client, and determine whether it is allowed.
+    ///
+    /// ### Arguments
+    /// - `client`: The address of the client
+    /// - `now`: The current time
+    ///
+    /// ### Errors
+    /// - How long the client should wait before trying again, if they have exceeded the limit
+    ///   within their current window
+    pub fn attempt(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let window = self.windows.get_with(client.to_canonical(), || {
+            Arc::new(Window {
+                started: now,
+                attempts: AtomicU32::new(0),
+            })
+        });
+
+        match window.attempts.fetch_add(1, Ordering::Relaxed) < self.limit {
+            true => Ok(()),
+            false => Err((window.started + self.window).saturating_duration_since(now)),
+        }
+    }
+}
+
+/// The rate limiter applied to login attempts, or None if login rate limiting is disabled.
+static LOGIN_LIMITER: Lazy<Option<RateLimiter>> = Lazy::new(|| {
+    (ENV.login_rate_limit > 0).then(|| RateLimiter::new(ENV.login_rate_limit, WINDOW))
+});
+
+/// The rate limiter applied to registration pre-validations, or None if it is disabled.
+static REGISTRATION_VALIDATION_LIMITER: Lazy<Option<RateLimiter>> = Lazy::new(|| {
+    (ENV.registration_validation_rate_limit > 0)
+        .then(|| RateLimiter::new(ENV.registration_validation_rate_limit, WINDOW))
+});
+
+/// Record an attempt by a client against a limiter, and determine whether it is allowed. Clients
+/// within `ENV.rate_limit_allowlist` are always allowed, and their attempts are not counted.
+///
+/// ### Arguments
+/// - `limiter`: The limiter to record the attempt against, or None if it is disabled
+/// - `client`: The address of the client
+///
+/// ### Errors
+/// - How long the client should wait before trying again, if the attempt is not allowed
+fn check_attempt(limiter: Option<&RateLimiter>, client: IpAddr) -> Result<(), Duration> {
+    if ENV.rate_limit_allowlist.iter().any(|c| c.contains(client)) {
+        return Ok(());
+    }
+
+    match limiter {
+        Some(limiter) => limiter.attempt(client, Instant::now()),
+        None => Ok(()),
+    }
+}
+
+/// Record a login attempt by a client, and determine whether it is allowed.
+///
+/// ### Arguments
+/// - `client`: The address of the client
+///
+/// ### Errors
+/// - How long the client should wait before trying again, if the attempt is not allowed
+pub fn check_login_attempt(client: IpAddr) -> Result<(), Duration> {
+    check_attempt(LOGIN_LIMITER.as_ref(), client)
+}
+
+/// Record a registration pre-validation by a client, and determine whether it is allowed.
+///
+/// ### Arguments
+/// - `client`: The address of the client
+///
+/// ### Errors
+/// - How long the client should wait before trying again, if the attempt is not allowed
+pub fn check_registration_validation(client: IpAddr) -> Result<(), Duration> {
+    check_attempt(REGISTRATION_VALIDATION_LIMITER.as_ref(), client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.attempt(ip("10.0.0.1"), start).is_ok());
+        assert!(limiter.attempt(ip("::ffff:10.0.0.1"), start).is_ok());
+
+        // The client is told to wait until their window ends.
+        let later = start + Duration::from_secs(15);
+        assert_eq!(
+            limiter.attempt(ip("10.0.0.1"), later),
+            Err(Duration::from_secs(45))
+        );
+
+        // Other clients are counted separately.
+        assert!(limiter.attempt(ip("10.0.0.2"), later).is_ok());
+    }
+}
@@ -8,24 +8,36 @@
 //! as inserts for repositories of **unconstrained** models, which are not constrained by uniqueness
 //! indices (except for their id fields).
 
+#[doc(hidden)]
+mod allowlist_entry_repo;
 #[doc(hidden)]
 mod confirmation_token_repo;
 pub mod counter_id;
 #[doc(hidden)]
+mod counter_snapshot_repo;
+#[doc(hidden)]
 mod counters_repo;
 #[doc(hidden)]
+mod outbox_email_repo;
+#[doc(hidden)]
 mod player_repo;
 #[doc(hidden)]
 mod refresh_token_repo;
+pub mod search;
+#[doc(hidden)]
+mod season_archive_repo;
 #[doc(hidden)]
 mod undo_token_repo;
 
 use mongodb::{Collection, bson::doc};
 
 use crate::{
-    adapters::mongo::database,
+    adapters::mongo::{database, with_breaker, with_retry},
     errors::DBoResult,
-    models::{Collectible, ConfirmationToken, Counter, Model, Player, RefreshToken, UndoToken},
+    models::{
+        AllowlistEntry, Collectible, ConfirmationToken, Counter, CounterSnapshot, Model,
+        OutboxEmail, Player, RefreshToken, SeasonArchive, UndoToken,
+    },
 };
 
 /// An interface over a database collection which handles all database interactions related to a
@@ -43,9 +55,7 @@ impl<T: Model + Send + Sync> Repository<T> {
     /// - `collection`: The MongoDB collection that this Repository will handle.
     pub async fn new(collection: Collection<T>) -> Self {
         T::index(&collection).await;
-        Self {
-            collection: collection,
-        }
+        Self { collection }
     }
 
     /// Find a document within the repository, referencing it by its unique identifier.
@@ -57,7 +67,7 @@ impl<T: Model + Send + Sync> Repository<T> {
     /// ### Errors
     /// - `AdapterError` if the query fails
     pub async fn find_by_id(&self, id: &str) -> DBoResult<Option<T>> {
-        Ok(self.collection.find_one(doc! { T::id_field(): id }).await?)
+        Ok(with_retry(|| self.collection.find_one(doc! { T::id_field(): id })).await?)
     }
 
     /// Delete a document within the repository, referencing it by its unique identifier.
@@ -69,24 +79,33 @@ impl<T: Model + Send + Sync> Repository<T> {
     /// ### Errors
     /// - `AdapterError` if the query fails
     pub async fn delete(&self, id: &str) -> DBoResult<Option<T>> {
-        Ok(self
-            .collection
-            .find_one_and_delete(doc! { T::id_field(): id })
-            .await?)
+        Ok(with_breaker(
+            self.collection
+                .find_one_and_delete(doc! { T::id_field(): id }),
+        )
+        .await?)
     }
 }
 
 /// A struct containing all of the repositories needed by the application.
 #[derive(Clone)]
 pub struct Repositories {
+    /// The repository handling the registration allow-list.
+    allowlist: Repository<AllowlistEntry>,
     /// The repository handling email confirmation tokens.
     confirmation_tokens: Repository<ConfirmationToken>,
+    /// The repository handling periodic snapshots of the counters.
+    counter_snapshots: Repository<CounterSnapshot>,
     /// The repository handling counters.
     counters: Repository<Counter>,
+    /// The repository handling outgoing emails.
+    email_outbox: Repository<OutboxEmail>,
     /// The repository handling player accounts.
     players: Repository<Player>,
     /// The repository handling player refresh tokens.
     refresh_tokens: Repository<RefreshToken>,
+    /// The repository handling archived season stats.
+    season_archives: Repository<SeasonArchive>,
     /// The repository handling player undo tokens.
     undo_tokens: Repository<UndoToken>,
 }
@@ -99,31 +118,62 @@ impl Repositories {
     pub async fn new() -> Self {
         let db = database().await;
         Self {
+            allowlist: Repository::<AllowlistEntry>::new(
+                db.collection(AllowlistEntry::collection_name()),
+            )
+            .await,
             confirmation_tokens: Repository::<ConfirmationToken>::new(
                 db.collection(ConfirmationToken::collection_name()),
             )
             .await,
-            counters: Repository::<Counter>::new(db.collection(&Counter::collection_name())).await,
-            players: Repository::<Player>::new(db.collection(&Player::collection_name())).await,
+            counter_snapshots: Repository::<CounterSnapshot>::new(
+                db.collection(CounterSnapshot::collection_name()),
+            )
+            .await,
+            counters: Repository::<Counter>::new(db.collection(Counter::collection_name())).await,
+            email_outbox: Repository::<OutboxEmail>::new(
+                db.collection(OutboxEmail::collection_name()),
+            )
+            .await,
+            players: Repository::<Player>::new(db.collection(Player::collection_name())).await,
             refresh_tokens: Repository::<RefreshToken>::new(
                 db.collection(RefreshToken::collection_name()),
             )
             .await,
+            season_archives: Repository::<SeasonArchive>::new(
+                db.collection(SeasonArchive::collection_name()),
+            )
+            .await,
             undo_tokens: Repository::<UndoToken>::new(db.collection(UndoToken::collection_name()))
                 .await,
         }
     }
 
+    /// Return the registration allow-list repository.
+    pub fn allowlist(&self) -> &Repository<AllowlistEntry> {
+        &self.allowlist
+    }
+
     /// Return the confirmation tokens repository.
     pub fn confirmation_tokens(&self) -> &Repository<ConfirmationToken> {
         &self.confirmation_tokens
     }
 
+    /// Return the counter snapshots repository.
+    pub fn counter_snapshots(&self) -> &Repository<CounterSnapshot> {
+        &self.counter_snapshots
+    }
+
     /// Return the counters repository.
     pub fn counters(&self) -> &Repository<Counter> {
         &self.counters
     }
 
+    /// Return the email outbox repository.
+    pub fn email_outbox(&self) -> &Repository<OutboxEmail> {
+        &self.email_outbox
+    }
+
     /// Return the players repository.
     pub fn players(&self) -> &Repository<Player> {
         &self.players
@@ -133,6 +183,11 @@ impl Repositories {
         &self.refresh_tokens
     }
 
+    /// Return the season archives repository.
+    pub fn season_archives(&self) -> &Repository<SeasonArchive> {
+        &self.season_archives
+    }
+
     pub fn undo_tokens(&self) -> &Repository<UndoToken> {
         &self.undo_tokens
     }
@@ -0,0 +1,59 @@
+//! This module provides unique functionality for the registration allow-list repository.
+
+use bson::doc;
+use futures::StreamExt;
+
+use crate::{
+    adapters::{mongo::with_retry, repositories::Repository},
+    errors::DBoResult,
+    models::{AllowlistEntry, Identifiable},
+};
+
+impl Repository<AllowlistEntry> {
+    /// Add an entry to the allow-list. Adding an entry which already exists does nothing.
+    ///
+    /// ### Arguments
+    /// - `entry`: The entry to add
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn insert(&self, entry: &AllowlistEntry) -> DBoResult<()> {
+        let document = bson::to_document(entry)?;
+
+        with_retry(|| {
+            self.collection
+                .update_one(
+                    doc! { AllowlistEntry::id_field(): entry.id() },
+                    doc! { "$setOnInsert": &document },
+                )
+                .upsert(true)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Find every entry on the allow-list.
+    ///
+    /// ### Returns
+    /// The entries, in alphabetical order
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn all_entries(&self) -> DBoResult<Vec<String>> {
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! {})
+                .sort(doc! { AllowlistEntry::id_field(): 1 })
+        })
+        .await?;
+
+        let mut entries = vec![];
+
+        while let Some(result) = cursor.next().await {
+            entries.push(String::from(result?.entry()));
+        }
+
+        Ok(entries)
+    }
+}
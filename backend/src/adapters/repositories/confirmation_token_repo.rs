@@ -1,8 +1,15 @@
 //! This module provides unique functionality for the confirmation token repository.
 
-use bson::doc;
+use bson::{DateTime, doc};
 
-use crate::{adapters::repositories::Repository, errors::DBoResult, models::ConfirmationToken};
+use crate::{
+    adapters::{
+        mongo::{with_breaker, with_retry},
+        repositories::Repository,
+    },
+    errors::DBoResult,
+    models::{ConfirmationToken, Identifiable},
+};
 
 impl Repository<ConfirmationToken> {
     /// Insert a new email confirmation token into the repository. This will replace any
@@ -16,10 +23,37 @@ impl Repository<ConfirmationToken> {
     /// ### Errors
     /// - `AdapterError` if the query fails.
     pub async fn insert(&self, token: &ConfirmationToken) -> DBoResult<()> {
-        self.collection
-            .find_one_and_replace(doc! { "player_id": token.player_id() }, token)
-            .upsert(true)
-            .await?;
+        with_retry(|| {
+            self.collection
+                .find_one_and_replace(doc! { "player_id": token.player_id() }, token)
+                .upsert(true)
+        })
+        .await?;
         Ok(())
     }
+
+    /// Mark a confirmation token as used, rather than deleting it, so that a repeated confirmation
+    /// can be recognized as such until the TTL index removes the token. Only one request can mark
+    /// any given token, so this can be used to ensure that a confirmation is only counted once,
+    /// even if it is requested several times at once.
+    ///
+    /// ### Arguments
+    /// - `token_id`: The token's unique identifier
+    /// - `used_at`: The time at which the token confirmed the account
+    ///
+    /// ### Returns
+    /// Whether this call marked the token as used; false if it was already used or cannot be found
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn mark_used(&self, token_id: &str, used_at: &DateTime) -> DBoResult<bool> {
+        // A retry could not tell whether the first attempt marked the token, so it is not retried.
+        let update = with_breaker(self.collection.update_one(
+            doc! { ConfirmationToken::id_field(): token_id, "used": false },
+            doc! { "$set": { "used": true, "used_at": used_at } },
+        ))
+        .await?;
+
+        Ok(update.modified_count == 1)
+    }
 }
@@ -1,5 +1,11 @@
 //! This module provides an enum containing all possible IDs of Counters stored in the database, and
-//! implements ToString in order to ensure safe handling of valid counters within the application.
+//! implements ToString and FromStr in order to ensure safe handling of valid counters within the
+//! application.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
 
 /// An enum storing all types of Counters the app keeps track of.
 pub enum CounterId {
@@ -17,12 +23,15 @@ pub enum CounterId {
     Logins,
     /// "failed_logins": Keeps track of failed login attempts
     FailedLogins,
+    /// "seasons_ended": Keeps track of competitive seasons ended by an admin. Its count is also the
+    /// number of the most recently ended season.
+    SeasonsEnded,
 }
 
-impl ToString for CounterId {
-    /// Return the `id` field of the specific Counter.
-    fn to_string(&self) -> String {
-        String::from(match self {
+impl Display for CounterId {
+    /// Write the `id` field of the specific Counter.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             Self::Pings => "pings",
             Self::AccountsRegistered => "accounts_registered",
             Self::AccountsConfirmed => "accounts_confirmed",
@@ -30,6 +39,50 @@ impl ToString for CounterId {
             Self::AccountsDeleted => "accounts_deleted",
             Self::Logins => "logins",
             Self::FailedLogins => "failed_logins",
+            Self::SeasonsEnded => "seasons_ended",
         })
     }
 }
+
+impl FromStr for CounterId {
+    type Err = String;
+
+    /// Parse the `id` field of a specific Counter.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pings" => Ok(Self::Pings),
+            "accounts_registered" => Ok(Self::AccountsRegistered),
+            "accounts_confirmed" => Ok(Self::AccountsConfirmed),
+            "accounts_rejected" => Ok(Self::AccountsRejected),
+            "accounts_deleted" => Ok(Self::AccountsDeleted),
+            "logins" => Ok(Self::Logins),
+            "failed_logins" => Ok(Self::FailedLogins),
+            "seasons_ended" => Ok(Self::SeasonsEnded),
+            _ => Err(format!("{} is not a known counter", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_id_round_trip() {
+        for id in [
+            CounterId::Pings,
+            CounterId::AccountsRegistered,
+            CounterId::AccountsConfirmed,
+            CounterId::AccountsRejected,
+            CounterId::AccountsDeleted,
+            CounterId::Logins,
+            CounterId::FailedLogins,
+            CounterId::SeasonsEnded,
+        ] {
+            let parsed: CounterId = id.to_string().parse().unwrap();
+            assert_eq!(parsed.to_string(), id.to_string());
+        }
+
+        assert!("games_started".parse::<CounterId>().is_err());
+    }
+}
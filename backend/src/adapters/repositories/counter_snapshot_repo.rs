@@ -0,0 +1,125 @@
+//! This module provides unique functionality for the counter snapshot repository.
+
+use bson::{DateTime, Document, doc};
+use futures::StreamExt;
+
+use crate::{
+    adapters::{
+        mongo::{analytics_selection_criteria, is_duplicate_key, with_retry},
+        repositories::Repository,
+    },
+    errors::DBoResult,
+    models::CounterSnapshot,
+};
+
+impl Repository<CounterSnapshot> {
+    /// Record several snapshots, skipping any whose counter already has a snapshot in the same
+    /// bucket, such as one recorded by another instance. Each snapshot is upserted on its counter
+    /// and bucket, so recording the same snapshots again changes nothing.
+    ///
+    /// ### Arguments
+    /// - `snapshots`: The snapshots to record
+    ///
+    /// ### Returns
+    /// The number of snapshots which were recorded
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a query fails
+    pub async fn record(&self, snapshots: &[CounterSnapshot]) -> DBoResult<u64> {
+        let mut recorded = 0;
+
+        for snapshot in snapshots {
+            let document = bson::to_document(snapshot)?;
+
+            let upsert = with_retry(|| {
+                self.collection
+                    .update_one(
+                        doc! { "counter": snapshot.counter(), "bucket": snapshot.bucket() },
+                        doc! { "$setOnInsert": &document },
+                    )
+                    .upsert(true)
+            })
+            .await;
+
+            match upsert {
+                Ok(update) if update.upserted_id.is_some() => recorded += 1,
+                Ok(_) => {}
+                // Another instance recorded the same snapshot at the same moment.
+                Err(e) if is_duplicate_key(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(recorded)
+    }
+
+    /// Find the time at which the most recent snapshot of any counter was taken.
+    ///
+    /// ### Returns
+    /// - `Some(taken)` if any snapshot exists
+    /// - `None` if no snapshot has ever been taken
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn latest_taken(&self) -> DBoResult<Option<DateTime>> {
+        let latest =
+            with_retry(|| self.collection.find_one(doc! {}).sort(doc! { "taken": -1 })).await?;
+
+        Ok(latest.map(|snapshot| *snapshot.taken()))
+    }
+
+    /// Find the snapshots of a single counter, optionally within a range of time.
+    ///
+    /// ### Arguments
+    /// - `counter`: The unique identifier of the counter
+    /// - `from`: If provided, only snapshots taken at or after this time are included
+    /// - `to`: If provided, only snapshots taken at or before this time are included
+    /// - `limit`: The maximum number of snapshots to return
+    ///
+    /// ### Returns
+    /// The newest snapshots, up to the limit, ordered from the oldest to the newest. As an
+    /// analytics-style read, the query may be served by a secondary replica.
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn find_history(
+        &self,
+        counter: &str,
+        from: Option<&DateTime>,
+        to: Option<&DateTime>,
+        limit: i64,
+    ) -> DBoResult<Vec<CounterSnapshot>> {
+        let mut filter = doc! { "counter": counter };
+
+        let mut taken = Document::new();
+        if let Some(from) = from {
+            taken.insert("$gte", from);
+        }
+        if let Some(to) = to {
+            taken.insert("$lte", to);
+        }
+        if !taken.is_empty() {
+            filter.insert("taken", taken);
+        }
+
+        // The newest snapshots are kept when there are more than the limit.
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(filter.clone())
+                .sort(doc! { "taken": -1 })
+                .limit(limit)
+                .selection_criteria(analytics_selection_criteria())
+        })
+        .await?;
+
+        let mut snapshots = vec![];
+
+        while let Some(result) = cursor.next().await {
+            snapshots.push(result?);
+        }
+
+        snapshots.reverse();
+
+        Ok(snapshots)
+    }
+}
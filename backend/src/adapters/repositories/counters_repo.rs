@@ -1,14 +1,17 @@
-//! This module provides unique functionality over the counter repository. The only current function
-//! for a Counter is to increment it by 1.
+//! This module provides unique functionality over the counter repository. A Counter can be
+//! incremented by 1, and all counters can be fetched together in order to snapshot them.
 
-// NOTE: Future enhancements to the application may include functionalities to fetch one or more
-// counters from the database, or to decrement a counter. These functions are not currently needed for
-// the app, so are not yet included.
+// NOTE: Future enhancements to the application may include functionalities to decrement a counter.
+// This is not currently needed for the app, so is not yet included.
 
+use futures::StreamExt;
 use mongodb::{bson::doc, options::ReturnDocument};
 
 use crate::{
-    adapters::repositories::{Repository, counter_id::CounterId},
+    adapters::{
+        mongo::{with_breaker, with_retry},
+        repositories::{Repository, counter_id::CounterId},
+    },
     errors::DBoResult,
     models::{Counter, Identifiable},
 };
@@ -23,16 +26,41 @@ impl Repository<Counter> {
     /// ### Errors
     /// `AdapterError` if the query fails
     pub async fn increment_counter(&self, id: CounterId) -> DBoResult<u64> {
-        Ok(self
-            .collection
-            .find_one_and_update(
-                doc! { Counter::id_field(): &id.to_string() },
-                doc! { "$inc": { "count": 1 } },
-            )
-            .upsert(true)
-            .return_document(ReturnDocument::After)
-            .await?
-            .unwrap()
-            .count())
+        Ok(with_breaker(
+            self.collection
+                .find_one_and_update(
+                    doc! { Counter::id_field(): &id.to_string() },
+                    doc! { "$inc": { "count": 1 } },
+                )
+                .upsert(true)
+                .return_document(ReturnDocument::After),
+        )
+        .await?
+        .unwrap()
+        .count())
+    }
+
+    /// Find every counter which has ever been incremented.
+    ///
+    /// ### Returns
+    /// The counters, in alphabetical order of their ids
+    ///
+    /// ### Errors
+    /// `AdapterError` if the query fails
+    pub async fn all_counters(&self) -> DBoResult<Vec<Counter>> {
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! {})
+                .sort(doc! { Counter::id_field(): 1 })
+        })
+        .await?;
+
+        let mut counters = vec![];
+
+        while let Some(result) = cursor.next().await {
+            counters.push(result?);
+        }
+
+        Ok(counters)
     }
 }
@@ -0,0 +1,244 @@
+//! This module provides unique functionality for the email outbox repository.
+
+use bson::{DateTime, doc};
+use futures::StreamExt;
+use mongodb::options::ReturnDocument;
+
+use crate::{
+    adapters::{
+        mongo::{case_insensitive_collation, with_breaker, with_retry},
+        repositories::Repository,
+    },
+    errors::DBoResult,
+    models::{Identifiable, OutboxEmail, submodels::OutboxStatus},
+};
+
+impl Repository<OutboxEmail> {
+    /// Insert a new email into the outbox.
+    ///
+    /// ### Arguments
+    /// - `email`: The email to insert
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn insert(&self, email: &OutboxEmail) -> DBoResult<()> {
+        with_breaker(self.collection.insert_one(email)).await?;
+
+        Ok(())
+    }
+
+    /// Claim a pending email which is due to be attempted, so that no other attempt is made until
+    /// the claim lapses. This prevents the same email from being sent twice, whether by the queue
+    /// and the sweep, or by two servers.
+    ///
+    /// ### Arguments
+    /// - `outbox_id`: The email's unique identifier
+    /// - `now`: The current time
+    /// - `claimed_until`: The time at which the claim lapses
+    ///
+    /// ### Returns
+    /// The claimed email, or None if it is not pending, or if it is not yet due
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn claim(
+        &self,
+        outbox_id: &str,
+        now: &DateTime,
+        claimed_until: &DateTime,
+    ) -> DBoResult<Option<OutboxEmail>> {
+        Ok(with_breaker(
+            self.collection
+                .find_one_and_update(
+                    doc! {
+                        OutboxEmail::id_field(): outbox_id,
+                        "status": OutboxStatus::Pending.to_string(),
+                        "next_attempt_at": { "$lte": now },
+                    },
+                    doc! { "$set": { "next_attempt_at": claimed_until } },
+                )
+                .return_document(ReturnDocument::After),
+        )
+        .await?)
+    }
+
+    /// Find pending emails which are due to be attempted, from the longest waiting.
+    ///
+    /// ### Arguments
+    /// - `now`: The current time
+    /// - `limit`: The maximum number of emails to return
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn find_due(&self, now: &DateTime, limit: i64) -> DBoResult<Vec<OutboxEmail>> {
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! {
+                    "status": OutboxStatus::Pending.to_string(),
+                    "next_attempt_at": { "$lte": now },
+                })
+                .sort(doc! { "next_attempt_at": 1 })
+                .limit(limit)
+        })
+        .await?;
+
+        let mut emails = vec![];
+
+        while let Some(result) = cursor.next().await {
+            emails.push(result?);
+        }
+
+        Ok(emails)
+    }
+
+    /// Find the emails which have failed to send at least once, whether or not they will be
+    /// attempted again, from the oldest.
+    ///
+    /// ### Arguments
+    /// - `limit`: The maximum number of emails to return
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn find_stuck(&self, limit: i64) -> DBoResult<Vec<OutboxEmail>> {
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! {
+                    "status": { "$ne": OutboxStatus::Sent.to_string() },
+                    "attempts": { "$gt": 0 },
+                })
+                .sort(doc! { "created": 1 })
+                .limit(limit)
+        })
+        .await?;
+
+        let mut emails = vec![];
+
+        while let Some(result) = cursor.next().await {
+            emails.push(result?);
+        }
+
+        Ok(emails)
+    }
+
+    /// Mark an email as sent. It is removed by the TTL index after 7 days.
+    ///
+    /// ### Arguments
+    /// - `outbox_id`: The email's unique identifier
+    /// - `sent_at`: The time at which the email was sent
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn mark_sent(&self, outbox_id: &str, sent_at: &DateTime) -> DBoResult<()> {
+        with_retry(|| {
+            self.collection.update_one(
+                doc! { OutboxEmail::id_field(): outbox_id },
+                doc! { "$set": {
+                    "status": OutboxStatus::Sent.to_string(),
+                    "sent_at": sent_at,
+                } },
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt to send an email. The email is either scheduled to be attempted
+    /// again, or marked as failed if it has run out of retries.
+    ///
+    /// ### Arguments
+    /// - `outbox_id`: The email's unique identifier
+    /// - `error`: A description of the failure
+    /// - `retry_at`: When the email may be attempted again, or None if it has run out of retries
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn record_failure(
+        &self,
+        outbox_id: &str,
+        error: &str,
+        retry_at: Option<&DateTime>,
+    ) -> DBoResult<()> {
+        let update = match retry_at {
+            Some(at) => doc! {
+                "$inc": { "attempts": 1 },
+                "$set": { "last_error": error, "next_attempt_at": at },
+            },
+            None => doc! {
+                "$inc": { "attempts": 1 },
+                "$set": { "last_error": error, "status": OutboxStatus::Failed.to_string() },
+            },
+        };
+
+        with_breaker(
+            self.collection
+                .update_one(doc! { OutboxEmail::id_field(): outbox_id }, update),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record that an email was permanently rejected by the mail server. It is not attempted again,
+    /// and its recipients are added to the suppression list.
+    ///
+    /// ### Arguments
+    /// - `outbox_id`: The email's unique identifier
+    /// - `error`: A description of the rejection
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn record_rejection(&self, outbox_id: &str, error: &str) -> DBoResult<()> {
+        with_breaker(self.collection.update_one(
+            doc! { OutboxEmail::id_field(): outbox_id },
+            doc! {
+                "$inc": { "attempts": 1 },
+                "$set": { "last_error": error, "status": OutboxStatus::Rejected.to_string() },
+            },
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark an email as suppressed, so that it is never attempted.
+    ///
+    /// ### Arguments
+    /// - `outbox_id`: The email's unique identifier
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn mark_suppressed(&self, outbox_id: &str) -> DBoResult<()> {
+        with_retry(|| {
+            self.collection.update_one(
+                doc! { OutboxEmail::id_field(): outbox_id },
+                doc! { "$set": { "status": OutboxStatus::Suppressed.to_string() } },
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Determine whether any of the given addresses has had an email permanently rejected by the
+    /// mail server. Addresses are compared case-insensitively.
+    ///
+    /// ### Arguments
+    /// - `addresses`: The addresses to check
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn is_suppressed(&self, addresses: &[String]) -> DBoResult<bool> {
+        let rejected = with_retry(|| {
+            self.collection
+                .count_documents(doc! {
+                    "status": OutboxStatus::Rejected.to_string(),
+                    "to": { "$in": addresses },
+                })
+                .collation(case_insensitive_collation())
+        })
+        .await?;
+
+        Ok(rejected > 0)
+    }
+}
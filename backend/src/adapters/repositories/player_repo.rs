@@ -1,24 +1,271 @@
 //! This module provides unique functionality for the player repository.
 
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration as StdDuration,
+};
+
 use bson::DateTime;
 use chrono::{Duration, Utc};
-use mongodb::{bson::doc, options::ReturnDocument};
+use futures::StreamExt;
+use moka::sync::Cache;
+use mongodb::{
+    bson::{Bson, Document, doc},
+    error::Error as MongoError,
+    options::ReturnDocument,
+};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
 
 use crate::{
     adapters::{
         hashing::{hash_secret, verify_secret},
-        jwt::decode_access_token,
-        mongo::case_insensitive_collation,
-        repositories::Repository,
+        jwt::{AccessTokenPayload, decode_access_token},
+        mongo::{
+            analytics_selection_criteria, case_insensitive_collation, is_duplicate_key,
+            with_breaker, with_retry,
+        },
+        repositories::{
+            Repository,
+            counter_id::CounterId,
+            search::{Pagination, PlayerSearchCriteria},
+        },
     },
-    errors::{DBoError, DBoResult},
-    handlers::responses::PlayerInvalidFieldsResponse,
+    config::environment::ENV,
+    errors::{AuthnFailureReason, DBoError, DBoResult},
+    handlers::responses::{AccountLockedResponse, PlayerInvalidFieldsResponse},
     models::{
-        Collectible, Identifiable, Player,
-        player_validation::{validate_email, validate_password, validate_username},
+        Collectible, Counter, Identifiable, Player, RefreshToken, SeasonArchive, UndoToken,
+        player_validation::{
+            validate_avatar, validate_email, validate_email_for_account, validate_password,
+            validate_time_zone, validate_username_choice,
+        },
+        submodels::{AvatarRef, PlayerStats},
     },
 };
 
+/// An in-memory cache of recently authenticated players, keyed by their unique identifier, or None
+/// if caching is disabled.
+///
+/// Every method in this module which writes to a player document must remove that player from the
+/// cache once the write succeeds; otherwise authenticated requests could act on stale data (such
+/// as a `session_valid_after` from before the player's sessions were invalidated) until the entry
+/// expires.
+static PLAYER_CACHE: Lazy<Option<Cache<String, Player>>> = Lazy::new(|| {
+    ENV.cache_players.then(|| {
+        Cache::builder()
+            .max_capacity(ENV.player_cache_capacity)
+            .time_to_live(StdDuration::from_secs(ENV.player_cache_ttl_secs))
+            .build()
+    })
+});
+
+/// The number of evictions from the player cache so far. A player read from the database while an
+/// eviction took place may predate the write which caused it, so it must not be cached.
+static PLAYER_CACHE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Remove an entry from a cache, marking the start of a new generation first so that a concurrent
+/// read cannot put a stale value back (see `insert_unless_evicted`).
+///
+/// ### Arguments
+/// - `cache`: The cache to remove the entry from
+/// - `generation`: The cache's eviction counter
+/// - `key`: The key of the entry, or None to remove every entry
+fn evict<V>(cache: &Cache<String, V>, generation: &AtomicU64, key: Option<&str>)
+where
+    V: Clone + Send + Sync + 'static,
+{
+    generation.fetch_add(1, Ordering::SeqCst);
+
+    match key {
+        Some(k) => cache.invalidate(k),
+        None => cache.invalidate_all(),
+    }
+}
+
+/// Insert a value which was read from the database into a cache, unless an entry was evicted
+/// since the read began. Checking the generation again after the insertion (rather than before)
+/// ensures that an eviction racing with it either removes the new entry or is noticed here.
+///
+/// ### Arguments
+/// - `cache`: The cache to insert the value into
+/// - `generation`: The cache's eviction counter
+/// - `seen`: The value of the eviction counter before the read began
+/// - `key`: The key of the entry
+/// - `value`: The value which was read
+fn insert_unless_evicted<V>(
+    cache: &Cache<String, V>,
+    generation: &AtomicU64,
+    seen: u64,
+    key: &str,
+    value: V,
+) where
+    V: Clone + Send + Sync + 'static,
+{
+    cache.insert(String::from(key), value);
+
+    if generation.load(Ordering::SeqCst) != seen {
+        cache.invalidate(key);
+    }
+}
+
+/// Remove a player from the in-memory cache, if caching is enabled.
+///
+/// ### Arguments
+/// - `player_id`: The player's unique identifier
+fn forget_cached_player(player_id: &str) {
+    if let Some(cache) = PLAYER_CACHE.as_ref() {
+        evict(cache, &PLAYER_CACHE_GENERATION, Some(player_id));
+    }
+}
+
+/// The fields of a player document which are needed to archive their stats.
+#[derive(Deserialize)]
+struct StatsProjection {
+    player_id: String,
+    stats: PlayerStats,
+}
+
+/// Remove every player from the in-memory cache, if caching is enabled.
+fn forget_all_cached_players() {
+    if let Some(cache) = PLAYER_CACHE.as_ref() {
+        evict(cache, &PLAYER_CACHE_GENERATION, None);
+    }
+}
+
+/// Ensure that the player represented by an access token may still use it. If the player no
+/// longer exists (for example, because their account was deleted after the token was issued), the
+/// client's session is simply invalid, and the failure is reported as such. A player whose account
+/// is scheduled for deletion may not use it until the deletion is cancelled.
+///
+/// ### Arguments
+/// - `payload`: The decoded payload of the access token
+/// - `player`: The player represented by the token, if they could be found
+///
+/// ### Errors
+/// - `AuthenticationFailure` if the player cannot be found, or is scheduled for deletion
+/// - `TokenPremature` if the token was made before the player's sessions were invalidated
+fn token_player(payload: &AccessTokenPayload, player: Option<Player>) -> DBoResult<Player> {
+    let player = match player {
+        Some(p) => p,
+        None => {
+            return Err(DBoError::AuthenticationFailure(
+                AuthnFailureReason::PlayerNotFound,
+            ));
+        }
+    };
+
+    if payload.made_before(&player.valid_after().to_chrono()) {
+        return Err(DBoError::TokenPremature);
+    }
+
+    if player.deleted_at().is_some() {
+        return Err(DBoError::AuthenticationFailure(
+            AuthnFailureReason::PendingDeletion,
+        ));
+    }
+
+    Ok(player)
+}
+
+/// Determine which documents belonging to a merged account must be deleted alongside it, by their
+/// collection names and filters. Deleting its refresh tokens invalidates all of its sessions.
+///
+/// ### Arguments
+/// - `source_id`: The unique identifier of the account being merged
+fn merge_cleanup(source_id: &str) -> [(&'static str, Document); 2] {
+    [
+        (
+            RefreshToken::collection_name(),
+            doc! { "player_id": source_id },
+        ),
+        (
+            UndoToken::collection_name(),
+            doc! { "player_id": source_id },
+        ),
+    ]
+}
+
+/// Determine the season archives an account should be left with after another account belonging
+/// to the same player is merged into it. Each season archived for only one of the accounts is kept,
+/// and the stats of a season archived for both are summed.
+///
+/// ### Arguments
+/// - `source`: The archives of the account being merged
+/// - `target`: The archives of the account being kept
+/// - `target_id`: The unique identifier of the account being kept
+///
+/// ### Returns
+/// The archives which the kept account should have, ordered by season
+fn merged_archives(
+    source: &[SeasonArchive],
+    target: &[SeasonArchive],
+    target_id: &str,
+) -> Vec<SeasonArchive> {
+    let mut merged: BTreeMap<u64, SeasonArchive> = target
+        .iter()
+        .map(|archive| (archive.season(), archive.clone()))
+        .collect();
+
+    for archive in source {
+        let combined = match merged.get(&archive.season()) {
+            Some(kept) => SeasonArchive::new(
+                target_id,
+                archive.season(),
+                &kept.stats().combined(archive.stats()),
+                kept.archived(),
+            ),
+            None => SeasonArchive::new(
+                target_id,
+                archive.season(),
+                archive.stats(),
+                archive.archived(),
+            ),
+        };
+        merged.insert(archive.season(), combined);
+    }
+
+    merged.into_values().collect()
+}
+
+/// Determine the password hashes a player would be left with after undoing their most recent
+/// password change: the most recent of their previous hashes becomes current again, and the rest
+/// move forward, freeing up the last record.
+///
+/// ### Arguments
+/// - `last_passwords`: The player's previous password hashes, most recent first
+///
+/// ### Returns
+/// The restored password hash, alongside the remaining records, or None if there is no previous
+/// password to restore
+fn restored_passwords(last_passwords: &[String; 4]) -> Option<(String, [String; 4])> {
+    if last_passwords[0].is_empty() {
+        return None;
+    }
+
+    let mut records = last_passwords.clone();
+    records.rotate_left(1);
+    records[3] = String::new();
+
+    Some((last_passwords[0].clone(), records))
+}
+
+/// Select the stored time zones which must be repaired, because they cannot be parsed.
+///
+/// ### Arguments
+/// - `stored`: The distinct time zones stored across all players
+///
+/// ### Returns
+/// The stored time zones which are not recognized IANA time zone names
+fn invalid_time_zones(stored: &[Bson]) -> Vec<&str> {
+    stored
+        .iter()
+        .filter_map(|value| value.as_str())
+        .filter(|time_zone| validate_time_zone(time_zone).is_some())
+        .collect()
+}
+
 impl Repository<Player> {
     /// Find a player by their email address.
     ///
@@ -31,11 +278,12 @@ impl Repository<Player> {
     /// ### Errors
     /// - `AdapterError` if the query fails
     pub async fn find_by_email(&self, email: &str) -> DBoResult<Option<Player>> {
-        Ok(self
-            .collection
-            .find_one(doc! { "email": email })
-            .collation(case_insensitive_collation())
-            .await?)
+        Ok(with_retry(|| {
+            self.collection
+                .find_one(doc! { "email": email })
+                .collation(case_insensitive_collation())
+        })
+        .await?)
     }
 
     /// Find a player by their username.
@@ -49,11 +297,44 @@ impl Repository<Player> {
     /// ### Errors
     /// - `AdapterError` if the query fails
     pub async fn find_by_username(&self, username: &str) -> DBoResult<Option<Player>> {
-        Ok(self
-            .collection
-            .find_one(doc! { "username": username })
-            .collation(case_insensitive_collation())
-            .await?)
+        Ok(with_retry(|| {
+            self.collection
+                .find_one(doc! { "username": username })
+                .collation(case_insensitive_collation())
+        })
+        .await?)
+    }
+
+    /// Find every confirmed player whose username is in a list. Usernames which do not match a
+    /// confirmed player are simply left out of the results.
+    ///
+    /// ### Arguments
+    /// - `usernames`: The usernames to search for (case-insensitive)
+    ///
+    /// ### Returns
+    /// The matching players, sorted case-insensitively by username
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails, or a found document cannot be parsed into a Player
+    pub async fn find_confirmed_by_usernames(
+        &self,
+        usernames: &[String],
+    ) -> DBoResult<Vec<Player>> {
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! { "username": { "$in": usernames }, "confirmed": true })
+                .sort(doc! { "username": 1 })
+                .collation(case_insensitive_collation())
+        })
+        .await?;
+
+        let mut players = vec![];
+
+        while let Some(result) = cursor.next().await {
+            players.push(result?);
+        }
+
+        Ok(players)
     }
 
     /// Find a player by their username *or* email address.
@@ -71,19 +352,131 @@ impl Repository<Player> {
         &self,
         username_or_email: &str,
     ) -> DBoResult<Option<Player>> {
-        Ok(self
-            .collection
-            .find_one(doc! {
-                "$or": [
-                    { "username": username_or_email },
-                    { "email": username_or_email }
-                ]
-            })
-            .collation(case_insensitive_collation())
-            .await?)
+        Ok(with_retry(|| {
+            self.collection
+                .find_one(doc! {
+                    "$or": [
+                        { "username": username_or_email },
+                        { "email": username_or_email }
+                    ]
+                })
+                .collation(case_insensitive_collation())
+        })
+        .await?)
     }
 
-    /// Find a player via a JWT access token.
+    /// Search for players matching some criteria, one page at a time. Results are sorted
+    /// case-insensitively by username. As an analytics-style read, the search may be served by a
+    /// secondary replica.
+    ///
+    /// ### Arguments
+    /// - `criteria`: Describes which players should be included
+    /// - `pagination`: Describes which page of results should be returned
+    ///
+    /// ### Returns
+    /// The players on the requested page, and the total number of players matching the criteria
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a query fails, or a found document cannot be parsed into a Player
+    pub async fn search(
+        &self,
+        criteria: &PlayerSearchCriteria,
+        pagination: &Pagination,
+    ) -> DBoResult<(Vec<Player>, u64)> {
+        let filter = criteria.filter();
+
+        let total = with_retry(|| {
+            self.collection
+                .count_documents(filter.clone())
+                .selection_criteria(analytics_selection_criteria())
+        })
+        .await?;
+
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(filter.clone())
+                .selection_criteria(analytics_selection_criteria())
+                .sort(doc! { "username": 1 })
+                .collation(case_insensitive_collation())
+                .skip(pagination.skip())
+                .limit(pagination.limit())
+        })
+        .await?;
+
+        let mut players = vec![];
+
+        while let Some(result) = cursor.next().await {
+            players.push(result?);
+        }
+
+        Ok((players, total))
+    }
+
+    /// Find a player by their unique identifier, preferring the in-memory cache when it is
+    /// enabled. A player found in the database is added to the cache, unless a player was evicted
+    /// from it in the meantime.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    async fn find_by_id_cached(&self, player_id: &str) -> DBoResult<Option<Player>> {
+        let cache = match PLAYER_CACHE.as_ref() {
+            Some(c) => c,
+            None => return self.find_by_id(player_id).await,
+        };
+
+        if let Some(player) = cache.get(player_id) {
+            return Ok(Some(player));
+        }
+
+        let seen = PLAYER_CACHE_GENERATION.load(Ordering::SeqCst);
+        let player = self.find_by_id(player_id).await?;
+        if let Some(p) = &player {
+            insert_unless_evicted(cache, &PLAYER_CACHE_GENERATION, seen, player_id, p.clone());
+        }
+
+        Ok(player)
+    }
+
+    /// Find a player's rank among all confirmed players, by wins and then by fewest losses (see
+    /// `PlayerStats::outranking_filter`). Players with the same wins and losses share a rank. As an
+    /// analytics-style read, the count may be served by a secondary replica.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Returns
+    /// The player's 1-based rank, or None if the player cannot be found
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a query fails
+    pub async fn player_rank(&self, player_id: &str) -> DBoResult<Option<u64>> {
+        let player = match self.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+
+        let mut filter = player.stats().outranking_filter();
+        filter.insert("confirmed", true);
+
+        let outranking = with_retry(|| {
+            self.collection
+                .count_documents(filter.clone())
+                .selection_criteria(analytics_selection_criteria())
+        })
+        .await?;
+
+        Ok(Some(outranking + 1))
+    }
+
+    /// Find a player via a JWT access token. The player is returned whether or not their account
+    /// has been confirmed; actions which require a confirmed account should use
+    /// `find_by_token_confirmed` instead.
+    ///
+    /// This is the hot path of every authenticated request, so the player is read from the
+    /// in-memory cache when it is enabled.
     ///
     /// ### Arguments
     /// - `jwt`: The JWT
@@ -92,24 +485,73 @@ impl Repository<Player> {
     /// - `TokenExpired` if the JWT is expired
     /// - `TokenPremature` if the JWT was made before player sessions were invalidated
     /// - `InvalidToken` if the token is bad
-    /// - `MissingDocument` if the player cannot be found
+    /// - `AuthenticationFailure` if the player no longer exists, or is scheduled for deletion
     /// - `AdapterError` if the database query fails, or if the token cannot be decoded due to a
     ///   server-side error
     pub async fn find_by_token(&self, jwt: &str) -> DBoResult<Player> {
         let payload = decode_access_token(jwt)?;
+        let player = self.find_by_id_cached(payload.sub()).await?;
 
-        let player = match self.find_by_id(payload.sub()).await? {
-            Some(p) => p,
-            None => return Err(DBoError::missing_document(Player::collection_name())),
-        };
+        token_player(&payload, player)
+    }
+
+    /// Find a player via a JWT access token, ensuring that their account has been confirmed.
+    ///
+    /// ### Arguments
+    /// - `jwt`: The JWT
+    ///
+    /// ### Errors
+    /// - `AccountUnconfirmed` if the player's email address has never been confirmed
+    /// - Any error returned by `find_by_token`
+    pub async fn find_by_token_confirmed(&self, jwt: &str) -> DBoResult<Player> {
+        let player = self.find_by_token(jwt).await?;
 
-        if payload.made_before(&player.valid_after().to_chrono()) {
-            return Err(DBoError::TokenPremature);
+        if !player.confirmed() {
+            return Err(DBoError::AccountUnconfirmed);
         }
 
         Ok(player)
     }
 
+    /// Reset every stored time zone which cannot be parsed (such as those imported before time
+    /// zones were validated) to a default, so that timestamps can be localized in emails again.
+    /// Each repaired value is logged.
+    ///
+    /// ### Arguments
+    /// - `default_tz`: The IANA time zone to store instead
+    ///
+    /// ### Returns
+    /// The number of players whose time zone was repaired
+    ///
+    /// ### Errors
+    /// - `AdapterError` if any query fails
+    pub async fn fix_invalid_time_zones(&self, default_tz: &str) -> DBoResult<u64> {
+        let stored = with_retry(|| self.collection.distinct("time_zone", doc! {})).await?;
+
+        let mut repaired = 0;
+        for time_zone in invalid_time_zones(&stored) {
+            let update = with_retry(|| {
+                self.collection.update_many(
+                    doc! { "time_zone": time_zone },
+                    doc! { "$set": { "time_zone": default_tz } },
+                )
+            })
+            .await?;
+
+            println!(
+                "Reset the invalid time zone {:?} of {} players to {:?}.",
+                time_zone, update.modified_count, default_tz
+            );
+            repaired += update.modified_count;
+        }
+
+        if repaired > 0 {
+            forget_all_cached_players();
+        }
+
+        Ok(repaired)
+    }
+
     /// Insert a new player into the database.
     ///
     /// ### Arguments
@@ -129,7 +571,7 @@ impl Repository<Player> {
                 existing_email,
             ))
         } else {
-            self.collection.insert_one(player).await?;
+            with_breaker(self.collection.insert_one(player)).await?;
             Ok(())
         }
     }
@@ -143,13 +585,64 @@ impl Repository<Player> {
     /// - `MissingDocument` if the player cannot be found
     /// - `AdapterError` if the query fails
     pub async fn confirm(&self, player_id: &str) -> DBoResult<()> {
-        let update = self
-            .collection
-            .update_one(
+        let update = with_retry(|| {
+            self.collection.update_one(
                 doc! { Player::id_field(): player_id },
                 doc! { "$set": { "confirmed": true } },
             )
-            .await?;
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::missing_document(Player::collection_name())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Update a player's avatar in the database. Ensure that the new avatar is valid, according to
+    /// the configured presets and avatar storage.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    /// - `avatar`: The player's new avatar, or None to remove their avatar
+    ///
+    /// ### Errors
+    /// - `InvalidPlayerInfo` if the avatar does not pass validation checks
+    /// - `MissingDocument` if the player cannot be found
+    /// - `AdapterError` if the query fails
+    pub async fn update_avatar(
+        &self,
+        player_id: &str,
+        avatar: Option<&AvatarRef>,
+    ) -> DBoResult<()> {
+        if let Some(a) = avatar {
+            let probs =
+                validate_avatar(a, &ENV.avatar_presets, ENV.avatar_storage_prefix.as_deref());
+
+            if probs.is_some() {
+                return Err(DBoError::invalid_player_info(
+                    PlayerInvalidFieldsResponse::new(None, None, None, None, None, probs),
+                ));
+            }
+        }
+
+        let value = bson::to_bson(&avatar).map_err(|e| {
+            eprintln!("An avatar could not be serialized!");
+            eprintln!("{:?}", e);
+            DBoError::AdapterError
+        })?;
+
+        let update = with_retry(|| {
+            self.collection.update_one(
+                doc! { Player::id_field(): player_id },
+                doc! { "$set": { "avatar": value.clone() } },
+            )
+        })
+        .await?;
+
+        forget_cached_player(player_id);
 
         match update.matched_count {
             0 => Err(DBoError::missing_document(Player::collection_name())),
@@ -185,20 +678,93 @@ impl Repository<Player> {
             Some(DateTime::from_chrono(Utc::now() + lockout_time))
         };
 
-        self.collection
-            .find_one_and_update(
-                doc! { Player::id_field(): player_id },
-                doc! { "$set": {
-                    "failed_logins": failed_logins as i32,
-                    "locked_until": lockout_end
-                } },
-            )
-            .return_document(ReturnDocument::After)
-            .await?;
+        with_retry(|| {
+            self.collection
+                .find_one_and_update(
+                    doc! { Player::id_field(): player_id },
+                    doc! { "$set": {
+                        "failed_logins": failed_logins as i32,
+                        "locked_until": lockout_end
+                    } },
+                )
+                .return_document(ReturnDocument::After)
+        })
+        .await?;
+        forget_cached_player(player_id);
 
         Ok(lockout_end)
     }
 
+    /// Claim the right to send a lockout email to a player, by recording it as sent. The claim only
+    /// succeeds if no lockout email has been sent since the player's last successful login, so
+    /// that concurrent failed logins cannot each send one.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    /// - `sent_at`: The time at which the email is sent
+    ///
+    /// ### Returns
+    /// Whether the email should be sent
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn claim_lockout_email(
+        &self,
+        player_id: &str,
+        sent_at: &DateTime,
+    ) -> DBoResult<bool> {
+        let update = with_breaker(async {
+            self.collection
+                .update_one(
+                    doc! {
+                        Player::id_field(): player_id,
+                        "$or": [
+                            { "last_lockout_email_at": null },
+                            { "$expr": { "$lt": ["$last_lockout_email_at", "$last_login"] } },
+                        ],
+                    },
+                    doc! { "$set": { "last_lockout_email_at": sent_at } },
+                )
+                .await
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        Ok(update.modified_count > 0)
+    }
+
+    /// Release a claim to send a lockout email which could not be sent after all, so that the
+    /// next lockout can try again. Nothing happens if the claim was superseded in the meantime.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    /// - `sent_at`: The time at which the claim was made
+    /// - `previous`: The time at which the previous lockout email was sent, if ever
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn release_lockout_email(
+        &self,
+        player_id: &str,
+        sent_at: &DateTime,
+        previous: Option<&DateTime>,
+    ) -> DBoResult<()> {
+        with_breaker(async {
+            self.collection
+                .update_one(
+                    doc! { Player::id_field(): player_id, "last_lockout_email_at": sent_at },
+                    doc! { "$set": { "last_lockout_email_at": previous } },
+                )
+                .await
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        Ok(())
+    }
+
     /// Record a successful login in the database, resetting the `failed_logins` field to `0` and
     /// `locked_until` back to `None`.
     ///
@@ -218,14 +784,15 @@ impl Repository<Player> {
         };
 
         if player.locked() {
-            return Err(DBoError::AccountLocked(
+            return Err(DBoError::AccountLocked(AccountLockedResponse::new(
                 player.locked_until().unwrap().to_chrono(),
-            ));
+                player.preferred_language(),
+                player.time_zone(),
+            )));
         }
 
-        let update = self
-            .collection
-            .update_one(
+        let update = with_retry(|| {
+            self.collection.update_one(
                 doc! { Player::id_field(): player_id },
                 doc! { "$set": {
                     "last_login": DateTime::now(),
@@ -233,7 +800,10 @@ impl Repository<Player> {
                     "locked_until": None::<DateTime>
                 } },
             )
-            .await?;
+        })
+        .await?;
+
+        forget_cached_player(player_id);
 
         match update.matched_count {
             0 => Err(DBoError::missing_document(Player::collection_name())),
@@ -247,18 +817,24 @@ impl Repository<Player> {
     ///
     /// ### Arguments
     /// - `player_id`: The player's unique identifier
-    /// - `value`: The new email address
+    /// - `value`: The new username
+    /// - `allow_reserved`: Whether the player may claim a reserved username (only admins may)
     ///
     /// ### Errors
     /// - `InvalidPlayerInfo` if the username does not pass validation checks
     /// - `UniquenessViolation` if the username is already taken
     /// - `MissingDocument` if the player cannot be found
     /// - `AdapterError` if any database query should fail
-    pub async fn update_username(&self, player_id: &str, value: &str) -> DBoResult<()> {
-        let probs = validate_username(value);
+    pub async fn update_username(
+        &self,
+        player_id: &str,
+        value: &str,
+        allow_reserved: bool,
+    ) -> DBoResult<()> {
+        let probs = validate_username_choice(value, allow_reserved);
         if probs.is_some() {
-            return Err(DBoError::InvalidPlayerInfo(
-                PlayerInvalidFieldsResponse::new(probs, None, None),
+            return Err(DBoError::invalid_player_info(
+                PlayerInvalidFieldsResponse::new(probs, None, None, None, None, None),
             ));
         }
 
@@ -268,16 +844,18 @@ impl Repository<Player> {
             return Err(DBoError::UniquenessViolation(true, false));
         }
 
-        let update = self
-            .collection
-            .update_one(
+        let update = with_retry(|| {
+            self.collection.update_one(
                 doc! { Player::id_field(): player_id},
                 doc! { "$set": {
                    "username": value,
                    "session_valid_after": DateTime::now()
                 } },
             )
-            .await?;
+        })
+        .await?;
+
+        forget_cached_player(player_id);
 
         match update.matched_count {
             0 => Err(DBoError::missing_document(Player::collection_name())),
@@ -285,23 +863,29 @@ impl Repository<Player> {
         }
     }
 
-    /// Update a player's proposed email address. Validate the new value and ensure that it is
-    /// case-insensitively unique. Update the "proposed_email" field in the player document.
+    /// Update a player's proposed email address. Validate the new value (rejecting disposable
+    /// domains while `ENV.block_disposable_email` is enabled, just as registration does) and ensure
+    /// that it is case-insensitively unique. Update the "proposed_email" field in the player
+    /// document.
+    ///
+    /// No two players may propose the same email address at once; this is enforced by a unique
+    /// index, so that two simultaneous proposals cannot both succeed.
     ///
     /// ### Arguments
     /// - `player_id`: The player's unique identifier
     /// - `value`: The new proposed email address
     ///
     /// ### Errors
-    /// - `InvalidPlayerInfo` if the email address cannot be validated
-    /// - `UniquenessViolation` if the email address is already in use
+    /// - `InvalidPlayerInfo` if the email address cannot be validated, or its domain is disposable
+    /// - `UniquenessViolation` if the email address is already in use, or already proposed by
+    ///   another player
     /// - `MissingDocument` if the player cannot be found
     /// - `AdapterError` if any database query should fail
     pub async fn update_proposed_email(&self, player_id: &str, value: &str) -> DBoResult<()> {
-        let probs = validate_email(value);
+        let probs = validate_email_for_account(value);
         if probs.is_some() {
-            return Err(DBoError::InvalidPlayerInfo(
-                PlayerInvalidFieldsResponse::new(None, None, probs),
+            return Err(DBoError::invalid_player_info(
+                PlayerInvalidFieldsResponse::new(None, None, probs, None, None, None),
             ));
         }
 
@@ -309,13 +893,62 @@ impl Repository<Player> {
             return Err(DBoError::UniquenessViolation(false, true));
         }
 
-        let update = self
-            .collection
-            .update_one(
+        let update = match with_retry(|| {
+            self.collection.update_one(
                 doc! { Player::id_field(): player_id},
                 doc! { "$set": { "proposed_email": value } },
             )
-            .await?;
+        })
+        .await
+        {
+            Ok(u) => u,
+            Err(e) if is_duplicate_key(&e) => {
+                return Err(DBoError::UniquenessViolation(false, true));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::missing_document(Player::collection_name())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace the email address of an unconfirmed player, so that a typo made during registration
+    /// can be corrected before the account is confirmed. The new value must already be validated;
+    /// this only ensures that it is case-insensitively unique.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    /// - `value`: The corrected email address
+    ///
+    /// ### Errors
+    /// - `UniquenessViolation` if the email address is already in use
+    /// - `MissingDocument` if no unconfirmed player can be found with that id
+    /// - `AdapterError` if any database query should fail
+    pub async fn update_unconfirmed_email(&self, player_id: &str, value: &str) -> DBoResult<()> {
+        if self.find_by_email(value).await?.is_some() {
+            return Err(DBoError::UniquenessViolation(false, true));
+        }
+
+        let update = match with_retry(|| {
+            self.collection.update_one(
+                doc! { Player::id_field(): player_id, "confirmed": false },
+                doc! { "$set": { "email": value } },
+            )
+        })
+        .await
+        {
+            Ok(u) => u,
+            Err(e) if is_duplicate_key(&e) => {
+                return Err(DBoError::UniquenessViolation(false, true));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        forget_cached_player(player_id);
 
         match update.matched_count {
             0 => Err(DBoError::missing_document(Player::collection_name())),
@@ -336,7 +969,8 @@ impl Repository<Player> {
     /// - `MissingDocument` if the player cannot be found
     /// - `InternalConflict` if the player does not have a proposed email address
     /// - `InvalidPlayerInfo` if the email address cannot be validated
-    /// - `UniquenessViolation` if the email address is already in use
+    /// - `UniquenessViolation` if the email address is already in use, including when another
+    ///   player claims it between the uniqueness check and the update
     /// - `AdapterError` if a database query should fail
     pub async fn confirm_proposed_email(&self, player_id: &str) -> DBoResult<()> {
         let player = match self.find_by_id(player_id).await? {
@@ -352,18 +986,17 @@ impl Repository<Player> {
         let probs = validate_email(proposed);
 
         if probs.is_some() {
-            return Err(DBoError::InvalidPlayerInfo(
-                PlayerInvalidFieldsResponse::new(None, None, probs),
+            return Err(DBoError::invalid_player_info(
+                PlayerInvalidFieldsResponse::new(None, None, probs, None, None, None),
             ));
         }
 
-        if self.find_by_email(&proposed).await?.is_some() {
+        if self.find_by_email(proposed).await?.is_some() {
             return Err(DBoError::UniquenessViolation(false, true));
         }
 
-        let update = self
-            .collection
-            .update_one(
+        let update = match with_retry(|| {
+            self.collection.update_one(
                 doc! { Player::id_field(): player_id },
                 doc! { "$set": {
                     "email": player.proposed_email(),
@@ -371,10 +1004,20 @@ impl Repository<Player> {
                     "session_valid_after": DateTime::now()
                 } },
             )
-            .await?;
+        })
+        .await
+        {
+            Ok(u) => u,
+            Err(e) if is_duplicate_key(&e) => {
+                return Err(DBoError::UniquenessViolation(false, true));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        forget_cached_player(player_id);
 
         match update.matched_count {
-            0 => return Err(DBoError::missing_document(Player::collection_name())),
+            0 => Err(DBoError::missing_document(Player::collection_name())),
             _ => Ok(()),
         }
     }
@@ -399,8 +1042,8 @@ impl Repository<Player> {
     pub async fn update_password(&self, player_id: &str, value: &str) -> DBoResult<()> {
         let probs = validate_password(value);
         if probs.is_some() {
-            return Err(DBoError::InvalidPlayerInfo(
-                PlayerInvalidFieldsResponse::new(None, probs, None),
+            return Err(DBoError::invalid_player_info(
+                PlayerInvalidFieldsResponse::new(None, probs, None, None, None, None),
             ));
         }
 
@@ -429,9 +1072,8 @@ impl Repository<Player> {
 
         let hash = hash_secret(value)?;
 
-        let update = self
-            .collection
-            .update_one(
+        let update = with_retry(|| {
+            self.collection.update_one(
                 doc! { Player::id_field(): player_id },
                 doc! { "$set": {
                     "password": &hash,
@@ -439,11 +1081,642 @@ impl Repository<Player> {
                     "session_valid_after": DateTime::now()
                 } },
             )
-            .await?;
+        })
+        .await?;
+
+        forget_cached_player(player_id);
 
         match update.matched_count {
             0 => Err(DBoError::missing_document(Player::collection_name())),
             _ => Ok(()),
         }
     }
+
+    /// Replace a player's password hash with a new hash of the same password, such as once a
+    /// pepper has been introduced. The player's sessions and previous passwords are unaffected.
+    /// Nothing is changed if the player's password was changed in the meantime.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    /// - `old_hash`: The hash which is being replaced
+    /// - `value`: The player's password, which was just verified against the old hash
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the password cannot be hashed, or if the database query should fail
+    pub async fn rehash_password(
+        &self,
+        player_id: &str,
+        old_hash: &str,
+        value: &str,
+    ) -> DBoResult<()> {
+        let hash = hash_secret(value)?;
+
+        with_retry(|| {
+            self.collection.update_one(
+                doc! { Player::id_field(): player_id, "password": old_hash },
+                doc! { "$set": { "password": &hash } },
+            )
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        Ok(())
+    }
+
+    /// Restore a player's previous password, undoing their most recent password change. Replace
+    /// their "password" field with the most recent of their "last_passwords", and move the rest of
+    /// the records forward, freeing up the last one. Invalidate the player's access tokens by
+    /// changing their "session_valid_after" field.
+    ///
+    /// The update only applies while the player's password is still the one which was read, so
+    /// that a password changed in the meantime is never silently reverted.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player cannot be found
+    /// - `InternalConflict` if the player has no previous password to restore, or if their
+    ///   password was changed in the meantime
+    /// - `AdapterError` if the database query should fail
+    pub async fn restore_previous_password(&self, player_id: &str) -> DBoResult<()> {
+        let player = match self.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => return Err(DBoError::missing_document(Player::collection_name())),
+        };
+
+        let (hash, records) = match restored_passwords(player.last_passwords()) {
+            Some(r) => r,
+            None => return Err(DBoError::InternalConflict),
+        };
+
+        let update = with_breaker(self.collection.update_one(
+            doc! { Player::id_field(): player_id, "password": player.password() },
+            doc! { "$set": {
+                "password": &hash,
+                "last_passwords": records.to_vec(),
+                "session_valid_after": DateTime::now()
+            } },
+        ))
+        .await?;
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::InternalConflict),
+            _ => Ok(()),
+        }
+    }
+
+    /// Withdraw a player's proposed email address, leaving their current one in place. Change the
+    /// "proposed_email" field back to none, and invalidate the player's access tokens by changing
+    /// their "session_valid_after" field.
+    ///
+    /// The update only applies while the proposed email address is still the one which was read,
+    /// so that an address proposed in the meantime is never silently withdrawn.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player cannot be found
+    /// - `InternalConflict` if the player does not have a proposed email address, or if it was
+    ///   changed in the meantime
+    /// - `AdapterError` if the database query should fail
+    pub async fn withdraw_proposed_email(&self, player_id: &str) -> DBoResult<()> {
+        let player = match self.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => return Err(DBoError::missing_document(Player::collection_name())),
+        };
+
+        let proposed = match player.proposed_email() {
+            Some(email) => email,
+            None => return Err(DBoError::InternalConflict),
+        };
+
+        let update = with_breaker(self.collection.update_one(
+            doc! { Player::id_field(): player_id, "proposed_email": proposed },
+            doc! { "$set": {
+                "proposed_email": None::<String>,
+                "session_valid_after": DateTime::now()
+            } },
+        ))
+        .await?;
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::InternalConflict),
+            _ => Ok(()),
+        }
+    }
+
+    /// Schedule a player account for deletion. Ensure that the account is not already scheduled
+    /// for deletion.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    /// - `deleted_at`: The time at which the account should be deleted
+    ///
+    /// ### Errors
+    /// - `InternalConflict` if the account is already scheduled for deletion
+    /// - `MissingDocument` if the player cannot be found
+    /// - `AdapterError` if a database query should fail
+    pub async fn schedule_deletion(&self, player_id: &str, deleted_at: &DateTime) -> DBoResult<()> {
+        let player = match self.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => return Err(DBoError::missing_document(Player::collection_name())),
+        };
+
+        if player.deleted_at().is_some() {
+            return Err(DBoError::InternalConflict);
+        }
+
+        let update = with_retry(|| {
+            self.collection.update_one(
+                doc! { Player::id_field(): player_id },
+                doc! { "$set": { "deleted_at": deleted_at } },
+            )
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::missing_document(Player::collection_name())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Cancel the scheduled deletion of a player account.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `InternalConflict` if the account is not scheduled for deletion
+    /// - `MissingDocument` if the player cannot be found
+    /// - `AdapterError` if a database query should fail
+    pub async fn cancel_deletion(&self, player_id: &str) -> DBoResult<()> {
+        let player = match self.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => return Err(DBoError::missing_document(Player::collection_name())),
+        };
+
+        if player.deleted_at().is_none() {
+            return Err(DBoError::InternalConflict);
+        }
+
+        let update = with_retry(|| {
+            self.collection.update_one(
+                doc! { Player::id_field(): player_id },
+                doc! { "$set": { "deleted_at": None::<DateTime> } },
+            )
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::missing_document(Player::collection_name())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Invalidate every access token issued to a player so far, by changing their
+    /// "session_valid_after" field.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player cannot be found
+    /// - `AdapterError` if the database query should fail
+    pub async fn invalidate_sessions(&self, player_id: &str) -> DBoResult<()> {
+        let update = with_retry(|| {
+            self.collection.update_one(
+                doc! { Player::id_field(): player_id },
+                doc! { "$set": { "session_valid_after": DateTime::now() } },
+            )
+        })
+        .await?;
+
+        forget_cached_player(player_id);
+
+        match update.matched_count {
+            0 => Err(DBoError::missing_document(Player::collection_name())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Find all player accounts whose deletion grace period has ended.
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails, or a found document cannot be parsed into a Player
+    pub async fn find_due_for_deletion(&self) -> DBoResult<Vec<Player>> {
+        let mut players = vec![];
+
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! { "deleted_at": { "$lte": DateTime::now() } })
+        })
+        .await?;
+
+        while let Some(result) = cursor.next().await {
+            players.push(result?);
+        }
+
+        Ok(players)
+    }
+
+    /// Delete a player account immediately.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Returns
+    /// The deleted player, or None if they could not be found
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn delete_player(&self, player_id: &str) -> DBoResult<Option<Player>> {
+        let player = self.delete(player_id).await?;
+
+        forget_cached_player(player_id);
+
+        Ok(player)
+    }
+
+    /// Delete a player account, but only if its deletion grace period has ended. This guards
+    /// against deleting an account whose deletion was cancelled after it was found.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Returns
+    /// Whether the account was deleted
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn delete_if_due(&self, player_id: &str) -> DBoResult<bool> {
+        // A retry could not tell whether the first attempt deleted the player, so it is not
+        // retried.
+        let deletion = with_breaker(self.collection.delete_one(doc! {
+            Player::id_field(): player_id,
+            "deleted_at": { "$lte": DateTime::now() }
+        }))
+        .await?;
+
+        forget_cached_player(player_id);
+
+        Ok(deletion.deleted_count == 1)
+    }
+
+    /// Merge one player account into another, within a single transaction: the target account
+    /// receives the combined stats and season archives of both accounts (see `merged_archives`),
+    /// and the source account is deleted, alongside its refresh tokens and undo tokens (see
+    /// `merge_cleanup`). If either account cannot be found, nothing is changed.
+    ///
+    /// ### Arguments
+    /// - `archives`: The Season Archive repository
+    /// - `source_id`: The unique identifier of the account to merge (and delete)
+    /// - `target_id`: The unique identifier of the account to keep
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if either player cannot be found
+    /// - `AdapterError` if a database query fails, or if the transaction cannot be committed
+    pub async fn merge_into(
+        &self,
+        archives: &Repository<SeasonArchive>,
+        source_id: &str,
+        target_id: &str,
+    ) -> DBoResult<()> {
+        // The whole transaction is guarded by the circuit breaker, but is never retried.
+        let merged = with_breaker(async {
+            let mut session = self.collection.client().start_session().await?;
+            session.start_transaction().await?;
+
+            let source = self
+                .collection
+                .find_one(doc! { Player::id_field(): source_id })
+                .session(&mut session)
+                .await?;
+            let target = self
+                .collection
+                .find_one(doc! { Player::id_field(): target_id })
+                .session(&mut session)
+                .await?;
+
+            // Dropping the session without committing aborts the transaction.
+            let (source, target) = match (source, target) {
+                (Some(s), Some(t)) => (s, t),
+                _ => return Ok(false),
+            };
+
+            let stats = bson::to_bson(&target.stats().combined(source.stats()))?;
+
+            self.collection
+                .update_one(
+                    doc! { Player::id_field(): target_id },
+                    doc! { "$set": { "stats": stats } },
+                )
+                .session(&mut session)
+                .await?;
+            self.collection
+                .delete_one(doc! { Player::id_field(): source_id })
+                .session(&mut session)
+                .await?;
+
+            let database = self
+                .collection
+                .client()
+                .database(&self.collection.namespace().db);
+            for (collection, filter) in merge_cleanup(source_id) {
+                database
+                    .collection::<Document>(collection)
+                    .delete_many(filter)
+                    .session(&mut session)
+                    .await?;
+            }
+
+            let both = doc! { "player_id": { "$in": [source_id, target_id] } };
+            let mut cursor = archives
+                .collection
+                .find(both.clone())
+                .session(&mut session)
+                .await?;
+
+            let (mut source_archives, mut target_archives) = (vec![], vec![]);
+
+            while let Some(result) = cursor.next(&mut session).await {
+                let archive = result?;
+                if archive.player_id() == source_id {
+                    source_archives.push(archive);
+                } else {
+                    target_archives.push(archive);
+                }
+            }
+
+            // Only the target's archives need to change if the source has none.
+            if !source_archives.is_empty() {
+                archives
+                    .collection
+                    .delete_many(both)
+                    .session(&mut session)
+                    .await?;
+                archives
+                    .collection
+                    .insert_many(merged_archives(
+                        &source_archives,
+                        &target_archives,
+                        target_id,
+                    ))
+                    .session(&mut session)
+                    .await?;
+            }
+
+            session.commit_transaction().await?;
+
+            Ok::<_, MongoError>(true)
+        })
+        .await?;
+
+        if !merged {
+            return Err(DBoError::missing_document(Player::collection_name()));
+        }
+
+        forget_cached_player(source_id);
+        forget_cached_player(target_id);
+
+        Ok(())
+    }
+
+    /// End the current competitive season. Every confirmed player's current stats are archived
+    /// under the number of the ending season, and then reset to zero. Unconfirmed players are left
+    /// alone, as their stats would otherwise be lost without an archive. The season counter, the
+    /// archives and the reset are all written within a single transaction, so a failure leaves
+    /// every player's stats untouched.
+    ///
+    /// ### Arguments
+    /// - `archives`: The Season Archive repository
+    /// - `counters`: The Counter repository
+    /// - `ended`: The time at which the season ends
+    ///
+    /// ### Returns
+    /// The number of the season which ended, and the number of players whose stats were archived
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails, or if the transaction cannot be committed
+    pub async fn end_season(
+        &self,
+        archives: &Repository<SeasonArchive>,
+        counters: &Repository<Counter>,
+        ended: &DateTime,
+    ) -> DBoResult<(u64, u64)> {
+        // The whole transaction is guarded by the circuit breaker, but is never retried.
+        let (season, archived) = with_breaker(async {
+            let mut session = self.collection.client().start_session().await?;
+            session.start_transaction().await?;
+
+            let season = counters
+                .collection
+                .find_one_and_update(
+                    doc! { Counter::id_field(): CounterId::SeasonsEnded.to_string() },
+                    doc! { "$inc": { "count": 1 } },
+                )
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .session(&mut session)
+                .await?
+                .map(|c| c.count())
+                .unwrap_or(1);
+
+            let mut cursor = self
+                .collection
+                .clone_with_type::<StatsProjection>()
+                .find(doc! { "confirmed": true })
+                .projection(doc! { Player::id_field(): 1, "stats": 1 })
+                .session(&mut session)
+                .await?;
+
+            let mut season_archives = vec![];
+
+            while let Some(result) = cursor.next(&mut session).await {
+                let player = result?;
+                season_archives.push(SeasonArchive::new(
+                    &player.player_id,
+                    season,
+                    &player.stats,
+                    ended,
+                ));
+            }
+
+            if !season_archives.is_empty() {
+                archives
+                    .collection
+                    .insert_many(&season_archives)
+                    .session(&mut session)
+                    .await?;
+            }
+
+            // The transaction reads from one snapshot, so exactly the archived players are reset.
+            let reset = bson::to_bson(&PlayerStats::default())?;
+            self.collection
+                .update_many(
+                    doc! { "confirmed": true },
+                    doc! { "$set": { "stats": reset } },
+                )
+                .session(&mut session)
+                .await?;
+
+            session.commit_transaction().await?;
+
+            Ok::<_, MongoError>((season, season_archives.len() as u64))
+        })
+        .await?;
+
+        forget_all_cached_players();
+
+        Ok((season, archived))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::array;
+
+    use super::*;
+
+    #[test]
+    fn test_invalid_time_zone_selection() {
+        let stored = [
+            Bson::from("America/Los_Angeles"),
+            Bson::from("UTC"),
+            Bson::from("Pacific Standard Time"),
+            Bson::from("america/los_angeles"),
+            Bson::from(""),
+            Bson::Null,
+        ];
+
+        assert_eq!(
+            invalid_time_zones(&stored),
+            vec!["Pacific Standard Time", "america/los_angeles", ""]
+        );
+    }
+
+    #[test]
+    fn test_token_of_deleted_player() {
+        // The token is still valid, but its player was deleted before it was used.
+        let payload = AccessTokenPayload::new("deleted-player");
+
+        assert!(matches!(
+            token_player(&payload, None),
+            Err(DBoError::AuthenticationFailure(
+                AuthnFailureReason::PlayerNotFound
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_cache_read_through() {
+        let cache: Cache<String, u32> = Cache::new(8);
+        let generation = AtomicU64::new(0);
+
+        let seen = generation.load(Ordering::SeqCst);
+        insert_unless_evicted(&cache, &generation, seen, "player", 1);
+        assert_eq!(cache.get("player"), Some(1));
+    }
+
+    #[test]
+    fn test_cache_skips_stale_reads() {
+        let cache: Cache<String, u32> = Cache::new(8);
+        let generation = AtomicU64::new(0);
+
+        // A write is made while the old value is being read from the database.
+        let seen = generation.load(Ordering::SeqCst);
+        evict(&cache, &generation, Some("player"));
+        insert_unless_evicted(&cache, &generation, seen, "player", 1);
+        assert_eq!(cache.get("player"), None);
+
+        // Evicting every entry also prevents stale values from being cached.
+        let seen = generation.load(Ordering::SeqCst);
+        evict(&cache, &generation, None);
+        insert_unless_evicted(&cache, &generation, seen, "other", 2);
+        assert_eq!(cache.get("other"), None);
+    }
+
+    #[test]
+    fn test_merge_invalidates_source_sessions() {
+        let cleanup = merge_cleanup("source");
+
+        assert!(cleanup.contains(&(
+            RefreshToken::collection_name(),
+            doc! { "player_id": "source" }
+        )));
+        assert!(
+            cleanup
+                .iter()
+                .all(|(_, filter)| filter == &doc! { "player_id": "source" })
+        );
+    }
+
+    #[test]
+    fn test_merged_archives() {
+        let archived = DateTime::now();
+        let stats = |wins: i64| -> PlayerStats {
+            bson::from_document(doc! { "wins": wins, "losses": 1_i64, "dropouts": 0_i64 }).unwrap()
+        };
+        let wins_and_losses = |stats: &PlayerStats| {
+            let stats = bson::to_document(stats).unwrap();
+            (
+                stats.get_i64("wins").unwrap(),
+                stats.get_i64("losses").unwrap(),
+            )
+        };
+
+        let source = [
+            SeasonArchive::new("source", 1, &stats(2), &archived),
+            SeasonArchive::new("source", 3, &stats(5), &archived),
+        ];
+        let target = [
+            SeasonArchive::new("target", 2, &stats(4), &archived),
+            SeasonArchive::new("target", 3, &stats(1), &archived),
+        ];
+
+        let merged = merged_archives(&source, &target, "target");
+        let seasons: Vec<_> = merged
+            .iter()
+            .map(|a| (a.player_id(), a.season(), wins_and_losses(a.stats())))
+            .collect();
+
+        assert_eq!(
+            seasons,
+            [
+                ("target", 1, (2, 1)),
+                ("target", 2, (4, 1)),
+                ("target", 3, (6, 2))
+            ]
+        );
+
+        // The target keeps its archives as they were when the source has none.
+        assert_eq!(merged_archives(&[], &target, "target").len(), 2);
+    }
+
+    #[test]
+    fn test_restored_passwords() {
+        let last = [
+            String::from("b"),
+            String::from("c"),
+            String::from("d"),
+            String::from("e"),
+        ];
+        let (hash, records) = restored_passwords(&last).unwrap();
+
+        assert_eq!(hash, "b");
+        assert_eq!(records, ["c", "d", "e", ""]);
+
+        // A player who has never changed their password has nothing to restore.
+        assert!(restored_passwords(&array::from_fn(|_| String::new())).is_none());
+    }
 }
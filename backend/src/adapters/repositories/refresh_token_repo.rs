@@ -2,14 +2,21 @@ use futures::StreamExt;
 use mongodb::bson::doc;
 
 use crate::{
-    adapters::repositories::Repository,
+    adapters::{
+        mongo::{with_breaker, with_retry},
+        repositories::Repository,
+    },
     errors::{DBoError, DBoResult},
-    models::{Collectible, Identifiable, RefreshToken},
+    models::{Collectible, Expirable, Identifiable, RefreshToken},
 };
 
+/// The maximum number of refresh tokens kept for a single player.
+const MAX_PLAYER_TOKENS: usize = 3;
+
 impl Repository<RefreshToken> {
     /// Insert a new RefreshToken into the database. If there are more than three refresh tokens
-    /// for the player, delete the oldest ones until there are only three.
+    /// for the player, delete the oldest ones until there are only three. The new token is never
+    /// deleted, even if another token shares its creation time.
     ///
     /// ### Arguments
     /// - `token`: The refresh token to insert.
@@ -17,18 +24,12 @@ impl Repository<RefreshToken> {
     /// ### Errors
     /// - `AdapterError` if a query fails.
     pub async fn insert(&self, token: &RefreshToken) -> DBoResult<()> {
-        self.collection.insert_one(token).await?;
+        with_breaker(self.collection.insert_one(token)).await?;
 
         let tokens = self.find_player_tokens(token.player_id()).await?;
 
-        let to_delete = if tokens.len() >= 3 {
-            tokens.len() - 3
-        } else {
-            0
-        };
-
-        for i in 0..to_delete {
-            self.delete(tokens[i].id()).await?;
+        for pruned in tokens_to_prune(&tokens, token.id()) {
+            self.delete(pruned.id()).await?;
         }
 
         Ok(())
@@ -42,9 +43,7 @@ impl Repository<RefreshToken> {
     /// ### Errors
     /// - `AdapterError` if the query should fail
     pub async fn delete_player_tokens(&self, player_id: &str) -> DBoResult<()> {
-        self.collection
-            .delete_many(doc! { "player_id": player_id })
-            .await?;
+        with_retry(|| self.collection.delete_many(doc! { "player_id": player_id })).await?;
         Ok(())
     }
 
@@ -58,10 +57,11 @@ impl Repository<RefreshToken> {
     /// - `MissingDocument` if the old token could not be found
     /// - `AdapterError` if the query should fail
     pub async fn replace(&self, old_token_id: &str, new_token: &RefreshToken) -> DBoResult<()> {
-        let option = self
-            .collection
-            .find_one_and_replace(doc! { "token_id": old_token_id}, new_token)
-            .await?;
+        let option = with_breaker(
+            self.collection
+                .find_one_and_replace(doc! { "token_id": old_token_id}, new_token),
+        )
+        .await?;
 
         if option.is_some() {
             Ok(())
@@ -70,7 +70,32 @@ impl Repository<RefreshToken> {
         }
     }
 
+    /// Revoke a refresh token, so that it can no longer be used to refresh a session.
+    ///
+    /// ### Arguments
+    /// - `token_id`: The token's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the token could not be found
+    /// - `AdapterError` if the query should fail
+    pub async fn revoke(&self, token_id: &str) -> DBoResult<()> {
+        let update = with_retry(|| {
+            self.collection.update_one(
+                doc! { RefreshToken::id_field(): token_id },
+                doc! { "$set": { "revoked": true } },
+            )
+        })
+        .await?;
+
+        match update.matched_count {
+            0 => Err(DBoError::missing_document(RefreshToken::collection_name())),
+            _ => Ok(()),
+        }
+    }
+
     /// Find all refresh tokens associated with a player account, in order of oldest to newest.
+    /// Tokens created at the same time are ordered by their ids, so that the order is always the
+    /// same.
     ///
     /// ### Arguments
     /// - `player_id`: The player's unique identifier
@@ -81,11 +106,12 @@ impl Repository<RefreshToken> {
     async fn find_player_tokens(&self, player_id: &str) -> DBoResult<Vec<RefreshToken>> {
         let mut tokens: Vec<RefreshToken> = vec![];
 
-        let mut cursor = self
-            .collection
-            .find(doc! { "player_id": player_id })
-            .sort(doc! { "created": 1 })
-            .await?;
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! { "player_id": player_id })
+                .sort(doc! { "created": 1, RefreshToken::id_field(): 1 })
+        })
+        .await?;
 
         while let Some(result) = cursor.next().await {
             tokens.push(result?);
@@ -94,3 +120,74 @@ impl Repository<RefreshToken> {
         Ok(tokens)
     }
 }
+
+/// Find the tokens which must be deleted so that a player keeps no more than `MAX_PLAYER_TOKENS`,
+/// including the token which was just issued. Tokens are pruned from the oldest; tokens created at
+/// the same time are ordered by id, but the new token is always kept.
+///
+/// ### Arguments
+/// - `tokens`: All of the player's tokens, in any order
+/// - `new_token_id`: The unique identifier of the token which was just issued
+///
+/// ### Returns
+/// The oldest tokens beyond the limit
+fn tokens_to_prune<'a>(tokens: &'a [RefreshToken], new_token_id: &str) -> Vec<&'a RefreshToken> {
+    let mut others: Vec<&RefreshToken> = tokens.iter().filter(|t| t.id() != new_token_id).collect();
+    others.sort_by(|a, b| (a.created(), a.id()).cmp(&(b.created(), b.id())));
+
+    let excess = (others.len() + 1).saturating_sub(MAX_PLAYER_TOKENS);
+    others.truncate(excess);
+    others
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::models::clock::FixedClock;
+
+    #[test]
+    fn test_tokens_to_prune_with_colliding_timestamps() {
+        let clock = FixedClock::at(Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap());
+        let tokens: Vec<RefreshToken> = (0..5)
+            .map(|_| RefreshToken::new_with_clock("player", "secret", &clock).unwrap())
+            .collect();
+        assert!(tokens.iter().all(|t| t.created() == tokens[0].created()));
+
+        let mut ids: Vec<&str> = tokens.iter().map(|t| t.id()).collect();
+        ids.sort();
+
+        // Whichever token is new, even the one with the lowest id, it is kept.
+        for new_id in &ids {
+            let pruned: Vec<&str> = tokens_to_prune(&tokens, new_id)
+                .iter()
+                .map(|t| t.id())
+                .collect();
+            let expected: Vec<&str> = ids
+                .iter()
+                .filter(|id| *id != new_id)
+                .take(2)
+                .copied()
+                .collect();
+            assert_eq!(pruned, expected);
+        }
+
+        assert!(tokens_to_prune(&tokens[..3], tokens[0].id()).is_empty());
+        assert!(tokens_to_prune(&[], "missing").is_empty());
+    }
+
+    #[test]
+    fn test_tokens_to_prune_oldest_first() {
+        let at = |minute| FixedClock::at(Utc.with_ymd_and_hms(2025, 1, 2, 3, minute, 0).unwrap());
+        let tokens: Vec<RefreshToken> = [4, 1, 3, 2]
+            .into_iter()
+            .map(|minute| RefreshToken::new_with_clock("player", "secret", &at(minute)).unwrap())
+            .collect();
+
+        // The newest token is the one created at minute 4; the oldest, at minute 1, is pruned.
+        let pruned = tokens_to_prune(&tokens, tokens[0].id());
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].id(), tokens[1].id());
+    }
+}
@@ -0,0 +1,116 @@
+//! This module provides the types used to search through a repository one page at a time.
+
+use bson::{Document, doc};
+use regex::escape;
+
+/// The number of documents returned in a single page, when none is requested.
+const DEFAULT_PAGE_SIZE: u32 = 20;
+
+/// The maximum number of documents which may be returned in a single page.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Describes which page of search results should be returned.
+pub struct Pagination {
+    /// The maximum number of documents to return.
+    limit: u32,
+    /// The number of documents to skip before the page begins.
+    skip: u64,
+}
+
+impl Pagination {
+    /// Construct a new Pagination. The limit is capped at 100 documents per page.
+    ///
+    /// ### Arguments
+    /// - `limit`: The requested number of documents per page; defaults to 20
+    /// - `skip`: The number of documents to skip; defaults to 0
+    pub fn new(limit: Option<u32>, skip: Option<u64>) -> Self {
+        Self {
+            limit: limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            skip: skip.unwrap_or(0),
+        }
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit as i64
+    }
+
+    pub fn skip(&self) -> u64 {
+        self.skip
+    }
+}
+
+/// Describes which player accounts should be included in a search.
+pub struct PlayerSearchCriteria {
+    /// A case-insensitive prefix of the player's username or email address.
+    query: Option<String>,
+    /// Whether the player accounts must be confirmed (or unconfirmed).
+    confirmed: Option<bool>,
+}
+
+impl PlayerSearchCriteria {
+    /// Construct a new PlayerSearchCriteria
+    ///
+    /// ### Arguments
+    /// - `query`: A prefix of the username or email address to search for; ignored if empty
+    /// - `confirmed`: The required confirmation status, if any
+    pub fn new(query: Option<&str>, confirmed: Option<bool>) -> Self {
+        Self {
+            query: query.filter(|q| !q.is_empty()).map(String::from),
+            confirmed,
+        }
+    }
+
+    /// Build the MongoDB filter matching these criteria.
+    pub fn filter(&self) -> Document {
+        let mut filter = doc! {};
+
+        if let Some(query) = &self.query {
+            let pattern = format!("^{}", escape(query));
+            filter.insert(
+                "$or",
+                vec![
+                    doc! { "username": { "$regex": &pattern, "$options": "i" } },
+                    doc! { "email": { "$regex": &pattern, "$options": "i" } },
+                ],
+            );
+        }
+
+        if let Some(confirmed) = self.confirmed {
+            filter.insert("confirmed", confirmed);
+        }
+
+        filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagination() {
+        let default = Pagination::new(None, None);
+        assert_eq!(default.limit(), 20);
+        assert_eq!(default.skip(), 0);
+
+        assert_eq!(Pagination::new(Some(1000), Some(40)).limit(), 100);
+        assert_eq!(Pagination::new(Some(0), None).limit(), 1);
+    }
+
+    #[test]
+    fn test_player_search_filter() {
+        assert_eq!(PlayerSearchCriteria::new(None, None).filter(), doc! {});
+        assert_eq!(PlayerSearchCriteria::new(Some(""), None).filter(), doc! {});
+
+        assert_eq!(
+            PlayerSearchCriteria::new(Some("a.b+"), Some(true)).filter(),
+            doc! {
+                "$or": [
+                    { "username": { "$regex": r"^a\.b\+", "$options": "i" } },
+                    { "email": { "$regex": r"^a\.b\+", "$options": "i" } },
+                ],
+                "confirmed": true,
+            }
+        );
+    }
+}
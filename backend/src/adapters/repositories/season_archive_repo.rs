@@ -0,0 +1,52 @@
+//! This module provides unique functionality for the season archive repository.
+
+use bson::doc;
+use futures::StreamExt;
+
+use crate::{
+    adapters::{mongo::with_retry, repositories::Repository},
+    errors::DBoResult,
+    models::SeasonArchive,
+};
+
+impl Repository<SeasonArchive> {
+    /// Find all of the archived season stats belonging to a specific player.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Returns
+    /// The player's archives, ordered from the oldest season to the newest
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn find_player_archives(&self, player_id: &str) -> DBoResult<Vec<SeasonArchive>> {
+        let mut cursor = with_retry(|| {
+            self.collection
+                .find(doc! { "player_id": player_id })
+                .sort(doc! { "season": 1 })
+        })
+        .await?;
+
+        let mut archives = vec![];
+
+        while let Some(result) = cursor.next().await {
+            archives.push(result?);
+        }
+
+        Ok(archives)
+    }
+
+    /// Delete all of the archived season stats belonging to a specific player.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query fails
+    pub async fn delete_player_archives(&self, player_id: &str) -> DBoResult<()> {
+        with_retry(|| self.collection.delete_many(doc! { "player_id": player_id })).await?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
-use bson::doc;
+use bson::{DateTime, doc};
+use chrono::Utc;
 
 use crate::{
-    adapters::repositories::Repository,
+    adapters::{
+        mongo::{with_breaker, with_retry},
+        repositories::Repository,
+    },
     errors::DBoResult,
-    models::{UndoToken, submodels::UndoTokenType},
+    models::{Expirable, Identifiable, UndoToken, submodels::UndoTokenType},
 };
 
 impl Repository<UndoToken> {
@@ -16,13 +20,15 @@ impl Repository<UndoToken> {
     /// ### Errors
     /// - `AdapterError` if the query should fail
     pub async fn insert(&self, token: &UndoToken) -> DBoResult<()> {
-        self.collection
-            .find_one_and_replace(
-                doc! { "player_id": token.player_id(), "function": token.function().to_string() },
-                token,
-            )
-            .upsert(true)
-            .await?;
+        with_retry(|| {
+            self.collection
+                .find_one_and_replace(
+                    doc! { "player_id": token.player_id(), "function": token.function().to_string() },
+                    token,
+                )
+                .upsert(true)
+        })
+        .await?;
 
         Ok(())
     }
@@ -40,12 +46,52 @@ impl Repository<UndoToken> {
         player_id: &str,
         function: &UndoTokenType,
     ) -> DBoResult<()> {
-        self.collection
-            .delete_many(doc! {
+        with_retry(|| {
+            self.collection.delete_many(doc! {
                 "player_id": player_id,
                 "function": function.to_string()
             })
-            .await?;
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Consume an undo token, deleting it only if it still belongs to the same player, serves the
+    /// same function, and is unexpired. Since the token is found and deleted at once, only one of
+    /// several simultaneous redemptions can consume it.
+    ///
+    /// ### Arguments
+    /// - `token`: The undo token to consume
+    ///
+    /// ### Returns
+    /// Whether the token was consumed; if not, it was already consumed, replaced, or has expired
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query should fail
+    pub async fn consume(&self, token: &UndoToken) -> DBoResult<bool> {
+        let cutoff = DateTime::from_chrono(Utc::now() - token.lifetime());
+
+        let consumed = with_breaker(self.collection.find_one_and_delete(doc! {
+            UndoToken::id_field(): token.id(),
+            "player_id": token.player_id(),
+            "function": token.function().to_string(),
+            "created": { "$gte": cutoff },
+        }))
+        .await?;
+
+        Ok(consumed.is_some())
+    }
+
+    /// Delete all tokens belonging to a specific player.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The player's unique identifier
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the query should fail
+    pub async fn delete_player_tokens(&self, player_id: &str) -> DBoResult<()> {
+        with_retry(|| self.collection.delete_many(doc! { "player_id": player_id })).await?;
 
         Ok(())
     }
@@ -5,4 +5,5 @@
 //! has begun to listen.
 
 pub mod assets;
+pub mod cidr;
 pub mod environment;
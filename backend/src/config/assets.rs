@@ -9,7 +9,10 @@ use std::{
 use lettre::message::header::ContentType;
 use once_cell::sync::Lazy;
 
-use crate::models::submodels::LanguagePreference;
+use crate::{
+    config::environment::{ENV, EmailMode},
+    models::submodels::LanguagePreference,
+};
 
 // Directories storing different types of assets
 #[doc(hidden)]
@@ -33,6 +36,8 @@ static DOT_PNG: &str = ".png";
 
 // Email template filenames
 #[doc(hidden)]
+static ACCOUNT_DELETION: &str = "account.deletion";
+#[doc(hidden)]
 static CHANGE_EMAIL_WARNING: &str = "change.email.warn";
 #[doc(hidden)]
 static CHANGE_EMAIL_CONFIRMATION: &str = "change.email.confirm";
@@ -41,11 +46,22 @@ static CHANGE_PASSWORD: &str = "change.password";
 #[doc(hidden)]
 static CHANGE_USERNAME: &str = "change.username";
 #[doc(hidden)]
+static PASSWORD_RESET: &str = "password.reset";
+#[doc(hidden)]
 static REGISTRATION_EMAIL: &str = "registration";
 #[doc(hidden)]
 static LOCKOUT_EMAIL: &str = "lockout";
+#[doc(hidden)]
+static USERNAME_REMINDER_EMAIL: &str = "username.reminder";
+#[doc(hidden)]
+static WELCOME_EMAIL: &str = "welcome";
 
 // Email subjects
+#[doc(hidden)]
+static EN_SUB_ACCOUNT_DELETION: &str = "Your D-Bo account is scheduled for deletion.";
+#[doc(hidden)]
+static ES_SUB_ACCOUNT_DELETION: &str = "Su cuenta de D-Bo está programada para ser eliminada.";
+
 #[doc(hidden)]
 static EN_SUB_CHANGE_EMAIL_WARNING: &str = "Your email address for D-Bo is about to change.";
 #[doc(hidden)]
@@ -68,6 +84,11 @@ static EN_SUB_CHANGE_USERNAME: &str = "Your username for D-Bo has changed.";
 #[doc(hidden)]
 static ES_SUB_CHANGE_USERNAME: &str = "Su nombre de usuario de D-Bo ha cambiado.";
 
+#[doc(hidden)]
+static EN_SUB_PASSWORD_RESET: &str = "Reset your password for D-Bo.";
+#[doc(hidden)]
+static ES_SUB_PASSWORD_RESET: &str = "Restablezca su contraseña de D-Bo.";
+
 #[doc(hidden)]
 static EN_SUB_REGISTRATION: &str = "Confirm your email address to start playing D-Bo!";
 #[doc(hidden)]
@@ -79,22 +100,60 @@ static EN_SUB_LOCKOUT: &str = "Your D-Bo account has been blocked!";
 #[doc(hidden)]
 static ES_SUB_LOCKOUT: &str = "¡Su cuenta de D-Bo ha sido bloqueado!";
 
+#[doc(hidden)]
+static EN_SUB_USERNAME_REMINDER: &str = "Your username for D-Bo.";
+#[doc(hidden)]
+static ES_SUB_USERNAME_REMINDER: &str = "Su nombre de usuario de D-Bo.";
+
+#[doc(hidden)]
+static EN_SUB_WELCOME: &str = "Welcome to D-Bo!";
+#[doc(hidden)]
+static ES_SUB_WELCOME: &str = "¡Bienvenido a D-Bo!";
+
 // Image filenames
 #[doc(hidden)]
 static D_BO_LOGO: &str = "d_bo_logo";
 #[doc(hidden)]
 static BIGDEVDOG_LOGO: &str = "bigdevdog_logo";
 
-/// Read a template file into a String.
+/// Read a template file into a String. When emails are stubbed, a missing template is replaced by
+/// a minimal placeholder.
 ///
 /// ### Arguments
 /// - `path`: The path to the template
 ///
 /// ### Panics
-/// If the template cannot be found.
+/// If the template cannot be found, and emails are not stubbed.
 #[doc(hidden)]
 fn read_template(path: &PathBuf) -> String {
-    read_to_string(path).unwrap_or_else(|_| panic!("Could not read template at {:?}", path))
+    read_template_or_placeholder(path, ENV.email_mode == EmailMode::Stub)
+}
+
+/// Read a template file into a String, optionally falling back to a minimal placeholder if it
+/// cannot be found.
+///
+/// ### Arguments
+/// - `path`: The path to the template
+/// - `allow_placeholder`: Whether a missing template may be replaced by a placeholder
+///
+/// ### Panics
+/// If the template cannot be found, and placeholders are not allowed.
+#[doc(hidden)]
+fn read_template_or_placeholder(path: &PathBuf, allow_placeholder: bool) -> String {
+    match read_to_string(path) {
+        Ok(template) => template,
+        Err(_) if allow_placeholder => {
+            eprintln!(
+                "Could not read template at {:?}; using a placeholder.",
+                path
+            );
+            format!(
+                "Hello, {{{{USERNAME}}}}. This is a placeholder for the missing template {:?}.",
+                path
+            )
+        }
+        Err(_) => panic!("Could not read template at {:?}", path),
+    }
 }
 
 /// Construct a path to the template based on filename and extension.
@@ -173,6 +232,9 @@ impl EmailLocalizationVariants {
 /// Holds all email templates used by the application, sorted by purpose first, then by language,
 /// and finally by format.
 pub struct EmailTemplates {
+    /// An email sent to the player following a request to delete their account, allowing them to
+    /// cancel the deletion during the grace period.
+    pub account_deletion: EmailLocalizationVariants,
     /// An email sent to the proposed email address following a player changing their email address,
     /// allowing them to confirm the new mailbox.
     pub change_email_confirmation: EmailLocalizationVariants,
@@ -186,8 +248,15 @@ pub struct EmailTemplates {
     pub change_username: EmailLocalizationVariants,
     /// The lockout notification email template, sent after five or more failed login attempts.
     pub lockout: EmailLocalizationVariants,
+    /// An email sent to the player following a request to reset their forgotten password, allowing
+    /// them to choose a new one for 24 hours.
+    pub password_reset: EmailLocalizationVariants,
     /// The registration email template, sent immediately upon player account creation.
     pub registration: EmailLocalizationVariants,
+    /// An email sent to the player reminding them of their username, upon request.
+    pub username_reminder: EmailLocalizationVariants,
+    /// An email sent to the player once their account is confirmed, helping them get started.
+    pub welcome: EmailLocalizationVariants,
 }
 
 impl EmailTemplates {
@@ -197,6 +266,11 @@ impl EmailTemplates {
     /// If any of the required template files cannot be found.
     fn configure() -> Self {
         Self {
+            account_deletion: EmailLocalizationVariants::new(
+                ACCOUNT_DELETION,
+                EN_SUB_ACCOUNT_DELETION,
+                ES_SUB_ACCOUNT_DELETION,
+            ),
             change_email_confirmation: EmailLocalizationVariants::new(
                 CHANGE_EMAIL_CONFIRMATION,
                 EN_SUB_CHANGE_EMAIL_CONF,
@@ -218,11 +292,22 @@ impl EmailTemplates {
                 ES_SUB_CHANGE_USERNAME,
             ),
             lockout: EmailLocalizationVariants::new(LOCKOUT_EMAIL, EN_SUB_LOCKOUT, ES_SUB_LOCKOUT),
+            password_reset: EmailLocalizationVariants::new(
+                PASSWORD_RESET,
+                EN_SUB_PASSWORD_RESET,
+                ES_SUB_PASSWORD_RESET,
+            ),
             registration: EmailLocalizationVariants::new(
                 REGISTRATION_EMAIL,
                 EN_SUB_REGISTRATION,
                 ES_SUB_REGISTRATION,
             ),
+            username_reminder: EmailLocalizationVariants::new(
+                USERNAME_REMINDER_EMAIL,
+                EN_SUB_USERNAME_REMINDER,
+                ES_SUB_USERNAME_REMINDER,
+            ),
+            welcome: EmailLocalizationVariants::new(WELCOME_EMAIL, EN_SUB_WELCOME, ES_SUB_WELCOME),
         }
     }
 }
@@ -243,13 +328,21 @@ fn image_path(image_name: &str, extension: &str) -> PathBuf {
     Path::new(IMAGES_DIRECTORY).join(format!("{}{}", image_name, extension))
 }
 
-/// Read the image into a vector of bytes.
+/// Read the image into a vector of bytes. When emails are stubbed, a missing image is replaced by
+/// an empty one.
 ///
 /// ### Panics
-/// If the image cannot be found.
+/// If the image cannot be found, and emails are not stubbed.
 #[doc(hidden)]
 fn read_image(path: &PathBuf) -> Vec<u8> {
-    read(path).unwrap_or_else(|_| panic!("Could not read image at {:?}", path))
+    match read(path) {
+        Ok(bytes) => bytes,
+        Err(_) if ENV.email_mode == EmailMode::Stub => {
+            eprintln!("Could not read image at {:?}; using an empty image.", path);
+            vec![]
+        }
+        Err(_) => panic!("Could not read image at {:?}", path),
+    }
 }
 
 impl Image {
@@ -340,3 +433,28 @@ impl Assets {
 
 /// Holds all of the required assets for safe use throughout the application.
 pub static ASSETS: Lazy<Assets> = Lazy::new(Assets::configure);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_template_placeholder() {
+        let existing = template_path(REGISTRATION_EMAIL, DOT_EN, DOT_TXT);
+        assert_eq!(
+            read_template_or_placeholder(&existing, true),
+            read_to_string(&existing).unwrap()
+        );
+
+        let missing = template_path("missing", DOT_EN, DOT_TXT);
+        let placeholder = read_template_or_placeholder(&missing, true);
+        assert!(placeholder.contains("{{USERNAME}}"));
+        assert!(placeholder.contains("missing.en.txt"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_missing_template_panics() {
+        read_template_or_placeholder(&template_path("missing", DOT_EN, DOT_TXT), false);
+    }
+}
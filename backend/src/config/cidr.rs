@@ -0,0 +1,119 @@
+//! This module provides `Cidr`, a range of IP addresses in CIDR notation, which the configuration
+//! uses to describe sets of client addresses (such as those exempt from rate limiting).
+
+use std::{net::IpAddr, str::FromStr};
+
+/// A range of IP addresses in CIDR notation, like `10.0.0.0/8` or `2001:db8::/32`. A single
+/// address without a prefix length is treated as a range containing only itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cidr {
+    /// The first address of the range.
+    network: IpAddr,
+    /// The number of leading bits shared by every address in the range.
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Determine whether an address falls within this range. IPv4 addresses mapped to IPv6 are
+    /// compared as IPv4 addresses, and never match an IPv6 range.
+    ///
+    /// ### Arguments
+    /// - `address`: The address to test
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let network: IpAddr = address
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} does not begin with a valid IP address", s))?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("{} has an invalid prefix length", s))?,
+            None => max_len,
+        };
+
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let office: Cidr = "203.0.113.0/24".parse().unwrap();
+        assert!(office.contains(ip("203.0.113.0")));
+        assert!(office.contains(ip("203.0.113.255")));
+        assert!(office.contains(ip("::ffff:203.0.113.9")));
+        assert!(!office.contains(ip("203.0.114.1")));
+        assert!(!office.contains(ip("2001:db8::1")));
+
+        let single: Cidr = "10.1.2.3".parse().unwrap();
+        assert!(single.contains(ip("10.1.2.3")));
+        assert!(!single.contains(ip("10.1.2.4")));
+
+        let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains(ip("198.51.100.7")));
+
+        let v6: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8:ffff::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+        assert!(!v6.contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn test_cidr_parse_errors() {
+        for invalid in [
+            "",
+            "10.0.0.0/33",
+            "2001:db8::/129",
+            "10.0.0/8",
+            "10.0.0.0/x",
+        ] {
+            assert!(
+                invalid.parse::<Cidr>().is_err(),
+                "{} should not parse",
+                invalid
+            );
+        }
+    }
+}
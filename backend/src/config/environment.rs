@@ -1,18 +1,202 @@
 //! This module provides the lazy variable `ENV` containing all environment variables used within
 //! the application.
 
-use std::env;
+use std::{env, fmt::Debug, net::IpAddr, str::FromStr};
 
+use chrono_tz::Tz;
 use dotenvy::dotenv;
 use once_cell::sync::Lazy;
+use reqwest::Url;
+
+use crate::config::cidr::Cidr;
+
+/// Describes how the application sends emails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmailMode {
+    /// Emails are sent to players over SMTP.
+    Smtp,
+    /// Emails are only logged, and missing email assets are replaced by placeholders. This allows
+    /// the API to be run without SMTP credentials or the `assets` tree, and is **only allowed in
+    /// development environments**.
+    Stub,
+}
+
+impl FromStr for EmailMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "smtp" => Ok(Self::Smtp),
+            "stub" => Ok(Self::Stub),
+            _ => Err(format!("Unknown email mode {}; expected SMTP or STUB", s)),
+        }
+    }
+}
+
+/// Describes the `SameSite` attribute of the refresh token cookie.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CookieSameSite {
+    /// The cookie is only sent with requests originating from the same site.
+    Strict,
+    /// The cookie is also sent when navigating to the API from another site.
+    Lax,
+    /// The cookie is sent with every request, including cross-site ones. This requires the cookie
+    /// to be `Secure`, and so is **only allowed over HTTPS**.
+    None,
+}
+
+impl FromStr for CookieSameSite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "lax" => Ok(Self::Lax),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "Unknown SameSite attribute {}; expected STRICT, LAX or NONE",
+                s
+            )),
+        }
+    }
+}
 
 /// Holds all of the environment variables used within the application.
 pub struct Environment {
+    /// Whether analytics-style reads (such as admin searches) prefer a secondary replica over the
+    /// primary.\
+    /// Defaults to false.
+    pub analytics_read_secondary: bool,
+    /// The largest avatar image which players may upload, in bytes.\
+    /// Defaults to 2 MiB.
+    pub avatar_max_bytes: u64,
+    /// The ids of the preset avatars which players may choose.\
+    /// Defaults to none.
+    pub avatar_presets: Vec<String>,
+    /// The id of the access key used to sign avatar uploads. Required when
+    /// `AVATAR_STORAGE_URL` is defined.
+    pub avatar_storage_access_key: Option<String>,
+    /// The prefix shared by the keys of every avatar image in object storage (like `avatars/`).
+    /// Uploaded avatars must have a key beginning with this prefix. Required when
+    /// `AVATAR_STORAGE_URL` is defined.\
+    /// Uploaded avatars are rejected entirely when this is undefined.
+    pub avatar_storage_prefix: Option<String>,
+    /// The region of the avatar storage bucket.\
+    /// Defaults to us-east-1.
+    pub avatar_storage_region: String,
+    /// The secret of the access key used to sign avatar uploads. Required when
+    /// `AVATAR_STORAGE_URL` is defined.
+    pub avatar_storage_secret_key: Option<String>,
+    /// The URL of the S3-compatible bucket to which players upload their avatars, like
+    /// `https://bucket.s3.amazonaws.com` or `http://localhost:9000/bucket`.\
+    /// Avatar uploads are unavailable when this is undefined.
+    pub avatar_storage_url: Option<String>,
     /// The secret used for encoding/decoding player authentication JWTs.
     pub authn_token_secret: String,
+    /// Whether registration with a disposable email address is rejected.\
+    /// Defaults to true.
+    pub block_disposable_email: bool,
+    /// Whether recently authenticated players are kept in an in-memory cache. Cache entries are
+    /// only invalidated within the instance that made the write, so this should stay disabled when
+    /// more than one instance of the application shares the database.\
+    /// Defaults to false.
+    pub cache_players: bool,
+    /// The secret key for the CAPTCHA verification service.\
+    /// CAPTCHA verification is skipped entirely when this is undefined.
+    pub captcha_secret: Option<String>,
+    /// The `siteverify` URL for the CAPTCHA verification service.\
+    /// Defaults to the hCaptcha endpoint.
+    pub captcha_verify_url: String,
+    /// The value of the `Content-Security-Policy` header sent with every response.\
+    /// Defaults to a policy which forbids loading any content, as the API only serves JSON.
+    pub content_security_policy: String,
+    /// The `Domain` attribute of the refresh token cookie, for deployments where the frontend and
+    /// the API are served from different subdomains.\
+    /// The attribute is omitted when this is undefined, so the cookie is only sent to the API host.
+    pub cookie_domain: Option<String>,
+    /// The `SameSite` attribute of the refresh token cookie: STRICT, LAX or NONE. NONE is only
+    /// allowed when `PROTOCOL` is HTTPS.\
+    /// Defaults to STRICT.
+    pub cookie_same_site: CookieSameSite,
+    /// The number of hours between each snapshot of the counters, recorded by the maintenance tasks
+    /// so that their history can be charted. Intervals are counted from the Unix epoch (so daily
+    /// snapshots begin at midnight UTC), and the counters are snapshotted by the first maintenance
+    /// run in each interval.\
+    /// Defaults to 24 (one day).
+    pub counter_snapshot_interval_hours: i64,
+    /// The number of days between a player requesting the deletion of their account, and the
+    /// account actually being deleted.\
+    /// Defaults to 7.
+    pub deletion_grace_days: i64,
+    /// The number of emails which the background email worker attempts at the same time, so that
+    /// one slow mail server does not hold up the rest of the queue.\
+    /// Defaults to 8.
+    pub email_concurrency: usize,
+    /// How emails are sent.\
+    /// Defaults to SMTP. STUB may only be used in development environments.
+    pub email_mode: EmailMode,
+    /// The number of seconds between each sweep of the email outbox, for emails which are due to
+    /// be retried.\
+    /// Defaults to 30.
+    pub email_outbox_sweep_secs: u64,
+    /// The maximum number of emails waiting to be attempted right away by the background email
+    /// worker. Emails which do not fit are sent by the next sweep of the outbox instead.\
+    /// Defaults to 1000.
+    pub email_queue_capacity: usize,
+    /// The number of times an email is retried after it fails to send, before it is marked as
+    /// failed.\
+    /// Defaults to 3.
+    pub email_retries: u32,
+    /// The delay in milliseconds before the first retry of a failed email; each following retry
+    /// waits twice as long. Retries are only attempted by sweeps of the outbox.\
+    /// Defaults to 60000 (one minute).
+    pub email_retry_base_delay_ms: u64,
+    /// The number of seconds to wait for the SMTP server to send an email.\
+    /// Defaults to 10.
+    pub email_send_timeout_secs: u64,
+    /// The comma-separated email addresses which are never sent emails, alongside those which the
+    /// mail server has permanently rejected. Addresses are compared case-insensitively.\
+    /// Defaults to an empty list.
+    pub email_suppression_list: Vec<String>,
+    /// Whether `401` responses include a coarse reason code, to help debug the frontend.\
+    /// Defaults to false. It may only be enabled in development environments.
+    pub expose_authn_reasons: bool,
+    /// The IANA time zone given to players whose stored time zone cannot be parsed, when an admin
+    /// repairs them.\
+    /// Defaults to "UTC".
+    pub fallback_time_zone: String,
+    /// Whether usernames containing offensive terms are rejected.\
+    /// Defaults to true.
+    pub filter_usernames: bool,
     /// The URL to the frontend of the application.\
     /// Defaults to "http:localhost:5173" in dev environments.
     pub frontend_url: String,
+    /// The `max-age` in seconds of the `Strict-Transport-Security` header, which is only sent when
+    /// the application is served over HTTPS.\
+    /// Defaults to 31536000 (one year).
+    pub hsts_max_age: u64,
+    /// The number of seconds of clock skew tolerated while validating the expiry of an access
+    /// token, so that tokens minted by one instance are not rejected by another whose clock has
+    /// drifted slightly.\
+    /// Defaults to 60.
+    pub jwt_leeway_secs: u64,
+    /// The number of hours after a lockout ends during which a successful login still includes a
+    /// security notice about it.\
+    /// Defaults to 24.
+    pub lockout_notice_hours: i64,
+    /// The number of login attempts which a single client IP address may make per minute. Set to 0
+    /// to disable login rate limiting. Behind a reverse proxy, `TRUSTED_PROXY_HOPS` must be set
+    /// before enabling it; otherwise, every client shares the proxy's address, and thus its limit.\
+    /// Defaults to 0.
+    pub login_rate_limit: u32,
+    /// The number of seconds between each run of the maintenance tasks, such as purging accounts
+    /// whose deletion grace period has ended.\
+    /// Defaults to 3600 (one hour).
+    pub maintenance_interval_secs: u64,
+    /// The comma-separated IP addresses which may read `GET /metrics`. While the list is empty,
+    /// no metrics are recorded, and the endpoint responds with `404 NOT FOUND`.\
+    /// Defaults to an empty list.
+    pub metrics_allowlist: Vec<IpAddr>,
     /// The username to access the MongoDB database.
     pub mongo_username: String,
     /// The password to access the MongoDB database.
@@ -21,14 +205,106 @@ pub struct Environment {
     pub mongo_server: String,
     /// The name of the MongoDB database.
     pub mongo_dbname: String,
+    /// The maximum number of connections in the MongoDB connection pool.\
+    /// Defaults to 20.
+    pub mongo_max_pool_size: u32,
+    /// The minimum number of connections kept open in the MongoDB connection pool.\
+    /// Defaults to 2.
+    pub mongo_min_pool_size: u32,
+    /// The time in milliseconds to wait while opening a new connection to MongoDB.\
+    /// Defaults to 5000.
+    pub mongo_connect_timeout_ms: u64,
+    /// The time in milliseconds to wait for a suitable MongoDB server before failing an operation.\
+    /// Defaults to 5000.
+    pub mongo_server_selection_timeout_ms: u64,
+    /// The number of times a database operation is retried after a transient failure.\
+    /// Defaults to 3.
+    pub mongo_retries: u32,
+    /// The delay in milliseconds before the first retry of a failed database operation; each
+    /// following retry waits twice as long.\
+    /// Defaults to 100.
+    pub mongo_retry_base_delay_ms: u64,
+    /// The number of consecutive database operations which must fail, after running out of
+    /// retries, before the database is considered unreachable. Requests are then answered with
+    /// `503 SERVICE UNAVAILABLE` until `mongo_breaker_cooldown_secs` has passed. 0 disables this.\
+    /// Defaults to 5.
+    pub mongo_breaker_threshold: u32,
+    /// The number of seconds for which the database is left alone once it is considered
+    /// unreachable, before a single operation is let through to test whether it has recovered.\
+    /// Defaults to 30.
+    pub mongo_breaker_cooldown_secs: u64,
+    /// A server-side secret mixed into every password (and token secret) before it is hashed, so
+    /// that a dump of the database alone cannot be attacked offline. **Losing or changing the
+    /// pepper invalidates every hash created with it**, so it must be kept as safely as the
+    /// database credentials. Hashes created before the pepper was set are still accepted.\
+    /// Hashes are created without a pepper when this is undefined.
+    pub password_pepper: Option<String>,
+    /// The maximum number of players kept in the in-memory cache.\
+    /// Defaults to 10000.
+    pub player_cache_capacity: u64,
+    /// The number of seconds a player is kept in the in-memory cache.\
+    /// Defaults to 60.
+    pub player_cache_ttl_secs: u64,
     /// HTTP or HTTPS?
     pub protocol: String,
-    /// The SMTP server used to send outgoing emails.
+    /// The comma-separated IP address ranges, in CIDR notation (like `203.0.113.0/24`), which are
+    /// exempt from rate limiting. A single address may be given without a prefix length.\
+    /// Defaults to an empty list.
+    pub rate_limit_allowlist: Vec<Cidr>,
+    /// The number of random bytes in each newly generated refresh token secret. Changing this only
+    /// affects new tokens; existing tokens remain valid until they expire. Must be at least 16.\
+    /// Defaults to 32.
+    pub refresh_secret_bytes: usize,
+    /// The comma-separated email addresses and domains which may register, alongside those added
+    /// by admins at runtime. While both lists are empty, registration is open to everyone.\
+    /// Defaults to an empty list.
+    pub registration_allowlist: Vec<String>,
+    /// Whether new player accounts may be registered when the server starts. Admins may open or
+    /// close registration at runtime; that change only lasts until the server restarts, and only
+    /// applies to the instance which received it.\
+    /// Defaults to true.
+    pub registration_open: bool,
+    /// The number of registration pre-validations which a single client IP address may request per
+    /// minute, limiting how quickly taken usernames and email addresses can be enumerated. Unlike
+    /// `LOGIN_RATE_LIMIT`, this is enabled by default, since the endpoint would otherwise reveal
+    /// every taken username and email address; behind a reverse proxy, `TRUSTED_PROXY_HOPS` must
+    /// be set, or every client shares the proxy's limit. Set to 0 to disable this rate limiting.\
+    /// Defaults to 10.
+    pub registration_validation_rate_limit: u32,
+    /// The number of seconds a request may take before it is abandoned with `408 REQUEST TIMEOUT`.\
+    /// Defaults to 30.
+    pub request_timeout_secs: u64,
+    /// Whether a welcome email is sent to players once their account is confirmed.\
+    /// Defaults to true.
+    pub send_welcome_email: bool,
+    /// The SMTP server used to send outgoing emails.\
+    /// The SMTP variables are only required when the email mode is SMTP.
     pub smtp_host: String,
     /// The email address that outgoing emails are sent from.
     pub smtp_username: String,
     /// The password for the SMTP server.
     pub smtp_password: String,
+    /// The URL at which players can contact support, linked from the emails sent to them. It is
+    /// available to every email template as `{{SUPPORT_URL}}`.\
+    /// Defaults to "mailto:devin@bigdevdog.com".
+    pub support_url: String,
+    /// The number of trusted reverse proxies in front of the application. Each of them appends the
+    /// address it received a request from to the `X-Forwarded-For` header, so the client's address
+    /// is found this many entries from the right. When this is 0, the header is ignored entirely,
+    /// as it could be forged by the client.\
+    /// Defaults to 0.
+    pub trusted_proxy_hops: usize,
+    /// The number of hours after which an account which has not been confirmed is deleted. The
+    /// deletion is performed by a TTL index; when this changes, the index is dropped and recreated
+    /// as the server starts.\
+    /// Defaults to 48.
+    pub unconfirmed_account_ttl_hours: u64,
+    /// The path to a file replacing the built-in list of offensive terms for usernames.\
+    /// Defaults to the list embedded from `assets/username-blocklist.txt`.
+    pub username_blocklist_path: Option<String>,
+    /// Whether the domain of a registering player's email address must have MX records.\
+    /// Defaults to false.
+    pub verify_email_mx: bool,
 }
 
 /// Find an environment variable which **must** be defined externally.
@@ -40,10 +316,25 @@ pub struct Environment {
 /// If the environment variable is undefined.
 #[doc(hidden)]
 fn secret_var(varname: &str) -> String {
-    env::var(varname).expect(&format!(
-        r#"Environment variable "{}" is not set!"#,
-        varname
-    ))
+    env::var(varname)
+        .unwrap_or_else(|_| panic!(r#"Environment variable "{}" is not set!"#, varname))
+}
+
+/// Find an environment variable which is required to send emails over SMTP. When emails are
+/// stubbed, it may be left undefined.
+///
+/// ### Arguments
+/// - `varname`: The name of the environment variable.
+/// - `email_mode`: The configured email mode.
+///
+/// ### Panics
+/// If the environment variable is undefined, and the email mode is SMTP.
+#[doc(hidden)]
+fn smtp_var(varname: &str, email_mode: EmailMode) -> String {
+    match email_mode {
+        EmailMode::Smtp => secret_var(varname),
+        EmailMode::Stub => optional_var(varname).unwrap_or_default(),
+    }
 }
 
 /// Try to find an environment variable, but if it cannot be found, set it to a default value.
@@ -68,30 +359,297 @@ fn default_var(varname: &str, default: &str) -> String {
     })
 }
 
+/// Find an environment variable which is **optional**, enabling some functionality only when it is
+/// defined.
+///
+/// ### Arguments
+/// - `varname`: The name of the environment variable.
+#[doc(hidden)]
+fn optional_var(varname: &str) -> Option<String> {
+    env::var(varname).ok().filter(|value| !value.is_empty())
+}
+
+/// Find an optional environment variable holding a comma-separated list. Items are trimmed, and
+/// empty items are ignored.
+///
+/// ### Arguments
+/// - `varname`: The name of the environment variable.
+#[doc(hidden)]
+fn list_var(varname: &str) -> Vec<String> {
+    optional_var(varname)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Try to find an environment variable and parse it into a value, but if it cannot be found, use a
+/// default value. Unlike `default_var`, the default value is acceptable in production environments.
+///
+/// ### Arguments
+/// - `varname`: The name of the environment variable.
+/// - `default`: The value to use when the environment variable is undefined.
+///
+/// ### Panics
+/// If the environment variable is defined, but cannot be parsed.
+#[doc(hidden)]
+fn parsed_var<T>(varname: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    match optional_var(varname) {
+        Some(value) => value.parse().unwrap_or_else(|e| {
+            panic!(
+                r#"Environment variable "{}" could not be parsed: {:?}"#,
+                varname, e
+            )
+        }),
+        None => default,
+    }
+}
+
 impl Environment {
     /// Configure the environment variable. If the app is running in a development environment, load
     /// the environment variables from a `.env` file first.
     ///
     /// ### Panics
     ///
-    /// If **any** of the secret environment variables are undefined, or if a default value is used
-    /// **in a production environment**.
+    /// If **any** of the secret environment variables are undefined, if a default value is used
+    /// **in a production environment**, or if any of the numeric settings are unusable.
     pub fn configure() -> Self {
         if cfg!(debug_assertions) {
             dotenv().ok();
         }
 
-        Self {
+        let email_mode = parsed_var("EMAIL_MODE", EmailMode::Smtp);
+
+        let environment = Self {
+            analytics_read_secondary: parsed_var("ANALYTICS_READ_SECONDARY", false),
             authn_token_secret: secret_var("AUTHN_TOKEN_SECRET"),
+            avatar_max_bytes: parsed_var("AVATAR_MAX_BYTES", 2 * 1024 * 1024),
+            avatar_presets: list_var("AVATAR_PRESETS"),
+            avatar_storage_access_key: optional_var("AVATAR_STORAGE_ACCESS_KEY"),
+            avatar_storage_prefix: optional_var("AVATAR_STORAGE_PREFIX"),
+            avatar_storage_region: optional_var("AVATAR_STORAGE_REGION")
+                .unwrap_or_else(|| String::from("us-east-1")),
+            avatar_storage_secret_key: optional_var("AVATAR_STORAGE_SECRET_KEY"),
+            avatar_storage_url: optional_var("AVATAR_STORAGE_URL"),
+            block_disposable_email: parsed_var("BLOCK_DISPOSABLE_EMAIL", true),
+            cache_players: parsed_var("CACHE_PLAYERS", false),
+            captcha_secret: optional_var("CAPTCHA_SECRET"),
+            captcha_verify_url: optional_var("CAPTCHA_VERIFY_URL")
+                .unwrap_or_else(|| String::from("https://api.hcaptcha.com/siteverify")),
+            content_security_policy: optional_var("CONTENT_SECURITY_POLICY")
+                .unwrap_or_else(|| String::from("default-src 'none'; frame-ancestors 'none'")),
+            cookie_domain: optional_var("COOKIE_DOMAIN"),
+            cookie_same_site: parsed_var("COOKIE_SAME_SITE", CookieSameSite::Strict),
+            counter_snapshot_interval_hours: parsed_var("COUNTER_SNAPSHOT_INTERVAL_HOURS", 24),
+            deletion_grace_days: parsed_var("DELETION_GRACE_DAYS", 7),
+            email_concurrency: parsed_var("EMAIL_CONCURRENCY", 8),
+            email_mode,
+            email_outbox_sweep_secs: parsed_var("EMAIL_OUTBOX_SWEEP_SECS", 30),
+            email_queue_capacity: parsed_var("EMAIL_QUEUE_CAPACITY", 1000),
+            email_retries: parsed_var("EMAIL_RETRIES", 3),
+            email_retry_base_delay_ms: parsed_var("EMAIL_RETRY_BASE_DELAY_MS", 60 * 1000),
+            email_send_timeout_secs: parsed_var("EMAIL_SEND_TIMEOUT_SECS", 10),
+            email_suppression_list: list_var("EMAIL_SUPPRESSION_LIST")
+                .iter()
+                .map(|address| address.to_lowercase())
+                .collect(),
+            expose_authn_reasons: parsed_var("EXPOSE_AUTHN_REASONS", false),
+            fallback_time_zone: optional_var("FALLBACK_TIME_ZONE")
+                .unwrap_or_else(|| String::from("UTC")),
+            filter_usernames: parsed_var("FILTER_USERNAMES", true),
             frontend_url: default_var("FRONTEND_URL", "http://localhost:5173"),
+            hsts_max_age: parsed_var("HSTS_MAX_AGE", 60 * 60 * 24 * 365),
+            jwt_leeway_secs: parsed_var("JWT_LEEWAY_SECS", 60),
+            lockout_notice_hours: parsed_var("LOCKOUT_NOTICE_HOURS", 24),
+            login_rate_limit: parsed_var("LOGIN_RATE_LIMIT", 0),
+            maintenance_interval_secs: parsed_var("MAINTENANCE_INTERVAL_SECS", 60 * 60),
+            metrics_allowlist: list_var("METRICS_ALLOWLIST")
+                .iter()
+                .map(|address| {
+                    address.parse().unwrap_or_else(|e| {
+                        panic!(
+                            r#"Environment variable "METRICS_ALLOWLIST" could not be parsed: {:?}"#,
+                            e
+                        )
+                    })
+                })
+                .collect(),
             mongo_username: secret_var("MONGO_USERNAME"),
             mongo_password: secret_var("MONGO_PASSWORD"),
             mongo_server: secret_var("MONGO_SERVER"),
             mongo_dbname: secret_var("MONGO_DBNAME"),
+            mongo_max_pool_size: parsed_var("MONGO_MAX_POOL_SIZE", 20),
+            mongo_min_pool_size: parsed_var("MONGO_MIN_POOL_SIZE", 2),
+            mongo_connect_timeout_ms: parsed_var("MONGO_CONNECT_TIMEOUT_MS", 5000),
+            mongo_server_selection_timeout_ms: parsed_var(
+                "MONGO_SERVER_SELECTION_TIMEOUT_MS",
+                5000,
+            ),
+            mongo_retries: parsed_var("MONGO_RETRIES", 3),
+            mongo_retry_base_delay_ms: parsed_var("MONGO_RETRY_BASE_DELAY_MS", 100),
+            mongo_breaker_threshold: parsed_var("MONGO_BREAKER_THRESHOLD", 5),
+            mongo_breaker_cooldown_secs: parsed_var("MONGO_BREAKER_COOLDOWN_SECS", 30),
+            password_pepper: optional_var("PASSWORD_PEPPER"),
+            player_cache_capacity: parsed_var("PLAYER_CACHE_CAPACITY", 10_000),
+            player_cache_ttl_secs: parsed_var("PLAYER_CACHE_TTL_SECS", 60),
             protocol: default_var("PROTOCOL", "HTTP"),
-            smtp_host: secret_var("SMTP_HOST"),
-            smtp_username: secret_var("SMTP_USERNAME"),
-            smtp_password: secret_var("SMTP_PASSWORD"),
+            rate_limit_allowlist: list_var("RATE_LIMIT_ALLOWLIST")
+                .iter()
+                .map(|range| {
+                    range.parse().unwrap_or_else(|e| {
+                        panic!(
+                            r#"Environment variable "RATE_LIMIT_ALLOWLIST" could not be parsed: {}"#,
+                            e
+                        )
+                    })
+                })
+                .collect(),
+            refresh_secret_bytes: parsed_var("REFRESH_SECRET_BYTES", 32),
+            registration_allowlist: list_var("REGISTRATION_ALLOWLIST"),
+            registration_open: parsed_var("REGISTRATION_OPEN", true),
+            registration_validation_rate_limit: parsed_var("REGISTRATION_VALIDATION_RATE_LIMIT", 10),
+            request_timeout_secs: parsed_var("REQUEST_TIMEOUT_SECS", 30),
+            send_welcome_email: parsed_var("SEND_WELCOME_EMAIL", true),
+            smtp_host: smtp_var("SMTP_HOST", email_mode),
+            smtp_username: smtp_var("SMTP_USERNAME", email_mode),
+            smtp_password: smtp_var("SMTP_PASSWORD", email_mode),
+            support_url: optional_var("SUPPORT_URL")
+                .unwrap_or_else(|| String::from("mailto:devin@bigdevdog.com")),
+            trusted_proxy_hops: parsed_var("TRUSTED_PROXY_HOPS", 0),
+            unconfirmed_account_ttl_hours: parsed_var("UNCONFIRMED_ACCOUNT_TTL_HOURS", 48),
+            username_blocklist_path: optional_var("USERNAME_BLOCKLIST_PATH"),
+            verify_email_mx: parsed_var("VERIFY_EMAIL_MX", false),
+        };
+
+        environment.validate();
+        environment
+    }
+
+    /// Ensure that the numeric settings can actually be used, and that development-only settings
+    /// are not used in production.
+    ///
+    /// ### Panics
+    /// If any of the numeric settings are out of their acceptable range, or if emails are stubbed
+    /// or authentication failure reasons are exposed in a production environment.
+    fn validate(&self) {
+        if let Some(url) = &self.avatar_storage_url {
+            if Url::parse(url).is_err() {
+                panic!(r#"Environment variable "AVATAR_STORAGE_URL" is not a valid URL!"#);
+            }
+
+            for (varname, value) in [
+                ("AVATAR_STORAGE_ACCESS_KEY", &self.avatar_storage_access_key),
+                ("AVATAR_STORAGE_PREFIX", &self.avatar_storage_prefix),
+                ("AVATAR_STORAGE_SECRET_KEY", &self.avatar_storage_secret_key),
+            ] {
+                if value.is_none() {
+                    panic!(
+                        r#"Environment variable "{}" is required when "AVATAR_STORAGE_URL" is defined!"#,
+                        varname
+                    );
+                }
+            }
+        }
+
+        if self.email_mode == EmailMode::Stub && !cfg!(debug_assertions) {
+            panic!(r#"Environment variable "EMAIL_MODE" may not be STUB in prod!"#);
+        }
+
+        if self.cookie_same_site == CookieSameSite::None && !self.secure() {
+            panic!(r#"Environment variable "COOKIE_SAME_SITE" may only be NONE over HTTPS!"#);
+        }
+
+        if self.expose_authn_reasons && !cfg!(debug_assertions) {
+            panic!(r#"Environment variable "EXPOSE_AUTHN_REASONS" may not be enabled in prod!"#);
+        }
+
+        if self.fallback_time_zone.parse::<Tz>().is_err() {
+            panic!(r#"Environment variable "FALLBACK_TIME_ZONE" must be an IANA time zone!"#);
+        }
+
+        if self.email_concurrency == 0 {
+            panic!(r#"Environment variable "EMAIL_CONCURRENCY" must be greater than zero!"#);
+        }
+
+        if self.email_outbox_sweep_secs == 0 {
+            panic!(r#"Environment variable "EMAIL_OUTBOX_SWEEP_SECS" must be greater than zero!"#);
+        }
+
+        if self.email_queue_capacity == 0 {
+            panic!(r#"Environment variable "EMAIL_QUEUE_CAPACITY" must be greater than zero!"#);
+        }
+
+        if self.email_send_timeout_secs == 0 {
+            panic!(r#"Environment variable "EMAIL_SEND_TIMEOUT_SECS" must be greater than zero!"#);
+        }
+
+        if self.counter_snapshot_interval_hours <= 0 {
+            panic!(
+                r#"Environment variable "COUNTER_SNAPSHOT_INTERVAL_HOURS" must be greater than zero!"#
+            );
+        }
+
+        if self.deletion_grace_days <= 0 {
+            panic!(r#"Environment variable "DELETION_GRACE_DAYS" must be greater than zero!"#);
+        }
+
+        if self.mongo_breaker_cooldown_secs == 0 {
+            panic!(
+                r#"Environment variable "MONGO_BREAKER_COOLDOWN_SECS" must be greater than zero!"#
+            );
+        }
+
+        if self.maintenance_interval_secs == 0 {
+            panic!(
+                r#"Environment variable "MAINTENANCE_INTERVAL_SECS" must be greater than zero!"#
+            );
+        }
+
+        if self.refresh_secret_bytes < 16 {
+            panic!(r#"Environment variable "REFRESH_SECRET_BYTES" must be at least 16!"#);
+        }
+
+        if self.unconfirmed_account_ttl_hours == 0 {
+            panic!(
+                r#"Environment variable "UNCONFIRMED_ACCOUNT_TTL_HOURS" must be greater than zero!"#
+            );
+        }
+
+        if self.request_timeout_secs == 0 {
+            panic!(r#"Environment variable "REQUEST_TIMEOUT_SECS" must be greater than zero!"#);
+        }
+
+        if self.cache_players && self.player_cache_ttl_secs == 0 {
+            panic!(r#"Environment variable "PLAYER_CACHE_TTL_SECS" must be greater than zero!"#);
+        }
+
+        if self.mongo_max_pool_size == 0 {
+            panic!(r#"Environment variable "MONGO_MAX_POOL_SIZE" must be greater than zero!"#);
+        }
+
+        if self.mongo_min_pool_size > self.mongo_max_pool_size {
+            panic!(
+                r#"Environment variable "MONGO_MIN_POOL_SIZE" may not exceed "MONGO_MAX_POOL_SIZE"!"#
+            );
+        }
+
+        if self.mongo_connect_timeout_ms == 0 {
+            panic!(r#"Environment variable "MONGO_CONNECT_TIMEOUT_MS" must be greater than zero!"#);
+        }
+
+        if self.mongo_server_selection_timeout_ms == 0 {
+            panic!(
+                r#"Environment variable "MONGO_SERVER_SELECTION_TIMEOUT_MS" must be greater than zero!"#
+            );
         }
     }
 
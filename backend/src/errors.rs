@@ -9,23 +9,34 @@
 //! annotations.
 
 use argon2::password_hash::Error as HashingError;
-use chrono::{DateTime, Utc};
+use bson::ser::Error as BsonSerError;
 use chrono_tz::ParseError as TzParseError;
 use jsonwebtoken::errors::{Error as JwtError, ErrorKind as JwtErrorKind};
 use lettre::{error::Error as LettreError, transport::smtp::Error as SmtpError};
 use mongodb::error::Error as MongoError;
+use reqwest::Error as ReqwestError;
+use serde_json::Error as JsonError;
 
-use crate::handlers::responses::PlayerInvalidFieldsResponse;
+use crate::{
+    adapters::mongo::DatabaseUnavailable,
+    handlers::responses::{AccountLockedResponse, PlayerInvalidFieldsResponse},
+};
 
 /// Encompasses all possible errors that may occur within the D-Bo application.
 #[derive(Debug)]
 pub enum DBoError {
-    /// The player account is currently locked.
-    AccountLocked(DateTime<Utc>),
+    /// The player account is currently locked, until the time described in the response.
+    AccountLocked(AccountLockedResponse),
+    /// The action requires a confirmed player account, but the player's email address has never
+    /// been confirmed.
+    AccountUnconfirmed,
     /// An error has occurred within an adapter function.
     AdapterError,
-    /// The player could not be authenticated.
-    AuthenticationFailure,
+    /// The player could not be authenticated, for the provided reason.
+    AuthenticationFailure(AuthnFailureReason),
+    /// A CAPTCHA token was required, but was either missing or rejected by the verification
+    /// service.
+    CaptchaFailure,
     /// An update to a document failed due to a conflicting state within that same document. The
     /// collection name is provided in the String.
     InternalConflict,
@@ -34,12 +45,14 @@ pub enum DBoError {
     /// validation functions, but is not impossible.
     InvalidEmailAddress,
     /// A user has tried to create a new account with an invalid field.
-    InvalidPlayerInfo(PlayerInvalidFieldsResponse),
+    InvalidPlayerInfo(Box<PlayerInvalidFieldsResponse>),
     /// A provided token is invalid.
     InvalidToken,
     /// A request has failed because a document cannot be found. The collection name is provided in
     /// the String.
     MissingDocument(String),
+    /// A requested change would leave the document exactly as it already is, so nothing was done.
+    NoChange,
     /// An update to a document failed due to a conflicting state with a related document.
     RelationalConflict,
     /// A time zone could not be parsed from a String! This can happen during registration, which
@@ -58,10 +71,49 @@ pub enum DBoError {
     UniquenessViolation(bool, bool),
 }
 
+/// Describes why a player could not be authenticated. The reason is logged for debugging; it
+/// must never be revealed to clients in production, as it could reveal whether an account exists.
+#[derive(Debug)]
+pub enum AuthnFailureReason {
+    /// No account matches the provided username or email address.
+    UnknownAccount,
+    /// The provided password does not match the account.
+    WrongPassword,
+    /// The account has no password, as it is authenticated by an external identity provider.
+    NoPassword,
+    /// No refresh token matches the provided id.
+    UnknownRefreshToken,
+    /// The provided secret does not match the refresh token.
+    WrongRefreshSecret,
+    /// The token is valid, but the player it represents no longer exists, such as when their
+    /// account was deleted after the token was issued.
+    PlayerNotFound,
+    /// The player is scheduled for deletion, and cannot use their account unless they cancel the
+    /// deletion using the link they were emailed.
+    PendingDeletion,
+}
+
+impl AuthnFailureReason {
+    /// Return a coarse code for the reason, which does not reveal whether an account exists.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownAccount | Self::WrongPassword | Self::NoPassword => "bad_credentials",
+            Self::UnknownRefreshToken | Self::WrongRefreshSecret | Self::PlayerNotFound => {
+                "invalid_token"
+            }
+            Self::PendingDeletion => "pending_deletion",
+        }
+    }
+}
+
 impl DBoError {
     pub fn missing_document(collection: &str) -> Self {
         Self::MissingDocument(String::from(collection))
     }
+
+    pub fn invalid_player_info(info: PlayerInvalidFieldsResponse) -> Self {
+        Self::InvalidPlayerInfo(Box::new(info))
+    }
 }
 
 impl From<HashingError> for DBoError {
@@ -72,8 +124,29 @@ impl From<HashingError> for DBoError {
     }
 }
 
+impl From<BsonSerError> for DBoError {
+    fn from(e: BsonSerError) -> Self {
+        eprintln!("A BSON serialization error has occurred!");
+        eprintln!("{:?}", e);
+        Self::AdapterError
+    }
+}
+
+impl From<JsonError> for DBoError {
+    fn from(e: JsonError) -> Self {
+        eprintln!("A JSON serialization error has occurred!");
+        eprintln!("{:?}", e);
+        Self::AdapterError
+    }
+}
+
 impl From<MongoError> for DBoError {
     fn from(e: MongoError) -> Self {
+        if e.get_custom::<DatabaseUnavailable>().is_some() {
+            // The circuit breaker has already logged that the database is unreachable.
+            return Self::AdapterError;
+        }
+
         eprintln!("A MongoDB driver error has occurred.");
         eprintln!("{:?}", e);
         Self::AdapterError
@@ -96,6 +169,14 @@ impl From<LettreError> for DBoError {
     }
 }
 
+impl From<ReqwestError> for DBoError {
+    fn from(e: ReqwestError) -> Self {
+        eprintln!("A Reqwest error has occurred!");
+        eprintln!("{:?}", e);
+        Self::AdapterError
+    }
+}
+
 impl From<JwtError> for DBoError {
     fn from(e: JwtError) -> Self {
         match e.kind() {
@@ -106,7 +187,14 @@ impl From<JwtError> for DBoError {
             | JwtErrorKind::InvalidIssuer
             | JwtErrorKind::InvalidAudience
             | JwtErrorKind::InvalidSubject
-            | JwtErrorKind::InvalidAlgorithm => Self::InvalidToken,
+            | JwtErrorKind::InvalidAlgorithm
+            | JwtErrorKind::MissingRequiredClaim(_) => Self::InvalidToken,
+
+            // The token's contents could not be decoded into claims, such as a payload which is
+            // missing its expiry; this is a bad token, not a server-side error.
+            JwtErrorKind::Base64(_) | JwtErrorKind::Json(_) | JwtErrorKind::Utf8(_) => {
+                Self::InvalidToken
+            }
 
             _ => {
                 eprintln!("An unexpected JWT error has occurred!");
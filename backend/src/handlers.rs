@@ -2,6 +2,11 @@
 //! responsible for parsing information from incoming HTTP requests, calling the appropriate
 //! function from the service layer, and mapping the result to an appropriate HTTP response.
 
+pub mod admin_handlers;
+pub mod authn;
+pub mod conditional;
+pub mod extractors;
+pub mod meta_handlers;
 pub mod player_handlers;
 pub mod request_bodies;
 pub mod responses;
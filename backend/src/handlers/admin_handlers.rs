@@ -0,0 +1,443 @@
+//! This module provides all HTTP handler functions reserved for admin players.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bson::DateTime;
+
+use crate::{
+    adapters::repositories::{
+        Repositories,
+        counter_id::CounterId,
+        search::{Pagination, PlayerSearchCriteria},
+    },
+    errors::DBoError,
+    handlers::{
+        extractors::AdminPlayer,
+        request_bodies::{
+            CounterHistoryQuery, PlayerMergeRequestBody, PlayerSearchQuery,
+            RegistrationToggleRequestBody,
+        },
+        responses::{MissingDocumentResponse, RegistrationStatusResponse},
+    },
+    models::player_validation::normalize_allowlist_entry,
+    services::admin_service::AdminService,
+    state::AppState,
+};
+
+/// Handle a request to search for player accounts.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `query`: The search and pagination query parameters
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `PlayerSearchResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the query parameters are malformed, or the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_player_search(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+    Query(query): Query<PlayerSearchQuery>,
+) -> Response {
+    let criteria = PlayerSearchCriteria::new(query.query.as_deref(), query.confirmed);
+    let pagination = Pagination::new(query.limit, query.skip);
+
+    match AdminService::search_players(repos.players(), &criteria, &pagination).await {
+        Ok(page) => (StatusCode::OK, Json(page)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred during admin player search!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to read the full record of a single player account.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `player_id`: The unique identifier of the player, from the request path
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with an `AdminPlayerResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `404 NOT FOUND` with a `MissingDocumentResponse` body if the player cannot be found
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_player_record(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+    Path(player_id): Path<String>,
+) -> Response {
+    match AdminService::player_record(repos.players(), &player_id).await {
+        Ok(record) => (StatusCode::OK, Json(record)).into_response(),
+        Err(DBoError::MissingDocument(collection)) => (
+            StatusCode::NOT_FOUND,
+            Json(MissingDocumentResponse::new(&collection)),
+        )
+            .into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred during admin player record lookup!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to merge a duplicate player account into another.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `body`: The HTTP request body
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` if the accounts were merged
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `404 NOT FOUND` with a `MissingDocumentResponse` body if either account is missing
+///   - `409 CONFLICT` if the source and target are the same account
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_player_merge(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+    Json(body): Json<PlayerMergeRequestBody>,
+) -> Response {
+    let outcome = AdminService::merge_accounts(
+        repos.players(),
+        repos.season_archives(),
+        &body.source_id,
+        &body.target_id,
+    )
+    .await;
+
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(DBoError::MissingDocument(collection)) => (
+            StatusCode::NOT_FOUND,
+            Json(MissingDocumentResponse::new(&collection)),
+        )
+            .into_response(),
+        Err(DBoError::RelationalConflict) => (StatusCode::CONFLICT).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred during admin player merge!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to end the current competitive season, archiving and resetting all stats.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `SeasonEndResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_season_reset(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+) -> Response {
+    let outcome =
+        AdminService::end_season(repos.players(), repos.season_archives(), repos.counters()).await;
+
+    match outcome {
+        Ok(season) => (StatusCode::OK, Json(season)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred during admin season reset!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to repair the players' stored time zones which cannot be parsed.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `TimeZoneRepairResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_time_zone_repair(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+) -> Response {
+    match AdminService::repair_time_zones(repos.players()).await {
+        Ok(repair) => (StatusCode::OK, Json(repair)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred during admin time zone repair!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to inspect the emails which have failed to send.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `StuckEmailListResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_stuck_emails(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+) -> Response {
+    match AdminService::stuck_emails(repos.email_outbox()).await {
+        Ok(emails) => (StatusCode::OK, Json(emails)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred while listing stuck emails!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to read the recorded history of a counter.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `query`: The counter, and the optional range of time, to read
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `CounterHistoryResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the counter is unknown, the query parameters are malformed, `from` is
+///     later than `to`, or the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_counter_history(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+    Query(query): Query<CounterHistoryQuery>,
+) -> Response {
+    let counter = match query.counter.parse::<CounterId>() {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+    if !query.has_valid_range() {
+        return (StatusCode::BAD_REQUEST).into_response();
+    }
+    let from = query.from.map(DateTime::from_chrono);
+    let to = query.to.map(DateTime::from_chrono);
+
+    let outcome = AdminService::counter_history(
+        repos.counter_snapshots(),
+        &counter,
+        from.as_ref(),
+        to.as_ref(),
+    )
+    .await;
+
+    match outcome {
+        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred while reading a counter's history!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to check whether new player accounts may currently be registered.
+///
+/// ### Arguments
+/// - `state`: The axum router's state
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `RegistrationStatusResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if the admin could not be authenticated due to a server-side
+///     error
+pub async fn handle_admin_registration_status(
+    _admin: AdminPlayer,
+    State(state): State<AppState>,
+) -> Response {
+    (
+        StatusCode::OK,
+        Json(RegistrationStatusResponse::new(state.registration_open())),
+    )
+        .into_response()
+}
+
+/// Handle a request to open or close the registration of new player accounts. Existing accounts
+/// are unaffected. The change lasts until the server restarts, and only applies to this instance.
+///
+/// ### Arguments
+/// - `state`: The axum router's state
+/// - `body`: The HTTP request body
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `RegistrationStatusResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if the admin could not be authenticated due to a server-side
+///     error
+pub async fn handle_admin_registration_toggle(
+    _admin: AdminPlayer,
+    State(state): State<AppState>,
+    Json(body): Json<RegistrationToggleRequestBody>,
+) -> Response {
+    state.set_registration_open(body.open);
+    println!(
+        "Registration has been {} by an admin.",
+        if body.open { "opened" } else { "closed" }
+    );
+
+    (
+        StatusCode::OK,
+        Json(RegistrationStatusResponse::new(body.open)),
+    )
+        .into_response()
+}
+
+/// Handle a request to list the entries of the registration allow-list.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with an `AllowlistResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_allowlist(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+) -> Response {
+    match AdminService::allowlist(repos.allowlist()).await {
+        Ok(allowlist) => (StatusCode::OK, Json(allowlist)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred while listing the allow-list!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to add an entry to the registration allow-list.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `entry`: The email address or domain to add
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` if the entry is on the allow-list
+/// - Error
+///   - `400 BAD REQUEST` if the entry is malformed, or the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_allowlist_addition(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+    Path(entry): Path<String>,
+) -> Response {
+    let entry = match normalize_allowlist_entry(&entry) {
+        Some(e) => e,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    match AdminService::add_to_allowlist(repos.allowlist(), &entry).await {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred while adding to the allow-list!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
+
+/// Handle a request to remove an entry from the registration allow-list.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `entry`: The email address or domain to remove
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` if the entry was removed
+/// - Error
+///   - `400 BAD REQUEST` if the entry is malformed, or the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid
+///   - `403 FORBIDDEN` if the player is not an admin
+///   - `404 NOT FOUND` with a `MissingDocumentResponse` body if the entry was not added at runtime
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_admin_allowlist_removal(
+    _admin: AdminPlayer,
+    State(repos): State<Repositories>,
+    Path(entry): Path<String>,
+) -> Response {
+    let entry = match normalize_allowlist_entry(&entry) {
+        Some(e) => e,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    match AdminService::remove_from_allowlist(repos.allowlist(), &entry).await {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(DBoError::MissingDocument(collection)) => (
+            StatusCode::NOT_FOUND,
+            Json(MissingDocumentResponse::new(&collection)),
+        )
+            .into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => {
+            eprintln!("An unexpected DBoError occurred while removing from the allow-list!");
+            eprintln!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! This module provides the single place where authentication failures are turned into
+//! `401 UNAUTHORIZED` responses.
+//!
+//! The specific reason for every failure is always logged. When `ENV.expose_authn_reasons` is
+//! enabled (which is only allowed in development), a coarse reason code is also included in the
+//! response body, to speed up frontend debugging. The code never reveals whether an account
+//! exists.
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    config::environment::ENV, errors::DBoError, handlers::responses::AuthnFailureResponse,
+};
+
+/// Return the coarse reason code for an error which prevented authentication.
+///
+/// ### Arguments
+/// - `error`: The error which prevented authentication
+fn reason_code(error: &DBoError) -> &'static str {
+    match error {
+        DBoError::AuthenticationFailure(reason) => reason.code(),
+        DBoError::TokenExpired => "expired_token",
+        DBoError::TokenPremature => "revoked_token",
+        DBoError::InvalidToken => "invalid_token",
+        _ => "bad_credentials",
+    }
+}
+
+/// Respond to a request which could not be authenticated. The reason is always logged, so that
+/// failures can be investigated in production, where it is never sent to the client.
+///
+/// ### Arguments
+/// - `error`: The error which prevented authentication
+///
+/// ### Returns
+/// `401 UNAUTHORIZED`, with an `AuthnFailureResponse` body only if reasons are exposed
+pub fn unauthorized(error: &DBoError) -> Response {
+    unauthorized_with(error, ENV.expose_authn_reasons)
+}
+
+/// Respond to a request which could not be authenticated, choosing whether to expose the reason.
+///
+/// ### Arguments
+/// - `error`: The error which prevented authentication
+/// - `expose_reason`: Whether the response body should include the reason code
+fn unauthorized_with(error: &DBoError, expose_reason: bool) -> Response {
+    println!("Authentication failed: {:?}", error);
+
+    if expose_reason {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(AuthnFailureResponse::new(reason_code(error))),
+        )
+            .into_response()
+    } else {
+        (StatusCode::UNAUTHORIZED).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+    use crate::errors::AuthnFailureReason;
+
+    #[test]
+    fn test_reason_codes_hide_account_existence() {
+        assert_eq!(
+            reason_code(&DBoError::AuthenticationFailure(
+                AuthnFailureReason::UnknownAccount
+            )),
+            reason_code(&DBoError::AuthenticationFailure(
+                AuthnFailureReason::WrongPassword
+            ))
+        );
+        assert_eq!(
+            reason_code(&DBoError::missing_document("players")),
+            "bad_credentials"
+        );
+        assert_eq!(reason_code(&DBoError::TokenExpired), "expired_token");
+    }
+
+    #[test]
+    fn test_deleted_player_reason_code() {
+        assert_eq!(
+            reason_code(&DBoError::AuthenticationFailure(
+                AuthnFailureReason::PlayerNotFound
+            )),
+            "invalid_token"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deleted_player_is_unauthorized() {
+        // A still-valid access token whose player was deleted (see `token_player`).
+        let error = DBoError::AuthenticationFailure(AuthnFailureReason::PlayerNotFound);
+
+        let hidden = unauthorized_with(&error, false);
+        assert_eq!(hidden.status(), StatusCode::UNAUTHORIZED);
+        assert!(
+            to_bytes(hidden.into_body(), usize::MAX)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let exposed = unauthorized_with(&error, true);
+        assert_eq!(exposed.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            to_bytes(exposed.into_body(), usize::MAX).await.unwrap(),
+            r#"{"reason":"invalid_token"}"#
+        );
+    }
+
+    #[test]
+    fn test_pending_deletion_reason_code() {
+        assert_eq!(
+            reason_code(&DBoError::AuthenticationFailure(
+                AuthnFailureReason::PendingDeletion
+            )),
+            "pending_deletion"
+        );
+    }
+}
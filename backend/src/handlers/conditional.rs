@@ -0,0 +1,138 @@
+//! This module provides support for **conditional GET requests**, allowing clients to cache
+//! responses which rarely change.
+//!
+//! A `CacheableBody` response carries an `ETag` header, a hash of its serialized body. When a
+//! client sends that ETag back in an `If-None-Match` header, and the body has not changed since,
+//! the body is omitted and `304 NOT MODIFIED` is returned instead. Content which never changes can
+//! be kept in a `Lazy`, so that it is only serialized and hashed when it is first served.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::errors::DBoResult;
+
+/// A serialized body alongside its content type and ETag.
+pub struct CacheableBody {
+    /// The serialized body.
+    body: Vec<u8>,
+    /// The media type of the body.
+    content_type: &'static str,
+    /// The quoted ETag of the body.
+    etag: HeaderValue,
+}
+
+impl CacheableBody {
+    /// Compute the ETag of an already serialized body.
+    ///
+    /// ### Arguments
+    /// - `body`: The response body
+    /// - `content_type`: The media type of the body
+    pub fn new(body: Vec<u8>, content_type: &'static str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let etag = HeaderValue::from_str(&format!(r#""{:016x}""#, hasher.finish()))
+            .expect("A hex digest is always a valid header value");
+
+        Self {
+            body,
+            content_type,
+            etag,
+        }
+    }
+
+    /// Serialize a value as JSON, and compute the ETag of the result.
+    ///
+    /// ### Arguments
+    /// - `value`: The value to be sent as the response body
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the value cannot be serialized
+    pub fn json<T: Serialize>(value: &T) -> DBoResult<Self> {
+        Ok(Self::new(serde_json::to_vec(value)?, "application/json"))
+    }
+
+    /// Respond to a request, omitting the body if the client already has it cached.
+    ///
+    /// ### Arguments
+    /// - `headers`: The headers of the request
+    ///
+    /// ### Returns
+    /// - `304 NOT MODIFIED` if the `If-None-Match` header matches the ETag
+    /// - `200 OK` with the body otherwise
+    pub fn respond(&self, headers: &HeaderMap) -> Response {
+        let cached = headers
+            .get(IF_NONE_MATCH)
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|v| etag_matches(v, self.etag.to_str().unwrap_or_default()));
+
+        if cached {
+            (StatusCode::NOT_MODIFIED, [(ETAG, self.etag.clone())]).into_response()
+        } else {
+            (
+                StatusCode::OK,
+                [
+                    (CONTENT_TYPE, HeaderValue::from_static(self.content_type)),
+                    (ETAG, self.etag.clone()),
+                ],
+                self.body.clone(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Determine whether an `If-None-Match` header matches an ETag. The header may list several
+/// ETags separated by commas, or be `*` to match any. As required for `If-None-Match`, weak ETags
+/// (prefixed by `W/`) are compared as if they were strong.
+///
+/// ### Arguments
+/// - `if_none_match`: The value of the `If-None-Match` header
+/// - `etag`: The quoted ETag of the current body
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = r#""0123456789abcdef""#;
+
+        assert!(etag_matches(etag, etag));
+        assert!(etag_matches(r#"W/"0123456789abcdef""#, etag));
+        assert!(etag_matches(r#""other", "0123456789abcdef""#, etag));
+        assert!(etag_matches("*", etag));
+
+        assert!(!etag_matches(r#""other""#, etag));
+        assert!(!etag_matches("0123456789abcdef", etag));
+        assert!(!etag_matches("", etag));
+    }
+
+    #[test]
+    fn test_cacheable_body_respond() {
+        let json = CacheableBody::json(&vec!["UTC"]).unwrap();
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(json.respond(&headers).status(), StatusCode::OK);
+
+        headers.insert(IF_NONE_MATCH, json.etag.clone());
+        assert_eq!(json.respond(&headers).status(), StatusCode::NOT_MODIFIED);
+
+        let changed = CacheableBody::json(&vec!["UTC", "Europe/Madrid"]).unwrap();
+        assert_ne!(changed.etag, json.etag);
+        assert_eq!(changed.respond(&headers).status(), StatusCode::OK);
+    }
+}
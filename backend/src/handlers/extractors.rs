@@ -0,0 +1,191 @@
+//! This module provides custom axum extractors, which authenticate a request (or otherwise
+//! describe where it came from) before its handler is ever called.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION, request::Parts},
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    adapters::repositories::Repositories, config::environment::ENV, errors::DBoError,
+    handlers::authn::unauthorized, models::Player,
+};
+
+/// An authenticated player with access to the admin endpoints. Extracting this from a request
+/// requires a valid access token belonging to an admin player.
+///
+/// ### Rejections
+/// - `400 BAD REQUEST` if the request has no bearer access token
+/// - `401 UNAUTHORIZED` if the access token is invalid, expired, or its player cannot be found
+/// - `403 FORBIDDEN` if the player is not an admin, or their account is unconfirmed
+/// - `500 INTERNAL SERVER ERROR` if the player could not be found due to a server-side error
+pub struct AdminPlayer(#[allow(dead_code)] pub Player);
+
+impl<S: Send + Sync> FromRequestParts<S> for AdminPlayer
+where
+    Repositories: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let repos = Repositories::from_ref(state);
+
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| (StatusCode::BAD_REQUEST).into_response())?;
+
+        let player = match repos.players().find_by_token_confirmed(token).await {
+            Ok(p) => p,
+            Err(
+                e @ (DBoError::InvalidToken
+                | DBoError::TokenExpired
+                | DBoError::TokenPremature
+                | DBoError::AuthenticationFailure(_)),
+            ) => return Err(unauthorized(&e)),
+            Err(DBoError::AccountUnconfirmed) => {
+                return Err((StatusCode::FORBIDDEN).into_response());
+            }
+            Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR).into_response()),
+        };
+
+        if !player.admin() {
+            return Err((StatusCode::FORBIDDEN).into_response());
+        }
+
+        Ok(Self(player))
+    }
+}
+
+/// The address of the client which made a request. Behind trusted reverse proxies (see
+/// `ENV.trusted_proxy_hops`), it is read from the `X-Forwarded-For` header; otherwise, it is the
+/// address of the connecting peer.
+///
+/// ### Rejections
+/// - `500 INTERNAL SERVER ERROR` if the router was not served with connection info
+pub struct ClientIp(pub IpAddr);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = match parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(address)) => address.ip(),
+            None => {
+                eprintln!("The client IP was requested, but the router has no connection info!");
+                return Err((StatusCode::INTERNAL_SERVER_ERROR).into_response());
+            }
+        };
+
+        let forwarded_for = forwarded_for(&parts.headers);
+
+        Ok(Self(client_ip(
+            peer,
+            forwarded_for.as_deref(),
+            ENV.trusted_proxy_hops,
+        )))
+    }
+}
+
+/// Read the complete `X-Forwarded-For` list of a request. Some proxies append their entry as a
+/// separate header line rather than extending the existing one, so every line is joined in order;
+/// reading only the first would let the client write the entries which should be trusted.
+///
+/// ### Arguments
+/// - `headers`: The request headers
+///
+/// ### Returns
+/// The joined list, or None if there is no such header, or any line of it is not valid text
+fn forwarded_for(headers: &HeaderMap) -> Option<String> {
+    let lines = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .map(|h| h.to_str().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    (!lines.is_empty()).then(|| lines.join(","))
+}
+
+/// Determine the address of the client which made a request.
+///
+/// Every trusted proxy appends the address it received the request from to `X-Forwarded-For`, so
+/// the client's address is the entry `trusted_hops` places from the right. Anything further left
+/// was written by the client, and cannot be trusted. If the header is missing, too short, or holds
+/// an invalid address at that position, the peer address is used instead.
+///
+/// ### Arguments
+/// - `peer`: The address of the connecting peer
+/// - `forwarded_for`: The value of the `X-Forwarded-For` header, if any
+/// - `trusted_hops`: The number of trusted proxies in front of the application
+///
+/// ### Returns
+/// The client's address; IPv4 addresses mapped to IPv6 are returned as IPv4
+fn client_ip(peer: IpAddr, forwarded_for: Option<&str>, trusted_hops: usize) -> IpAddr {
+    let forwarded = match (trusted_hops, forwarded_for) {
+        (0, _) | (_, None) => None,
+        (hops, Some(header)) => header
+            .split(',')
+            .rev()
+            .nth(hops - 1)
+            .and_then(|entry| entry.trim().parse::<IpAddr>().ok()),
+    };
+
+    forwarded.unwrap_or(peer).to_canonical()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        // Without trusted proxies, the header is ignored.
+        assert_eq!(client_ip(peer, Some("203.0.113.7"), 0), peer);
+        assert_eq!(client_ip(ip("::ffff:10.0.0.1"), None, 0), ip("10.0.0.1"));
+
+        // Behind one proxy, only the rightmost entry is trusted.
+        assert_eq!(client_ip(peer, Some("203.0.113.7"), 1), ip("203.0.113.7"));
+        assert_eq!(
+            client_ip(peer, Some("6.6.6.6, 203.0.113.7"), 1),
+            ip("203.0.113.7")
+        );
+
+        // Behind two proxies, the second entry from the right is the client.
+        assert_eq!(
+            client_ip(peer, Some("6.6.6.6, 2001:db8::1 , 198.51.100.2"), 2),
+            ip("2001:db8::1")
+        );
+
+        // A missing, short, or invalid header falls back to the peer.
+        assert_eq!(client_ip(peer, None, 1), peer);
+        assert_eq!(client_ip(peer, Some("203.0.113.7"), 2), peer);
+        assert_eq!(client_ip(peer, Some("unknown"), 1), peer);
+    }
+
+    #[test]
+    fn test_forwarded_for_joins_header_lines() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+
+        // The client sends its own header line, and the proxy appends another.
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", "6.6.6.6".parse().unwrap());
+        headers.append("x-forwarded-for", "203.0.113.7".parse().unwrap());
+
+        let joined = forwarded_for(&headers);
+        assert_eq!(joined.as_deref(), Some("6.6.6.6,203.0.113.7"));
+        assert_eq!(
+            client_ip(peer, joined.as_deref(), 1),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        assert_eq!(forwarded_for(&HeaderMap::new()), None);
+    }
+}
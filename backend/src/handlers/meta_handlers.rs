@@ -0,0 +1,121 @@
+//! This module provides all HTTP handler functions which describe the application itself, such as
+//! the values accepted by other endpoints.
+
+use std::time::Instant;
+
+use axum::{
+    Json,
+    http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use once_cell::sync::Lazy;
+
+use crate::{
+    adapters::{
+        metrics::{METRICS, peer_allowed},
+        mongo::DATABASE_BREAKER,
+    },
+    config::environment::ENV,
+    handlers::{
+        conditional::CacheableBody,
+        extractors::ClientIp,
+        responses::{HealthResponse, RootResponse},
+    },
+    models::clock::RealClock,
+    services::meta_service::MetaService,
+};
+
+/// The path at which the OpenAPI document is served. The document is written in YAML, and served
+/// exactly as written, rather than as `/openapi.json`: converting it would need a YAML parser,
+/// which is not worth a new dependency when every OpenAPI tool reads YAML as well.
+const OPENAPI_PATH: &str = "/openapi.yaml";
+
+/// The OpenAPI document describing this API, which is embedded in the binary.
+static OPENAPI_DOCUMENT: Lazy<CacheableBody> = Lazy::new(|| {
+    CacheableBody::new(
+        include_bytes!("../../documentation/openapi.yaml").to_vec(),
+        "application/yaml",
+    )
+});
+
+/// Handle a request for the root path, so that uptime monitors and browsers receive a sensible
+/// response, pointing them at the API documentation.
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `RootResponse` body
+pub async fn handle_root() -> Response {
+    (StatusCode::OK, Json(RootResponse::new(OPENAPI_PATH))).into_response()
+}
+
+/// Handle a request for the health of the service. The database is not queried; instead, the state
+/// of the circuit breaker guarding it is reported, so that the check stays cheap.
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `HealthResponse` body, even while the database is unreachable
+pub async fn handle_health() -> Response {
+    let now = Instant::now();
+    let response = HealthResponse::new(
+        DATABASE_BREAKER.status(now),
+        DATABASE_BREAKER.retry_after(now),
+    );
+
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+/// Handle a request for the OpenAPI document describing this API. The response carries an ETag,
+/// which only changes when the server is deployed with a new version of the document.
+///
+/// ### Arguments
+/// - `headers`: The headers of the request, possibly including `If-None-Match`
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with the OpenAPI document, in YAML
+///   - `304 NOT MODIFIED` if the client's cached copy is still current
+pub async fn handle_openapi_document(headers: HeaderMap) -> Response {
+    OPENAPI_DOCUMENT.respond(&headers)
+}
+
+/// Handle a request to list the available time zones. The response carries an ETag, which only
+/// changes when the offset of a time zone does.
+///
+/// ### Arguments
+/// - `headers`: The headers of the request, possibly including `If-None-Match`
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `TimeZoneListResponse` body
+///   - `304 NOT MODIFIED` if the client's cached copy is still current
+/// - Error
+///   - `500 INTERNAL SERVER ERROR` if the list could not be serialized
+pub async fn handle_time_zone_list(headers: HeaderMap) -> Response {
+    match CacheableBody::json(&MetaService::list_time_zones(&RealClock)) {
+        Ok(json) => json.respond(&headers),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+    }
+}
+
+/// Handle a request for the recorded metrics, in the Prometheus text format. Only the addresses on
+/// `ENV.metrics_allowlist` may read them; to everyone else, the endpoint does not exist.
+///
+/// ### Arguments
+/// - `client`: The address of the client, behind any trusted proxies
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with the metrics in the Prometheus text format
+/// - Error
+///   - `404 NOT FOUND` if metrics are disabled, or the client is not on the allow-list
+pub async fn handle_metrics(ClientIp(client): ClientIp) -> Response {
+    match METRICS.as_ref() {
+        Some(handle) if peer_allowed(client, &ENV.metrics_allowlist) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response(),
+        _ => (StatusCode::NOT_FOUND).into_response(),
+    }
+}
@@ -1,9 +1,14 @@
 //! This module provides all HTTP handler functions related to player accounts.
 
+use std::time::Duration;
+
 use axum::{
     Json,
     extract::{Path, State},
-    http::{HeaderMap, StatusCode, header::SET_COOKIE},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{RETRY_AFTER, SET_COOKIE},
+    },
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::{
@@ -12,27 +17,57 @@ use axum_extra::extract::{
 };
 
 use crate::{
-    adapters::repositories::Repositories,
-    config::environment::ENV,
+    adapters::{
+        rate_limit::{check_login_attempt, check_registration_validation},
+        repositories::Repositories,
+        storage::AVATAR_STORAGE,
+    },
+    config::environment::{CookieSameSite, ENV},
     errors::DBoError,
     handlers::{
+        authn::unauthorized,
+        extractors::ClientIp,
         request_bodies::{
-            PasswordChangeRequestBody, PasswordRequestBody, PlayerLoginRequestBody,
+            AvatarChangeRequestBody, AvatarUploadRequestBody, PasswordChangeRequestBody,
+            PasswordRequestBody, PasswordResetRequestBody, PlayerLoginRequestBody,
             PlayerRegistrationRequestBody, ProposedEmailChangeRequestBody,
-            UsernameChangeRequestBody,
+            PublicPlayerBatchRequestBody, RefreshTokenRequestBody, UsernameChangeRequestBody,
+            UsernameRecoveryRequestBody,
         },
         responses::{
-            AccessTokenResponse, AccountLockedResponse, MissingDocumentResponse,
-            PlayerUniquenessViolationResponse,
+            AccessTokenResponse, MissingDocumentResponse, PlayerUniquenessViolationResponse,
+            RegistrationClosedResponse, TokenPairResponse,
         },
     },
+    models::submodels::LanguagePreference,
     services::player_service::PlayerService,
+    state::AppState,
 };
 
+/// The maximum number of public profiles which may be requested at once.
+const PUBLIC_BATCH_LIMIT: usize = 50;
+
+/// The path to which the refresh token cookie is scoped. The cookie is only needed to refresh a
+/// session and to log out, so both of those endpoints live under this path, and the cookie is never
+/// sent to any other endpoint.
+const REFRESH_COOKIE_PATH: &str = "/players/refresh";
+
 // //////////////// //
 // HELPER FUNCTIONS //
 // //////////////// //
 
+/// Build the response refusing a registration while an admin has closed registration.
+///
+/// ### Arguments
+/// - `language`: The language requested during registration
+fn registration_closed(language: &LanguagePreference) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(RegistrationClosedResponse::new(language)),
+    )
+        .into_response()
+}
+
 fn unexpected_error(error: DBoError, request_name: &str) -> Response {
     eprintln!("An unexpected DBoError occurred during {}!", request_name);
     eprintln!("This should not happen!");
@@ -40,21 +75,80 @@ fn unexpected_error(error: DBoError, request_name: &str) -> Response {
     (StatusCode::INTERNAL_SERVER_ERROR).into_response()
 }
 
-fn build_refresh_token_header(id: &str, secret: &str) -> HeaderMap {
-    let cookie_value = format!("{}:{}", id, secret);
-    let cookie = Cookie::build(("refresh_token", cookie_value))
+/// Build the HTTP-only cookie holding a refresh token.
+///
+/// ### Arguments
+/// - `value`: The value of the cookie
+fn refresh_token_cookie(value: String) -> Cookie<'static> {
+    let same_site = match ENV.cookie_same_site {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+        CookieSameSite::None => SameSite::None,
+    };
+
+    let mut cookie = Cookie::build(("refresh_token", value))
         .http_only(true)
         .secure(ENV.secure())
-        .same_site(SameSite::Strict)
-        .path("/players/refresh")
+        .same_site(same_site)
+        .path(REFRESH_COOKIE_PATH)
         .build();
 
+    if let Some(domain) = &ENV.cookie_domain {
+        cookie.set_domain(domain.clone());
+    }
+
+    cookie
+}
+
+/// Build headers removing the client's refresh token cookie.
+fn remove_refresh_token_header() -> HeaderMap {
+    let mut cookie = refresh_token_cookie(String::new());
+    cookie.make_removal();
+
     let mut headers = HeaderMap::new();
     headers.insert(SET_COOKIE, cookie.to_string().parse().unwrap());
 
     headers
 }
 
+fn build_refresh_token_header(id: &str, secret: &str) -> HeaderMap {
+    let cookie = refresh_token_cookie(format!("{}:{}", id, secret));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(SET_COOKIE, cookie.to_string().parse().unwrap());
+
+    headers
+}
+
+/// Return a `429 TOO MANY REQUESTS` response, asking the client to retry after a delay.
+///
+/// ### Arguments
+/// - `retry_after`: How long the client should wait, rounded up to whole seconds
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, HeaderValue::from(seconds.max(1)))],
+    )
+        .into_response()
+}
+
+/// Map a failure to refresh a player's authentication tokens to a response. Shared by both the
+/// cookie-based and the body-based refresh endpoints.
+fn refresh_failure(error: DBoError) -> Response {
+    match error {
+        DBoError::InvalidToken
+        | DBoError::TokenPremature
+        | DBoError::AuthenticationFailure(_)
+        | DBoError::MissingDocument(_) => unauthorized(&error),
+        DBoError::TokenExpired => (StatusCode::GONE).into_response(),
+        DBoError::InternalConflict => (StatusCode::FORBIDDEN).into_response(),
+        DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        _ => unexpected_error(error, "player authentication refresh"),
+    }
+}
+
 fn extract_access_token(headers: HeaderMap) -> Option<String> {
     let header = match headers.get("Authorization") {
         Some(h) => h.to_str(),
@@ -66,12 +160,7 @@ fn extract_access_token(headers: HeaderMap) -> Option<String> {
         Err(_) => return None,
     };
 
-    let token = value.strip_prefix("Bearer ");
-
-    match token {
-        Some(t) => Some(t.to_string()),
-        None => None,
-    }
+    value.strip_prefix("Bearer ").map(|t| t.to_string())
 }
 
 // //////// //
@@ -81,6 +170,7 @@ fn extract_access_token(headers: HeaderMap) -> Option<String> {
 /// Handle a request to create a new player account.
 ///
 /// ### Arguments
+/// - `state`: The axum router's state
 /// - `repos`: The Repositories stored in the axum router's state
 /// - `body`: The HTTP request body
 ///
@@ -91,23 +181,34 @@ fn extract_access_token(headers: HeaderMap) -> Option<String> {
 ///   - `400 BAD REQUEST`
 ///     - with `InputValidationResponse` body if input fails validation
 ///     - with plaintext message if JSON body is malformed
+///     - with no body if the CAPTCHA token is missing or rejected
 ///   - `409 CONFLICT` with an `ExistingFieldViolationResponse` body
 ///   - `422 UNPROCESSABLE ENTITY` with plaintext message if request body is missing fields
 ///   - `500 INTERNAL SERVER ERROR` if an HTTP adapter failed
+///   - `503 SERVICE UNAVAILABLE` with a `RegistrationClosedResponse` body if an admin has closed
+///     registration
 pub async fn handle_player_registration(
+    State(state): State<AppState>,
     State(repos): State<Repositories>,
     Json(body): Json<PlayerRegistrationRequestBody>,
 ) -> Response {
+    if !state.registration_open() {
+        return registration_closed(body.preferred_language());
+    }
+
     let outcome = PlayerService::register_player(
         repos.players(),
         repos.confirmation_tokens(),
         repos.counters(),
+        repos.allowlist(),
         body.username(),
         body.password(),
         body.email(),
         body.gender(),
         body.preferred_language(),
         body.pronoun(),
+        body.time_zone(),
+        body.captcha_token(),
     )
     .await;
 
@@ -117,6 +218,7 @@ pub async fn handle_player_registration(
             DBoError::InvalidPlayerInfo(info) => {
                 (StatusCode::BAD_REQUEST, Json(info)).into_response()
             }
+            DBoError::CaptchaFailure => (StatusCode::BAD_REQUEST).into_response(),
             DBoError::UniquenessViolation(username, email) => (
                 StatusCode::CONFLICT,
                 Json(PlayerUniquenessViolationResponse::new(username, email)),
@@ -130,6 +232,51 @@ pub async fn handle_player_registration(
     }
 }
 
+/// Handle a request to check whether a registration would be accepted, without creating anything.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `client`: The address of the client, behind any trusted proxies
+/// - `body`: The HTTP request body, shaped exactly like a registration
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `RegistrationValidationResponse` body, whether or not the registration is
+///     valid
+/// - Error
+///   - `400 BAD REQUEST` with plaintext message if JSON body is malformed
+///   - `422 UNPROCESSABLE ENTITY` with plaintext message if request body is missing fields
+///   - `429 TOO MANY REQUESTS` if the client has requested too many validations within the last
+///     minute
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_registration_validation(
+    State(repos): State<Repositories>,
+    ClientIp(client): ClientIp,
+    Json(body): Json<PlayerRegistrationRequestBody>,
+) -> Response {
+    if let Err(retry_after) = check_registration_validation(client) {
+        return too_many_requests(retry_after);
+    }
+
+    let outcome = PlayerService::validate_registration(
+        repos.players(),
+        body.username(),
+        body.password(),
+        body.email(),
+        body.gender(),
+        body.preferred_language(),
+        body.pronoun(),
+        body.time_zone(),
+    )
+    .await;
+
+    match outcome {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => unexpected_error(e, "registration validation"),
+    }
+}
+
 pub async fn handle_player_account_confirmation(
     State(repos): State<Repositories>,
     Path((player_id, token_id)): Path<(String, String)>,
@@ -146,14 +293,7 @@ pub async fn handle_player_account_confirmation(
     match outcome {
         Ok(()) => (StatusCode::NO_CONTENT).into_response(),
         Err(e) => match e {
-            DBoError::MissingDocument(collection) => (
-                StatusCode::NOT_FOUND,
-                Json(MissingDocumentResponse::new(&collection)),
-            )
-                .into_response(),
-            DBoError::InternalConflict => (StatusCode::CONFLICT).into_response(),
-            DBoError::RelationalConflict => (StatusCode::FORBIDDEN).into_response(),
-            DBoError::TokenExpired => (StatusCode::GONE).into_response(),
+            DBoError::InvalidToken => (StatusCode::NOT_FOUND).into_response(),
             DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
             _ => unexpected_error(e, "account confirmation"),
         },
@@ -187,8 +327,13 @@ pub async fn handle_player_account_rejection(
 
 pub async fn handle_player_login(
     State(repos): State<Repositories>,
+    ClientIp(client): ClientIp,
     Json(body): Json<PlayerLoginRequestBody>,
 ) -> Response {
+    if let Err(retry_after) = check_login_attempt(client) {
+        return too_many_requests(retry_after);
+    }
+
     let outcome = PlayerService::login(
         repos.players(),
         repos.refresh_tokens(),
@@ -206,20 +351,17 @@ pub async fn handle_player_login(
             (
                 StatusCode::OK,
                 headers,
-                Json(AccessTokenResponse::new(&info.access_token)),
+                Json(AccessTokenResponse::new(
+                    &info.access_token,
+                    info.security_notice.as_ref(),
+                )),
             )
                 .into_response()
         }
         Err(e) => match e {
-            DBoError::AuthenticationFailure | DBoError::MissingDocument(_) => {
-                (StatusCode::UNAUTHORIZED).into_response()
-            }
+            DBoError::AuthenticationFailure(_) | DBoError::MissingDocument(_) => unauthorized(&e),
             DBoError::InternalConflict => (StatusCode::CONFLICT).into_response(),
-            DBoError::AccountLocked(time) => (
-                StatusCode::FORBIDDEN,
-                Json(AccountLockedResponse::new(time)),
-            )
-                .into_response(),
+            DBoError::AccountLocked(info) => (StatusCode::FORBIDDEN, Json(info)).into_response(),
             DBoError::AdapterError | DBoError::InvalidEmailAddress => {
                 (StatusCode::INTERNAL_SERVER_ERROR).into_response()
             }
@@ -258,13 +400,56 @@ pub async fn handle_resend_registration_email(
     }
 }
 
+pub async fn handle_unconfirmed_email_correction(
+    State(repos): State<Repositories>,
+    Path((player_id, token_id)): Path<(String, String)>,
+    Json(body): Json<ProposedEmailChangeRequestBody>,
+) -> Response {
+    let outcome = PlayerService::correct_unconfirmed_email(
+        repos.players(),
+        repos.confirmation_tokens(),
+        repos.allowlist(),
+        &player_id,
+        &token_id,
+        &body.password,
+        &body.new_email,
+    )
+    .await;
+
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(e) => match e {
+            DBoError::MissingDocument(collection) => (
+                StatusCode::NOT_FOUND,
+                Json(MissingDocumentResponse::new(&collection)),
+            )
+                .into_response(),
+            DBoError::AuthenticationFailure(_) => unauthorized(&e),
+            DBoError::InternalConflict => (StatusCode::CONFLICT).into_response(),
+            DBoError::RelationalConflict => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::InvalidPlayerInfo(info) => {
+                (StatusCode::BAD_REQUEST, Json(info)).into_response()
+            }
+            DBoError::UniquenessViolation(username, email) => (
+                StatusCode::CONFLICT,
+                Json(PlayerUniquenessViolationResponse::new(username, email)),
+            )
+                .into_response(),
+            DBoError::AdapterError | DBoError::InvalidEmailAddress => {
+                (StatusCode::INTERNAL_SERVER_ERROR).into_response()
+            }
+            _ => unexpected_error(e, "unconfirmed email correction"),
+        },
+    }
+}
+
 pub async fn handle_player_refresh(
     State(repos): State<Repositories>,
     cookies: CookieJar,
 ) -> Response {
     let token_info = match cookies.get("refresh_token") {
         Some(cookie) => cookie.value(),
-        None => return (StatusCode::UNAUTHORIZED).into_response(),
+        None => return unauthorized(&DBoError::InvalidToken),
     };
 
     let output =
@@ -279,19 +464,120 @@ pub async fn handle_player_refresh(
             (
                 StatusCode::OK,
                 headers,
-                Json(AccessTokenResponse::new(&info.access_token)),
+                Json(AccessTokenResponse::new(
+                    &info.access_token,
+                    info.security_notice.as_ref(),
+                )),
             )
                 .into_response()
         }
-        Err(e) => match e {
-            DBoError::InvalidToken
-            | DBoError::AuthenticationFailure
-            | DBoError::MissingDocument(_) => (StatusCode::UNAUTHORIZED).into_response(),
-            DBoError::TokenExpired => (StatusCode::GONE).into_response(),
-            DBoError::InternalConflict => (StatusCode::FORBIDDEN).into_response(),
-            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
-            _ => unexpected_error(e, "player authentication refresh"),
-        },
+        Err(e) => refresh_failure(e),
+    }
+}
+
+/// Handle a refresh request from a client which cannot rely on cookies, such as a mobile app. The
+/// refresh token is read from the request body, and the new one is returned in the response body
+/// rather than in a cookie.
+pub async fn handle_player_token_refresh(
+    State(repos): State<Repositories>,
+    Json(body): Json<RefreshTokenRequestBody>,
+) -> Response {
+    let output = PlayerService::refresh_authn_tokens(
+        repos.players(),
+        repos.refresh_tokens(),
+        &body.refresh_token,
+    )
+    .await;
+
+    match output {
+        Ok(info) => (
+            StatusCode::OK,
+            Json(TokenPairResponse::new(
+                &info.access_token,
+                &info.refresh_token_id,
+                &info.refresh_token_secret,
+                info.security_notice.as_ref(),
+            )),
+        )
+            .into_response(),
+        Err(e) => refresh_failure(e),
+    }
+}
+
+/// Handle a request to log out, ending the session of the refresh_token cookie.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `cookies`: The request cookies, possibly containing the refresh token
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` with headers removing the refresh_token cookie, even if there was no
+///     session to end
+/// - Error
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_player_logout(
+    State(repos): State<Repositories>,
+    cookies: CookieJar,
+) -> Response {
+    if let Some(cookie) = cookies.get("refresh_token") {
+        match PlayerService::logout(repos.refresh_tokens(), cookie.value()).await {
+            Ok(()) => {}
+            Err(DBoError::AdapterError) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR).into_response();
+            }
+            Err(e) => return unexpected_error(e, "player logout"),
+        }
+    }
+
+    (StatusCode::NO_CONTENT, remove_refresh_token_header()).into_response()
+}
+
+/// Handle a request to log out everywhere, ending every one of the authenticated player's
+/// sessions.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `headers`: The request headers, containing the bearer access token
+/// - `body`: The request body, containing the player's password
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` with headers removing the refresh_token cookie
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid or expired, if its player no longer
+///     exists, or if the password is wrong
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_player_logout_all(
+    State(repos): State<Repositories>,
+    headers: HeaderMap,
+    Json(body): Json<PasswordRequestBody>,
+) -> Response {
+    let token = match extract_access_token(headers) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    let outcome = PlayerService::logout_all_sessions(
+        repos.players(),
+        repos.refresh_tokens(),
+        &token,
+        &body.password,
+    )
+    .await;
+
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT, remove_refresh_token_header()).into_response(),
+        Err(
+            e @ (DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)
+            | DBoError::MissingDocument(_)),
+        ) => unauthorized(&e),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => unexpected_error(e, "logout everywhere"),
     }
 }
 
@@ -307,7 +593,7 @@ pub async fn handle_player_deletion(
 
     let outcome = PlayerService::delete_player_account(
         repos.players(),
-        repos.counters(),
+        repos.undo_tokens(),
         &token,
         &body.password,
     )
@@ -315,8 +601,208 @@ pub async fn handle_player_deletion(
 
     match outcome {
         Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(DBoError::InternalConflict) => (StatusCode::CONFLICT).into_response(),
+        Err(
+            e @ (DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)),
+        ) => unauthorized(&e),
+        Err(e) => unexpected_error(e, "player deletion"),
+    }
+}
+
+/// Handle a request for the authenticated player's own information.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `headers`: The request headers, containing the bearer access token
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `SafePlayerResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid or expired, or its player no longer exists
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_get_current_player(
+    State(repos): State<Repositories>,
+    headers: HeaderMap,
+) -> Response {
+    let token = match extract_access_token(headers) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    match PlayerService::current_player(repos.players(), &token).await {
+        Ok(player) => (StatusCode::OK, Json(player)).into_response(),
         Err(e) => match e {
-            _ => unexpected_error(e, "player deletion"),
+            DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_) => unauthorized(&e),
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "current player"),
+        },
+    }
+}
+
+/// Handle a request for the authenticated player's rank.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `headers`: The request headers, containing the bearer access token
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `PlayerRankResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid or expired, or its player cannot be found
+///   - `403 FORBIDDEN` if the player's account is unconfirmed
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_player_rank(State(repos): State<Repositories>, headers: HeaderMap) -> Response {
+    let token = match extract_access_token(headers) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    match PlayerService::player_rank(repos.players(), &token).await {
+        Ok(rank) => (StatusCode::OK, Json(rank)).into_response(),
+        Err(e) => match e {
+            DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)
+            | DBoError::MissingDocument(_) => unauthorized(&e),
+            DBoError::AccountUnconfirmed => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "player rank"),
+        },
+    }
+}
+
+/// Handle a request for the authenticated player's stats from past seasons.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `headers`: The request headers, containing the bearer access token
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `SeasonHistoryResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `401 UNAUTHORIZED` if the access token is invalid or expired, or its player cannot be found
+///   - `403 FORBIDDEN` if the player's account is unconfirmed
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_player_season_history(
+    State(repos): State<Repositories>,
+    headers: HeaderMap,
+) -> Response {
+    let token = match extract_access_token(headers) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    match PlayerService::season_history(repos.players(), repos.season_archives(), &token).await {
+        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
+        Err(e) => match e {
+            DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)
+            | DBoError::MissingDocument(_) => unauthorized(&e),
+            DBoError::AccountUnconfirmed => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "player season history"),
+        },
+    }
+}
+
+/// Handle a request to cancel the scheduled deletion of a player account.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `player_id`: The player's unique identifier
+/// - `token_id`: The undo token's unique identifier
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` if the deletion was cancelled
+/// - Error
+///   - `403 FORBIDDEN` if the token does not match the player, or is not a deletion token
+///   - `404 NOT FOUND` with a `MissingDocumentResponse` body if the player or token is missing
+///   - `409 CONFLICT` if the account is not scheduled for deletion
+///   - `410 GONE` if the grace period has already ended
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_player_deletion_cancellation(
+    State(repos): State<Repositories>,
+    Path((player_id, token_id)): Path<(String, String)>,
+) -> Response {
+    let outcome =
+        PlayerService::cancel_deletion(repos.players(), repos.undo_tokens(), &player_id, &token_id)
+            .await;
+
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(e) => match e {
+            DBoError::MissingDocument(collection) => (
+                StatusCode::NOT_FOUND,
+                Json(MissingDocumentResponse::new(&collection)),
+            )
+                .into_response(),
+            DBoError::RelationalConflict => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::InternalConflict => (StatusCode::CONFLICT).into_response(),
+            DBoError::TokenExpired => (StatusCode::GONE).into_response(),
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "player deletion cancellation"),
+        },
+    }
+}
+
+/// Handle a request to redeem an undo token, undoing the operation it was sent for.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `player_id`: The player's unique identifier
+/// - `token_id`: The undo token's unique identifier
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` if the operation was undone
+/// - Error
+///   - `403 FORBIDDEN` if the token does not match the player, or cannot be redeemed
+///   - `404 NOT FOUND` with a `MissingDocumentResponse` body if the player or token is missing
+///   - `409 CONFLICT` if there is nothing left to undo
+///   - `410 GONE` if the token has expired
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_undo_token_redemption(
+    State(repos): State<Repositories>,
+    Path((player_id, token_id)): Path<(String, String)>,
+) -> Response {
+    let outcome = PlayerService::redeem_undo_token(
+        repos.players(),
+        repos.refresh_tokens(),
+        repos.undo_tokens(),
+        &player_id,
+        &token_id,
+    )
+    .await;
+
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(e) => match e {
+            DBoError::MissingDocument(collection) => (
+                StatusCode::NOT_FOUND,
+                Json(MissingDocumentResponse::new(&collection)),
+            )
+                .into_response(),
+            DBoError::RelationalConflict => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::InternalConflict => (StatusCode::CONFLICT).into_response(),
+            DBoError::TokenExpired => (StatusCode::GONE).into_response(),
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "undo token redemption"),
         },
     }
 }
@@ -341,9 +827,138 @@ pub async fn handle_player_username_change(
     .await;
 
     match outcome {
+        Ok(receipt) => (StatusCode::OK, Json(receipt)).into_response(),
+        Err(DBoError::AccountUnconfirmed) => (StatusCode::FORBIDDEN).into_response(),
+        Err(DBoError::NoChange) => (StatusCode::BAD_REQUEST).into_response(),
+        Err(DBoError::InvalidPlayerInfo(info)) => {
+            (StatusCode::BAD_REQUEST, Json(info)).into_response()
+        }
+        Err(DBoError::UniquenessViolation(username, email)) => (
+            StatusCode::CONFLICT,
+            Json(PlayerUniquenessViolationResponse::new(username, email)),
+        )
+            .into_response(),
+        Err(
+            e @ (DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)),
+        ) => unauthorized(&e),
+        Err(e) => unexpected_error(e, "username change"),
+    }
+}
+
+/// Handle a request to change the authenticated player's avatar.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `headers`: The request headers, containing the bearer access token
+/// - `body`: The request body, containing the new avatar
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT`
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing, or the avatar is unchanged
+///   - `400 BAD REQUEST` with a `PlayerInvalidFieldsResponse` body if the avatar is invalid
+///   - `401 UNAUTHORIZED` if the access token is invalid or expired, or its player no longer exists
+///   - `403 FORBIDDEN` if the player's account is unconfirmed
+///   - `404 NOT FOUND` if the player is deleted while the request is being handled
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_player_avatar_change(
+    State(repos): State<Repositories>,
+    headers: HeaderMap,
+    Json(body): Json<AvatarChangeRequestBody>,
+) -> Response {
+    let token = match extract_access_token(headers) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    match PlayerService::change_avatar(repos.players(), &token, body.avatar.as_ref()).await {
         Ok(()) => (StatusCode::NO_CONTENT).into_response(),
         Err(e) => match e {
-            _ => unexpected_error(e, "username change"),
+            DBoError::NoChange => (StatusCode::BAD_REQUEST).into_response(),
+            DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_) => unauthorized(&e),
+            DBoError::AccountUnconfirmed => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::MissingDocument(collection) => (
+                StatusCode::NOT_FOUND,
+                Json(MissingDocumentResponse::new(&collection)),
+            )
+                .into_response(),
+            DBoError::InvalidPlayerInfo(info) => {
+                (StatusCode::BAD_REQUEST, Json(info)).into_response()
+            }
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "avatar change"),
+        },
+    }
+}
+
+/// Handle a request for a URL to which the authenticated player may upload an avatar image.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `headers`: The request headers, containing the bearer access token
+/// - `body`: The request body, describing the image to upload
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with an `AvatarUploadResponse` body
+/// - Error
+///   - `400 BAD REQUEST` if the access token is missing
+///   - `400 BAD REQUEST` with a `PlayerInvalidFieldsResponse` body if the image is in an
+///     unsupported format, or is too large
+///   - `401 UNAUTHORIZED` if the access token is invalid or expired, or its player no longer exists
+///   - `403 FORBIDDEN` if the player's account is unconfirmed
+///   - `404 NOT FOUND` if the player is deleted while the request is being handled
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+///   - `501 NOT IMPLEMENTED` if no avatar storage is configured
+pub async fn handle_avatar_upload_url(
+    State(repos): State<Repositories>,
+    headers: HeaderMap,
+    Json(body): Json<AvatarUploadRequestBody>,
+) -> Response {
+    let storage = match AVATAR_STORAGE.as_ref() {
+        Some(s) => s,
+        None => return (StatusCode::NOT_IMPLEMENTED).into_response(),
+    };
+
+    let token = match extract_access_token(headers) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST).into_response(),
+    };
+
+    let outcome = PlayerService::avatar_upload_url(
+        repos.players(),
+        storage,
+        &token,
+        &body.content_type,
+        body.content_length,
+    )
+    .await;
+
+    match outcome {
+        Ok(upload) => (StatusCode::OK, Json(upload)).into_response(),
+        Err(e) => match e {
+            DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_) => unauthorized(&e),
+            DBoError::AccountUnconfirmed => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::MissingDocument(collection) => (
+                StatusCode::NOT_FOUND,
+                Json(MissingDocumentResponse::new(&collection)),
+            )
+                .into_response(),
+            DBoError::InvalidPlayerInfo(info) => {
+                (StatusCode::BAD_REQUEST, Json(info)).into_response()
+            }
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "avatar upload URL"),
         },
     }
 }
@@ -360,6 +975,7 @@ pub async fn handle_player_password_change(
 
     let outcome = PlayerService::change_password(
         repos.players(),
+        repos.refresh_tokens(),
         repos.undo_tokens(),
         &token,
         &body.old_password,
@@ -368,10 +984,19 @@ pub async fn handle_player_password_change(
     .await;
 
     match outcome {
-        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
-        Err(e) => match e {
-            _ => unexpected_error(e, "change password"),
-        },
+        Ok(receipt) => (StatusCode::OK, Json(receipt)).into_response(),
+        Err(DBoError::AccountUnconfirmed) => (StatusCode::FORBIDDEN).into_response(),
+        Err(DBoError::InvalidPlayerInfo(info)) => {
+            (StatusCode::BAD_REQUEST, Json(info)).into_response()
+        }
+        Err(DBoError::InternalConflict) => (StatusCode::CONFLICT).into_response(),
+        Err(
+            e @ (DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)),
+        ) => unauthorized(&e),
+        Err(e) => unexpected_error(e, "change password"),
     }
 }
 
@@ -396,10 +1021,23 @@ pub async fn handle_player_proposed_email_change(
     .await;
 
     match outcome {
-        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
-        Err(e) => match e {
-            _ => unexpected_error(e, "change proposed email"),
-        },
+        Ok(receipt) => (StatusCode::OK, Json(receipt)).into_response(),
+        Err(DBoError::UniquenessViolation(username, email)) => (
+            StatusCode::CONFLICT,
+            Json(PlayerUniquenessViolationResponse::new(username, email)),
+        )
+            .into_response(),
+        Err(DBoError::NoChange) => (StatusCode::BAD_REQUEST).into_response(),
+        Err(DBoError::InvalidPlayerInfo(info)) => {
+            (StatusCode::BAD_REQUEST, Json(info)).into_response()
+        }
+        Err(
+            e @ (DBoError::InvalidToken
+            | DBoError::TokenExpired
+            | DBoError::TokenPremature
+            | DBoError::AuthenticationFailure(_)),
+        ) => unauthorized(&e),
+        Err(e) => unexpected_error(e, "change proposed email"),
     }
 }
 
@@ -410,16 +1048,174 @@ pub async fn handle_player_proposed_email_confirmation(
     let outcome = PlayerService::confirm_proposed_email(
         repos.players(),
         repos.confirmation_tokens(),
+        repos.refresh_tokens(),
         repos.undo_tokens(),
         &player_id,
         &token_id,
     )
     .await;
 
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(DBoError::UniquenessViolation(username, email)) => (
+            StatusCode::CONFLICT,
+            Json(PlayerUniquenessViolationResponse::new(username, email)),
+        )
+            .into_response(),
+        Err(DBoError::TokenExpired) => (StatusCode::GONE).into_response(),
+        Err(e) => unexpected_error(e, "proposed email confirmation"),
+    }
+}
+
+/// Handle a request to be reminded of a forgotten username.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `body`: The HTTP request body
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT`, whether or not a confirmed account uses the email address
+pub async fn handle_username_recovery(
+    State(repos): State<Repositories>,
+    Json(body): Json<UsernameRecoveryRequestBody>,
+) -> Response {
+    PlayerService::recover_username(repos.players(), &body.email);
+
+    (StatusCode::NO_CONTENT).into_response()
+}
+
+/// Handle a request to reset a forgotten password.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `body`: The HTTP request body
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT`, whether or not a confirmed account uses the username or email address
+/// - Error
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_password_reset_request(
+    State(repos): State<Repositories>,
+    Json(body): Json<PasswordResetRequestBody>,
+) -> Response {
+    let outcome = PlayerService::request_password_reset(
+        repos.players(),
+        repos.undo_tokens(),
+        &body.username_or_email,
+    )
+    .await;
+
+    match outcome {
+        Ok(()) => (StatusCode::NO_CONTENT).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => unexpected_error(e, "password reset request"),
+    }
+}
+
+/// Handle a request to set a new password using a password reset link.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `player_id`: The player's unique identifier
+/// - `token_id`: The undo token's unique identifier
+/// - `body`: The HTTP request body, containing the new password
+///
+/// ### Returns
+/// - Success
+///   - `204 NO CONTENT` if the password was reset
+/// - Error
+///   - `400 BAD REQUEST` with a `PlayerInvalidFieldsResponse` body if the password is invalid
+///   - `403 FORBIDDEN` if the token does not match the player, or is not a password reset token
+///   - `404 NOT FOUND` with a `MissingDocumentResponse` body if the player or token is missing, or
+///     the token was already used
+///   - `409 CONFLICT` if the password matches the current password, or a recent one
+///   - `410 GONE` if the token has expired
+///   - `500 INTERNAL SERVER ERROR` if a database query failed
+pub async fn handle_password_reset(
+    State(repos): State<Repositories>,
+    Path((player_id, token_id)): Path<(String, String)>,
+    Json(body): Json<PasswordRequestBody>,
+) -> Response {
+    let outcome = PlayerService::complete_password_reset(
+        repos.players(),
+        repos.refresh_tokens(),
+        repos.undo_tokens(),
+        &player_id,
+        &token_id,
+        &body.password,
+    )
+    .await;
+
     match outcome {
         Ok(()) => (StatusCode::NO_CONTENT).into_response(),
         Err(e) => match e {
-            _ => unexpected_error(e, "proposed email confirmation"),
+            DBoError::InvalidPlayerInfo(info) => {
+                (StatusCode::BAD_REQUEST, Json(info)).into_response()
+            }
+            DBoError::MissingDocument(collection) => (
+                StatusCode::NOT_FOUND,
+                Json(MissingDocumentResponse::new(&collection)),
+            )
+                .into_response(),
+            DBoError::RelationalConflict => (StatusCode::FORBIDDEN).into_response(),
+            DBoError::InternalConflict => (StatusCode::CONFLICT).into_response(),
+            DBoError::TokenExpired => (StatusCode::GONE).into_response(),
+            DBoError::AdapterError => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+            _ => unexpected_error(e, "password reset"),
         },
     }
 }
+
+/// Handle a request for the public profiles of several players at once.
+///
+/// ### Arguments
+/// - `repos`: The Repositories stored in the axum router's state
+/// - `body`: The request body, containing the usernames to find
+///
+/// ### Returns
+/// - Success
+///   - `200 OK` with a `PublicPlayerListResponse` body, leaving out any username which does not
+///     belong to a confirmed player
+/// - Error
+///   - `400 BAD REQUEST` if more than 50 usernames were requested
+///   - `500 INTERNAL SERVER ERROR` if the database query failed
+pub async fn handle_public_player_batch(
+    State(repos): State<Repositories>,
+    Json(body): Json<PublicPlayerBatchRequestBody>,
+) -> Response {
+    if body.usernames.len() > PUBLIC_BATCH_LIMIT {
+        return (StatusCode::BAD_REQUEST).into_response();
+    }
+
+    match PlayerService::public_profiles(repos.players(), &body.usernames).await {
+        Ok(profiles) => (StatusCode::OK, Json(profiles)).into_response(),
+        Err(DBoError::AdapterError) => (StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+        Err(e) => unexpected_error(e, "public player batch"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_registration_closed_response() {
+        let english = registration_closed(&LanguagePreference::English);
+        assert_eq!(english.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            to_bytes(english.into_body(), usize::MAX).await.unwrap(),
+            r#"{"message":"Registration is temporarily closed. Please try again later."}"#
+        );
+
+        let spanish = registration_closed(&LanguagePreference::Spanish);
+        assert_eq!(spanish.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            to_bytes(spanish.into_body(), usize::MAX).await.unwrap(),
+            r#"{"message":"El registro está cerrado temporalmente. Por favor, inténtalo más tarde."}"#
+        );
+    }
+}
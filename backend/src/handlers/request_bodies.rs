@@ -1,7 +1,11 @@
 /// This module contains all the request bodies that are required in incoming HTTP requests.
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
-use crate::models::submodels::{Gender, LanguagePreference};
+use crate::models::{
+    DEFAULT_TIME_ZONE,
+    submodels::{AvatarRef, Gender, LanguagePreference},
+};
 
 /// The required request body for registering a new player account.
 #[derive(Deserialize)]
@@ -18,6 +22,10 @@ pub struct PlayerRegistrationRequestBody {
     preferred_language: LanguagePreference,
     /// The player's chosen pronouns
     pronoun: Option<Gender>,
+    /// The IANA name of the player's time zone, if the client provided one
+    time_zone: Option<String>,
+    /// The token provided by the CAPTCHA widget, required only when CAPTCHA verification is enabled
+    captcha_token: Option<String>,
 }
 
 impl PlayerRegistrationRequestBody {
@@ -44,6 +52,15 @@ impl PlayerRegistrationRequestBody {
     pub fn pronoun(&self) -> &Option<Gender> {
         &self.pronoun
     }
+
+    /// The player's time zone, or `DEFAULT_TIME_ZONE` if the client did not provide one.
+    pub fn time_zone(&self) -> &str {
+        self.time_zone.as_deref().unwrap_or(DEFAULT_TIME_ZONE)
+    }
+
+    pub fn captcha_token(&self) -> Option<&str> {
+        self.captcha_token.as_deref()
+    }
 }
 
 #[derive(Deserialize)]
@@ -52,6 +69,13 @@ pub struct PlayerLoginRequestBody {
     pub password: String,
 }
 
+/// The request body used by clients which cannot rely on cookies to refresh their tokens.
+#[derive(Deserialize)]
+pub struct RefreshTokenRequestBody {
+    /// The refresh token, like `"{id}:{secret}"`
+    pub refresh_token: String,
+}
+
 #[derive(Deserialize)]
 pub struct PasswordRequestBody {
     pub password: String,
@@ -74,3 +98,125 @@ pub struct ProposedEmailChangeRequestBody {
     pub new_email: String,
     pub password: String,
 }
+
+/// The request body used to change a player's avatar.
+#[derive(Deserialize)]
+pub struct AvatarChangeRequestBody {
+    /// The new avatar, or null to remove the player's avatar
+    pub avatar: Option<AvatarRef>,
+}
+
+/// The request body used to request a URL for uploading an avatar image.
+#[derive(Deserialize)]
+pub struct AvatarUploadRequestBody {
+    /// The MIME type of the image
+    pub content_type: String,
+    /// The size of the image, in bytes
+    pub content_length: u64,
+}
+
+/// The request body used to fetch the public profiles of several players at once.
+#[derive(Deserialize)]
+pub struct PublicPlayerBatchRequestBody {
+    /// The usernames of the players to find; at most 50 may be requested at once
+    pub usernames: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UsernameRecoveryRequestBody {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequestBody {
+    pub username_or_email: String,
+}
+
+/// The query parameters accepted while searching for player accounts.
+#[derive(Deserialize)]
+pub struct PlayerSearchQuery {
+    /// A case-insensitive prefix of the username or email address
+    pub query: Option<String>,
+    /// The required confirmation status
+    pub confirmed: Option<bool>,
+    /// The number of players per page (at most 100)
+    pub limit: Option<u32>,
+    /// The number of players to skip
+    pub skip: Option<u64>,
+}
+
+/// The query parameters accepted while reading the history of a counter.
+#[derive(Deserialize)]
+pub struct CounterHistoryQuery {
+    /// The unique identifier of the counter
+    pub counter: String,
+    /// If provided, only snapshots taken at or after this time are included
+    pub from: Option<DateTime<Utc>>,
+    /// If provided, only snapshots taken at or before this time are included
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl CounterHistoryQuery {
+    /// Determine whether the range of time is valid; that is, whether `from` is no later than `to`
+    /// when both are provided.
+    pub fn has_valid_range(&self) -> bool {
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) => from <= to,
+            _ => true,
+        }
+    }
+}
+
+/// The request body used to open or close the registration of new player accounts.
+#[derive(Deserialize)]
+pub struct RegistrationToggleRequestBody {
+    /// Whether new player accounts may be registered
+    pub open: bool,
+}
+
+/// The request body used to merge a duplicate player account into another.
+#[derive(Deserialize)]
+pub struct PlayerMergeRequestBody {
+    /// The unique identifier of the duplicate account, which will be deleted
+    pub source_id: String,
+    /// The unique identifier of the account to keep
+    pub target_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_time_zone_is_optional() {
+        let body: PlayerRegistrationRequestBody = serde_json::from_str(
+            r#"{
+                "username": "jaeger_dog",
+                "password": "Password1!",
+                "email": "jaeger.dog@bigdevdog.com",
+                "gender": "m",
+                "preferred_language": "en"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(body.time_zone(), DEFAULT_TIME_ZONE);
+    }
+
+    #[test]
+    fn test_counter_history_range() {
+        let query = |from: Option<&str>, to: Option<&str>| CounterHistoryQuery {
+            counter: String::from("logins"),
+            from: from.map(|f| f.parse().unwrap()),
+            to: to.map(|t| t.parse().unwrap()),
+        };
+        let earlier = Some("2025-01-01T00:00:00Z");
+        let later = Some("2025-02-01T00:00:00Z");
+
+        assert!(query(earlier, later).has_valid_range());
+        assert!(query(earlier, earlier).has_valid_range());
+        assert!(query(None, earlier).has_valid_range());
+        assert!(query(later, None).has_valid_range());
+        assert!(!query(later, earlier).has_valid_range());
+    }
+}
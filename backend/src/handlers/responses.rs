@@ -1,11 +1,18 @@
 //! This module defines all JSON response bodies that may be returned by the HTTP handler functions.
 
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
-use crate::models::{
-    Identifiable, Player,
-    submodels::{Gender, LanguagePreference, PlayerStats},
+use crate::{
+    adapters::{circuit_breaker::BreakerStatus, email::format_date_time},
+    models::{
+        CounterSnapshot, Identifiable, OutboxEmail, Player, SeasonArchive,
+        player_validation::ValidationProblem,
+        submodels::{AuthMethod, AvatarRef, Gender, LanguagePreference, OutboxStatus, PlayerStats},
+    },
+    services::types::SecurityNotice,
 };
 
 /// Returned when a player account cannot be created or modified, due to its fields violating a
@@ -45,17 +52,26 @@ impl PlayerUniquenessViolationResponse {
 /// **Note**: This struct is serializable as it will be returned in the HTTP response body when a
 /// user provides bad input. However, it will only include the fields which **failed validation**
 /// within that serialized version.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 pub struct PlayerInvalidFieldsResponse {
     /// A list of problems with the username.
     #[serde(skip_serializing_if = "core::option::Option::is_none")]
-    username_problems: Option<Vec<String>>,
+    username_problems: Option<Vec<ValidationProblem>>,
     /// A list of problems with the password.
     #[serde(skip_serializing_if = "core::option::Option::is_none")]
-    password_problems: Option<Vec<String>>,
+    password_problems: Option<Vec<ValidationProblem>>,
     /// A list of problems with the email.
     #[serde(skip_serializing_if = "core::option::Option::is_none")]
-    email_problems: Option<Vec<String>>,
+    email_problems: Option<Vec<ValidationProblem>>,
+    /// A list of problems with the time zone.
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    time_zone_problems: Option<Vec<ValidationProblem>>,
+    /// A list of problems with the pronoun.
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    pronoun_problems: Option<Vec<ValidationProblem>>,
+    /// A list of problems with the avatar.
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    avatar_problems: Option<Vec<ValidationProblem>>,
 }
 
 impl PlayerInvalidFieldsResponse {
@@ -65,15 +81,383 @@ impl PlayerInvalidFieldsResponse {
     /// - `username_problems`: A list of problems with the username
     /// - `password_problems`: A list of problems with the password
     /// - `email_problems`: A list of problems with the email
+    /// - `time_zone_problems`: A list of problems with the time zone
+    /// - `pronoun_problems`: A list of problems with the pronoun
+    /// - `avatar_problems`: A list of problems with the avatar
     pub fn new(
-        username_problems: Option<Vec<String>>,
-        password_problems: Option<Vec<String>>,
-        email_problems: Option<Vec<String>>,
+        username_problems: Option<Vec<ValidationProblem>>,
+        password_problems: Option<Vec<ValidationProblem>>,
+        email_problems: Option<Vec<ValidationProblem>>,
+        time_zone_problems: Option<Vec<ValidationProblem>>,
+        pronoun_problems: Option<Vec<ValidationProblem>>,
+        avatar_problems: Option<Vec<ValidationProblem>>,
     ) -> Self {
         Self {
             username_problems,
             password_problems,
             email_problems,
+            time_zone_problems,
+            pronoun_problems,
+            avatar_problems,
+        }
+    }
+
+    /// Replace the list of problems with the pronoun.
+    ///
+    /// ### Arguments
+    /// - `pronoun_problems`: A list of problems with the pronoun
+    pub fn set_pronoun_problems(&mut self, pronoun_problems: Option<Vec<ValidationProblem>>) {
+        self.pronoun_problems = pronoun_problems;
+    }
+
+    /// Returns true if no field has any problems.
+    pub fn is_empty(&self) -> bool {
+        self.username_problems.is_none()
+            && self.password_problems.is_none()
+            && self.email_problems.is_none()
+            && self.time_zone_problems.is_none()
+            && self.pronoun_problems.is_none()
+            && self.avatar_problems.is_none()
+    }
+}
+
+/// A report on whether a registration would succeed, returned without creating anything.
+#[derive(Serialize)]
+pub struct RegistrationValidationResponse {
+    /// Whether every field is valid and available
+    valid: bool,
+    /// The problems with each field, only including the fields which failed validation
+    problems: PlayerInvalidFieldsResponse,
+    /// Whether the username already belongs to another account
+    username_taken: bool,
+    /// Whether the email address already belongs to another account
+    email_taken: bool,
+    /// Whether the time zone is a recognized IANA time zone
+    time_zone_valid: bool,
+}
+
+impl RegistrationValidationResponse {
+    /// Construct a new RegistrationValidationResponse
+    ///
+    /// ### Arguments
+    /// - `problems`: The problems with each field
+    /// - `username_taken`: Whether the username already belongs to another account
+    /// - `email_taken`: Whether the email address already belongs to another account
+    /// - `time_zone_valid`: Whether the time zone is a recognized IANA time zone
+    pub fn new(
+        problems: PlayerInvalidFieldsResponse,
+        username_taken: bool,
+        email_taken: bool,
+        time_zone_valid: bool,
+    ) -> Self {
+        Self {
+            valid: problems.is_empty() && !username_taken && !email_taken && time_zone_valid,
+            problems,
+            username_taken,
+            email_taken,
+            time_zone_valid,
+        }
+    }
+}
+
+/// Contains a player's position on the leaderboard.
+#[derive(Serialize)]
+pub struct PlayerRankResponse {
+    /// The player's 1-based rank among all confirmed players
+    rank: u64,
+    /// The stats on which the rank is based
+    stats: PlayerStats,
+}
+
+impl PlayerRankResponse {
+    /// Construct a new PlayerRankResponse
+    ///
+    /// ### Arguments
+    /// - `rank`: The player's 1-based rank
+    /// - `stats`: The player's stats
+    pub fn new(rank: u64, stats: &PlayerStats) -> Self {
+        Self {
+            rank,
+            stats: stats.clone(),
+        }
+    }
+}
+
+/// Describes the service to clients requesting the root path, such as uptime monitors.
+#[derive(Serialize)]
+pub struct RootResponse {
+    /// The name of the service
+    service: &'static str,
+    /// Always "ok"; the service is up if it can respond at all
+    status: &'static str,
+    /// The path at which the API documentation is served
+    docs: &'static str,
+}
+
+impl RootResponse {
+    /// Construct a new RootResponse
+    ///
+    /// ### Arguments
+    /// - `docs`: The path at which the API documentation is served
+    pub fn new(docs: &'static str) -> Self {
+        Self {
+            service: env!("CARGO_PKG_NAME"),
+            status: "ok",
+            docs,
+        }
+    }
+}
+
+/// Describes the health of the service and its dependencies, for load balancers and uptime
+/// monitors.
+#[derive(Serialize)]
+pub struct HealthResponse {
+    /// "ok" while the database is reachable; otherwise "degraded"
+    status: &'static str,
+    /// The state of the circuit breaker guarding the database
+    database: BreakerStatus,
+    /// While the breaker is open, the number of seconds until the database is tried again
+    retry_after_secs: Option<u64>,
+}
+
+impl HealthResponse {
+    /// Construct a new HealthResponse
+    ///
+    /// ### Arguments
+    /// - `database`: The state of the circuit breaker guarding the database
+    /// - `retry_after`: While the breaker is open, how long until the database is tried again
+    pub fn new(database: BreakerStatus, retry_after: Option<Duration>) -> Self {
+        Self {
+            status: match database {
+                BreakerStatus::Closed => "ok",
+                BreakerStatus::Open | BreakerStatus::HalfOpen => "degraded",
+            },
+            database,
+            retry_after_secs: retry_after.map(|d| d.as_secs_f64().ceil() as u64),
+        }
+    }
+}
+
+/// Contains the entries of the registration allow-list. While both lists are empty, registration is
+/// open to everyone.
+#[derive(Serialize)]
+pub struct AllowlistResponse {
+    /// The entries configured by the environment, which cannot be changed at runtime
+    configured: Vec<String>,
+    /// The entries added by admins at runtime
+    added: Vec<String>,
+}
+
+impl AllowlistResponse {
+    /// Construct a new AllowlistResponse
+    ///
+    /// ### Arguments
+    /// - `configured`: The entries configured by the environment
+    /// - `added`: The entries added by admins at runtime
+    pub fn new(configured: &[String], added: Vec<String>) -> Self {
+        Self {
+            configured: configured.to_vec(),
+            added,
+        }
+    }
+}
+
+/// Describes an email which has failed to send at least once, for admins to inspect.
+#[derive(Serialize)]
+pub struct StuckEmailResponse {
+    /// The email's unique identifier within the outbox
+    outbox_id: String,
+    /// The addresses the email is sent to
+    to: Vec<String>,
+    /// The subject of the email
+    subject: String,
+    /// Whether the email will be attempted again (`pending`), or has given up (`failed`)
+    status: OutboxStatus,
+    /// The number of times the email has failed to send
+    attempts: u32,
+    /// The time at which the email was queued, in UTC time, converted to RFC 3339
+    created: String,
+    /// The earliest time at which the email may be attempted again, in UTC time, converted to RFC
+    /// 3339
+    next_attempt_at: String,
+    /// A description of the most recent failure
+    last_error: Option<String>,
+}
+
+impl From<&OutboxEmail> for StuckEmailResponse {
+    fn from(email: &OutboxEmail) -> Self {
+        Self {
+            outbox_id: String::from(email.id()),
+            to: email.to().to_vec(),
+            subject: String::from(email.subject()),
+            status: email.status().clone(),
+            attempts: email.attempts(),
+            created: email.created().to_chrono().to_rfc3339(),
+            next_attempt_at: email.next_attempt_at().to_chrono().to_rfc3339(),
+            last_error: email.last_error().clone(),
+        }
+    }
+}
+
+/// Contains the emails which have failed to send at least once.
+#[derive(Serialize)]
+pub struct StuckEmailListResponse {
+    /// The stuck emails, from the oldest
+    emails: Vec<StuckEmailResponse>,
+}
+
+impl StuckEmailListResponse {
+    /// Construct a new StuckEmailListResponse
+    ///
+    /// ### Arguments
+    /// - `emails`: The stuck emails, from the oldest
+    pub fn new(emails: &[OutboxEmail]) -> Self {
+        Self {
+            emails: emails.iter().map(StuckEmailResponse::from).collect(),
+        }
+    }
+}
+
+/// A single point in the history of a counter.
+#[derive(Serialize)]
+pub struct CounterSnapshotResponse {
+    /// The count of the counter when the snapshot was taken
+    count: u64,
+    /// The time at which the snapshot was taken, in UTC time, converted to RFC 3339
+    taken: String,
+}
+
+impl From<&CounterSnapshot> for CounterSnapshotResponse {
+    fn from(snapshot: &CounterSnapshot) -> Self {
+        Self {
+            count: snapshot.count(),
+            taken: snapshot.taken().to_chrono().to_rfc3339(),
+        }
+    }
+}
+
+/// Contains the recorded history of a single counter.
+#[derive(Serialize)]
+pub struct CounterHistoryResponse {
+    /// The unique identifier of the counter
+    counter: String,
+    /// The snapshots of the counter, from the oldest
+    snapshots: Vec<CounterSnapshotResponse>,
+}
+
+impl CounterHistoryResponse {
+    /// Construct a new CounterHistoryResponse
+    ///
+    /// ### Arguments
+    /// - `counter`: The unique identifier of the counter
+    /// - `snapshots`: The snapshots of the counter, from the oldest
+    pub fn new(counter: &str, snapshots: &[CounterSnapshot]) -> Self {
+        Self {
+            counter: String::from(counter),
+            snapshots: snapshots
+                .iter()
+                .map(CounterSnapshotResponse::from)
+                .collect(),
+        }
+    }
+}
+
+/// Describes whether new player accounts may currently be registered.
+#[derive(Serialize)]
+pub struct RegistrationStatusResponse {
+    /// Whether registration is open
+    open: bool,
+}
+
+impl RegistrationStatusResponse {
+    /// Construct a new RegistrationStatusResponse
+    ///
+    /// ### Arguments
+    /// - `open`: Whether registration is open
+    pub fn new(open: bool) -> Self {
+        Self { open }
+    }
+}
+
+/// Describes a competitive season which has just been ended by an admin.
+#[derive(Serialize)]
+pub struct SeasonEndResponse {
+    /// The number of the season which ended
+    season: u64,
+    /// The number of players whose stats were archived
+    archived_players: u64,
+}
+
+impl SeasonEndResponse {
+    /// Construct a new SeasonEndResponse
+    ///
+    /// ### Arguments
+    /// - `season`: The number of the season which ended
+    /// - `archived_players`: The number of players whose stats were archived
+    pub fn new(season: u64, archived_players: u64) -> Self {
+        Self {
+            season,
+            archived_players,
+        }
+    }
+}
+
+/// Reports the outcome of repairing the players' stored time zones.
+#[derive(Serialize)]
+pub struct TimeZoneRepairResponse {
+    /// The time zone which replaced the invalid ones
+    fallback_time_zone: String,
+    /// The number of players whose time zone was repaired
+    repaired_players: u64,
+}
+
+impl TimeZoneRepairResponse {
+    /// Construct a new TimeZoneRepairResponse
+    ///
+    /// ### Arguments
+    /// - `fallback_time_zone`: The time zone which replaced the invalid ones
+    /// - `repaired_players`: The number of players whose time zone was repaired
+    pub fn new(fallback_time_zone: &str, repaired_players: u64) -> Self {
+        Self {
+            fallback_time_zone: String::from(fallback_time_zone),
+            repaired_players,
+        }
+    }
+}
+
+/// Contains a player's stats from a single past season.
+#[derive(Serialize)]
+pub struct SeasonStatsResponse {
+    /// The number of the season
+    season: u64,
+    /// The time at which the season ended, in UTC time, converted to RFC 3339
+    ended: String,
+    /// The player's stats at the end of the season
+    stats: PlayerStats,
+}
+
+/// Contains a player's stats from every past season in which they played.
+#[derive(Serialize)]
+pub struct SeasonHistoryResponse {
+    /// The player's archived seasons, from the oldest to the newest
+    seasons: Vec<SeasonStatsResponse>,
+}
+
+impl SeasonHistoryResponse {
+    /// Construct a new SeasonHistoryResponse
+    ///
+    /// ### Arguments
+    /// - `archives`: The player's archived seasons, from the oldest to the newest
+    pub fn new(archives: &[SeasonArchive]) -> Self {
+        Self {
+            seasons: archives
+                .iter()
+                .map(|a| SeasonStatsResponse {
+                    season: a.season(),
+                    ended: a.archived().to_chrono().to_rfc3339(),
+                    stats: a.stats().clone(),
+                })
+                .collect(),
         }
     }
 }
@@ -90,12 +474,18 @@ pub struct SafePlayerResponse {
     email: String,
     /// The time at which the player account was created, in UTC time, converted to RFC 3339
     created: String,
+    /// Whether the player has confirmed their account
+    confirmed: bool,
     /// The player's gender
     gender: Gender,
     /// The player's preferred language
     preferred_language: LanguagePreference,
     /// The player's preferred pronouns
     pronoun: Gender,
+    /// The IANA name of the player's time zone
+    time_zone: String,
+    /// The player's chosen avatar, if any
+    avatar: Option<AvatarRef>,
     /// A tracker of the player's wins, losses, and dropouts
     stats: PlayerStats,
 }
@@ -111,19 +501,265 @@ impl SafePlayerResponse {
             username: String::from(player.username()),
             email: String::from(player.email()),
             created: player.created().to_chrono().to_rfc3339(),
+            confirmed: player.confirmed(),
             gender: player.gender().clone(),
             preferred_language: player.preferred_language().clone(),
             pronoun: player.pronoun().clone(),
+            time_zone: String::from(player.time_zone()),
+            avatar: player.avatar().cloned(),
+            stats: player.stats().clone(),
+        }
+    }
+}
+
+/// Contains the information about a player account that anyone may see, such as the name and
+/// record shown beside them on a leaderboard.
+#[derive(Serialize)]
+pub struct PublicPlayerResponse {
+    /// The player's username
+    username: String,
+    /// The player's preferred pronouns
+    pronoun: Gender,
+    /// The player's chosen avatar, if any
+    avatar: Option<AvatarRef>,
+    /// The time at which the player account was created, in UTC time, converted to RFC 3339
+    created: String,
+    /// A tracker of the player's wins, losses, and dropouts
+    stats: PlayerStats,
+}
+
+impl PublicPlayerResponse {
+    /// Construct a new PublicPlayerResponse from a complete Player
+    ///
+    /// ### Arguments
+    /// - `player`: The complete player account
+    pub fn from(player: &Player) -> Self {
+        Self {
+            username: String::from(player.username()),
+            pronoun: player.pronoun().clone(),
+            avatar: player.avatar().cloned(),
+            created: player.created().to_chrono().to_rfc3339(),
             stats: player.stats().clone(),
         }
     }
 }
 
+/// Contains a presigned URL to which a player may upload their avatar image.
+#[derive(Serialize)]
+pub struct AvatarUploadResponse {
+    /// The URL to which the image should be uploaded, with a PUT request
+    upload_url: String,
+    /// The key at which the image will be stored, which should then be set as the player's avatar
+    key: String,
+    /// The time at which the upload URL expires, in UTC time, converted to RFC 3339
+    expires_at: String,
+}
+
+impl AvatarUploadResponse {
+    /// Construct a new AvatarUploadResponse
+    ///
+    /// ### Arguments
+    /// - `upload_url`: The presigned upload URL
+    /// - `key`: The key at which the image will be stored
+    /// - `expires_at`: The time at which the upload URL expires
+    pub fn new(upload_url: String, key: String, expires_at: &DateTime<Utc>) -> Self {
+        Self {
+            upload_url,
+            key,
+            expires_at: expires_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Contains the public profiles of several players.
+#[derive(Serialize)]
+pub struct PublicPlayerListResponse {
+    /// The players which were found, sorted by username
+    players: Vec<PublicPlayerResponse>,
+}
+
+impl PublicPlayerListResponse {
+    /// Construct a new PublicPlayerListResponse
+    ///
+    /// ### Arguments
+    /// - `players`: The players which were found
+    pub fn new(players: &[Player]) -> Self {
+        Self {
+            players: players.iter().map(PublicPlayerResponse::from).collect(),
+        }
+    }
+}
+
+/// Describes a single time zone, as it should be presented to a player choosing one.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TimeZoneInfo {
+    /// The IANA name of the time zone
+    id: &'static str,
+    /// The current offset from UTC, formatted like `-08:00`
+    offset: String,
+    /// A human-friendly name for the time zone, like `Los Angeles (PST)`
+    label: String,
+}
+
+impl TimeZoneInfo {
+    /// Construct a new TimeZoneInfo
+    ///
+    /// ### Arguments
+    /// - `id`: The IANA name of the time zone
+    /// - `offset`: The current offset from UTC
+    /// - `label`: A human-friendly name for the time zone
+    pub fn new(id: &'static str, offset: String, label: String) -> Self {
+        Self { id, offset, label }
+    }
+}
+
+/// Contains every time zone which a player may choose when registering.
+#[derive(Serialize)]
+pub struct TimeZoneListResponse {
+    /// The available time zones, sorted alphabetically by IANA name
+    time_zones: Vec<TimeZoneInfo>,
+}
+
+impl TimeZoneListResponse {
+    /// Construct a new TimeZoneListResponse
+    ///
+    /// ### Arguments
+    /// - `time_zones`: The available time zones
+    pub fn new(time_zones: Vec<TimeZoneInfo>) -> Self {
+        Self { time_zones }
+    }
+}
+
+/// Contains the information about a player account that support staff need to identify it within
+/// a list of search results.
+#[derive(Serialize)]
+pub struct PlayerSummaryResponse {
+    /// The player's unique identifier
+    player_id: String,
+    /// The player's username
+    username: String,
+    /// The player's email address
+    email: String,
+    /// Whether the player's email address has ever been confirmed
+    confirmed: bool,
+    /// The time at which the player account was created, in UTC time, converted to RFC 3339
+    created: String,
+}
+
+impl PlayerSummaryResponse {
+    /// Construct a new PlayerSummaryResponse from a complete Player
+    ///
+    /// ### Arguments
+    /// - `player`: The complete player account
+    pub fn from(player: &Player) -> Self {
+        Self {
+            player_id: String::from(player.id()),
+            username: String::from(player.username()),
+            email: String::from(player.email()),
+            confirmed: player.confirmed(),
+            created: player.created().to_chrono().to_rfc3339(),
+        }
+    }
+}
+
+/// Contains the information about a player account that support staff need to diagnose problems
+/// with it, such as a player being unable to log in. Password hashes and token secrets are never
+/// included.
+#[derive(Serialize)]
+pub struct AdminPlayerResponse {
+    /// The player's unique identifier
+    player_id: String,
+    /// The player's username
+    username: String,
+    /// The player's email address
+    email: String,
+    /// The email address the player has requested to change to, but not yet verified
+    proposed_email: Option<String>,
+    /// Whether the player's email address has ever been confirmed
+    confirmed: bool,
+    /// How the player proves their identity
+    auth_method: AuthMethod,
+    /// Whether the player is an admin
+    admin: bool,
+    /// The time at which the player account was created, in UTC time, converted to RFC 3339
+    created: String,
+    /// The time of the player's last successful login, in UTC time, converted to RFC 3339
+    last_login: String,
+    /// The number of consecutive failed logins
+    failed_logins: u8,
+    /// The time until which the player cannot log in, in UTC time, converted to RFC 3339
+    locked_until: Option<String>,
+    /// Sessions created before this time are invalid, in UTC time, converted to RFC 3339
+    session_valid_after: String,
+    /// The time at which the account will be deleted, if its deletion was requested, in UTC time,
+    /// converted to RFC 3339
+    deleted_at: Option<String>,
+    /// The player's preferred language
+    preferred_language: LanguagePreference,
+    /// The IANA name of the player's time zone
+    time_zone: String,
+    /// A tracker of the player's wins, losses, and dropouts
+    stats: PlayerStats,
+}
+
+impl AdminPlayerResponse {
+    /// Construct a new AdminPlayerResponse from a complete Player
+    ///
+    /// ### Arguments
+    /// - `player`: The complete player account
+    pub fn from(player: &Player) -> Self {
+        Self {
+            player_id: String::from(player.id()),
+            username: String::from(player.username()),
+            email: String::from(player.email()),
+            proposed_email: player.proposed_email().clone(),
+            confirmed: player.confirmed(),
+            auth_method: player.auth_method().clone(),
+            admin: player.admin(),
+            created: player.created().to_chrono().to_rfc3339(),
+            last_login: player.last_login().to_chrono().to_rfc3339(),
+            failed_logins: player.failed_logins(),
+            locked_until: player.locked_until().map(|t| t.to_chrono().to_rfc3339()),
+            session_valid_after: player.valid_after().to_chrono().to_rfc3339(),
+            deleted_at: player.deleted_at().map(|t| t.to_chrono().to_rfc3339()),
+            preferred_language: player.preferred_language().clone(),
+            time_zone: String::from(player.time_zone()),
+            stats: player.stats().clone(),
+        }
+    }
+}
+
+/// A single page of player search results.
+#[derive(Serialize)]
+pub struct PlayerSearchResponse {
+    /// The players on this page
+    players: Vec<PlayerSummaryResponse>,
+    /// The total number of players matching the search, across all pages
+    total: u64,
+}
+
+impl PlayerSearchResponse {
+    /// Construct a new PlayerSearchResponse
+    ///
+    /// ### Arguments
+    /// - `players`: The players on this page
+    /// - `total`: The total number of players matching the search
+    pub fn new(players: &[Player], total: u64) -> Self {
+        Self {
+            players: players.iter().map(PlayerSummaryResponse::from).collect(),
+            total,
+        }
+    }
+}
+
 /// Return an Access Token to the player - a JWT that can be used to authenticate them for 15
 /// minutes.
 #[derive(Serialize)]
 pub struct AccessTokenResponse {
     access_token: String,
+    /// A notice about suspicious activity on the account since the player last logged in
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    security_notice: Option<SecurityNoticeResponse>,
 }
 
 impl AccessTokenResponse {
@@ -131,13 +767,150 @@ impl AccessTokenResponse {
     ///
     /// ### Arguments
     /// - `access_token`: The access JWT
-    pub fn new(access_token: &str) -> Self {
+    /// - `security_notice`: A notice about suspicious activity on the account, if any
+    pub fn new(access_token: &str, security_notice: Option<&SecurityNotice>) -> Self {
+        Self {
+            access_token: String::from(access_token),
+            security_notice: security_notice.map(SecurityNoticeResponse::new),
+        }
+    }
+}
+
+/// Return both authentication tokens to a client which cannot rely on cookies, such as a mobile
+/// app. The client is responsible for storing the refresh token securely.
+#[derive(Serialize)]
+pub struct TokenPairResponse {
+    access_token: String,
+    /// The new refresh token, like `"{id}:{secret}"`
+    refresh_token: String,
+    /// A notice about suspicious activity on the account since the player last logged in
+    #[serde(skip_serializing_if = "core::option::Option::is_none")]
+    security_notice: Option<SecurityNoticeResponse>,
+}
+
+impl TokenPairResponse {
+    /// Create a new TokenPairResponse struct
+    ///
+    /// ### Arguments
+    /// - `access_token`: The access JWT
+    /// - `refresh_token_id`: The new refresh token's unique identifier
+    /// - `refresh_token_secret`: The new refresh token's secret
+    /// - `security_notice`: A notice about suspicious activity on the account, if any
+    pub fn new(
+        access_token: &str,
+        refresh_token_id: &str,
+        refresh_token_secret: &str,
+        security_notice: Option<&SecurityNotice>,
+    ) -> Self {
         Self {
             access_token: String::from(access_token),
+            refresh_token: format!("{}:{}", refresh_token_id, refresh_token_secret),
+            security_notice: security_notice.map(SecurityNoticeResponse::new),
         }
     }
 }
 
+/// Describes what a successful change to a player's login credentials did, so that the client can
+/// tell the player (for example, that they have been signed out of their other devices).
+#[derive(Serialize)]
+pub struct ChangeReceiptResponse {
+    /// The names of the fields which were changed
+    changed: Vec<String>,
+    /// Whether all of the player's sessions were invalidated, signing them out everywhere
+    sessions_invalidated: bool,
+    /// Whether an email was sent to notify the player of the change
+    email_notification_sent: bool,
+}
+
+impl ChangeReceiptResponse {
+    /// Construct a new ChangeReceiptResponse
+    ///
+    /// ### Arguments
+    /// - `changed`: The names of the fields which were changed
+    /// - `sessions_invalidated`: Whether all of the player's sessions were invalidated
+    /// - `email_notification_sent`: Whether an email was sent to notify the player of the change
+    pub fn new(
+        changed: &[&str],
+        sessions_invalidated: bool,
+        email_notification_sent: bool,
+    ) -> Self {
+        Self {
+            changed: changed.iter().map(|field| String::from(*field)).collect(),
+            sessions_invalidated,
+            email_notification_sent,
+        }
+    }
+}
+
+/// Tells a player who has just logged in that their account was recently locked, so that they can
+/// notice if someone else has been trying to guess their password.
+#[derive(Clone, Serialize)]
+pub struct SecurityNoticeResponse {
+    /// The UTC DateTime at which the lockout ended, in RFC 3339
+    lockout_cleared_at: String,
+    /// The number of failed logins since the player last logged in successfully
+    failed_logins: u8,
+}
+
+impl SecurityNoticeResponse {
+    /// Create a new SecurityNoticeResponse
+    ///
+    /// ### Arguments
+    /// - `notice`: The security notice produced by the login
+    pub fn new(notice: &SecurityNotice) -> Self {
+        Self {
+            lockout_cleared_at: notice.lockout_cleared_at.to_rfc3339(),
+            failed_logins: notice.failed_logins,
+        }
+    }
+}
+
+/// An error response explaining why a request could not be authenticated. This is only sent when
+/// `ENV.expose_authn_reasons` is enabled in development.
+#[derive(Serialize)]
+pub struct AuthnFailureResponse {
+    /// A coarse reason code, like `bad_credentials` or `expired_token`
+    reason: &'static str,
+}
+
+impl AuthnFailureResponse {
+    /// Create a new AuthnFailureResponse
+    ///
+    /// ### Arguments
+    /// - `reason`: The coarse reason code
+    pub fn new(reason: &'static str) -> Self {
+        Self { reason }
+    }
+}
+
+/// An error response indicating that new player accounts cannot currently be registered, because
+/// an admin has temporarily closed registration.
+#[derive(Serialize)]
+pub struct RegistrationClosedResponse {
+    /// A message explaining that registration is closed in the requested language, for clients
+    /// which display server messages directly
+    message: &'static str,
+}
+
+impl RegistrationClosedResponse {
+    /// Create a new RegistrationClosedResponse
+    ///
+    /// ### Arguments
+    /// - `language`: The language requested during registration
+    pub fn new(language: &LanguagePreference) -> Self {
+        let message = match language {
+            LanguagePreference::English => {
+                "Registration is temporarily closed. Please try again later."
+            }
+            LanguagePreference::Spanish => {
+                "El registro está cerrado temporalmente. Por favor, inténtalo más tarde."
+            }
+        };
+
+        Self { message }
+    }
+}
+
 /// An error response indicating that a document could not be found.
 #[derive(Serialize)]
 pub struct MissingDocumentResponse {
@@ -159,10 +932,13 @@ impl MissingDocumentResponse {
 
 /// An error response indicating that the account is locked - the player cannot log into their
 /// account until the time provided.
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct AccountLockedResponse {
     /// The UTC DateTime indicating when the account will become unlocked again, in RFC 3339
     locked_until: String,
+    /// A message explaining the lockout in the player's preferred language and local time, for
+    /// clients which display server messages directly
+    message: String,
 }
 
 impl AccountLockedResponse {
@@ -170,9 +946,69 @@ impl AccountLockedResponse {
     ///
     /// ### Arguments
     /// - `date`: The time at which the account will become unlocked again.
-    pub fn new(date: DateTime<Utc>) -> Self {
+    /// - `language`: The player's preferred language
+    /// - `time_zone`: The IANA name of the player's time zone
+    pub fn new(date: DateTime<Utc>, language: &LanguagePreference, time_zone: &str) -> Self {
+        let until = format_date_time(&date, language, time_zone);
+        let message = match language {
+            LanguagePreference::English => format!("Your account is locked until {}.", until),
+            LanguagePreference::Spanish => format!("Tu cuenta está bloqueada hasta el {}.", until),
+        };
+
         Self {
             locked_until: date.to_rfc3339(),
+            message,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_account_locked_message() {
+        let until = Utc.with_ymd_and_hms(2025, 1, 5, 13, 4, 5).unwrap();
+
+        let english = AccountLockedResponse::new(until, &LanguagePreference::English, "UTC");
+        assert_eq!(english.locked_until, until.to_rfc3339());
+        assert_eq!(
+            english.message,
+            "Your account is locked until Sunday, 01/05/2025 at 01:04:05 pm."
+        );
+
+        let spanish =
+            AccountLockedResponse::new(until, &LanguagePreference::Spanish, "America/Mexico_City");
+        assert_eq!(spanish.locked_until, until.to_rfc3339());
+        assert_eq!(
+            spanish.message,
+            "Tu cuenta está bloqueada hasta el Domingo, 05/01/2025 a las 07:04:05."
+        );
+    }
+
+    #[test]
+    fn test_registration_validation_validity() {
+        let valid = RegistrationValidationResponse::new(
+            PlayerInvalidFieldsResponse::default(),
+            false,
+            false,
+            true,
+        );
+        assert!(valid.valid);
+
+        let taken = RegistrationValidationResponse::new(
+            PlayerInvalidFieldsResponse::default(),
+            false,
+            true,
+            true,
+        );
+        assert!(!taken.valid);
+
+        let mut problems = PlayerInvalidFieldsResponse::default();
+        problems.set_pronoun_problems(Some(vec![]));
+        let invalid = RegistrationValidationResponse::new(problems, false, false, true);
+        assert!(!invalid.valid);
+    }
+}
@@ -13,9 +13,11 @@ mod adapters;
 mod config;
 mod errors;
 mod handlers;
+mod maintenance;
 mod models;
 mod router;
 mod services;
+mod state;
 
 use std::net::SocketAddr;
 
@@ -23,18 +25,25 @@ use once_cell::sync::Lazy;
 use tokio::net::TcpListener;
 
 use crate::{
-    adapters::repositories::{Repositories, counter_id::CounterId},
+    adapters::{
+        email::spawn_email_worker,
+        metrics::{METRICS, spawn_metrics_upkeep},
+        repositories::{Repositories, counter_id::CounterId},
+    },
     config::{assets::ASSETS, environment::ENV},
+    maintenance::spawn_maintenance,
     router::router,
+    state::AppState,
 };
 
-/// Initialize lazy variables, create Repositories struct to be used as a state by the axum router,
-/// ping the database to ensure a stable connection, and create the axum router to listen for
-/// requests on port 60600.
+/// Initialize lazy variables, create Repositories struct to be used within the axum router's state,
+/// ping the database to ensure a stable connection, start the background maintenance tasks and
+/// email worker, and create the axum router to listen for requests on port 60600.
 #[tokio::main]
 async fn main() {
     Lazy::force(&ENV);
     Lazy::force(&ASSETS);
+    Lazy::force(&METRICS);
 
     let repositories = Repositories::new().await;
 
@@ -44,12 +53,21 @@ async fn main() {
         .await
         .expect("Failed to ping the MongoDB database.");
 
-    let app = router().with_state(repositories);
+    spawn_maintenance(repositories.clone());
+    spawn_email_worker(repositories.email_outbox().clone());
+    spawn_metrics_upkeep();
+
+    let app = router().with_state(AppState::new(repositories, ENV.registration_open));
 
     let address = SocketAddr::from(([0, 0, 0, 0], 60600));
     let listener = TcpListener::bind(address).await.unwrap();
 
-    println!("Listening on {}", address.to_string());
+    println!("Listening on {}", address);
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,69 @@
+//! This module runs the maintenance tasks of the application periodically in the background, for as
+//! long as the server is running.
+
+use std::time::Duration;
+
+use bson::DateTime;
+use tokio::time::interval;
+
+use crate::{
+    adapters::repositories::Repositories, config::environment::ENV,
+    services::maintenance_service::MaintenanceService,
+};
+
+/// Spawn a background task which runs all maintenance tasks once immediately, and then again after
+/// every `ENV.maintenance_interval_secs` seconds.
+///
+/// ### Arguments
+/// - `repos`: The repositories that the maintenance tasks operate on
+pub fn spawn_maintenance(repos: Repositories) {
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(ENV.maintenance_interval_secs));
+
+        loop {
+            ticker.tick().await;
+            run_maintenance(&repos).await;
+        }
+    });
+}
+
+/// Run each maintenance task once. A failing task is logged, and will be attempted again on the
+/// next run.
+///
+/// ### Arguments
+/// - `repos`: The repositories that the maintenance tasks operate on
+async fn run_maintenance(repos: &Repositories) {
+    let purge = MaintenanceService::purge_deleted_accounts(
+        repos.players(),
+        repos.refresh_tokens(),
+        repos.undo_tokens(),
+        repos.season_archives(),
+        repos.counters(),
+    )
+    .await;
+
+    match purge {
+        Ok(0) => {}
+        Ok(count) => println!("Purged {} player accounts after their grace period.", count),
+        Err(e) => {
+            eprintln!("Failed to purge player accounts after their grace period!");
+            eprintln!("{:?}", e);
+        }
+    }
+
+    let snapshot = MaintenanceService::snapshot_counters(
+        repos.counters(),
+        repos.counter_snapshots(),
+        &DateTime::now(),
+    )
+    .await;
+
+    match snapshot {
+        Ok(0) => {}
+        Ok(count) => println!("Recorded snapshots of {} counters.", count),
+        Err(e) => {
+            eprintln!("Failed to record snapshots of the counters!");
+            eprintln!("{:?}", e);
+        }
+    }
+}
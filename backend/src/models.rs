@@ -5,23 +5,33 @@
 //! the database; they are basically just shapes. Actual interaction with the database is handled by
 //! the repository layer.
 
+pub mod clock;
 pub mod player_validation;
 pub mod submodels;
 
 use std::{array, time::Duration as StdDuration};
 
 use bson::{DateTime, doc};
-use chrono::{Duration as ChronoDuration, Utc};
+use chrono::Duration as ChronoDuration;
+use futures::StreamExt;
 use mongodb::{Collection, IndexModel, options::IndexOptions};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    adapters::{hashing::hash_secret, mongo::case_insensitive_collation},
+    adapters::{
+        hashing::hash_secret,
+        mongo::{case_insensitive_collation, is_missing_namespace_or_index},
+    },
+    config::environment::ENV,
     errors::DBoResult,
     models::{
-        player_validation::validate_all,
-        submodels::{Gender, LanguagePreference, PlayerStats, UndoTokenType},
+        clock::{Clock, RealClock},
+        player_validation::{resolve_time_zone, validate_all},
+        submodels::{
+            AuthMethod, AvatarRef, Gender, LanguagePreference, OutboxStatus, PlayerStats,
+            UndoTokenType,
+        },
     },
 };
 
@@ -47,6 +57,29 @@ pub trait Indexed: Send + Sync + Sized {
     async fn index(collection: &Collection<Self>);
 }
 
+/// A trait for all Models which are only valid for a limited time after their creation.
+pub trait Expirable {
+    /// Return the time at which the model was created.
+    fn created(&self) -> &DateTime;
+
+    /// Return how long the model remains valid after its creation.
+    fn lifetime(&self) -> ChronoDuration;
+
+    /// Determine whether the model has outlived its lifetime. A model created in the future is
+    /// always considered expired.
+    fn expired(&self) -> bool {
+        self.expired_at(&RealClock)
+    }
+
+    /// Determine whether the model has outlived its lifetime, according to the provided clock.
+    ///
+    /// ### Arguments
+    /// - `clock`: The source of the current time
+    fn expired_at(&self, clock: &impl Clock) -> bool {
+        token_age(self.created(), &clock.now()).is_none_or(|age| age > self.lifetime())
+    }
+}
+
 /// A composite trait that is required for any database model. Any struct implementing these traits
 /// will automatically receive the trait `Model`.
 pub trait Model:
@@ -59,6 +92,82 @@ impl<T> Model for T where
 {
 }
 
+// //////////////// //
+// HELPER FUNCTIONS //
+// //////////////// //
+
+/// Return how much time has passed since a token was created.
+///
+/// A `created` timestamp in the future can only be the result of clock skew or bad data, so the
+/// token cannot be trusted. In that case, a warning is logged, and the token should be treated as
+/// expired.
+///
+/// ### Arguments
+/// - `created`: The time at which the token was created
+/// - `now`: The current time
+///
+/// ### Returns
+/// The age of the token, or None if it was created in the future
+fn token_age(created: &DateTime, now: &DateTime) -> Option<ChronoDuration> {
+    let age = now.to_chrono() - created.to_chrono();
+
+    if age < ChronoDuration::zero() {
+        eprintln!(
+            "A token claims to have been created in the future ({})!",
+            created
+        );
+        eprintln!("This likely indicates clock skew or bad data; treating it as expired.");
+        None
+    } else {
+        Some(age)
+    }
+}
+
+/// Determine whether a lockout email should be sent, or whether one was already sent during the
+/// current lockout window. A window begins with the player's last successful login, and lasts for
+/// as long as the lockout keeps escalating. Without this, an attacker repeatedly guessing a
+/// password would flood the player's inbox with a new email every time the lockout escalates.
+///
+/// ### Arguments
+/// - `last_sent`: The time at which the last lockout email was sent, if ever
+/// - `window_start`: The time at which the current lockout window began
+fn lockout_email_due(last_sent: Option<&DateTime>, window_start: &DateTime) -> bool {
+    match last_sent {
+        Some(time) => time < window_start,
+        None => true,
+    }
+}
+
+/// Find when a lockout ended, if it ended no longer than `window` before `now`. A lockout which
+/// has not yet ended is not reported, since the player cannot have logged in during it.
+///
+/// ### Arguments
+/// - `locked_until`: The time at which the player's most recent lockout ends, if ever locked
+/// - `now`: The current time
+/// - `window`: How long after a lockout ends it is still considered recent
+fn recent_lockout(
+    locked_until: Option<&DateTime>,
+    now: &DateTime,
+    window: ChronoDuration,
+) -> Option<DateTime> {
+    locked_until
+        .filter(|time| *time <= now && now.to_chrono() - time.to_chrono() <= window)
+        .copied()
+}
+
+/// Determine whether an existing index is the named TTL index, but with a different TTL than the
+/// one configured, meaning that its TTL must be changed.
+///
+/// ### Arguments
+/// - `index`: The existing index
+/// - `name`: The name of the TTL index
+/// - `expire_after`: The configured TTL
+fn ttl_changed(index: &IndexModel, name: &str, expire_after: StdDuration) -> bool {
+    index.options.as_ref().is_some_and(|options| {
+        options.name.as_deref() == Some(name) && options.expire_after != Some(expire_after)
+    })
+}
+
 // /////////////// //
 // DATABASE MODELS //
 // /////////////// //
@@ -66,6 +175,9 @@ impl<T> Model for T where
 // PLAYER
 // //////
 
+/// The name of the TTL index deleting unconfirmed accounts.
+const UNCONFIRMED_TTL_INDEX: &str = "created-ttl-condition-unconfirmed";
+
 /// A document representing a player's account information, stored in the `players` collection.
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Player {
@@ -75,6 +187,9 @@ pub struct Player {
     username: String,
     /// A hash of the player's password used for logging in
     password: String,
+    /// How the player proves their identity.
+    #[serde(default)]
+    auth_method: AuthMethod,
     /// A case-insensitively unique email address at which the player can be contacted
     email: String,
     /// The time at which the player account was created
@@ -93,6 +208,13 @@ pub struct Player {
     /// The player's preferred pronouns, specifically useful while translating to Spanish for
     /// players with `gender == Gender.Other`.
     pronoun: Gender,
+    /// The IANA name of the player's time zone (like "America/Los_Angeles"), used to localize the
+    /// timestamps sent to the player. Accounts created before time zones were collected use UTC.
+    #[serde(default = "default_time_zone")]
+    time_zone: String,
+    /// The player's chosen avatar, if they have chosen one.
+    #[serde(default)]
+    avatar: Option<AvatarRef>,
     /// The player's gameplay stats.
     stats: PlayerStats,
     /// The date of the player's last **successful** login.
@@ -101,8 +223,26 @@ pub struct Player {
     failed_logins: u8,
     /// The date when a player can attempt to log in again.
     locked_until: Option<DateTime>,
+    /// The date when the player was last sent an email notifying them of a lockout.
+    #[serde(default)]
+    last_lockout_email_at: Option<DateTime>,
     /// Any access JWTs or Refresh Tokens created *before* this date will be considered invalid.
     session_valid_after: DateTime,
+    /// The time at which the player account will be permanently deleted, if the player has
+    /// requested its deletion.
+    #[serde(default)]
+    deleted_at: Option<DateTime>,
+    /// Whether the player is a staff member with access to the admin endpoints. This can only be
+    /// set directly within the database.
+    #[serde(default)]
+    admin: bool,
+}
+
+/// The time zone assumed for player accounts which were created without one.
+pub const DEFAULT_TIME_ZONE: &str = "UTC";
+
+fn default_time_zone() -> String {
+    String::from(DEFAULT_TIME_ZONE)
 }
 
 impl Player {
@@ -115,6 +255,7 @@ impl Player {
     /// - `gender`: The player's preferred gender
     /// - `preferred_language`: The player's preferred language
     /// - `pronoun`: The player's preferred pronouns
+    /// - `time_zone`: The IANA name of the player's time zone, or a raw UTC offset
     ///
     /// ### Errors
     /// - `InvalidPlayerInput` if the input does not pass validation
@@ -126,15 +267,56 @@ impl Player {
         gender: &Gender,
         preferred_language: &LanguagePreference,
         pronoun: &Gender,
+        time_zone: &str,
     ) -> DBoResult<Self> {
-        validate_all(username, password, email)?;
+        Self::new_with_clock(
+            username,
+            password,
+            email,
+            gender,
+            preferred_language,
+            pronoun,
+            time_zone,
+            &RealClock,
+        )
+    }
 
-        let now = DateTime::now();
+    /// Construct a new player, created at the current time of the provided clock
+    ///
+    /// ### Arguments
+    /// - `username`: The username of the new player
+    /// - `password`: The raw text password of the new player
+    /// - `email`: The email address of the new player
+    /// - `gender`: The player's preferred gender
+    /// - `preferred_language`: The player's preferred language
+    /// - `pronoun`: The player's preferred pronouns
+    /// - `time_zone`: The IANA name of the player's time zone, or a raw UTC offset
+    /// - `clock`: The source of the current time
+    ///
+    /// ### Errors
+    /// - `InvalidPlayerInput` if the input does not pass validation
+    /// - `AdapterError` if password hashing fails
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_clock(
+        username: &str,
+        password: &str,
+        email: &str,
+        gender: &Gender,
+        preferred_language: &LanguagePreference,
+        pronoun: &Gender,
+        time_zone: &str,
+        clock: &impl Clock,
+    ) -> DBoResult<Self> {
+        let time_zone = resolve_time_zone(time_zone);
+        validate_all(username, password, email, &time_zone)?;
+
+        let now = clock.now();
 
         Ok(Self {
             player_id: Uuid::new_v4().to_string(),
             username: String::from(username),
             password: hash_secret(password)?,
+            auth_method: AuthMethod::Password,
             email: String::from(email),
             created: now,
             confirmed: false,
@@ -143,11 +325,16 @@ impl Player {
             gender: gender.clone(),
             preferred_language: preferred_language.clone(),
             pronoun: pronoun.clone(),
+            time_zone,
+            avatar: None,
             stats: PlayerStats::default(),
             last_login: now,
             failed_logins: 0,
             locked_until: None,
+            last_lockout_email_at: None,
             session_valid_after: now,
+            deleted_at: None,
+            admin: false,
         })
     }
 
@@ -159,6 +346,10 @@ impl Player {
         &self.password
     }
 
+    pub fn auth_method(&self) -> &AuthMethod {
+        &self.auth_method
+    }
+
     pub fn email(&self) -> &str {
         &self.email
     }
@@ -179,6 +370,14 @@ impl Player {
         &self.pronoun
     }
 
+    pub fn time_zone(&self) -> &str {
+        &self.time_zone
+    }
+
+    pub fn avatar(&self) -> Option<&AvatarRef> {
+        self.avatar.as_ref()
+    }
+
     pub fn stats(&self) -> &PlayerStats {
         &self.stats
     }
@@ -191,18 +390,55 @@ impl Player {
         self.failed_logins
     }
 
+    pub fn last_login(&self) -> &DateTime {
+        &self.last_login
+    }
+
     pub fn locked_until(&self) -> &Option<DateTime> {
         &self.locked_until
     }
 
+    pub fn last_lockout_email_at(&self) -> &Option<DateTime> {
+        &self.last_lockout_email_at
+    }
+
     pub fn locked(&self) -> bool {
-        if let Some(time) = self.locked_until {
-            time.to_chrono() > Utc::now()
-        } else {
-            false
+        self.locked_at(&RealClock)
+    }
+
+    /// Determine whether the player is locked out of logging in, according to the provided clock.
+    ///
+    /// ### Arguments
+    /// - `clock`: The source of the current time
+    pub fn locked_at(&self, clock: &impl Clock) -> bool {
+        match self.locked_until {
+            Some(time) => time > clock.now(),
+            None => false,
         }
     }
 
+    /// Determine whether the player should be emailed about a new lockout. Only one lockout email
+    /// is sent per lockout window, however many times the lockout escalates within it; the window
+    /// ends when the player next logs in successfully.
+    pub fn lockout_email_due(&self) -> bool {
+        lockout_email_due(self.last_lockout_email_at.as_ref(), &self.last_login)
+    }
+
+    /// Find when the player's most recent lockout ended, if it ended within the provided window
+    /// before the current time of the provided clock. Lockouts are only cleared by a successful
+    /// login, so a lockout which has ended means that nobody has logged in since.
+    ///
+    /// ### Arguments
+    /// - `clock`: The source of the current time
+    /// - `window`: How long after a lockout ends it is still considered recent
+    pub fn recent_lockout_at(
+        &self,
+        clock: &impl Clock,
+        window: ChronoDuration,
+    ) -> Option<DateTime> {
+        recent_lockout(self.locked_until.as_ref(), &clock.now(), window)
+    }
+
     pub fn valid_after(&self) -> &DateTime {
         &self.session_valid_after
     }
@@ -214,6 +450,14 @@ impl Player {
     pub fn proposed_email(&self) -> &Option<String> {
         &self.proposed_email
     }
+
+    pub fn deleted_at(&self) -> &Option<DateTime> {
+        &self.deleted_at
+    }
+
+    pub fn admin(&self) -> bool {
+        self.admin
+    }
 }
 
 impl Collectible for Player {
@@ -237,11 +481,64 @@ impl Indexed for Player {
     /// - A uniqueness index on `player_id`
     /// - A case-insensitive uniqueness index on `username`
     /// - A case-insensitive uniqueness index on `email`
-    /// - A conditional 2-day TTL index on `created` when `confirmed == false`
+    /// - A case-insensitive uniqueness index on `proposed_email`, when it is set
+    /// - A conditional TTL index on `created` when `confirmed == false`, lasting
+    ///   `ENV.unconfirmed_account_ttl_hours`
+    ///
+    /// MongoDB cannot change the TTL of an existing index through `createIndexes`, so when the
+    /// configured TTL differs from that of the existing index, it is changed in place with
+    /// `collMod`.
     ///
     /// ### Panics
-    /// If the indices cannot be created for any reason
+    /// If the indices cannot be created or updated for any reason
     async fn index(collection: &Collection<Self>) {
+        let unconfirmed_ttl = StdDuration::from_secs(60 * 60 * ENV.unconfirmed_account_ttl_hours);
+
+        // This index used to have a fixed 2-day TTL; it is fine if it has already been dropped.
+        if let Err(e) = collection
+            .drop_index("created-ttl-2d-condition-unconfirmed")
+            .await
+            && !is_missing_namespace_or_index(&e)
+        {
+            panic!(
+                "Failed to drop the legacy unconfirmed account TTL index! {:?}",
+                e
+            );
+        }
+
+        // The collection does not exist yet on a fresh database, in which case it has no indices.
+        let existing = match collection.list_indexes().await {
+            Ok(cursor) => Some(cursor),
+            Err(e) if is_missing_namespace_or_index(&e) => None,
+            Err(e) => panic!("Failed to list the Player indices! {:?}", e),
+        };
+
+        if let Some(mut cursor) = existing {
+            while let Some(result) = cursor.next().await {
+                let index = result.expect("Failed to list the Player indices!");
+
+                if ttl_changed(&index, UNCONFIRMED_TTL_INDEX, unconfirmed_ttl) {
+                    println!(
+                        "Changing the TTL of the {} index to {:?}.",
+                        UNCONFIRMED_TTL_INDEX, unconfirmed_ttl
+                    );
+                    collection
+                        .client()
+                        .database(&collection.namespace().db)
+                        .run_command(doc! {
+                            "collMod": collection.name(),
+                            "index": {
+                                "name": UNCONFIRMED_TTL_INDEX,
+                                "expireAfterSeconds": unconfirmed_ttl.as_secs() as i64,
+                            },
+                        })
+                        .await
+                        .expect("Failed to change the TTL of the unconfirmed account index!");
+                    break;
+                }
+            }
+        }
+
         collection
             .create_indexes(vec![
                 IndexModel::builder()
@@ -273,22 +570,114 @@ impl Indexed for Player {
                             .build(),
                     )
                     .build(),
+                IndexModel::builder()
+                    .keys(doc! { "proposed_email": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("proposed-email-unique-insensitive"))
+                            .unique(true)
+                            .collation(case_insensitive_collation())
+                            .partial_filter_expression(
+                                doc! { "proposed_email": { "$type": "string" } },
+                            )
+                            .build(),
+                    )
+                    .build(),
                 IndexModel::builder()
                     .keys(doc! { "created": 1 })
                     .options(
                         IndexOptions::builder()
-                            .name(String::from("created-ttl-2d-condition-unconfirmed"))
-                            .expire_after(StdDuration::from_secs(60 * 60 * 24 * 2))
+                            .name(String::from(UNCONFIRMED_TTL_INDEX))
+                            .expire_after(unconfirmed_ttl)
                             .partial_filter_expression(doc! { "confirmed": false })
                             .build(),
                     )
                     .build(),
+                IndexModel::builder()
+                    .keys(doc! { "stats.wins": -1, "stats.losses": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("stats-rank"))
+                            .build(),
+                    )
+                    .build(),
             ])
             .await
             .expect("Failed to index the Player collection!");
     }
 }
 
+// ALLOWLIST ENTRY
+// ///////////////
+
+/// A document representing an email address or domain which may register while registration is
+/// restricted, stored in the `registration-allowlist` collection. Entries are added by admins at
+/// runtime, alongside those configured by `ENV.registration_allowlist`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AllowlistEntry {
+    /// The normalized email address or domain
+    entry: String,
+    /// The time at which the entry was added
+    added: DateTime,
+}
+
+impl AllowlistEntry {
+    /// Construct a new AllowlistEntry
+    ///
+    /// ### Arguments
+    /// - `entry`: The normalized email address or domain
+    pub fn new(entry: &str) -> Self {
+        Self {
+            entry: String::from(entry),
+            added: DateTime::now(),
+        }
+    }
+
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+}
+
+impl Collectible for AllowlistEntry {
+    fn collection_name() -> &'static str {
+        "registration-allowlist"
+    }
+}
+
+impl Identifiable for AllowlistEntry {
+    fn id(&self) -> &str {
+        &self.entry
+    }
+
+    fn id_field() -> &'static str {
+        "entry"
+    }
+}
+
+impl Indexed for AllowlistEntry {
+    /// Index a collection of AllowlistEntries with the following index:
+    /// - A uniqueness index on `entry`
+    ///
+    /// ### Panics
+    /// If the index cannot be created for any reason
+    async fn index(collection: &Collection<Self>) {
+        collection
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { Self::id_field(): 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("entry-unique"))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+            )
+            .await
+            .expect("Failed to index the AllowlistEntry collection!");
+    }
+}
+
 // CONFIRMATION TOKEN
 // //////////////////
 
@@ -304,15 +693,28 @@ pub struct ConfirmationToken {
     created: DateTime,
     /// An indicator for whether or not this token has been used to confirm a player's account yet.
     used: bool,
+    /// The time at which the token confirmed a player's account, if it has
+    #[serde(default)]
+    used_at: Option<DateTime>,
 }
 
 impl ConfirmationToken {
     pub fn new(player_id: &str) -> Self {
+        Self::new_with_clock(player_id, &RealClock)
+    }
+
+    /// Construct a new confirmation token, created at the current time of the provided clock.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The unique identifier of the player to confirm
+    /// - `clock`: The source of the current time
+    pub fn new_with_clock(player_id: &str, clock: &impl Clock) -> Self {
         Self {
             token_id: Uuid::new_v4().to_string(),
             player_id: String::from(player_id),
-            created: DateTime::now(),
+            created: clock.now(),
             used: false,
+            used_at: None,
         }
     }
 
@@ -320,8 +722,38 @@ impl ConfirmationToken {
         &self.player_id
     }
 
-    pub fn expired(&self) -> bool {
-        Utc::now() - self.created.to_chrono() > ChronoDuration::seconds(60 * 15)
+    /// Determine whether this token is the one which recently confirmed a player's account. A used
+    /// token serves as a short grace record, allowing a repeated confirmation request (such as a
+    /// double-clicked link) to succeed for one token lifetime after it was used.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The unique identifier of the confirmed player
+    pub fn confirmed_player(&self, player_id: &str) -> bool {
+        self.confirmed_player_at(player_id, &RealClock)
+    }
+
+    /// Determine whether this token recently confirmed a player's account, according to the
+    /// provided clock. Tokens used before `used_at` was recorded fall back to their creation time.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The unique identifier of the confirmed player
+    /// - `clock`: The source of the current time
+    pub fn confirmed_player_at(&self, player_id: &str, clock: &impl Clock) -> bool {
+        let used_at = self.used_at.as_ref().unwrap_or(&self.created);
+
+        self.used
+            && self.player_id == player_id
+            && token_age(used_at, &clock.now()).is_some_and(|age| age <= self.lifetime())
+    }
+}
+
+impl Expirable for ConfirmationToken {
+    fn created(&self) -> &DateTime {
+        &self.created
+    }
+
+    fn lifetime(&self) -> ChronoDuration {
+        ChronoDuration::seconds(60 * 15)
     }
 }
 
@@ -443,6 +875,315 @@ impl Indexed for Counter {
     }
 }
 
+// COUNTER SNAPSHOT
+// ////////////////
+
+/// A document recording the count of a single counter at a moment in time, stored in the
+/// `counter-snapshots` collection, so that the history of each counter can be charted.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CounterSnapshot {
+    /// A unique UUID v4 to identify the snapshot
+    snapshot_id: String,
+    /// The unique identifier of the counter which was recorded
+    counter: String,
+    /// The count of the counter when the snapshot was taken
+    count: u64,
+    /// The time at which the snapshot was taken
+    taken: DateTime,
+    /// The snapshot interval during which the snapshot was taken (see `snapshot_bucket`); each
+    /// counter has at most one snapshot per bucket. Snapshots recorded before buckets were
+    /// introduced have none.
+    #[serde(default)]
+    bucket: Option<i64>,
+}
+
+impl CounterSnapshot {
+    /// Construct a new CounterSnapshot
+    ///
+    /// ### Arguments
+    /// - `counter`: The counter to record
+    /// - `taken`: The time at which the snapshot is taken
+    /// - `bucket`: The snapshot interval during which the snapshot is taken
+    pub fn new(counter: &Counter, taken: &DateTime, bucket: i64) -> Self {
+        Self {
+            snapshot_id: Uuid::new_v4().to_string(),
+            counter: String::from(counter.id()),
+            count: counter.count(),
+            taken: *taken,
+            bucket: Some(bucket),
+        }
+    }
+
+    pub fn counter(&self) -> &str {
+        &self.counter
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn taken(&self) -> &DateTime {
+        &self.taken
+    }
+
+    pub fn bucket(&self) -> Option<i64> {
+        self.bucket
+    }
+}
+
+/// Find the snapshot interval during which a time falls, counting whole intervals since the Unix
+/// epoch. Every instance agrees on the buckets, so that each counter is snapshotted at most once
+/// per interval, however many instances run the maintenance tasks.
+///
+/// ### Arguments
+/// - `time`: The time to place in a bucket
+/// - `interval`: The time between snapshots
+pub fn snapshot_bucket(time: &DateTime, interval: ChronoDuration) -> i64 {
+    time.timestamp_millis()
+        .div_euclid(interval.num_milliseconds().max(1))
+}
+
+impl Collectible for CounterSnapshot {
+    fn collection_name() -> &'static str {
+        "counter-snapshots"
+    }
+}
+
+impl Identifiable for CounterSnapshot {
+    fn id(&self) -> &str {
+        &self.snapshot_id
+    }
+
+    fn id_field() -> &'static str {
+        "snapshot_id"
+    }
+}
+
+impl Indexed for CounterSnapshot {
+    /// Index a collection of CounterSnapshots. The indices include:
+    /// - A uniqueness index on `snapshot_id`
+    /// - A compound index on `counter` and `taken`
+    /// - An index on `taken`, used to find the latest snapshot
+    /// - A compound uniqueness index on `counter` and `bucket`, so that no counter is snapshotted
+    ///   twice in the same interval
+    ///
+    /// ### Panics
+    /// If the indices cannot be created for any reason
+    async fn index(collection: &Collection<Self>) {
+        collection
+            .create_indexes(vec![
+                IndexModel::builder()
+                    .keys(doc! { Self::id_field(): 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("snapshot-id-unique"))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "counter": 1, "taken": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("counter-taken-compound"))
+                            .build(),
+                    )
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "taken": -1 })
+                    .options(IndexOptions::builder().name(String::from("taken")).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "counter": 1, "bucket": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("counter-bucket-unique"))
+                            .unique(true)
+                            .partial_filter_expression(doc! { "bucket": { "$exists": true } })
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .await
+            .expect("Failed to index the CounterSnapshot collection!");
+    }
+}
+
+// OUTBOX EMAIL
+// ////////////
+
+/// A document representing an outgoing email, stored in the `email-outbox` collection before it is
+/// first attempted, so that it is not lost if the server stops before the email is sent.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct OutboxEmail {
+    /// A unique UUID v4 to identify the email
+    outbox_id: String,
+    /// The address the email is sent from
+    from: Option<String>,
+    /// The addresses the email is sent to
+    to: Vec<String>,
+    /// The subject of the email, kept for inspection
+    subject: String,
+    /// The complete, formatted email message
+    raw: String,
+    /// Whether the email is pending, sent, or has failed
+    status: OutboxStatus,
+    /// The number of times the email has failed to send
+    attempts: u32,
+    /// The time at which the email was queued
+    created: DateTime,
+    /// The earliest time at which the email may be attempted (again)
+    next_attempt_at: DateTime,
+    /// The time at which the email was sent, if it has been
+    sent_at: Option<DateTime>,
+    /// A description of the most recent failure to send the email, if any
+    last_error: Option<String>,
+}
+
+impl OutboxEmail {
+    /// Construct a new pending OutboxEmail, which may be attempted immediately.
+    ///
+    /// ### Arguments
+    /// - `from`: The address the email is sent from
+    /// - `to`: The addresses the email is sent to
+    /// - `subject`: The subject of the email
+    /// - `raw`: The complete, formatted email message
+    pub fn new(from: Option<String>, to: Vec<String>, subject: &str, raw: String) -> Self {
+        let now = DateTime::now();
+        Self {
+            outbox_id: Uuid::new_v4().to_string(),
+            from,
+            to,
+            subject: String::from(subject),
+            raw,
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            created: now,
+            next_attempt_at: now,
+            sent_at: None,
+            last_error: None,
+        }
+    }
+
+    pub fn from(&self) -> &Option<String> {
+        &self.from
+    }
+
+    pub fn to(&self) -> &[String] {
+        &self.to
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn status(&self) -> &OutboxStatus {
+        &self.status
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn created(&self) -> &DateTime {
+        &self.created
+    }
+
+    pub fn next_attempt_at(&self) -> &DateTime {
+        &self.next_attempt_at
+    }
+
+    pub fn last_error(&self) -> &Option<String> {
+        &self.last_error
+    }
+}
+
+/// Return how long to wait before attempting an email again, after it has failed a number of times.
+/// The delay doubles with every failure, but never exceeds one day.
+///
+/// ### Arguments
+/// - `base`: The delay following the first failure
+/// - `failures`: The number of times the email has failed to send (at least 1)
+pub fn outbox_retry_delay(base: ChronoDuration, failures: u32) -> ChronoDuration {
+    let factor = 2_i32.saturating_pow(failures.saturating_sub(1));
+    base.checked_mul(factor)
+        .unwrap_or(ChronoDuration::MAX)
+        .min(ChronoDuration::days(1))
+}
+
+impl Collectible for OutboxEmail {
+    fn collection_name() -> &'static str {
+        "email-outbox"
+    }
+}
+
+impl Identifiable for OutboxEmail {
+    fn id(&self) -> &str {
+        &self.outbox_id
+    }
+
+    fn id_field() -> &'static str {
+        "outbox_id"
+    }
+}
+
+impl Indexed for OutboxEmail {
+    /// Index a collection of OutboxEmails. The indices include:
+    /// - A uniqueness index on `outbox_id`
+    /// - A compound index on `status` and `next_attempt_at`, to find emails which are due
+    /// - A 7-day TTL index on `sent_at`, so that delivered emails are cleaned up
+    /// - A case-insensitive compound index on `status` and `to`, to find rejected recipients
+    ///
+    /// ### Panics
+    /// If the indices cannot be created for any reason
+    async fn index(collection: &Collection<Self>) {
+        collection
+            .create_indexes(vec![
+                IndexModel::builder()
+                    .keys(doc! { Self::id_field(): 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("outbox-id-unique"))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "status": 1, "next_attempt_at": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("status-next-attempt-compound"))
+                            .build(),
+                    )
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "sent_at": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("sent-at-ttl-7d"))
+                            .expire_after(StdDuration::from_secs(60 * 60 * 24 * 7))
+                            .build(),
+                    )
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "status": 1, "to": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("status-to-compound-insensitive"))
+                            .collation(case_insensitive_collation())
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .await
+            .expect("Failed to index the OutboxEmail collection!");
+    }
+}
+
 // REFRESH TOKEN
 // /////////////
 
@@ -472,11 +1213,24 @@ impl RefreshToken {
     /// ### Errors
     /// - `AdapterError` if the secret could not be hashed.
     pub fn new(player_id: &str, secret: &str) -> DBoResult<Self> {
+        Self::new_with_clock(player_id, secret, &RealClock)
+    }
+
+    /// Construct a new refresh token, created at the current time of the provided clock.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The represented player's unique identifier.
+    /// - `secret`: The secret, to be hashed and safely stored in the database.
+    /// - `clock`: The source of the current time.
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the secret could not be hashed.
+    pub fn new_with_clock(player_id: &str, secret: &str, clock: &impl Clock) -> DBoResult<Self> {
         Ok(Self {
             token_id: Uuid::new_v4().to_string(),
             player_id: String::from(player_id),
             secret: hash_secret(secret)?,
-            created: DateTime::now(),
+            created: clock.now(),
             revoked: false,
         })
     }
@@ -493,8 +1247,37 @@ impl RefreshToken {
         self.revoked
     }
 
-    pub fn expired(&self) -> bool {
-        Utc::now() - self.created.to_chrono() > ChronoDuration::seconds(60 * 60 * 24 * 30)
+    /// Determine whether the token was created before the player's sessions were last invalidated
+    /// (such as by a password change), in which case it must no longer be accepted.
+    ///
+    /// ### Arguments
+    /// - `session_valid_after`: The time at which the player's sessions were last invalidated
+    pub fn predates_session(&self, session_valid_after: &DateTime) -> bool {
+        self.created < *session_valid_after
+    }
+
+    /// Split a refresh token value, formatted as `id:secret`, into its parts. Token ids never
+    /// contain a colon, so the value is split on the first one, and the secret may contain more.
+    ///
+    /// ### Arguments
+    /// - `value`: The refresh token value provided by the client
+    ///
+    /// ### Returns
+    /// The token id and the raw secret, or None if either is missing
+    pub fn split_value(value: &str) -> Option<(&str, &str)> {
+        value
+            .split_once(':')
+            .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+    }
+}
+
+impl Expirable for RefreshToken {
+    fn created(&self) -> &DateTime {
+        &self.created
+    }
+
+    fn lifetime(&self) -> ChronoDuration {
+        ChronoDuration::seconds(60 * 60 * 24 * 30)
     }
 }
 
@@ -557,6 +1340,110 @@ impl Indexed for RefreshToken {
     }
 }
 
+// SEASON ARCHIVE
+// //////////////
+
+/// A document preserving a player's stats as they were at the end of a competitive season, stored
+/// in the `season-archives` collection.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SeasonArchive {
+    /// A unique UUID v4 to identify the archive
+    archive_id: String,
+    /// The unique identifier of the player whose stats were archived
+    player_id: String,
+    /// The 1-based number of the season which ended
+    season: u64,
+    /// The player's stats at the end of the season
+    stats: PlayerStats,
+    /// The time at which the season ended
+    archived: DateTime,
+}
+
+impl SeasonArchive {
+    /// Construct a new SeasonArchive
+    ///
+    /// ### Arguments
+    /// - `player_id`: The unique identifier of the player whose stats are archived
+    /// - `season`: The number of the season which ended
+    /// - `stats`: The player's stats at the end of the season
+    /// - `archived`: The time at which the season ended
+    pub fn new(player_id: &str, season: u64, stats: &PlayerStats, archived: &DateTime) -> Self {
+        Self {
+            archive_id: Uuid::new_v4().to_string(),
+            player_id: String::from(player_id),
+            season,
+            stats: stats.clone(),
+            archived: *archived,
+        }
+    }
+
+    pub fn player_id(&self) -> &str {
+        &self.player_id
+    }
+
+    pub fn season(&self) -> u64 {
+        self.season
+    }
+
+    pub fn stats(&self) -> &PlayerStats {
+        &self.stats
+    }
+
+    pub fn archived(&self) -> &DateTime {
+        &self.archived
+    }
+}
+
+impl Collectible for SeasonArchive {
+    fn collection_name() -> &'static str {
+        "season-archives"
+    }
+}
+
+impl Identifiable for SeasonArchive {
+    fn id(&self) -> &str {
+        &self.archive_id
+    }
+
+    fn id_field() -> &'static str {
+        "archive_id"
+    }
+}
+
+impl Indexed for SeasonArchive {
+    /// Index a collection of SeasonArchives. The indices include:
+    /// - A uniqueness index on `archive_id`
+    /// - A compound uniqueness index on `player_id` and `season`
+    ///
+    /// ### Panics
+    /// If the indices cannot be created for any reason
+    async fn index(collection: &Collection<Self>) {
+        collection
+            .create_indexes(vec![
+                IndexModel::builder()
+                    .keys(doc! { Self::id_field(): 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("archive-id-unique"))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "player_id": 1, "season": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(String::from("player-id-season-compound-unique"))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .await
+            .expect("Failed to index the SeasonArchive collection!");
+    }
+}
+
 // UNDO TOKEN
 // //////////
 
@@ -570,11 +1457,21 @@ pub struct UndoToken {
 
 impl UndoToken {
     pub fn new(player_id: &str, function: &UndoTokenType) -> Self {
+        Self::new_with_clock(player_id, function, &RealClock)
+    }
+
+    /// Construct a new undo token, created at the current time of the provided clock.
+    ///
+    /// ### Arguments
+    /// - `player_id`: The unique identifier of the player who may use the token
+    /// - `function`: The operation which the token can undo
+    /// - `clock`: The source of the current time
+    pub fn new_with_clock(player_id: &str, function: &UndoTokenType, clock: &impl Clock) -> Self {
         Self {
             token_id: Uuid::new_v4().to_string(),
             player_id: String::from(player_id),
             function: function.clone(),
-            created: DateTime::now(),
+            created: clock.now(),
         }
     }
 
@@ -585,9 +1482,20 @@ impl UndoToken {
     pub fn function(&self) -> &UndoTokenType {
         &self.function
     }
+}
 
-    pub fn expired(&self) -> bool {
-        Utc::now() - self.created.to_chrono() > ChronoDuration::seconds(60 * 60 * 24)
+impl Expirable for UndoToken {
+    fn created(&self) -> &DateTime {
+        &self.created
+    }
+
+    /// Undo tokens are good for one day, except for those cancelling an account deletion, which
+    /// are good for the entire grace period.
+    fn lifetime(&self) -> ChronoDuration {
+        match self.function {
+            UndoTokenType::Deletion => ChronoDuration::days(ENV.deletion_grace_days),
+            _ => ChronoDuration::seconds(60 * 60 * 24),
+        }
     }
 }
 
@@ -611,11 +1519,24 @@ impl Indexed for UndoToken {
     /// Index a collection of UndoTokens. The indices include:
     /// - A uniqueness index on `token_id`
     /// - A compound uniqueness index on `player_id` and `function`
-    /// - A 1-day TTL index on `created`
+    /// - A conditional 1-day TTL index on `created` for password, email and password reset tokens
+    ///
+    /// Deletion tokens have no TTL index, as they must last the entire grace period; they are
+    /// removed alongside the account, or once the deletion is cancelled.
     ///
     /// ### Panics
     /// If the indices cannot be created for any reason
     async fn index(collection: &Collection<Self>) {
+        // The unconditional TTL index would expire deletion tokens too early, and cannot coexist
+        // with the conditional one; it is fine if it has already been dropped.
+        collection.drop_index("created-1d-ttl").await.ok();
+        // The conditional TTL index used to exclude password reset tokens, and cannot be replaced
+        // under the same name.
+        collection
+            .drop_index("created-ttl-1d-condition-pw-em")
+            .await
+            .ok();
+
         collection
             .create_indexes(vec![
                 IndexModel::builder()
@@ -631,8 +1552,15 @@ impl Indexed for UndoToken {
                     .keys(doc! { "created": 1 })
                     .options(
                         IndexOptions::builder()
-                            .name(String::from("created-1d-ttl"))
+                            .name(String::from("created-ttl-1d-condition-pw-em-rs"))
                             .expire_after(StdDuration::from_secs(60 * 60 * 24))
+                            .partial_filter_expression(doc! { "function": {
+                                "$in": [
+                                    UndoTokenType::Password.to_string(),
+                                    UndoTokenType::Email.to_string(),
+                                    UndoTokenType::PasswordReset.to_string()
+                                ]
+                            } })
                             .build(),
                     )
                     .build(),
@@ -650,3 +1578,215 @@ impl Indexed for UndoToken {
             .expect("Failed to index the UndoToken collection!");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::models::clock::FixedClock;
+
+    #[test]
+    fn test_token_age() {
+        let now = DateTime::now();
+
+        let past = DateTime::from_chrono(now.to_chrono() - ChronoDuration::minutes(5));
+        assert_eq!(token_age(&past, &now), Some(ChronoDuration::minutes(5)));
+
+        let future = DateTime::from_chrono(now.to_chrono() + ChronoDuration::minutes(5));
+        assert!(token_age(&future, &now).is_none());
+    }
+
+    #[test]
+    fn test_lockout_email_dedup() {
+        let last_login = DateTime::from_chrono(Utc::now() - ChronoDuration::days(1));
+        let start = Utc::now();
+        let mut last_sent: Option<DateTime> = None;
+        let mut sent = 0;
+
+        // An attacker fails a login as soon as each escalating lockout ends, for over a day.
+        let mut elapsed = ChronoDuration::zero();
+        for failures in 5..25 {
+            let now = DateTime::from_chrono(start + elapsed);
+            if lockout_email_due(last_sent.as_ref(), &last_login) {
+                last_sent = Some(now);
+                sent += 1;
+            }
+            elapsed += ChronoDuration::minutes(15) * (failures - 4);
+        }
+        assert!(elapsed > ChronoDuration::days(1));
+        assert_eq!(sent, 1);
+
+        // Once the player has logged in again, a new lockout starts a new window.
+        let next_login = DateTime::from_chrono(start + elapsed);
+        assert!(lockout_email_due(last_sent.as_ref(), &next_login));
+    }
+
+    #[test]
+    fn test_recent_lockout() {
+        let now = DateTime::now();
+        let ago = |elapsed| DateTime::from_chrono(now.to_chrono() - elapsed);
+        let window = ChronoDuration::hours(24);
+
+        assert_eq!(recent_lockout(None, &now, window), None);
+
+        let cleared = ago(ChronoDuration::hours(2));
+        assert_eq!(recent_lockout(Some(&cleared), &now, window), Some(cleared));
+        assert_eq!(recent_lockout(Some(&now), &now, window), Some(now));
+
+        let edge = ago(window);
+        assert_eq!(recent_lockout(Some(&edge), &now, window), Some(edge));
+
+        let stale = ago(window + ChronoDuration::seconds(1));
+        assert_eq!(recent_lockout(Some(&stale), &now, window), None);
+
+        // A lockout which is still in effect has not been cleared yet.
+        let ongoing = DateTime::from_chrono(now.to_chrono() + ChronoDuration::minutes(15));
+        assert_eq!(recent_lockout(Some(&ongoing), &now, window), None);
+    }
+
+    #[test]
+    fn test_outbox_retry_delay() {
+        let base = ChronoDuration::seconds(30);
+        assert_eq!(outbox_retry_delay(base, 1), base);
+        assert_eq!(outbox_retry_delay(base, 2), ChronoDuration::seconds(60));
+        assert_eq!(outbox_retry_delay(base, 4), ChronoDuration::seconds(240));
+        assert_eq!(outbox_retry_delay(base, 200), ChronoDuration::days(1));
+    }
+
+    #[test]
+    fn test_future_tokens_are_expired() {
+        let created = Utc::now();
+        let token = ConfirmationToken::new_with_clock("player", &FixedClock::at(created));
+        assert!(!token.expired_at(&FixedClock::at(created)));
+        assert!(token.expired_at(&FixedClock::at(created - ChronoDuration::minutes(5))));
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let created = Utc::now();
+        let clock = FixedClock::at(created);
+
+        let confirmation = ConfirmationToken::new_with_clock("player", &clock);
+        let refresh = RefreshToken::new_with_clock("player", "secret", &clock).unwrap();
+        let undo = UndoToken::new_with_clock("player", &UndoTokenType::Password, &clock);
+
+        let at = |elapsed| FixedClock::at(created + elapsed);
+        let second = ChronoDuration::seconds(1);
+
+        let lifetime = ChronoDuration::minutes(15);
+        assert!(!confirmation.expired_at(&at(lifetime)));
+        assert!(confirmation.expired_at(&at(lifetime + second)));
+
+        let lifetime = ChronoDuration::days(30);
+        assert!(!refresh.expired_at(&at(lifetime)));
+        assert!(refresh.expired_at(&at(lifetime + second)));
+
+        let lifetime = ChronoDuration::days(1);
+        assert!(!undo.expired_at(&at(lifetime)));
+        assert!(undo.expired_at(&at(lifetime + second)));
+    }
+
+    #[test]
+    fn test_refresh_token_dies_with_session() {
+        let created = Utc::now();
+        let token =
+            RefreshToken::new_with_clock("player", "secret", &FixedClock::at(created)).unwrap();
+
+        // The password is changed after the token was issued; the thief's token is dead.
+        let changed = DateTime::from_chrono(created + ChronoDuration::minutes(5));
+        assert!(token.predates_session(&changed));
+
+        // A token issued by logging in with the new password is still alive.
+        let changed = DateTime::from_chrono(created - ChronoDuration::minutes(5));
+        assert!(!token.predates_session(&changed));
+        assert!(!token.predates_session(&DateTime::from_chrono(created)));
+    }
+
+    #[test]
+    fn test_ttl_changed() {
+        let ttl = StdDuration::from_secs(60 * 60 * 48);
+        let index = |name: &str, expire_after: Option<StdDuration>| {
+            IndexModel::builder()
+                .keys(doc! { "created": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name(String::from(name))
+                        .expire_after(expire_after)
+                        .build(),
+                )
+                .build()
+        };
+
+        assert!(!ttl_changed(
+            &index(UNCONFIRMED_TTL_INDEX, Some(ttl)),
+            UNCONFIRMED_TTL_INDEX,
+            ttl
+        ));
+        assert!(ttl_changed(
+            &index(UNCONFIRMED_TTL_INDEX, Some(ttl * 2)),
+            UNCONFIRMED_TTL_INDEX,
+            ttl
+        ));
+        assert!(ttl_changed(
+            &index(UNCONFIRMED_TTL_INDEX, None),
+            UNCONFIRMED_TTL_INDEX,
+            ttl
+        ));
+
+        // Other indices are left alone.
+        assert!(!ttl_changed(
+            &index("created-ttl-2d", Some(ttl * 2)),
+            UNCONFIRMED_TTL_INDEX,
+            ttl
+        ));
+    }
+
+    #[test]
+    fn test_refresh_token_split_value() {
+        assert_eq!(
+            RefreshToken::split_value("token-id:s3cr3t_-"),
+            Some(("token-id", "s3cr3t_-"))
+        );
+
+        // Only the first colon separates the id from the secret.
+        assert_eq!(
+            RefreshToken::split_value("token-id:s3c:r3t:"),
+            Some(("token-id", "s3c:r3t:"))
+        );
+
+        for invalid in ["", "token-id", "token-id:", ":s3cr3t"] {
+            assert_eq!(RefreshToken::split_value(invalid), None);
+        }
+    }
+
+    #[test]
+    fn test_confirmed_player() {
+        let mut token = ConfirmationToken::new("player");
+        assert!(!token.confirmed_player("player"));
+
+        token.used = true;
+        assert!(token.confirmed_player("player"));
+        assert!(!token.confirmed_player("someone-else"));
+
+        token.created = DateTime::from_chrono(Utc::now() - ChronoDuration::minutes(16));
+        assert!(!token.confirmed_player("player"));
+    }
+
+    #[test]
+    fn test_confirmation_replay_grace() {
+        let created = Utc::now();
+        let mut token = ConfirmationToken::new_with_clock("player", &FixedClock::at(created));
+
+        // The link is followed just before the token would expire.
+        let used = created + ChronoDuration::minutes(14);
+        token.used = true;
+        token.used_at = Some(DateTime::from_chrono(used));
+
+        let at = |elapsed| FixedClock::at(used + elapsed);
+        assert!(token.confirmed_player_at("player", &at(ChronoDuration::minutes(2))));
+        assert!(token.confirmed_player_at("player", &at(ChronoDuration::minutes(15))));
+        assert!(!token.confirmed_player_at("player", &at(ChronoDuration::minutes(16))));
+        assert!(!token.confirmed_player_at("someone-else", &at(ChronoDuration::zero())));
+    }
+}
@@ -0,0 +1,46 @@
+//! This module provides the `Clock` trait, the source of the current time for the model layer.
+//!
+//! Models never ask the system for the time directly; their constructors and expiry checks accept
+//! a `Clock` instead. The application always uses the `RealClock`, while tests can use a
+//! `FixedClock` to check time-dependent logic (expiry, lockouts) deterministically.
+
+use bson::DateTime;
+#[cfg(test)]
+use chrono::{DateTime as ChronoDateTime, Utc};
+
+/// A source of the current time.
+pub trait Clock {
+    /// Return the current time.
+    fn now(&self) -> DateTime;
+}
+
+/// The system clock, which is used everywhere outside of tests.
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> DateTime {
+        DateTime::now()
+    }
+}
+
+/// A clock which is stopped at a specific time.
+#[cfg(test)]
+pub struct FixedClock(pub DateTime);
+
+#[cfg(test)]
+impl FixedClock {
+    /// Construct a new FixedClock, stopped at the provided time
+    ///
+    /// ### Arguments
+    /// - `time`: The time at which the clock is stopped
+    pub fn at(time: ChronoDateTime<Utc>) -> Self {
+        Self(DateTime::from_chrono(time))
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime {
+        self.0
+    }
+}
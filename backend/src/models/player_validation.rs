@@ -4,14 +4,173 @@
 //!
 //! This module also provides a function to validate all input fields at once, which is the most
 //! concise way to utilize this module's functionality.
+//!
+//! Each problem found is reported as a structured `ValidationProblem`, made up of a `ProblemCode`
+//! and any parameters of the broken rule, so that clients can present the problem in the player's
+//! own language.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::read_to_string,
+};
 
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::Serialize;
 
 use crate::{
+    config::environment::ENV,
     errors::{DBoError, DBoResult},
     handlers::responses::PlayerInvalidFieldsResponse,
+    models::submodels::{AvatarRef, Gender, LanguagePreference},
 };
 
+/// Identifies a single validation rule which the input failed to meet.
+///
+/// Serialized in snake case, e.g. `username_too_short`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemCode {
+    /// The username is shorter than the minimum length.
+    UsernameTooShort,
+    /// The username is longer than the maximum length.
+    UsernameTooLong,
+    /// The username contains characters other than letters, numbers, and underscores.
+    UsernameIllegalCharacters,
+    /// The username starts with an underscore.
+    UsernameLeadingUnderscore,
+    /// The username contains two or more consecutive underscores.
+    UsernameConsecutiveUnderscores,
+    /// The username contains an offensive term.
+    UsernameOffensive,
+    /// The username is reserved for staff accounts.
+    UsernameReserved,
+    /// The password is shorter than the minimum length.
+    PasswordTooShort,
+    /// The password is longer than the maximum length.
+    PasswordTooLong,
+    /// The password does not include a lowercase letter.
+    PasswordMissingLowercase,
+    /// The password does not include an uppercase letter.
+    PasswordMissingUppercase,
+    /// The password does not include a number.
+    PasswordMissingNumber,
+    /// The password does not include one of the allowed symbols.
+    PasswordMissingSymbol,
+    /// The password contains spaces or symbols which are not allowed.
+    PasswordIllegalCharacters,
+    /// The email does not include a single `@` character.
+    EmailSingleAt,
+    /// The email prefix is empty.
+    EmailPrefixEmpty,
+    /// The email prefix contains illegal characters.
+    EmailPrefixIllegalCharacters,
+    /// The email prefix begins or ends with a dot.
+    EmailPrefixDotBoundary,
+    /// The email prefix contains consecutive dots.
+    EmailPrefixConsecutiveDots,
+    /// The email domain is empty.
+    EmailDomainEmpty,
+    /// The email domain contains illegal characters.
+    EmailDomainIllegalCharacters,
+    /// The email domain does not include both a subdomain and a top level domain.
+    EmailDomainMissingLevels,
+    /// The email domain contains consecutive dots.
+    EmailDomainConsecutiveDots,
+    /// A level of the email domain begins or ends with a hyphen.
+    EmailDomainHyphenBoundary,
+    /// The email top level domain is shorter than the minimum length.
+    EmailTldTooShort,
+    /// The email domain belongs to a disposable email provider.
+    EmailDomainDisposable,
+    /// The email domain has no MX records, so it cannot receive emails.
+    EmailDomainNoMx,
+    /// Registration is restricted to an allow-list, and the email address is not on it.
+    EmailNotAllowlisted,
+    /// The time zone is not a recognized IANA time zone name.
+    TimeZoneUnknown,
+    /// A Spanish-speaking non-binary player chose the neutral pronoun, which is already the
+    /// default; only the masculine or feminine pronoun may be chosen.
+    PronounInvalidChoice,
+    /// The avatar refers to a preset which does not exist.
+    AvatarPresetUnknown,
+    /// The avatar refers to an uploaded image outside of the configured avatar storage.
+    AvatarUploadOutsideStorage,
+    /// The avatar image is not in one of the accepted formats.
+    AvatarUnsupportedType,
+    /// The avatar image is empty, or larger than the maximum size.
+    AvatarSizeOutOfRange,
+}
+
+/// A single problem found while validating an input field.
+///
+/// The `params` hold the values of the broken rule (such as the `min` and `max` length), and are
+/// omitted from the serialized version when the rule has none.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ValidationProblem {
+    /// The rule which was broken.
+    code: ProblemCode,
+    /// The parameters of the broken rule.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<&'static str, usize>,
+}
+
+impl ValidationProblem {
+    /// Construct a new ValidationProblem for a rule without parameters
+    ///
+    /// ### Arguments
+    /// - `code`: The rule which was broken
+    pub fn new(code: ProblemCode) -> Self {
+        Self {
+            code,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Construct a new ValidationProblem for a rule with parameters
+    ///
+    /// ### Arguments
+    /// - `code`: The rule which was broken
+    /// - `params`: The names and values of the rule's parameters
+    fn with_params(code: ProblemCode, params: &[(&'static str, usize)]) -> Self {
+        Self {
+            code,
+            params: params.iter().copied().collect(),
+        }
+    }
+}
+
+/// Check that the length of the input falls within a range.
+///
+/// ### Arguments
+/// - `length`: The length of the input
+/// - `min`: The minimum allowed length
+/// - `max`: The maximum allowed length
+/// - `too_short`: The problem code reported when the input is too short
+/// - `too_long`: The problem code reported when the input is too long
+///
+/// ### Returns
+/// - `Some`: The problem with the input length
+/// - `None`: The length is within range
+#[doc(hidden)]
+fn check_length(
+    length: usize,
+    min: usize,
+    max: usize,
+    too_short: ProblemCode,
+    too_long: ProblemCode,
+) -> Option<ValidationProblem> {
+    let params = [("min", min), ("max", max)];
+    if length < min {
+        Some(ValidationProblem::with_params(too_short, &params))
+    } else if length > max {
+        Some(ValidationProblem::with_params(too_long, &params))
+    } else {
+        None
+    }
+}
+
 /// Check a string to make sure that it could be a valid username.
 ///
 /// A valid username must pass the following checks:
@@ -30,31 +189,33 @@ use crate::{
 /// ### Returns
 /// - `Some`: A list of problems with the input
 /// - `None`: Input is valid
-pub fn validate_username(input: &str) -> Option<Vec<String>> {
-    let mut problems: Vec<String> = vec![];
-
-    let length = input.len();
-    if length < 6 || length > 16 {
-        problems.push(format!(
-            "Username must be between 6 and 16 characters - found: {}",
-            length
-        ));
-    }
+pub fn validate_username(input: &str) -> Option<Vec<ValidationProblem>> {
+    let mut problems: Vec<ValidationProblem> = vec![];
+
+    problems.extend(check_length(
+        input.len(),
+        6,
+        16,
+        ProblemCode::UsernameTooShort,
+        ProblemCode::UsernameTooLong,
+    ));
 
     let legal_chars = Regex::new(r"^[\w\d]+$").unwrap();
     if !legal_chars.is_match(input) {
-        problems.push(String::from(
-            "Username contains illegal characters - it may only include letters, numbers, and underscores."
+        problems.push(ValidationProblem::new(
+            ProblemCode::UsernameIllegalCharacters,
         ));
     }
 
     if input.starts_with("_") {
-        problems.push(String::from("Username cannot start with an underscore."));
+        problems.push(ValidationProblem::new(
+            ProblemCode::UsernameLeadingUnderscore,
+        ));
     }
 
     if input.contains("__") {
-        problems.push(String::from(
-            "Username may not contain consecutive underscores.",
+        problems.push(ValidationProblem::new(
+            ProblemCode::UsernameConsecutiveUnderscores,
         ));
     }
 
@@ -64,6 +225,167 @@ pub fn validate_username(input: &str) -> Option<Vec<String>> {
     }
 }
 
+/// The built-in list of offensive terms for usernames.
+#[doc(hidden)]
+static DEFAULT_USERNAME_BLOCKLIST: &str = include_str!("../../assets/username-blocklist.txt");
+
+/// A list of offensive terms which may not appear in usernames. See
+/// `assets/username-blocklist.txt` for the format of the list.
+pub struct UsernameBlocklist {
+    /// Terms which may not appear anywhere within a username.
+    anywhere: Vec<String>,
+    /// Terms which may not appear as a whole word of a username.
+    words: HashSet<String>,
+}
+
+impl UsernameBlocklist {
+    /// Parse a blocklist from the contents of a blocklist file.
+    ///
+    /// ### Arguments
+    /// - `list`: The contents of the file
+    pub fn parse(list: &str) -> Self {
+        let mut blocklist = Self {
+            anywhere: vec![],
+            words: HashSet::new(),
+        };
+
+        for line in list.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix('=') {
+                Some(word) => {
+                    blocklist.words.insert(normalize_username(word));
+                }
+                None => blocklist
+                    .anywhere
+                    .push(normalize_username(line).replace('_', "")),
+            }
+        }
+
+        blocklist
+    }
+
+    /// Check whether a username is free of all blocked terms.
+    ///
+    /// ### Arguments
+    /// - `name`: The username to check
+    pub fn allows(&self, name: &str) -> bool {
+        let normalized = normalize_username(name);
+        let joined = normalized.replace('_', "");
+
+        !self
+            .anywhere
+            .iter()
+            .any(|term| joined.contains(term.as_str()))
+            && !normalized.split('_').any(|word| self.words.contains(word))
+    }
+}
+
+/// Lowercase a username and undo common leet-speak substitutions, so that blocked terms cannot be
+/// disguised.
+#[doc(hidden)]
+fn normalize_username(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => 'i',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            '7' => 't',
+            '@' => 'a',
+            '$' => 's',
+            c => c,
+        })
+        .collect()
+}
+
+/// The blocklist used by the application, read from `ENV.username_blocklist_path` if it is set.
+///
+/// ### Panics
+/// If the configured blocklist file cannot be read.
+static USERNAME_BLOCKLIST: Lazy<UsernameBlocklist> =
+    Lazy::new(|| match &ENV.username_blocklist_path {
+        Some(path) => UsernameBlocklist::parse(
+            &read_to_string(path)
+                .unwrap_or_else(|_| panic!("Could not read username blocklist at {:?}", path)),
+        ),
+        None => UsernameBlocklist::parse(DEFAULT_USERNAME_BLOCKLIST),
+    });
+
+/// Check whether a username is free of the offensive terms in the configured blocklist.
+///
+/// ### Arguments
+/// - `name`: The username to check
+pub fn username_allowed(name: &str) -> bool {
+    USERNAME_BLOCKLIST.allows(name)
+}
+
+/// Words which are reserved for staff accounts, so that regular players cannot impersonate them.
+#[doc(hidden)]
+static RESERVED_USERNAME_WORDS: [&str; 11] = [
+    "admin",
+    "administrator",
+    "bigdevdog",
+    "dbo",
+    "mod",
+    "moderator",
+    "official",
+    "root",
+    "staff",
+    "support",
+    "system",
+];
+
+/// Check whether a username is reserved for staff accounts. A username is reserved if any of its
+/// words (separated by underscores), or the whole username with its underscores removed, is a
+/// reserved word - so `admin_bob`, `Dbo_Support` and `d_bo` are all reserved.
+///
+/// ### Arguments
+/// - `name`: The username to check
+pub fn username_reserved(name: &str) -> bool {
+    let normalized = normalize_username(name);
+    let joined = normalized.replace('_', "");
+
+    RESERVED_USERNAME_WORDS.contains(&joined.as_str())
+        || normalized
+            .split('_')
+            .any(|word| RESERVED_USERNAME_WORDS.contains(&word))
+}
+
+/// Check a username which a player has chosen (during registration, or when changing it), applying
+/// the naming policy on top of the structural rules of `validate_username`. Offensive terms are
+/// only checked when `ENV.filter_usernames` is enabled.
+///
+/// ### Arguments
+/// - `input`: The username to be tested
+/// - `allow_reserved`: Whether the player may claim a reserved username (only admins may)
+///
+/// ### Returns
+/// - `Some`: A list of problems with the input
+/// - `None`: Input is valid
+pub fn validate_username_choice(
+    input: &str,
+    allow_reserved: bool,
+) -> Option<Vec<ValidationProblem>> {
+    let problems = validate_username(input);
+    if problems.is_some() {
+        return problems;
+    }
+
+    if !allow_reserved && username_reserved(input) {
+        return Some(vec![ValidationProblem::new(ProblemCode::UsernameReserved)]);
+    }
+
+    if ENV.filter_usernames && !username_allowed(input) {
+        return Some(vec![ValidationProblem::new(ProblemCode::UsernameOffensive)]);
+    }
+
+    None
+}
+
 /// Check a string to make sure that it could be a valid password.
 ///
 /// A valid password must pass the following checks:
@@ -82,42 +404,46 @@ pub fn validate_username(input: &str) -> Option<Vec<String>> {
 /// ### Returns
 /// - `Some`: A list of problems with the input
 /// - `None`: The input is valid
-pub fn validate_password(input: &str) -> Option<Vec<String>> {
-    let mut problems: Vec<String> = vec![];
-
-    let length = input.len();
-    if length < 8 || length > 32 {
-        problems.push(format!(
-            "Password must be between 8 and 32 characters - found {}",
-            length
-        ));
-    }
+pub fn validate_password(input: &str) -> Option<Vec<ValidationProblem>> {
+    let mut problems: Vec<ValidationProblem> = vec![];
+
+    problems.extend(check_length(
+        input.len(),
+        8,
+        32,
+        ProblemCode::PasswordTooShort,
+        ProblemCode::PasswordTooLong,
+    ));
 
     let lower = Regex::new("[a-z]").unwrap();
     if !lower.is_match(input) {
-        problems.push(String::from("Password must include a lowercase letter."));
+        problems.push(ValidationProblem::new(
+            ProblemCode::PasswordMissingLowercase,
+        ));
     }
 
     let upper = Regex::new("[A-Z]").unwrap();
     if !upper.is_match(input) {
-        problems.push(String::from("Password must include an uppercase letter."))
+        problems.push(ValidationProblem::new(
+            ProblemCode::PasswordMissingUppercase,
+        ))
     }
 
     let digit = Regex::new(r"\d").unwrap();
     if !digit.is_match(input) {
-        problems.push(String::from("Password must include a number."))
+        problems.push(ValidationProblem::new(ProblemCode::PasswordMissingNumber))
     }
 
     let symbol = Regex::new("[!@#$%^&*+=?]").unwrap();
     if !symbol.is_match(input) {
-        problems.push(String::from(
-            "Password must include one of the following symbols: ! @ # $ % ^ & * + = ?",
-        ));
+        problems.push(ValidationProblem::new(ProblemCode::PasswordMissingSymbol));
     }
 
     let illegal_char = Regex::new(r"^[\dA-Za-z!@#$%^&*+=?]+$").unwrap();
     if !illegal_char.is_match(input) {
-        problems.push(String::from("Password includes illegal characters."))
+        problems.push(ValidationProblem::new(
+            ProblemCode::PasswordIllegalCharacters,
+        ))
     }
 
     match problems.len() {
@@ -153,72 +479,75 @@ pub fn validate_password(input: &str) -> Option<Vec<String>> {
 /// ### Returns
 /// - `Some`: A list of problems with the input
 /// - `None`: Input is valid
-pub fn validate_email(input: &str) -> Option<Vec<String>> {
-    let mut problems: Vec<String> = vec![];
+pub fn validate_email(input: &str) -> Option<Vec<ValidationProblem>> {
+    let mut problems: Vec<ValidationProblem> = vec![];
 
     let parts: Vec<&str> = input.split('@').collect();
     if parts.len() != 2 {
-        problems.push(String::from(
-            "Email must include a single @ character, separating the prefix and the domain.",
-        ));
+        problems.push(ValidationProblem::new(ProblemCode::EmailSingleAt));
         return Some(problems);
     }
 
     let prefix = parts[0];
     let domain = parts[1];
 
-    if prefix.len() == 0 {
-        problems.push(String::from("Email prefix is empty!"));
+    if prefix.is_empty() {
+        problems.push(ValidationProblem::new(ProblemCode::EmailPrefixEmpty));
     } else {
         let illegal_chars = Regex::new(r"^[A-Za-z\d._+-]+$").unwrap();
         if !illegal_chars.is_match(prefix) {
-            problems.push(String::from("Email prefix contains illegal characters. Allowable characters are letters, numbers, and the following symbols: . _ + -"));
+            problems.push(ValidationProblem::new(
+                ProblemCode::EmailPrefixIllegalCharacters,
+            ));
         }
 
         if prefix.starts_with('.') || prefix.ends_with('.') {
-            problems.push(String::from(
-                "Email prefix cannot begin nor end with a dot.",
-            ));
+            problems.push(ValidationProblem::new(ProblemCode::EmailPrefixDotBoundary));
         }
 
         let consecutive_dots = Regex::new(r"\.\.").unwrap();
         if consecutive_dots.is_match(prefix) {
-            problems.push(String::from(
-                "Email prefix cannot contain consecutive dots.",
+            problems.push(ValidationProblem::new(
+                ProblemCode::EmailPrefixConsecutiveDots,
             ));
         }
     }
 
-    if domain.len() == 0 {
-        problems.push(String::from("Email domain is empty!"));
+    if domain.is_empty() {
+        problems.push(ValidationProblem::new(ProblemCode::EmailDomainEmpty));
     } else {
         let illegal_chars = Regex::new(r"^[A-Za-z\d\.-]+$").unwrap();
         if !illegal_chars.is_match(domain) {
-            problems.push(String::from("Email domain includes illegal characters. Allowed characters are letters, numbers, and hyphens."));
+            problems.push(ValidationProblem::new(
+                ProblemCode::EmailDomainIllegalCharacters,
+            ));
         }
 
         let levels: Vec<&str> = domain.split('.').collect();
         if levels.len() < 2 {
-            problems.push(String::from("Email domain must include at least one subdomain and a top level domain, separated by a dot."))
+            problems.push(ValidationProblem::new(
+                ProblemCode::EmailDomainMissingLevels,
+            ))
         }
 
         for &level in &levels {
-            if level.len() == 0 {
-                problems.push(String::from(
-                    "Email domain may not include consecutive dots.",
+            if level.is_empty() {
+                problems.push(ValidationProblem::new(
+                    ProblemCode::EmailDomainConsecutiveDots,
                 ));
             }
             if level.starts_with('-') || level.ends_with('-') {
-                problems.push(String::from(
-                    "Email contains a domain level which either starts or ends with a hyphen.",
+                problems.push(ValidationProblem::new(
+                    ProblemCode::EmailDomainHyphenBoundary,
                 ));
             }
         }
 
         let tld = levels[levels.len() - 1];
         if tld.len() < 2 {
-            problems.push(String::from(
-                "Email top level domain must meet or exceed two characters.",
+            problems.push(ValidationProblem::with_params(
+                ProblemCode::EmailTldTooShort,
+                &[("min", 2)],
             ));
         }
     }
@@ -229,6 +558,379 @@ pub fn validate_email(input: &str) -> Option<Vec<String>> {
     }
 }
 
+/// The known disposable email domains, embedded from `assets/disposable-email-domains.txt`.
+static DISPOSABLE_EMAIL_DOMAINS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    include_str!("../../assets/disposable-email-domains.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+});
+
+/// Check an email address to make sure that its domain does not belong to a disposable email
+/// provider. Subdomains of a disposable domain are treated as disposable as well.
+///
+/// ### Arguments
+/// - `input`: The email address to be tested
+///
+/// ### Returns
+/// - `Some`: The problem with the input
+/// - `None`: The domain is allowed
+pub fn validate_email_domain_allowed(input: &str) -> Option<ValidationProblem> {
+    let domain = input.rsplit_once('@')?.1.to_lowercase();
+
+    let mut candidate = domain.as_str();
+    loop {
+        if DISPOSABLE_EMAIL_DOMAINS.contains(candidate) {
+            return Some(ValidationProblem::new(ProblemCode::EmailDomainDisposable));
+        }
+        candidate = candidate.split_once('.')?.1;
+    }
+}
+
+/// Normalize an entry of the registration allow-list. An entry is either a complete email address,
+/// or a domain (optionally written with a leading `@`), and is compared case-insensitively.
+///
+/// ### Arguments
+/// - `input`: The entry to normalize
+///
+/// ### Returns
+/// The lowercase entry without a leading `@`, or None if it is empty, contains whitespace or
+/// commas, or contains more than one `@`
+pub fn normalize_allowlist_entry(input: &str) -> Option<String> {
+    let entry = input.trim().trim_start_matches('@').to_lowercase();
+
+    let malformed = entry.is_empty()
+        || entry.contains(|c: char| c.is_whitespace() || c == ',')
+        || entry.matches('@').count() > 1;
+
+    (!malformed).then_some(entry)
+}
+
+/// Check an email address against the registration allow-list. The address is allowed if it
+/// matches an address on the list, or if its domain matches a domain on the list. While the list
+/// is empty, registration is open, and every address is allowed.
+///
+/// ### Arguments
+/// - `input`: The email address to be tested
+/// - `entries`: The entries of the allow-list
+///
+/// ### Returns
+/// None if the address is allowed, or a list containing the problem if not
+pub fn validate_email_allowlisted(
+    input: &str,
+    entries: &[String],
+) -> Option<Vec<ValidationProblem>> {
+    if entries.is_empty() {
+        return None;
+    }
+
+    let email = input.to_lowercase();
+    let domain = email.rsplit_once('@').map(|(_, d)| d);
+
+    let allowed = entries
+        .iter()
+        .filter_map(|e| normalize_allowlist_entry(e))
+        .any(|entry| entry == email || Some(entry.as_str()) == domain);
+
+    match allowed {
+        true => None,
+        false => Some(vec![ValidationProblem::new(
+            ProblemCode::EmailNotAllowlisted,
+        )]),
+    }
+}
+
+/// Determine whether a proposed username or email address is the same as the player's current
+/// one. Both are compared case-insensitively, just like they are by the uniqueness checks, so a
+/// change which only alters the case of either is also considered to be no change.
+///
+/// ### Arguments
+/// - `current`: The player's current username or email address
+/// - `proposed`: The proposed username or email address
+pub fn is_same_identifier(current: &str, proposed: &str) -> bool {
+    current.to_lowercase() == proposed.to_lowercase()
+}
+
+/// Check a time zone to make sure it is a recognized IANA time zone name (like
+/// "America/Los_Angeles"), which can be parsed into a `chrono_tz::Tz`.
+///
+/// ### Arguments
+/// - `input`: The time zone name to test
+///
+/// ### Returns
+/// None if the time zone is valid, or a list containing the problem if not
+pub fn validate_time_zone(input: &str) -> Option<Vec<ValidationProblem>> {
+    match input.parse::<Tz>() {
+        Ok(_) => None,
+        Err(_) => Some(vec![ValidationProblem::new(ProblemCode::TimeZoneUnknown)]),
+    }
+}
+
+/// The largest and smallest UTC offsets (in minutes) of the `Etc/GMT` zones.
+const MAX_OFFSET_MINUTES: i32 = 14 * 60;
+const MIN_OFFSET_MINUTES: i32 = -12 * 60;
+
+/// UTC offsets (in minutes) which are not whole hours, and so have no `Etc/GMT` zone, each mapped
+/// to a zone which keeps that offset all year round.
+const FRACTIONAL_OFFSET_ZONES: [(i32, &str); 8] = [
+    (-570, "Pacific/Marquesas"),
+    (210, "Asia/Tehran"),
+    (270, "Asia/Kabul"),
+    (330, "Asia/Kolkata"),
+    (345, "Asia/Kathmandu"),
+    (390, "Asia/Yangon"),
+    (525, "Australia/Eucla"),
+    (570, "Australia/Darwin"),
+];
+
+/// Parse a raw UTC offset, like "+05:30", "-0800", "UTC+3" or "Z".
+///
+/// ### Arguments
+/// - `input`: The offset to parse
+///
+/// ### Returns
+/// The offset in minutes east of UTC, or None if the input is not an offset
+fn parse_utc_offset(input: &str) -> Option<i32> {
+    let input = input.trim();
+
+    let offset = match input.get(..3) {
+        Some(prefix)
+            if prefix.eq_ignore_ascii_case("UTC") || prefix.eq_ignore_ascii_case("GMT") =>
+        {
+            &input[3..]
+        }
+        _ if input.eq_ignore_ascii_case("Z") => "",
+        _ => input,
+    };
+
+    if offset.is_empty() {
+        return (input.len() != offset.len()).then_some(0);
+    }
+
+    let (sign, digits) = match offset.split_at(1) {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+
+    let (hours, minutes) = match digits.split_once(':') {
+        Some(parts) => parts,
+        None if digits.len() > 2 => digits.split_at(digits.len() - 2),
+        None => (digits, "00"),
+    };
+
+    if !(1..=2).contains(&hours.len())
+        || minutes.len() != 2
+        || !hours
+            .chars()
+            .chain(minutes.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if minutes >= 60 {
+        return None;
+    }
+
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Find a time zone which keeps a UTC offset all year round.
+///
+/// Whole-hour offsets map to the `Etc/GMT` zones, whose signs are inverted from the usual
+/// convention: "+05:00" (five hours *ahead* of UTC) maps to "Etc/GMT-5". The few offsets which are
+/// not whole hours map to a representative zone without daylight saving time instead.
+///
+/// ### Arguments
+/// - `minutes`: The offset in minutes east of UTC
+///
+/// ### Returns
+/// The IANA name of the time zone, or None if no zone keeps the offset
+fn offset_time_zone(minutes: i32) -> Option<String> {
+    if !(MIN_OFFSET_MINUTES..=MAX_OFFSET_MINUTES).contains(&minutes) {
+        return None;
+    }
+
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => Some(String::from("Etc/UTC")),
+        (hours, 0) => Some(format!("Etc/GMT{:+}", -hours)),
+        _ => FRACTIONAL_OFFSET_ZONES
+            .iter()
+            .find(|(offset, _)| *offset == minutes)
+            .map(|(_, zone)| String::from(*zone)),
+    }
+}
+
+/// Resolve the time zone provided by a registering player into an IANA time zone name. IANA names
+/// are always kept as they are; otherwise, clients without a full time zone database may provide
+/// a raw UTC offset (like "+05:30" or "UTC"), which is mapped to a zone keeping that offset.
+///
+/// The mapped zone never observes daylight saving time, so the offset will not follow the player
+/// through seasonal changes; they should choose their actual time zone once they can.
+///
+/// ### Arguments
+/// - `input`: The time zone provided by the player
+///
+/// ### Returns
+/// The resolved IANA name, or the unchanged input if it cannot be resolved (so that
+/// `validate_time_zone` reports the problem)
+pub fn resolve_time_zone(input: &str) -> String {
+    if input.parse::<Tz>().is_ok() {
+        return String::from(input);
+    }
+
+    parse_utc_offset(input)
+        .and_then(offset_time_zone)
+        .unwrap_or_else(|| String::from(input))
+}
+
+/// Check the pronoun chosen by a registering player.
+///
+/// Only Spanish-speaking non-binary players choose a pronoun; they are referred to neutrally
+/// unless they choose the masculine or feminine pronoun instead. Choosing the neutral pronoun is
+/// meaningless, so it is rejected. All other players' pronouns match their gender, and any pronoun
+/// they provide is ignored.
+///
+/// ### Arguments
+/// - `gender`: The player's gender
+/// - `preferred_language`: The player's preferred language
+/// - `pronoun`: The pronoun chosen by the player, if any
+///
+/// ### Returns
+/// None if the pronoun is valid (or ignored), or a list containing every problem if not
+pub fn validate_pronoun(
+    gender: &Gender,
+    preferred_language: &LanguagePreference,
+    pronoun: &Option<Gender>,
+) -> Option<Vec<ValidationProblem>> {
+    let mut problems: Vec<ValidationProblem> = vec![];
+
+    let chooses_pronoun = matches!(
+        (gender, preferred_language),
+        (Gender::Other, LanguagePreference::Spanish)
+    );
+
+    if chooses_pronoun && matches!(pronoun, Some(Gender::Other)) {
+        problems.push(ValidationProblem::new(ProblemCode::PronounInvalidChoice));
+    }
+
+    match problems.len() {
+        0 => None,
+        _ => Some(problems),
+    }
+}
+
+/// Check an avatar chosen by a player. A preset must be one of the configured presets, and an
+/// uploaded image must be stored under the configured storage prefix (uploads are not allowed at
+/// all without one). Keys which could escape the prefix, by containing `..`, are rejected.
+///
+/// ### Arguments
+/// - `avatar`: The avatar chosen by the player
+/// - `presets`: The ids of the available preset avatars
+/// - `storage_prefix`: The prefix of every uploaded avatar key, if uploads are allowed
+///
+/// ### Returns
+/// None if the avatar is valid, or a list containing the problem if not
+pub fn validate_avatar(
+    avatar: &AvatarRef,
+    presets: &[String],
+    storage_prefix: Option<&str>,
+) -> Option<Vec<ValidationProblem>> {
+    let problem = match avatar {
+        AvatarRef::Preset(id) if !presets.contains(id) => ProblemCode::AvatarPresetUnknown,
+        AvatarRef::Upload(key) => match storage_prefix {
+            Some(prefix)
+                if key.len() > prefix.len() && key.starts_with(prefix) && !key.contains("..") =>
+            {
+                return None;
+            }
+            _ => ProblemCode::AvatarUploadOutsideStorage,
+        },
+        AvatarRef::Preset(_) => return None,
+    };
+
+    Some(vec![ValidationProblem::new(problem)])
+}
+
+/// The MIME types of the images which players may upload as their avatar, with the file extension
+/// given to each.
+const AVATAR_CONTENT_TYPES: [(&str, &str); 3] = [
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+];
+
+/// Find the file extension given to an uploaded avatar image.
+///
+/// ### Arguments
+/// - `content_type`: The MIME type of the image
+///
+/// ### Returns
+/// The file extension, or None if images of this type cannot be uploaded
+pub fn avatar_extension(content_type: &str) -> Option<&'static str> {
+    AVATAR_CONTENT_TYPES
+        .iter()
+        .find(|(accepted, _)| *accepted == content_type)
+        .map(|(_, extension)| *extension)
+}
+
+/// Check an avatar image which a player is about to upload, making sure that it is in an accepted
+/// format, and that it is neither empty nor larger than the maximum size.
+///
+/// ### Arguments
+/// - `content_type`: The MIME type of the image
+/// - `content_length`: The size of the image, in bytes
+/// - `max_bytes`: The largest size allowed, in bytes
+///
+/// ### Returns
+/// None if the image may be uploaded, or a list of its problems if not
+pub fn validate_avatar_upload(
+    content_type: &str,
+    content_length: u64,
+    max_bytes: u64,
+) -> Option<Vec<ValidationProblem>> {
+    let mut problems = vec![];
+
+    if avatar_extension(content_type).is_none() {
+        problems.push(ValidationProblem::new(ProblemCode::AvatarUnsupportedType));
+    }
+
+    if content_length == 0 || content_length > max_bytes {
+        problems.push(ValidationProblem::with_params(
+            ProblemCode::AvatarSizeOutOfRange,
+            &[("min", 1), ("max", max_bytes as usize)],
+        ));
+    }
+
+    if problems.is_empty() {
+        None
+    } else {
+        Some(problems)
+    }
+}
+
+/// Check an email address which a player wants to use for their account, whether registering,
+/// correcting it before confirmation, or proposing a new one. The address must be valid, and its
+/// domain must not be disposable while `ENV.block_disposable_email` is enabled.
+///
+/// ### Arguments
+/// - `input`: The email address to be tested
+///
+/// ### Returns
+/// - `Some`: A list of problems with the input
+/// - `None`: Input is valid
+pub fn validate_email_for_account(input: &str) -> Option<Vec<ValidationProblem>> {
+    match validate_email(input) {
+        None if ENV.block_disposable_email => {
+            validate_email_domain_allowed(input).map(|problem| vec![problem])
+        }
+        problems => problems,
+    }
+}
+
 /// Check the input to make sure that all fields are valid, according to the defined rules for each
 /// input field.
 ///
@@ -236,28 +938,44 @@ pub fn validate_email(input: &str) -> Option<Vec<String>> {
 /// - `username`: The input to test for a valid username.
 /// - `password`: The input to test for a valid password.
 /// - `email`: The input to test for a valid email address.
+/// - `time_zone`: The input to test for a valid time zone.
 ///
 /// ### Returns:
 /// The unit type if all input is valid
 ///
 /// ### Errors:
-/// - `DBoError::InvalidPlayerInfo` if even a single field fails validation
-pub fn validate_all(username: &str, password: &str, email: &str) -> DBoResult<()> {
-    let username_problems = validate_username(username);
+/// - `DBoError::InvalidPlayerInfo` if even a single field fails validation, or if the email domain
+///   is disposable while `ENV.block_disposable_email` is enabled
+pub fn validate_all(username: &str, password: &str, email: &str, time_zone: &str) -> DBoResult<()> {
+    let username_problems = validate_username_choice(username, false);
     let password_problems = validate_password(password);
-    let email_problems = validate_email(email);
+    let email_problems = validate_email_for_account(email);
+    let time_zone_problems = validate_time_zone(time_zone);
 
-    if username_problems.is_none() && password_problems.is_none() && email_problems.is_none() {
+    if username_problems.is_none()
+        && password_problems.is_none()
+        && email_problems.is_none()
+        && time_zone_problems.is_none()
+    {
         Ok(())
     } else {
-        Err(DBoError::InvalidPlayerInfo(
-            PlayerInvalidFieldsResponse::new(username_problems, password_problems, email_problems),
+        Err(DBoError::invalid_player_info(
+            PlayerInvalidFieldsResponse::new(
+                username_problems,
+                password_problems,
+                email_problems,
+                time_zone_problems,
+                None,
+                None,
+            ),
         ))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::{NaiveDate, Offset, TimeZone};
+
     use super::*;
 
     #[test]
@@ -405,4 +1123,424 @@ mod tests {
             }
         }
     }
+
+    /// Collect the problem codes reported for a single input.
+    fn codes(problems: Option<Vec<ValidationProblem>>) -> Vec<ProblemCode> {
+        problems
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.code)
+            .collect()
+    }
+
+    #[test]
+    fn test_username_problem_codes() {
+        use ProblemCode::*;
+
+        let cases = vec![
+            ("abc", vec![UsernameTooShort]),
+            ("aaaaaaaaaaaaaaaaa", vec![UsernameTooLong]),
+            ("user!name", vec![UsernameIllegalCharacters]),
+            ("_alice", vec![UsernameLeadingUnderscore]),
+            ("bob__smith", vec![UsernameConsecutiveUnderscores]),
+        ];
+
+        for (username, expected) in cases {
+            assert_eq!(codes(validate_username(username)), expected, "{}", username);
+        }
+    }
+
+    #[test]
+    fn test_password_problem_codes() {
+        use ProblemCode::*;
+
+        let cases = vec![
+            ("Short1!", vec![PasswordTooShort]),
+            ("Aa1!aaaaaaaaaaaaaaaaaaaaaaaaaaaaa", vec![PasswordTooLong]),
+            ("ALLUPPERCASE1!", vec![PasswordMissingLowercase]),
+            ("alllowercase1!", vec![PasswordMissingUppercase]),
+            ("NoNumber!", vec![PasswordMissingNumber]),
+            ("MissingSymbol1", vec![PasswordMissingSymbol]),
+            ("BadSymbol1!~", vec![PasswordIllegalCharacters]),
+        ];
+
+        for (password, expected) in cases {
+            assert_eq!(codes(validate_password(password)), expected, "{}", password);
+        }
+    }
+
+    #[test]
+    fn test_email_problem_codes() {
+        use ProblemCode::*;
+
+        let cases = vec![
+            ("alice.example.com", vec![EmailSingleAt]),
+            ("@example.com", vec![EmailPrefixEmpty]),
+            ("ali ce@example.com", vec![EmailPrefixIllegalCharacters]),
+            (".alice@example.com", vec![EmailPrefixDotBoundary]),
+            ("alice..bob@example.com", vec![EmailPrefixConsecutiveDots]),
+            ("alice@", vec![EmailDomainEmpty]),
+            ("alice@exa mple.com", vec![EmailDomainIllegalCharacters]),
+            ("alice@localhost", vec![EmailDomainMissingLevels]),
+            ("alice@exam..ple.com", vec![EmailDomainConsecutiveDots]),
+            ("alice@example-.com", vec![EmailDomainHyphenBoundary]),
+            ("alice@example.c", vec![EmailTldTooShort]),
+        ];
+
+        for (email, expected) in cases {
+            assert_eq!(codes(validate_email(email)), expected, "{}", email);
+        }
+    }
+
+    #[test]
+    fn test_problem_serialization() {
+        let problems = validate_username("abc").unwrap();
+        assert_eq!(
+            serde_json::to_value(&problems[0]).unwrap(),
+            serde_json::json!({ "code": "username_too_short", "params": { "min": 6, "max": 16 } })
+        );
+
+        let problems = validate_username("_alice").unwrap();
+        assert_eq!(
+            serde_json::to_value(&problems[0]).unwrap(),
+            serde_json::json!({ "code": "username_leading_underscore" })
+        );
+    }
+
+    #[test]
+    fn test_validate_email_domain_allowed() {
+        for email in [
+            "alice@example.com",
+            "bob@gmail.com",
+            "carol@notmailinator.com",
+        ] {
+            assert!(
+                validate_email_domain_allowed(email).is_none(),
+                "Expected '{}' to be allowed",
+                email
+            );
+        }
+
+        for email in [
+            "alice@mailinator.com",
+            "bob@YOPMAIL.com",
+            "carol@eu.guerrillamail.com",
+        ] {
+            assert_eq!(
+                validate_email_domain_allowed(email).map(|p| p.code),
+                Some(ProblemCode::EmailDomainDisposable),
+                "Expected '{}' to be disposable",
+                email
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalize_allowlist_entry() {
+        assert_eq!(
+            normalize_allowlist_entry(" @Example.COM "),
+            Some(String::from("example.com"))
+        );
+        assert_eq!(
+            normalize_allowlist_entry("Tester@Example.com"),
+            Some(String::from("tester@example.com"))
+        );
+
+        for entry in ["", " @ ", "a b.com", "a.com,b.com", "a@b@c.com"] {
+            assert!(
+                normalize_allowlist_entry(entry).is_none(),
+                "Expected '{}' to be rejected",
+                entry
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_email_allowlisted() {
+        // An empty allow-list means that registration is open.
+        assert!(validate_email_allowlisted("anyone@anywhere.com", &[]).is_none());
+
+        let entries = [
+            String::from("@partner.org"),
+            String::from("Tester@Example.com"),
+        ];
+
+        for email in ["alice@partner.org", "BOB@PARTNER.ORG", "tester@example.com"] {
+            assert!(
+                validate_email_allowlisted(email, &entries).is_none(),
+                "Expected '{}' to be allowed",
+                email
+            );
+        }
+
+        for email in [
+            "someone@example.com",
+            "alice@sub.partner.org",
+            "alice@partner.org.evil.com",
+        ] {
+            assert_eq!(
+                validate_email_allowlisted(email, &entries).map(|p| p[0].code),
+                Some(ProblemCode::EmailNotAllowlisted),
+                "Expected '{}' to be rejected",
+                email
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_same_identifier() {
+        assert!(is_same_identifier(
+            "player@example.com",
+            "player@example.com"
+        ));
+        assert!(is_same_identifier(
+            "Player@Example.com",
+            "pLAYER@example.COM"
+        ));
+
+        assert!(!is_same_identifier(
+            "player@example.com",
+            "player2@example.com"
+        ));
+        assert!(!is_same_identifier(
+            "player@example.com",
+            "player@example.org"
+        ));
+
+        assert!(is_same_identifier("d_bo_fan", "d_bo_fan"));
+        assert!(is_same_identifier("D_Bo_Fan", "d_bo_FAN"));
+
+        assert!(!is_same_identifier("d_bo_fan", "d_bo_fan2"));
+        assert!(!is_same_identifier("d_bo_fan", "dbo_fan"));
+    }
+
+    #[test]
+    fn test_username_blocklist() {
+        let blocklist = UsernameBlocklist::parse(DEFAULT_USERNAME_BLOCKLIST);
+
+        for name in [
+            "ShitHead99",
+            "sh1t_happens",
+            "f_u_c_k_off",
+            "big_ass",
+            "tu_puta_madre",
+        ] {
+            assert!(!blocklist.allows(name), "Expected '{}' to be blocked", name);
+        }
+
+        for name in [
+            "classic_bass",
+            "grass_assassin",
+            "computadora",
+            "title_holder",
+        ] {
+            assert!(blocklist.allows(name), "Expected '{}' to be allowed", name);
+        }
+
+        let custom = UsernameBlocklist::parse("# comment\n\nbanana\n=kiwi\n");
+        assert!(!custom.allows("b4n4n4_split"));
+        assert!(!custom.allows("kiwi_bird"));
+        assert!(custom.allows("kiwifruit"));
+    }
+
+    #[test]
+    fn test_username_reserved() {
+        for name in [
+            "admin_bob",
+            "Dbo_Support",
+            "d_bo",
+            "m0derator",
+            "the_official",
+        ] {
+            assert!(
+                username_reserved(name),
+                "Expected '{}' to be reserved",
+                name
+            );
+        }
+
+        for name in ["badminton", "modern_art", "supporter", "rooted_tree"] {
+            assert!(
+                !username_reserved(name),
+                "Expected '{}' to be allowed",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_time_zone() {
+        for time_zone in ["America/Los_Angeles", "Europe/Madrid", "UTC"] {
+            assert!(
+                validate_time_zone(time_zone).is_none(),
+                "Expected '{}' to be valid",
+                time_zone
+            );
+        }
+
+        for time_zone in ["", "america/los angeles", "Mars/Olympus_Mons"] {
+            assert_eq!(
+                validate_time_zone(time_zone),
+                Some(vec![ValidationProblem::new(ProblemCode::TimeZoneUnknown)]),
+                "Expected '{}' to be invalid",
+                time_zone
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_time_zone() {
+        for time_zone in ["America/Los_Angeles", "Europe/Madrid", "UTC", "Etc/GMT+8"] {
+            assert_eq!(resolve_time_zone(time_zone), time_zone);
+        }
+
+        assert_eq!(resolve_time_zone("+05:00"), "Etc/GMT-5");
+        assert_eq!(resolve_time_zone("UTC-8"), "Etc/GMT+8");
+        assert_eq!(resolve_time_zone("z"), "Etc/UTC");
+
+        // Every offset resolves to a zone which keeps it all year round.
+        let winter = NaiveDate::from_ymd_opt(2025, 1, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let summer = NaiveDate::from_ymd_opt(2025, 7, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        for (offset, minutes) in [
+            ("+05:30", 330),
+            ("-08:00", -480),
+            ("UTC+3", 180),
+            ("gmt-0930", -570),
+            ("+0545", 345),
+            ("+14", 840),
+            ("-12:00", -720),
+            ("Z", 0),
+        ] {
+            let tz: Tz = resolve_time_zone(offset).parse().unwrap();
+            for instant in [winter, summer] {
+                assert_eq!(
+                    tz.offset_from_utc_datetime(&instant)
+                        .fix()
+                        .local_minus_utc(),
+                    minutes * 60,
+                    "Expected '{}' to keep its offset",
+                    offset
+                );
+            }
+        }
+
+        for invalid in [
+            "",
+            "05:30",
+            "+15:00",
+            "+05:20",
+            "+5:3",
+            "+05:60",
+            "UTC+",
+            "Mars/Olympus_Mons",
+        ] {
+            assert_eq!(resolve_time_zone(invalid), invalid);
+            assert!(validate_time_zone(&resolve_time_zone(invalid)).is_some());
+        }
+    }
+
+    #[test]
+    fn test_validate_pronoun() {
+        use Gender::{Female, Male, Other};
+        use LanguagePreference::{English, Spanish};
+
+        let invalid_choice = Some(vec![ValidationProblem::new(
+            ProblemCode::PronounInvalidChoice,
+        )]);
+
+        // Spanish-speaking non-binary players may keep the neutral pronoun, or choose another one.
+        assert_eq!(validate_pronoun(&Other, &Spanish, &None), None);
+        assert_eq!(validate_pronoun(&Other, &Spanish, &Some(Male)), None);
+        assert_eq!(validate_pronoun(&Other, &Spanish, &Some(Female)), None);
+        assert_eq!(
+            validate_pronoun(&Other, &Spanish, &Some(Other)),
+            invalid_choice
+        );
+
+        // Every other player's pronoun is ignored, whatever it is.
+        for (gender, language) in [
+            (Male, English),
+            (Male, Spanish),
+            (Female, English),
+            (Female, Spanish),
+            (Other, English),
+        ] {
+            for pronoun in [None, Some(Male), Some(Female), Some(Other)] {
+                assert_eq!(validate_pronoun(&gender, &language, &pronoun), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_avatar() {
+        let presets = vec![String::from("fox"), String::from("owl")];
+        let prefix = Some("avatars/");
+
+        let preset = |id: &str| AvatarRef::Preset(String::from(id));
+        let upload = |key: &str| AvatarRef::Upload(String::from(key));
+
+        assert!(validate_avatar(&preset("fox"), &presets, prefix).is_none());
+        assert!(validate_avatar(&preset("owl"), &presets, None).is_none());
+        assert!(validate_avatar(&upload("avatars/abc.png"), &presets, prefix).is_none());
+
+        let unknown_preset = Some(vec![ValidationProblem::new(
+            ProblemCode::AvatarPresetUnknown,
+        )]);
+        assert_eq!(
+            validate_avatar(&preset("cat"), &presets, prefix),
+            unknown_preset
+        );
+        assert_eq!(validate_avatar(&preset("fox"), &[], prefix), unknown_preset);
+
+        let outside = Some(vec![ValidationProblem::new(
+            ProblemCode::AvatarUploadOutsideStorage,
+        )]);
+        for key in [
+            "elsewhere/abc.png",
+            "avatars/",
+            "avatars/../secret.png",
+            "https://evil.example/avatars/abc.png",
+        ] {
+            assert_eq!(validate_avatar(&upload(key), &presets, prefix), outside);
+        }
+        assert_eq!(
+            validate_avatar(&upload("avatars/abc.png"), &presets, None),
+            outside
+        );
+    }
+
+    #[test]
+    fn test_validate_avatar_upload() {
+        assert_eq!(avatar_extension("image/png"), Some("png"));
+        assert_eq!(avatar_extension("image/jpeg"), Some("jpg"));
+        assert_eq!(avatar_extension("image/svg+xml"), None);
+
+        assert!(validate_avatar_upload("image/webp", 1, 1024).is_none());
+        assert!(validate_avatar_upload("image/png", 1024, 1024).is_none());
+
+        let unsupported = ValidationProblem::new(ProblemCode::AvatarUnsupportedType);
+        let size = ValidationProblem::with_params(
+            ProblemCode::AvatarSizeOutOfRange,
+            &[("min", 1), ("max", 1024)],
+        );
+
+        assert_eq!(
+            validate_avatar_upload("text/html", 10, 1024),
+            Some(vec![unsupported.clone()])
+        );
+        assert_eq!(
+            validate_avatar_upload("image/png", 0, 1024),
+            Some(vec![size.clone()])
+        );
+        assert_eq!(
+            validate_avatar_upload("image/gif", 1025, 1024),
+            Some(vec![unsupported, size])
+        );
+    }
 }
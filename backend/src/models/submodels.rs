@@ -1,6 +1,9 @@
 //! This module contains models that are stored as fields of greater documents within the database.
 //! These can either be enum values **or** they can be entire structs.
 
+use std::fmt::{self, Display, Formatter};
+
+use bson::{Document, doc};
 use serde::{Deserialize, Serialize};
 
 // ///////////////// //
@@ -36,6 +39,31 @@ pub enum LanguagePreference {
     Spanish,
 }
 
+/// The way in which a player proves their identity. Sensitive operations (like changing an email
+/// address) re-authenticate the player according to this method.
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+pub enum AuthMethod {
+    /// The player logs in with a password, which is verified again for sensitive operations.
+    #[default]
+    #[serde(rename = "pw")]
+    Password,
+    /// The player logs in through an external identity provider, and has no password.
+    #[serde(rename = "ext")]
+    External,
+}
+
+/// A reference to the image a player has chosen as their avatar. Only the reference is stored;
+/// the images themselves are served from elsewhere.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AvatarRef {
+    /// One of the preset avatars offered by the application, by its id.
+    #[serde(rename = "preset")]
+    Preset(String),
+    /// An image uploaded by the player, by its key within the configured avatar storage.
+    #[serde(rename = "upload")]
+    Upload(String),
+}
+
 /// Keeps track of a player's gameplay statistics.
 #[derive(Clone, Deserialize, Serialize)]
 pub struct PlayerStats {
@@ -58,6 +86,66 @@ impl PlayerStats {
             dropouts: 0,
         }
     }
+
+    /// Combine the stats of two accounts belonging to the same player, summing each field. The sums
+    /// saturate rather than overflowing.
+    ///
+    /// ### Arguments
+    /// - `other`: The stats to add to these ones
+    pub fn combined(&self, other: &PlayerStats) -> Self {
+        Self {
+            wins: self.wins.saturating_add(other.wins),
+            losses: self.losses.saturating_add(other.losses),
+            dropouts: self.dropouts.saturating_add(other.dropouts),
+        }
+    }
+
+    /// Return a filter matching the player documents whose stats rank strictly higher than these
+    /// ones. Players are ranked by their wins, and players with the same number of wins are ranked
+    /// by their losses (fewer is better). Players with the same wins and losses share a rank.
+    pub fn outranking_filter(&self) -> Document {
+        doc! { "$or": [
+            { "stats.wins": { "$gt": self.wins as i64 } },
+            { "stats.wins": self.wins as i64, "stats.losses": { "$lt": self.losses as i64 } },
+        ] }
+    }
+}
+
+// ////////////////////// //
+// OUTBOX EMAIL SUBMODELS //
+// ////////////////////// //
+
+/// The delivery status of an email in the outbox.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum OutboxStatus {
+    /// The email has yet to be sent, and will be attempted again.
+    #[serde(rename = "pending")]
+    Pending,
+    /// The email has been sent.
+    #[serde(rename = "sent")]
+    Sent,
+    /// The email failed to send too many times, and will not be attempted again.
+    #[serde(rename = "failed")]
+    Failed,
+    /// The email was permanently rejected by the mail server, and will not be attempted again. Its
+    /// recipients are added to the suppression list.
+    #[serde(rename = "rejected")]
+    Rejected,
+    /// The email was not sent, because one of its recipients is on the suppression list.
+    #[serde(rename = "suppressed")]
+    Suppressed,
+}
+
+impl Display for OutboxStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pending => "pending",
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+            Self::Rejected => "rejected",
+            Self::Suppressed => "suppressed",
+        })
+    }
 }
 
 // //////////////////// //
@@ -65,7 +153,7 @@ impl PlayerStats {
 // //////////////////// //
 
 /// The type of operation that an Undo Token can be used for
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
 pub enum UndoTokenType {
     /// The undo token is used to reset a password without further verification
     #[serde(rename = "pw")]
@@ -73,13 +161,112 @@ pub enum UndoTokenType {
     /// The undo token is used to undo a pending email change
     #[serde(rename = "em")]
     Email,
+    /// The undo token is used to cancel a scheduled account deletion
+    #[serde(rename = "dl")]
+    Deletion,
+    /// The undo token is used to reset a forgotten password, upon the player's request
+    #[serde(rename = "rs")]
+    PasswordReset,
 }
 
-impl ToString for UndoTokenType {
-    fn to_string(&self) -> String {
-        String::from(match self {
+impl Display for UndoTokenType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             Self::Password => "pw",
             Self::Email => "em",
+            Self::Deletion => "dl",
+            Self::PasswordReset => "rs",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combined_stats() {
+        let target = PlayerStats {
+            wins: 3,
+            losses: 5,
+            dropouts: 1,
+        };
+        let source = PlayerStats {
+            wins: 2,
+            losses: 0,
+            dropouts: u64::MAX,
+        };
+
+        let combined = target.combined(&source);
+        assert_eq!(combined.wins, 5);
+        assert_eq!(combined.losses, 5);
+        assert_eq!(combined.dropouts, u64::MAX);
+
+        let unchanged = target.combined(&PlayerStats::default());
+        assert_eq!(
+            (unchanged.wins, unchanged.losses, unchanged.dropouts),
+            (3, 5, 1)
+        );
+    }
+
+    /// Evaluate the subset of MongoDB query operators used by `outranking_filter` against a
+    /// player document, so that the filter itself can be checked without a database.
+    fn matches(filter: &Document, player: &Document) -> bool {
+        filter.iter().all(|(key, condition)| {
+            if key == "$or" {
+                return condition
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .any(|branch| matches(branch.as_document().unwrap(), player));
+            }
+            let (parent, field) = key.split_once('.').unwrap();
+            let value = player.get_document(parent).unwrap().get_i64(field).unwrap();
+            match condition.as_document() {
+                Some(ops) => ops.iter().all(|(op, operand)| match op.as_str() {
+                    "$gt" => value > operand.as_i64().unwrap(),
+                    "$lt" => value < operand.as_i64().unwrap(),
+                    _ => panic!("unsupported operator {op}"),
+                }),
+                None => value == condition.as_i64().unwrap(),
+            }
         })
     }
+
+    #[test]
+    fn test_rank_order() {
+        let stats = |wins, losses| PlayerStats {
+            wins,
+            losses,
+            dropouts: 0,
+        };
+        let players = [
+            stats(10, 4),
+            stats(10, 2),
+            stats(7, 0),
+            stats(10, 4),
+            stats(0, 0),
+        ];
+        let documents: Vec<Document> = players
+            .iter()
+            .map(|p| {
+                doc! { "stats": {
+                    "wins": p.wins as i64,
+                    "losses": p.losses as i64,
+                    "dropouts": p.dropouts as i64,
+                } }
+            })
+            .collect();
+
+        // A player's rank is one more than the number of players matched by their filter.
+        let ranks: Vec<usize> = players
+            .iter()
+            .map(|p| {
+                let filter = p.outranking_filter();
+                documents.iter().filter(|d| matches(&filter, d)).count() + 1
+            })
+            .collect();
+
+        assert_eq!(ranks, vec![2, 1, 4, 2, 5]);
+    }
 }
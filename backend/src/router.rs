@@ -1,20 +1,58 @@
-//! This module will handle the creation of the HTTP router, as well as configure CORS settings.
+//! This module will handle the creation of the HTTP router, as well as configure CORS settings and
+//! the security headers sent with every response.
+
+use std::time::Duration;
 
 use axum::{
     Router,
-    routing::{post, put},
+    http::{
+        HeaderName, HeaderValue,
+        header::{
+            CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+            X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+        },
+    },
+    middleware::from_fn,
+    routing::{get, post, put},
+};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{Any, CorsLayer},
+    set_header::SetResponseHeaderLayer,
+    timeout::TimeoutLayer,
 };
-use tower_http::cors::{Any, CorsLayer};
 
 use crate::{
-    adapters::repositories::Repositories,
-    handlers::player_handlers::{
-        handle_player_account_confirmation, handle_player_account_rejection,
-        handle_player_deletion, handle_player_login, handle_player_password_change,
-        handle_player_proposed_email_change, handle_player_proposed_email_confirmation,
-        handle_player_refresh, handle_player_registration, handle_player_username_change,
-        handle_resend_registration_email,
+    adapters::{metrics::track_request_latency, mongo::reject_while_database_unavailable},
+    config::environment::ENV,
+    handlers::{
+        admin_handlers::{
+            handle_admin_allowlist, handle_admin_allowlist_addition,
+            handle_admin_allowlist_removal, handle_admin_counter_history,
+            handle_admin_player_merge, handle_admin_player_record, handle_admin_player_search,
+            handle_admin_registration_status, handle_admin_registration_toggle,
+            handle_admin_season_reset, handle_admin_stuck_emails, handle_admin_time_zone_repair,
+        },
+        meta_handlers::{
+            handle_health, handle_metrics, handle_openapi_document, handle_root,
+            handle_time_zone_list,
+        },
+        player_handlers::{
+            handle_avatar_upload_url, handle_get_current_player, handle_password_reset,
+            handle_password_reset_request, handle_player_account_confirmation,
+            handle_player_account_rejection, handle_player_avatar_change, handle_player_deletion,
+            handle_player_deletion_cancellation, handle_player_login, handle_player_logout,
+            handle_player_logout_all, handle_player_password_change,
+            handle_player_proposed_email_change, handle_player_proposed_email_confirmation,
+            handle_player_rank, handle_player_refresh, handle_player_registration,
+            handle_player_season_history, handle_player_token_refresh,
+            handle_player_username_change, handle_public_player_batch,
+            handle_registration_validation, handle_resend_registration_email,
+            handle_unconfirmed_email_correction, handle_undo_token_redemption,
+            handle_username_recovery,
+        },
     },
+    state::AppState,
 };
 
 /// Return the CORS configuration for the application.
@@ -26,9 +64,49 @@ fn cors() -> CorsLayer {
         .allow_headers(Any)
 }
 
+/// Return a layer setting a response header, unless the handler has already set it.
+///
+/// ### Arguments
+/// - `name`: The name of the header
+/// - `value`: The value of the header
+///
+/// ### Panics
+/// If the value is not a valid header value.
+fn security_header(name: HeaderName, value: &str) -> SetResponseHeaderLayer<HeaderValue> {
+    let value = HeaderValue::from_str(value)
+        .unwrap_or_else(|_| panic!("Invalid value for the {} header: {}", name, value));
+
+    SetResponseHeaderLayer::if_not_present(name, value)
+}
+
+/// Apply the standard security headers to every response of a router. The
+/// `Strict-Transport-Security` header is only sent when the application is served over HTTPS.
+///
+/// ### Arguments
+/// - `router`: The router to apply the headers to
+fn with_security_headers(router: Router<AppState>) -> Router<AppState> {
+    let router = router
+        .layer(security_header(X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .layer(security_header(X_FRAME_OPTIONS, "DENY"))
+        .layer(security_header(REFERRER_POLICY, "no-referrer"))
+        .layer(security_header(
+            CONTENT_SECURITY_POLICY,
+            &ENV.content_security_policy,
+        ));
+
+    if ENV.secure() {
+        router.layer(security_header(
+            STRICT_TRANSPORT_SECURITY,
+            &format!("max-age={}; includeSubDomains", ENV.hsts_max_age),
+        ))
+    } else {
+        router
+    }
+}
+
 /// Return the HTTP router which will handle all incoming requests.
-pub fn router() -> Router<Repositories> {
-    Router::new()
+pub fn router() -> Router<AppState> {
+    let router = Router::new()
         .route(
             "/players",
             post(handle_player_registration).delete(handle_player_deletion),
@@ -39,8 +117,36 @@ pub fn router() -> Router<Repositories> {
                 .delete(handle_player_account_rejection)
                 .put(handle_resend_registration_email),
         )
+        .route(
+            "/players/{player_id}/correct-email/{token_id}",
+            put(handle_unconfirmed_email_correction),
+        )
+        .route(
+            "/players/validate-registration",
+            post(handle_registration_validation),
+        )
         .route("/players/login", post(handle_player_login))
         .route("/players/refresh", post(handle_player_refresh))
+        .route("/players/refresh/logout", post(handle_player_logout))
+        .route("/players/logout-all", post(handle_player_logout_all))
+        .route("/players/refresh-token", post(handle_player_token_refresh))
+        .route("/players/me", get(handle_get_current_player))
+        .route("/players/me/rank", get(handle_player_rank))
+        .route("/players/me/seasons", get(handle_player_season_history))
+        .route(
+            "/players/me/avatar/upload-url",
+            post(handle_avatar_upload_url),
+        )
+        .route("/players/public-batch", post(handle_public_player_batch))
+        .route("/players/recover-username", post(handle_username_recovery))
+        .route(
+            "/players/reset-password",
+            post(handle_password_reset_request),
+        )
+        .route(
+            "/players/{player_id}/reset-password/{token_id}",
+            put(handle_password_reset),
+        )
         .route(
             "/players/change/password",
             put(handle_player_password_change),
@@ -49,6 +155,7 @@ pub fn router() -> Router<Repositories> {
             "/players/change/username",
             put(handle_player_username_change),
         )
+        .route("/players/change/avatar", put(handle_player_avatar_change))
         .route(
             "/players/change/proposed-email",
             put(handle_player_proposed_email_change),
@@ -57,5 +164,58 @@ pub fn router() -> Router<Repositories> {
             "/players/{player_id}/confirm-proposed-email/{token_id}",
             put(handle_player_proposed_email_confirmation),
         )
+        .route(
+            "/players/{player_id}/cancel-deletion/{token_id}",
+            put(handle_player_deletion_cancellation),
+        )
+        .route(
+            "/players/{player_id}/undo/{token_id}",
+            post(handle_undo_token_redemption),
+        )
+        .route("/admin/players", get(handle_admin_player_search))
+        .route("/admin/players/merge", post(handle_admin_player_merge))
+        .route(
+            "/admin/players/{player_id}",
+            get(handle_admin_player_record),
+        )
+        .route("/admin/season/reset", post(handle_admin_season_reset))
+        .route("/admin/emails/stuck", get(handle_admin_stuck_emails))
+        .route("/admin/stats/history", get(handle_admin_counter_history))
+        .route(
+            "/admin/maintenance/time-zones",
+            post(handle_admin_time_zone_repair),
+        )
+        .route(
+            "/admin/registration",
+            get(handle_admin_registration_status).put(handle_admin_registration_toggle),
+        )
+        .route("/admin/registration-allowlist", get(handle_admin_allowlist))
+        .route(
+            "/admin/registration-allowlist/{entry}",
+            put(handle_admin_allowlist_addition).delete(handle_admin_allowlist_removal),
+        )
+        // While the database is unreachable, the routes above are answered with 503 SERVICE
+        // UNAVAILABLE. The routes below do not depend on the database, so they are added after
+        // this layer, and remain available.
+        .route_layer(from_fn(reject_while_database_unavailable))
+        .route("/", get(handle_root))
+        .route("/openapi.yaml", get(handle_openapi_document))
+        .route("/health", get(handle_health))
+        .route("/meta/timezones", get(handle_time_zone_list))
+        .route("/metrics", get(handle_metrics))
+        // The latency of every matched route is recorded, labelled by its path template. Requests
+        // abandoned by the timeout below are not recorded.
+        .route_layer(from_fn(track_request_latency))
+        // Requests which hang on a slow downstream (such as the database or the SMTP server) are
+        // abandoned with 408 REQUEST TIMEOUT. Long-lived routes (such as WebSocket upgrades) must
+        // be merged in after this layer, so that they are not subject to it.
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            ENV.request_timeout_secs,
+        )))
         .layer(cors())
+        // Responses are compressed when the client accepts it. Bodies smaller than 32 bytes (such
+        // as the empty bodies of 204 responses) are never compressed.
+        .layer(CompressionLayer::new());
+
+    with_security_headers(router)
 }
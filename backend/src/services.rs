@@ -5,5 +5,8 @@
 //! HTTP request - and map it to an appropriate DBoError, which can be mapped to an HTTP response
 //! by the handlers.
 
+pub mod admin_service;
+pub mod maintenance_service;
+pub mod meta_service;
 pub mod player_service;
 pub mod types;
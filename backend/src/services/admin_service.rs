@@ -0,0 +1,259 @@
+//! This module handles all services which are reserved for **admin** players (support staff).
+//!
+//! These services do not check whether the requesting player is an admin; that is handled by the
+//! `AdminPlayer` extractor before the service is ever called.
+
+use bson::DateTime;
+
+use crate::{
+    adapters::repositories::{
+        Repository,
+        counter_id::CounterId,
+        search::{Pagination, PlayerSearchCriteria},
+    },
+    config::environment::ENV,
+    errors::{DBoError, DBoResult},
+    handlers::responses::{
+        AdminPlayerResponse, AllowlistResponse, CounterHistoryResponse, PlayerSearchResponse,
+        SeasonEndResponse, StuckEmailListResponse, TimeZoneRepairResponse,
+    },
+    models::{
+        AllowlistEntry, Collectible, Counter, CounterSnapshot, OutboxEmail, Player, SeasonArchive,
+    },
+};
+
+/// The maximum number of stuck emails returned for inspection.
+const STUCK_EMAIL_LIMIT: i64 = 100;
+
+/// The maximum number of snapshots returned within the history of a counter.
+const COUNTER_HISTORY_LIMIT: i64 = 1000;
+
+pub struct AdminService {}
+
+impl AdminService {
+    /// Search for player accounts whose username or email address begins with a query, optionally
+    /// filtering them by confirmation status. The results are returned one page at a time.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `criteria`: Describes which players should be included
+    /// - `pagination`: Describes which page of results should be returned
+    ///
+    /// ### Returns
+    /// The requested page of results, alongside the total number of matching players
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    pub async fn search_players(
+        players: &Repository<Player>,
+        criteria: &PlayerSearchCriteria,
+        pagination: &Pagination,
+    ) -> DBoResult<PlayerSearchResponse> {
+        let (page, total) = players.search(criteria, pagination).await?;
+
+        Ok(PlayerSearchResponse::new(&page, total))
+    }
+
+    /// Read the full record of a single player account, for support staff diagnosing a problem
+    /// with it.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `player_id`: The unique identifier of the player
+    ///
+    /// ### Returns
+    /// The player's record, without any password hashes or token secrets
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player cannot be found
+    /// - `AdapterError` if the database query fails
+    pub async fn player_record(
+        players: &Repository<Player>,
+        player_id: &str,
+    ) -> DBoResult<AdminPlayerResponse> {
+        match players.find_by_id(player_id).await? {
+            Some(player) => Ok(AdminPlayerResponse::from(&player)),
+            None => Err(DBoError::missing_document(Player::collection_name())),
+        }
+    }
+
+    /// Merge a duplicate player account into another account belonging to the same player. The
+    /// target account receives the summed stats of both accounts, and the source account is
+    /// deleted. All of the source account's sessions are invalidated by deleting its refresh
+    /// tokens; its access tokens stop working as soon as the account no longer exists. The source
+    /// account's archived seasons are moved to the target account. Everything happens within a
+    /// single transaction (see `Repository::merge_into`).
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `season_archives`: The Season Archive repository
+    /// - `source_id`: The unique identifier of the duplicate account, which will be deleted
+    /// - `target_id`: The unique identifier of the account to keep
+    ///
+    /// ### Errors
+    /// - `RelationalConflict` if the source and target are the same account
+    /// - `MissingDocument` if either account cannot be found
+    /// - `AdapterError` if a database query fails
+    pub async fn merge_accounts(
+        players: &Repository<Player>,
+        season_archives: &Repository<SeasonArchive>,
+        source_id: &str,
+        target_id: &str,
+    ) -> DBoResult<()> {
+        if source_id == target_id {
+            return Err(DBoError::RelationalConflict);
+        }
+
+        // TODO: Once games are stored, the source's player id should also be replaced by the
+        // target's within any active games, inside the same transaction.
+        players
+            .merge_into(season_archives, source_id, target_id)
+            .await
+    }
+
+    /// End the current competitive season. Every confirmed player's stats are archived, so that
+    /// they can view their past seasons, and then reset to zero.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `season_archives`: The Season Archive repository
+    /// - `counters`: The Counter repository
+    ///
+    /// ### Returns
+    /// The number of the season which ended, and how many players' stats were archived
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    pub async fn end_season(
+        players: &Repository<Player>,
+        season_archives: &Repository<SeasonArchive>,
+        counters: &Repository<Counter>,
+    ) -> DBoResult<SeasonEndResponse> {
+        let (season, archived_players) = players
+            .end_season(season_archives, counters, &DateTime::now())
+            .await?;
+
+        Ok(SeasonEndResponse::new(season, archived_players))
+    }
+
+    /// Reset every player's time zone which cannot be parsed to `ENV.fallback_time_zone`, so that
+    /// emails containing timestamps can be sent to them again.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    ///
+    /// ### Returns
+    /// The fallback time zone, and how many players' time zones were repaired
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    pub async fn repair_time_zones(
+        players: &Repository<Player>,
+    ) -> DBoResult<TimeZoneRepairResponse> {
+        let repaired = players
+            .fix_invalid_time_zones(&ENV.fallback_time_zone)
+            .await?;
+
+        Ok(TimeZoneRepairResponse::new(
+            &ENV.fallback_time_zone,
+            repaired,
+        ))
+    }
+
+    /// List the emails in the outbox which have failed to send at least once, whether they are
+    /// still being retried or have been given up on.
+    ///
+    /// ### Arguments
+    /// - `email_outbox`: The Email Outbox repository
+    ///
+    /// ### Returns
+    /// Up to 100 stuck emails, from the oldest
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the database query fails
+    pub async fn stuck_emails(
+        email_outbox: &Repository<OutboxEmail>,
+    ) -> DBoResult<StuckEmailListResponse> {
+        let emails = email_outbox.find_stuck(STUCK_EMAIL_LIMIT).await?;
+
+        Ok(StuckEmailListResponse::new(&emails))
+    }
+
+    /// Read the recorded history of a single counter, optionally within a range of time. The
+    /// snapshots are recorded periodically by the maintenance tasks.
+    ///
+    /// ### Arguments
+    /// - `counter_snapshots`: The Counter Snapshot repository
+    /// - `counter`: The counter whose history is read
+    /// - `from`: If provided, only snapshots taken at or after this time are included
+    /// - `to`: If provided, only snapshots taken at or before this time are included
+    ///
+    /// ### Returns
+    /// The newest 1000 snapshots of the counter within the range, from the oldest
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the database query fails
+    pub async fn counter_history(
+        counter_snapshots: &Repository<CounterSnapshot>,
+        counter: &CounterId,
+        from: Option<&DateTime>,
+        to: Option<&DateTime>,
+    ) -> DBoResult<CounterHistoryResponse> {
+        let counter = counter.to_string();
+        let snapshots = counter_snapshots
+            .find_history(&counter, from, to, COUNTER_HISTORY_LIMIT)
+            .await?;
+
+        Ok(CounterHistoryResponse::new(&counter, &snapshots))
+    }
+
+    /// List the entries of the registration allow-list, both those configured by the environment
+    /// and those added at runtime.
+    ///
+    /// ### Arguments
+    /// - `allowlist`: The registration allow-list repository
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the database query fails
+    pub async fn allowlist(allowlist: &Repository<AllowlistEntry>) -> DBoResult<AllowlistResponse> {
+        let added = allowlist.all_entries().await?;
+
+        Ok(AllowlistResponse::new(&ENV.registration_allowlist, added))
+    }
+
+    /// Add an entry to the registration allow-list. Once the list has any entries, only matching
+    /// email addresses may register.
+    ///
+    /// ### Arguments
+    /// - `allowlist`: The registration allow-list repository
+    /// - `entry`: The normalized email address or domain to add
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the database query fails
+    pub async fn add_to_allowlist(
+        allowlist: &Repository<AllowlistEntry>,
+        entry: &str,
+    ) -> DBoResult<()> {
+        allowlist.insert(&AllowlistEntry::new(entry)).await
+    }
+
+    /// Remove an entry from the registration allow-list. Entries configured by the environment
+    /// cannot be removed.
+    ///
+    /// ### Arguments
+    /// - `allowlist`: The registration allow-list repository
+    /// - `entry`: The normalized email address or domain to remove
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the entry was not added at runtime
+    /// - `AdapterError` if the database query fails
+    pub async fn remove_from_allowlist(
+        allowlist: &Repository<AllowlistEntry>,
+        entry: &str,
+    ) -> DBoResult<()> {
+        match allowlist.delete(entry).await? {
+            Some(_) => Ok(()),
+            None => Err(DBoError::missing_document(AllowlistEntry::collection_name())),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! This module handles the **maintenance tasks** of the application. These are not triggered by
+//! HTTP requests; instead, they are run periodically in the background.
+
+use bson::DateTime;
+use chrono::Duration as ChronoDuration;
+
+use crate::{
+    adapters::repositories::{Repository, counter_id::CounterId},
+    config::environment::ENV,
+    errors::DBoResult,
+    models::{
+        Counter, CounterSnapshot, Identifiable, Player, RefreshToken, SeasonArchive, UndoToken,
+        snapshot_bucket,
+    },
+};
+
+pub struct MaintenanceService {}
+
+impl MaintenanceService {
+    /// Permanently delete all player accounts whose deletion grace period has ended. Alongside each
+    /// account, delete its refresh tokens, undo tokens and season archives, and increment the
+    /// counter.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `refresh_tokens`: The Refresh Token repository
+    /// - `undo_tokens`: The Undo Token repository
+    /// - `season_archives`: The Season Archive repository
+    /// - `counters`: The Counter repository
+    ///
+    /// ### Returns
+    /// The number of player accounts deleted
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    pub async fn purge_deleted_accounts(
+        players: &Repository<Player>,
+        refresh_tokens: &Repository<RefreshToken>,
+        undo_tokens: &Repository<UndoToken>,
+        season_archives: &Repository<SeasonArchive>,
+        counters: &Repository<Counter>,
+    ) -> DBoResult<u64> {
+        let mut purged = 0;
+
+        for player in players.find_due_for_deletion().await? {
+            if !players.delete_if_due(player.id()).await? {
+                continue;
+            }
+
+            refresh_tokens.delete_player_tokens(player.id()).await?;
+            undo_tokens.delete_player_tokens(player.id()).await?;
+            season_archives.delete_player_archives(player.id()).await?;
+            counters
+                .increment_counter(CounterId::AccountsDeleted)
+                .await?;
+
+            purged += 1;
+        }
+
+        Ok(purged)
+    }
+
+    /// Record the current count of every counter as a snapshot, unless the counters have already
+    /// been snapshotted within the current interval of `ENV.counter_snapshot_interval_hours` hours.
+    /// Intervals are counted from the Unix epoch, and each counter is snapshotted at most once per
+    /// interval, even when several instances run the maintenance tasks at once.
+    ///
+    /// ### Arguments
+    /// - `counters`: The Counter repository
+    /// - `counter_snapshots`: The Counter Snapshot repository
+    /// - `now`: The time at which the snapshots are taken
+    ///
+    /// ### Returns
+    /// The number of counters snapshotted; 0 if no snapshot was due
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    pub async fn snapshot_counters(
+        counters: &Repository<Counter>,
+        counter_snapshots: &Repository<CounterSnapshot>,
+        now: &DateTime,
+    ) -> DBoResult<u64> {
+        let latest = counter_snapshots.latest_taken().await?;
+        let interval = ChronoDuration::hours(ENV.counter_snapshot_interval_hours);
+
+        if !snapshot_due(latest.as_ref(), now, interval) {
+            return Ok(0);
+        }
+
+        let bucket = snapshot_bucket(now, interval);
+        let snapshots: Vec<CounterSnapshot> = counters
+            .all_counters()
+            .await?
+            .iter()
+            .map(|counter| CounterSnapshot::new(counter, now, bucket))
+            .collect();
+
+        counter_snapshots.record(&snapshots).await
+    }
+}
+
+/// Determine whether the counters should be snapshotted again; that is, whether the latest
+/// snapshot was taken during an earlier interval.
+///
+/// ### Arguments
+/// - `latest`: The time at which the latest snapshot was taken, if any has been
+/// - `now`: The current time
+/// - `interval`: The time which must pass between snapshots
+fn snapshot_due(latest: Option<&DateTime>, now: &DateTime, interval: ChronoDuration) -> bool {
+    match latest {
+        Some(latest) => snapshot_bucket(latest, interval) < snapshot_bucket(now, interval),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_due() {
+        let day = ChronoDuration::days(1);
+        let at =
+            |d, h, m| DateTime::from_chrono(Utc.with_ymd_and_hms(2025, 1, d, h, m, 0).unwrap());
+        let now = at(2, 1, 0);
+
+        assert!(snapshot_due(None, &now, day));
+        // Only two hours have passed, but the latest snapshot was taken on the previous day.
+        assert!(snapshot_due(Some(&at(1, 23, 0)), &now, day));
+        assert!(!snapshot_due(Some(&at(2, 0, 30)), &now, day));
+        assert!(!snapshot_due(Some(&now), &now, day));
+
+        assert_eq!(
+            snapshot_bucket(&at(2, 0, 0), day),
+            snapshot_bucket(&at(2, 23, 59), day)
+        );
+        assert_eq!(
+            snapshot_bucket(&at(2, 0, 0), day) + 1,
+            snapshot_bucket(&at(3, 0, 0), day)
+        );
+    }
+}
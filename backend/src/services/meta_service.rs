@@ -0,0 +1,124 @@
+//! This module handles all services which describe the application itself, rather than any
+//! particular player.
+
+use chrono::{DateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetName, TZ_VARIANTS, Tz};
+use once_cell::sync::Lazy;
+
+use crate::{
+    handlers::responses::{TimeZoneInfo, TimeZoneListResponse},
+    models::clock::Clock,
+};
+
+/// Every time zone which can be parsed into a `chrono_tz::Tz`, and is therefore accepted during
+/// registration, sorted by name. The list never changes while the application is running, so it is
+/// only sorted once. Their offsets, on the other hand, shift with daylight saving time, so they are
+/// computed on every request.
+static TIME_ZONES: Lazy<Vec<Tz>> = Lazy::new(|| {
+    let mut zones = TZ_VARIANTS.to_vec();
+    zones.sort_unstable_by_key(|tz| tz.name());
+    zones
+});
+
+/// Describe a time zone as it is at a specific time.
+///
+/// The label is built from the final segment of the IANA name (usually a city), followed by the
+/// zone's current abbreviation when it has a real one (`PST`, but not `-03`).
+///
+/// ### Arguments
+/// - `tz`: The time zone to describe
+/// - `now`: The time at which the offset and abbreviation should be checked
+fn describe_time_zone(tz: Tz, now: &DateTime<Utc>) -> TimeZoneInfo {
+    let offset = tz.offset_from_utc_datetime(&now.naive_utc());
+
+    let seconds = offset.fix().local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    let formatted = format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60);
+
+    let place = tz
+        .name()
+        .rsplit('/')
+        .next()
+        .unwrap_or(tz.name())
+        .replace('_', " ");
+    let label = match offset.abbreviation() {
+        Some(abbr) if abbr != place && abbr.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+            format!("{} ({})", place, abbr)
+        }
+        _ => place,
+    };
+
+    TimeZoneInfo::new(tz.name(), formatted, label)
+}
+
+pub struct MetaService {}
+
+impl MetaService {
+    /// List the time zones which a player may choose when registering, alongside their current
+    /// offsets from UTC.
+    ///
+    /// ### Arguments
+    /// - `clock`: The source of the current time
+    ///
+    /// ### Returns
+    /// A description of every available time zone
+    pub fn list_time_zones(clock: &impl Clock) -> TimeZoneListResponse {
+        let now = clock.now().to_chrono();
+
+        TimeZoneListResponse::new(
+            TIME_ZONES
+                .iter()
+                .map(|tz| describe_time_zone(*tz, &now))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_time_zone() {
+        let winter = Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2025, 7, 15, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            describe_time_zone(Tz::America__Los_Angeles, &winter),
+            TimeZoneInfo::new(
+                "America/Los_Angeles",
+                String::from("-08:00"),
+                String::from("Los Angeles (PST)")
+            )
+        );
+        assert_eq!(
+            describe_time_zone(Tz::America__Los_Angeles, &summer),
+            TimeZoneInfo::new(
+                "America/Los_Angeles",
+                String::from("-07:00"),
+                String::from("Los Angeles (PDT)")
+            )
+        );
+        assert_eq!(
+            describe_time_zone(Tz::Asia__Kolkata, &winter),
+            TimeZoneInfo::new(
+                "Asia/Kolkata",
+                String::from("+05:30"),
+                String::from("Kolkata (IST)")
+            )
+        );
+        assert_eq!(
+            describe_time_zone(Tz::America__Argentina__Buenos_Aires, &winter),
+            TimeZoneInfo::new(
+                "America/Argentina/Buenos_Aires",
+                String::from("-03:00"),
+                String::from("Buenos Aires")
+            )
+        );
+        assert_eq!(
+            describe_time_zone(Tz::UTC, &winter),
+            TimeZoneInfo::new("UTC", String::from("+00:00"), String::from("UTC"))
+        );
+    }
+}
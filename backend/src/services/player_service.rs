@@ -1,36 +1,264 @@
 //! This module handles all services related to **player accounts**.
 
-use regex::Regex;
+use bson::DateTime;
+use chrono::{Duration as ChronoDuration, Utc};
+use futures::future::join;
+use uuid::Uuid;
 
 use crate::{
     adapters::{
+        captcha::{CAPTCHA, check_captcha},
+        dns::{MX_LOOKUP, check_email_mx},
         email::{
-            send_change_email_confirmation_email, send_change_email_warning_email,
-            send_change_password_email, send_change_username_email, send_lockout_email,
-            send_registration_email,
+            send_account_deletion_email, send_change_email_confirmation_email,
+            send_change_email_warning_email, send_change_password_email,
+            send_change_username_email, send_lockout_email, send_password_reset_email,
+            send_registration_email, send_username_reminder_email, send_welcome_email,
         },
-        hashing::{generate_secret, verify_secret},
+        hashing::{generate_secret, needs_rehash, verify_secret},
         jwt::generate_access_token,
         repositories::{Repository, counter_id::CounterId},
+        storage::ObjectStorage,
+    },
+    config::environment::ENV,
+    errors::{AuthnFailureReason, DBoError, DBoResult},
+    handlers::responses::{
+        AccountLockedResponse, AvatarUploadResponse, ChangeReceiptResponse,
+        PlayerInvalidFieldsResponse, PlayerRankResponse, PublicPlayerListResponse,
+        RegistrationValidationResponse, SafePlayerResponse, SeasonHistoryResponse,
     },
-    errors::{DBoError, DBoResult},
-    handlers::responses::SafePlayerResponse,
     models::{
-        Collectible, ConfirmationToken, Counter, Identifiable, Player, RefreshToken, UndoToken,
-        submodels::{Gender, LanguagePreference, UndoTokenType},
+        AllowlistEntry, Collectible, ConfirmationToken, Counter, Expirable, Identifiable, Player,
+        RefreshToken, SeasonArchive, UndoToken,
+        clock::RealClock,
+        player_validation::{
+            avatar_extension, is_same_identifier, resolve_time_zone, validate_all,
+            validate_avatar_upload, validate_email_allowlisted, validate_email_for_account,
+            validate_password, validate_pronoun, validate_time_zone,
+        },
+        submodels::{AuthMethod, AvatarRef, Gender, LanguagePreference, UndoTokenType},
     },
-    services::types::LoginTokenInfo,
+    services::types::{LoginTokenInfo, SecurityNotice},
 };
 
+/// How long a presigned avatar upload URL remains valid.
+const AVATAR_UPLOAD_LIFETIME: ChronoDuration = ChronoDuration::minutes(5);
+
 pub struct PlayerService {}
 
+/// Re-authenticate a player before a sensitive operation, according to their authentication
+/// method.
+///
+/// ### Arguments
+/// - `player`: The player performing the operation
+/// - `password`: The password provided with the request
+///
+/// ### Errors
+/// - `AuthenticationFailure` if the password does not match, or if the player has no password
+/// - `AdapterError` if the player's stored hash could not be parsed
+fn reauthenticate(player: &Player, password: &str) -> DBoResult<()> {
+    match player.auth_method() {
+        AuthMethod::Password => match verify_secret(password, player.password())? {
+            true => Ok(()),
+            false => Err(DBoError::AuthenticationFailure(
+                AuthnFailureReason::WrongPassword,
+            )),
+        },
+        // TODO: Once external accounts can be created, they should re-authenticate here with an
+        // elevated token issued by the identity provider, rather than with a password.
+        AuthMethod::External => Err(DBoError::AuthenticationFailure(
+            AuthnFailureReason::NoPassword,
+        )),
+    }
+}
+
+/// Ensure that an email address may register. While neither `ENV.registration_allowlist` nor the
+/// allow-list collection has any entries, registration is open to everyone.
+///
+/// ### Arguments
+/// - `allowlist`: The registration allow-list repository
+/// - `email`: The email address of the registering player
+///
+/// ### Errors
+/// - `InvalidPlayerInfo` if registration is restricted, and the email is not on the allow-list
+/// - `AdapterError` if the database query fails
+async fn check_allowlisted(allowlist: &Repository<AllowlistEntry>, email: &str) -> DBoResult<()> {
+    let mut entries = ENV.registration_allowlist.clone();
+    entries.extend(allowlist.all_entries().await?);
+
+    match validate_email_allowlisted(email, &entries) {
+        Some(problems) => Err(DBoError::invalid_player_info(
+            PlayerInvalidFieldsResponse::new(None, None, Some(problems), None, None, None),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Find a player and one of their undo tokens by id, ensuring that the token serves the provided
+/// function, and that it is unexpired.
+///
+/// ### Arguments
+/// - `players`: The Player repository
+/// - `undo_tokens`: The Undo Token repository
+/// - `player_id`: The player's unique identifier
+/// - `token_id`: The undo token's unique identifier
+/// - `function`: The operation which the token must be able to undo
+///
+/// ### Errors
+/// - `MissingDocument` if the player or the undo token cannot be found
+/// - `RelationalConflict` if the token does not match the player, or serves another function
+/// - `TokenExpired` if the token has expired
+/// - `AdapterError` if a database query fails
+async fn find_undo_token(
+    players: &Repository<Player>,
+    undo_tokens: &Repository<UndoToken>,
+    player_id: &str,
+    token_id: &str,
+    function: &UndoTokenType,
+) -> DBoResult<(Player, UndoToken)> {
+    let player = match players.find_by_id(player_id).await? {
+        Some(p) => p,
+        None => return Err(DBoError::missing_document(Player::collection_name())),
+    };
+
+    let token = match undo_tokens.find_by_id(token_id).await? {
+        Some(t) => t,
+        None => return Err(DBoError::missing_document(UndoToken::collection_name())),
+    };
+
+    if token.player_id() != player.id() || token.function() != function {
+        return Err(DBoError::RelationalConflict);
+    }
+
+    if token.expired() {
+        return Err(DBoError::TokenExpired);
+    }
+
+    Ok((player, token))
+}
+
+/// A change to a player's credentials, after which all of their sessions must end.
+enum CredentialChange<'a> {
+    /// Change the player's username, optionally allowing a reserved one.
+    Username {
+        value: &'a str,
+        allow_reserved: bool,
+    },
+    /// Change the player's password.
+    Password(&'a str),
+    /// Confirm the player's proposed email address.
+    ProposedEmail,
+}
+
+/// The storage which applies changes to a player's credentials. Each change also invalidates the
+/// player's access tokens, by changing their "session_valid_after" field.
+trait CredentialStore {
+    async fn apply(&self, player_id: &str, change: &CredentialChange<'_>) -> DBoResult<()>;
+}
+
+impl CredentialStore for Repository<Player> {
+    async fn apply(&self, player_id: &str, change: &CredentialChange<'_>) -> DBoResult<()> {
+        match change {
+            CredentialChange::Username {
+                value,
+                allow_reserved,
+            } => {
+                self.update_username(player_id, value, *allow_reserved)
+                    .await
+            }
+            CredentialChange::Password(value) => self.update_password(player_id, value).await,
+            CredentialChange::ProposedEmail => self.confirm_proposed_email(player_id).await,
+        }
+    }
+}
+
+/// The storage from which refresh tokens are found and revoked.
+trait RefreshTokenStore {
+    async fn find_token(&self, token_id: &str) -> DBoResult<Option<RefreshToken>>;
+    async fn revoke_token(&self, token_id: &str) -> DBoResult<()>;
+    async fn revoke_player_tokens(&self, player_id: &str) -> DBoResult<()>;
+}
+
+impl RefreshTokenStore for Repository<RefreshToken> {
+    async fn find_token(&self, token_id: &str) -> DBoResult<Option<RefreshToken>> {
+        self.find_by_id(token_id).await
+    }
+
+    async fn revoke_token(&self, token_id: &str) -> DBoResult<()> {
+        self.revoke(token_id).await
+    }
+
+    async fn revoke_player_tokens(&self, player_id: &str) -> DBoResult<()> {
+        self.delete_player_tokens(player_id).await
+    }
+}
+
+/// End the session of a refresh token, if the token value identifies one (see
+/// `PlayerService::logout`).
+///
+/// ### Arguments
+/// - `tokens`: The store holding the refresh token
+/// - `token_value`: The refresh token (should be like `"{id}:{secret}"`)
+///
+/// ### Errors
+/// - `AdapterError` if any query should fail, or if the secret could not be verified
+async fn end_session(tokens: &impl RefreshTokenStore, token_value: &str) -> DBoResult<()> {
+    let (token_id, secret) = match RefreshToken::split_value(token_value) {
+        Some(parts) => parts,
+        None => return Ok(()),
+    };
+
+    let token = match tokens.find_token(token_id).await? {
+        Some(t) if !t.revoked() => t,
+        _ => return Ok(()),
+    };
+
+    if !verify_secret(secret, token.secret())? {
+        return Ok(());
+    }
+
+    match tokens.revoke_token(token.id()).await {
+        Ok(()) | Err(DBoError::MissingDocument(_)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Change a player's credentials, ending all of their sessions. The change itself invalidates
+/// their access tokens, and their refresh tokens are deleted once it succeeds. Every change of a
+/// username, password or email address goes through here, so that they all end sessions alike.
+///
+/// ### Arguments
+/// - `players`: The store applying the change
+/// - `refresh_tokens`: The store holding the player's refresh tokens
+/// - `player_id`: The player's unique identifier
+/// - `change`: The change to apply
+///
+/// ### Errors
+/// - Any error from applying the change, in which case no refresh tokens are deleted
+/// - `AdapterError` if the refresh tokens cannot be deleted
+async fn change_credentials(
+    players: &impl CredentialStore,
+    refresh_tokens: &impl RefreshTokenStore,
+    player_id: &str,
+    change: CredentialChange<'_>,
+) -> DBoResult<()> {
+    players.apply(player_id, &change).await?;
+    refresh_tokens.revoke_player_tokens(player_id).await
+}
+
 impl PlayerService {
     /// Create a new player account in the database, create a new confirmation token for them to
-    /// use, and send a confirmation email to the provided email address.
+    /// use, and send a confirmation email to the provided email address. If CAPTCHA verification is
+    /// enabled, the CAPTCHA token is verified before anything else happens.
+    ///
+    /// If the confirmation email cannot be queued, the error is logged, and the account is still
+    /// created; the player can request that the email be resent.
     ///
     /// ### Arguments
     /// - `players`: The player repository
     /// - `tokens`: The confirmation tokens repository
+    /// - `counters`: The counters repository
+    /// - `allowlist`: The registration allow-list repository
     /// - `username`: The requested username
     /// - `password`: The requested password
     /// - `email`: The requested email address
@@ -39,31 +267,38 @@ impl PlayerService {
     /// - `pronoun`: The player's preferred pronouns. This is only used in the case of Spanish
     ///   speaking non-binary players; all other players' pronouns will match with their gender
     ///   automatically.
+    /// - `time_zone`: The IANA name of the player's time zone, or a raw UTC offset
+    /// - `captcha_token`: The token provided by the CAPTCHA widget, if any
     ///
     /// ### Returns
     /// The created player's safe information.
     ///
     /// ### Errors
-    /// - `InvalidPlayerInfo` if the username, password, or email cannot pass validation.
+    /// - `CaptchaFailure` if CAPTCHA verification is enabled and the token is missing or rejected.
+    /// - `InvalidPlayerInfo` if the username, password, email, time zone, or pronoun cannot pass
+    ///   validation, if registration is restricted and the email is not on the allow-list, or if
+    ///   MX verification is enabled and the email domain has no MX records.
     /// - `UniquenessViolation` if the username or email are not case-insensitively unique.
-    /// - `ServerSideError` if the email templates cannot be found.
-    /// - `InvalidEmailAddress` if the user's email address could not be parsed into a Mailbox
-    /// - `AdapterError` if a database query fails, if the password cannot be hashed, or if the
-    ///   confirmation email could not be sent
+    /// - `AdapterError` if a database query fails, or if the password cannot be hashed
+    #[allow(clippy::too_many_arguments)]
     pub async fn register_player(
         players: &Repository<Player>,
         tokens: &Repository<ConfirmationToken>,
         counters: &Repository<Counter>,
+        allowlist: &Repository<AllowlistEntry>,
         username: &str,
         password: &str,
         email: &str,
         gender: &Gender,
         preferred_language: &LanguagePreference,
         pronoun: &Option<Gender>,
+        time_zone: &str,
+        captcha_token: Option<&str>,
     ) -> DBoResult<SafePlayerResponse> {
-        // TODO: Implement time zones into the registration process. The request should provide a
-        // valid time zone string (like "America/Los_Angeles"), validate that it can be parsed into
-        // a `chrono_tz::Tz`, and store that string as its value.
+        check_captcha(CAPTCHA.as_ref(), captcha_token).await?;
+
+        let pronoun_problems = validate_pronoun(gender, preferred_language, pronoun);
+
         let assumed_pronoun = match (gender, preferred_language) {
             (Gender::Other, LanguagePreference::Spanish) => match pronoun {
                 Some(p) => p,
@@ -72,20 +307,45 @@ impl PlayerService {
             _ => gender,
         };
 
-        let player = Player::new(
+        // A bad pronoun is reported alongside any problems with the other fields.
+        let player = match Player::new(
             username,
             password,
             email,
             gender,
             preferred_language,
             assumed_pronoun,
-        )?;
+            time_zone,
+        ) {
+            Ok(_) if pronoun_problems.is_some() => {
+                return Err(DBoError::invalid_player_info(
+                    PlayerInvalidFieldsResponse::new(
+                        None,
+                        None,
+                        None,
+                        None,
+                        pronoun_problems,
+                        None,
+                    ),
+                ));
+            }
+            Ok(p) => p,
+            Err(DBoError::InvalidPlayerInfo(mut info)) => {
+                info.set_pronoun_problems(pronoun_problems);
+                return Err(DBoError::InvalidPlayerInfo(info));
+            }
+            Err(e) => return Err(e),
+        };
+        check_allowlisted(allowlist, email).await?;
+        check_email_mx(MX_LOOKUP.as_ref(), email).await?;
         players.insert(&player).await?;
 
         let token = ConfirmationToken::new(player.id());
         tokens.insert(&token).await?;
 
-        send_registration_email(
+        // The account exists either way; if the email cannot be queued, the player can request that
+        // it be resent.
+        if let Err(e) = send_registration_email(
             email,
             username,
             token.id(),
@@ -93,7 +353,11 @@ impl PlayerService {
             preferred_language,
             assumed_pronoun,
         )
-        .await?;
+        .await
+        {
+            eprintln!("A registration email could not be queued!");
+            eprintln!("{:?}", e);
+        }
 
         counters
             .increment_counter(CounterId::AccountsRegistered)
@@ -102,10 +366,70 @@ impl PlayerService {
         Ok(SafePlayerResponse::from(&player))
     }
 
-    /// Confirm a player's account. Find a player by their id, ensure that the account is not
-    /// already confirmed; find the token by its id, ensure that it matches the same player, and
-    /// that it is unexpired; delete the token, confirm the player's account, and increment the
-    /// counter.
+    /// Check whether a registration would be accepted, without creating anything. Every field is
+    /// validated exactly as during registration, and the username and email address are checked
+    /// for uniqueness, so that every problem can be reported at once.
+    ///
+    /// The CAPTCHA, the registration allow-list, and the email domain's MX records are not checked,
+    /// so a registration may still be rejected for those reasons.
+    ///
+    /// ### Arguments
+    /// - `players`: The player repository
+    /// - `username`: The requested username
+    /// - `password`: The requested password
+    /// - `email`: The requested email address
+    /// - `gender`: The player's gender
+    /// - `preferred_language`: The player's preferred language
+    /// - `pronoun`: The player's preferred pronouns
+    /// - `time_zone`: The IANA name of the player's time zone, or a raw UTC offset
+    ///
+    /// ### Returns
+    /// A report of every problem with the registration, whether or not it is valid
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    #[allow(clippy::too_many_arguments)]
+    pub async fn validate_registration(
+        players: &Repository<Player>,
+        username: &str,
+        password: &str,
+        email: &str,
+        gender: &Gender,
+        preferred_language: &LanguagePreference,
+        pronoun: &Option<Gender>,
+        time_zone: &str,
+    ) -> DBoResult<RegistrationValidationResponse> {
+        let time_zone = &resolve_time_zone(time_zone);
+        let mut problems = match validate_all(username, password, email, time_zone) {
+            Ok(()) => PlayerInvalidFieldsResponse::default(),
+            Err(DBoError::InvalidPlayerInfo(info)) => *info,
+            Err(e) => return Err(e),
+        };
+        problems.set_pronoun_problems(validate_pronoun(gender, preferred_language, pronoun));
+
+        let username_taken = players.find_by_username(username).await?.is_some();
+        let email_taken = players.find_by_email(email).await?.is_some();
+
+        Ok(RegistrationValidationResponse::new(
+            problems,
+            username_taken,
+            email_taken,
+            validate_time_zone(time_zone).is_none(),
+        ))
+    }
+
+    /// Confirm a player's account. Find the player and the token by their ids (always looking up
+    /// both), and ensure that the token matches the player and is unexpired; mark the token as
+    /// used, confirm the player's account, and increment the counter. If the account was already
+    /// confirmed by this same token within the last 15 minutes, the request succeeds without doing
+    /// anything, making repeated confirmations harmless.
+    ///
+    /// Every other failure is reported as the same `InvalidToken` error, so that this public
+    /// endpoint does not reveal whether a player id exists, or whether its account is confirmed.
+    ///
+    /// Upon the first confirmation, a welcome email is sent to the player (unless disabled by
+    /// `ENV.send_welcome_email`). If it cannot be sent, the error is logged, and the confirmation
+    /// still succeeds.
     ///
     /// ### Arguments
     /// - `players`: The Player repository
@@ -115,10 +439,8 @@ impl PlayerService {
     /// - `token_id`: The token's unique identifier
     ///
     /// ### Errors
-    /// - `MissingDocument` if either the player or the token could not be found
-    /// - `InternalConflict` if the player account is already confirmed
-    /// - `RelationalConflict` if the token does not match the player
-    /// - `TokenExpired` if the confirmation token is expired (older than 15 minutes)
+    /// - `InvalidToken` if the player or the token is missing, if they do not match, if the token
+    ///   is expired (older than 15 minutes), or if the account was already confirmed by other means
     /// - `AdapterError` if any database query should fail
     pub async fn confirm_player_account(
         players: &Repository<Player>,
@@ -127,39 +449,48 @@ impl PlayerService {
         player_id: &str,
         token_id: &str,
     ) -> DBoResult<()> {
-        let player = match players.find_by_id(player_id).await? {
-            Some(p) => p,
-            None => {
-                return Err(DBoError::missing_document(Player::collection_name()));
-            }
-        };
-
-        if player.confirmed() {
-            return Err(DBoError::InternalConflict);
-        }
+        let (player, token) =
+            join(players.find_by_id(player_id), tokens.find_by_id(token_id)).await;
 
-        let token = match tokens.find_by_id(token_id).await? {
-            Some(t) => t,
-            None => {
-                return Err(DBoError::missing_document(
-                    ConfirmationToken::collection_name(),
-                ));
-            }
+        let (player, token) = match (player?, token?) {
+            (Some(p), Some(t)) if t.player_id() == p.id() => (p, t),
+            _ => return Err(DBoError::InvalidToken),
         };
 
-        if token.player_id() != player.id() {
-            return Err(DBoError::RelationalConflict);
+        if player.confirmed() {
+            return match token.confirmed_player(player.id()) {
+                true => Ok(()),
+                false => Err(DBoError::InvalidToken),
+            };
         }
 
         if token.expired() {
-            return Err(DBoError::TokenExpired);
+            return Err(DBoError::InvalidToken);
         }
 
-        tokens.delete(token.id()).await?;
+        // Only the request which marks the token as used counts the confirmation; a concurrent
+        // duplicate still confirms the player, in case the first request fails partway through.
+        let first_use = tokens.mark_used(token.id(), &DateTime::now()).await?;
         players.confirm(player.id()).await?;
-        counters
-            .increment_counter(CounterId::AccountsConfirmed)
-            .await?;
+
+        if first_use {
+            counters
+                .increment_counter(CounterId::AccountsConfirmed)
+                .await?;
+
+            if ENV.send_welcome_email
+                && let Err(e) = send_welcome_email(
+                    player.email(),
+                    player.username(),
+                    player.preferred_language(),
+                    player.pronoun(),
+                )
+                .await
+            {
+                eprintln!("A welcome email could not be sent!");
+                eprintln!("{:?}", e);
+            }
+        }
 
         Ok(())
     }
@@ -212,7 +543,7 @@ impl PlayerService {
             return Err(DBoError::RelationalConflict);
         }
 
-        players.delete(player.id()).await?;
+        players.delete_player(player.id()).await?;
         tokens.delete(token.id()).await?;
         counters
             .increment_counter(CounterId::AccountsRejected)
@@ -225,10 +556,14 @@ impl PlayerService {
     /// ensure that the account is not currently locked. Check the password against the hash in the
     /// database - if it does not match, increment the `failed_login` count, locking the player out
     /// if that count exceeds 4. If the account becomes locked out due to this login attempt, send
-    /// an email to the player notifying them that their account has been locked out.
+    /// an email to the player notifying them that their account has been locked out, unless one
+    /// was already sent since their last successful login.
     ///
     /// Upon a login success, generate an access token (a JWT good for 15 minutes) to authenticate
-    /// the player. Then generate a persistent refresh token in the database, good for 30 days.
+    /// the player. Then generate a persistent refresh token in the database, good for 30 days. If
+    /// the account was locked out within the last `ENV.lockout_notice_hours`, include a security
+    /// notice so that the player can notice if someone else has been guessing their password. If
+    /// the player's password hash is not yet peppered, it is replaced with a peppered one.
     ///
     /// ### Arguments
     /// - `players`: The player repository
@@ -237,10 +572,12 @@ impl PlayerService {
     /// - `password`: The player's password
     ///
     /// ### Returns
-    /// The information related to both of the created authentication tokens
+    /// The information related to both of the created authentication tokens, and the security
+    /// notice, if any
     ///
     /// ### Errors
-    /// - `AuthenticationFailure` if the username/email and password do not match our records
+    /// - `AuthenticationFailure` if the username/email and password do not match our records, or
+    ///   if the account is scheduled for deletion
     /// - `InternalConflict` if the account is unconfirmed.
     /// - `AccountLocked` if either the account is already locked, or if authentication failed for a
     ///   fifth (or greater) time, resulting in a new lockout.
@@ -262,7 +599,9 @@ impl PlayerService {
             Some(p) => p,
             None => {
                 counters.increment_counter(CounterId::FailedLogins).await?;
-                return Err(DBoError::AuthenticationFailure);
+                return Err(DBoError::AuthenticationFailure(
+                    AuthnFailureReason::UnknownAccount,
+                ));
             }
         };
 
@@ -271,9 +610,11 @@ impl PlayerService {
         }
 
         if player.locked() {
-            return Err(DBoError::AccountLocked(
+            return Err(DBoError::AccountLocked(AccountLockedResponse::new(
                 player.locked_until().unwrap().to_chrono(),
-            ));
+                player.preferred_language(),
+                player.time_zone(),
+            )));
         }
 
         if !verify_secret(password, player.password())? {
@@ -282,22 +623,61 @@ impl PlayerService {
             let lockout = players.increment_failed_logins(player.id()).await?;
 
             if let Some(time) = lockout {
-                send_lockout_email(
-                    player.email(),
-                    player.username(),
-                    player.failed_logins() + 1,
-                    &time.to_chrono(),
-                    // TODO: replace this value with a getter from the Player model once implemented
-                    "America/Los_Angeles",
+                let sent_at = DateTime::now();
+                if player.lockout_email_due()
+                    && players.claim_lockout_email(player.id(), &sent_at).await?
+                    && let Err(e) = send_lockout_email(
+                        player.email(),
+                        player.username(),
+                        player.failed_logins() + 1,
+                        &time.to_chrono(),
+                        player.time_zone(),
+                        player.preferred_language(),
+                    )
+                    .await
+                {
+                    players
+                        .release_lockout_email(
+                            player.id(),
+                            &sent_at,
+                            player.last_lockout_email_at().as_ref(),
+                        )
+                        .await?;
+                    return Err(e);
+                }
+                return Err(DBoError::AccountLocked(AccountLockedResponse::new(
+                    time.to_chrono(),
                     player.preferred_language(),
-                )
-                .await?;
-                return Err(DBoError::AccountLocked(time.to_chrono()));
+                    player.time_zone(),
+                )));
             } else {
-                return Err(DBoError::AuthenticationFailure);
+                return Err(DBoError::AuthenticationFailure(
+                    AuthnFailureReason::WrongPassword,
+                ));
             }
         }
 
+        // The password is checked first, so that this does not reveal that the account exists.
+        if player.deleted_at().is_some() {
+            return Err(DBoError::AuthenticationFailure(
+                AuthnFailureReason::PendingDeletion,
+            ));
+        }
+
+        // Hashes which are not yet peppered are replaced while the password is at hand.
+        if needs_rehash(player.password())
+            && let Err(e) = players
+                .rehash_password(player.id(), player.password(), password)
+                .await
+        {
+            eprintln!("A player's password could not be rehashed!");
+            eprintln!("{:?}", e);
+        }
+
+        let security_notice = player
+            .recent_lockout_at(&RealClock, ChronoDuration::hours(ENV.lockout_notice_hours))
+            .map(|time| SecurityNotice::new(time.to_chrono(), player.failed_logins()));
+
         let access_token = generate_access_token(player.id())?;
 
         let refresh_secret = generate_secret();
@@ -311,6 +691,7 @@ impl PlayerService {
             &access_token,
             refresh_token.id(),
             &refresh_secret,
+            security_notice,
         ))
     }
 
@@ -380,40 +761,140 @@ impl PlayerService {
         Ok(())
     }
 
-    /// Refresh a players authentication tokens. Parse the cookie to find the ID and secret; find
+    /// Correct the email address of an unconfirmed account, for a player who made a typo while
+    /// registering. The player must prove that they made the registration by providing its
+    /// password.
+    ///
+    /// Search for the player by id, and make sure that it is not already confirmed. Find the old
+    /// confirmation token, and make sure that it belongs to the player. Validate the new email
+    /// address the same way as during registration, and ensure that it is case-insensitively
+    /// unique. Update the email address, replace the confirmation token, and send the registration
+    /// email to the new address.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `conf_tokens`: The ConfirmationToken repository
+    /// - `allowlist`: The registration allow-list repository
+    /// - `player_id`: The player's unique identifier
+    /// - `token_id`: The old confirmation token's unique identifier
+    /// - `password`: The password chosen during registration
+    /// - `new_email`: The corrected email address
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if either the player or token cannot be found
+    /// - `InternalConflict` if the player account is already confirmed
+    /// - `RelationalConflict` if the token is not associated with the same player
+    /// - `AuthenticationFailure` if the password does not match the database
+    /// - `InvalidPlayerInfo` if the new email address is invalid, disposable, not on the
+    ///   registration allow-list, or has no MX records
+    /// - `UniquenessViolation` if the new email address is already in use
+    /// - `InvalidEmailAddress` if the email cannot be sent because the new address cannot be
+    ///   parsed into a Mailbox
+    /// - `AdapterError` if a database query should fail, or if the email could not be sent
+    pub async fn correct_unconfirmed_email(
+        players: &Repository<Player>,
+        conf_tokens: &Repository<ConfirmationToken>,
+        allowlist: &Repository<AllowlistEntry>,
+        player_id: &str,
+        token_id: &str,
+        password: &str,
+        new_email: &str,
+    ) -> DBoResult<()> {
+        let player = match players.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => {
+                return Err(DBoError::missing_document(Player::collection_name()));
+            }
+        };
+
+        if player.confirmed() {
+            return Err(DBoError::InternalConflict);
+        }
+
+        let old_token = match conf_tokens.find_by_id(token_id).await? {
+            Some(t) => t,
+            None => {
+                return Err(DBoError::missing_document(
+                    ConfirmationToken::collection_name(),
+                ));
+            }
+        };
+
+        if old_token.player_id() != player.id() {
+            return Err(DBoError::RelationalConflict);
+        }
+
+        reauthenticate(&player, password)?;
+
+        let email_problems = validate_email_for_account(new_email);
+        if email_problems.is_some() {
+            return Err(DBoError::invalid_player_info(
+                PlayerInvalidFieldsResponse::new(None, None, email_problems, None, None, None),
+            ));
+        }
+
+        check_allowlisted(allowlist, new_email).await?;
+        check_email_mx(MX_LOOKUP.as_ref(), new_email).await?;
+
+        players
+            .update_unconfirmed_email(player.id(), new_email)
+            .await?;
+
+        let new_token = ConfirmationToken::new(player.id());
+        conf_tokens.insert(&new_token).await?;
+
+        send_registration_email(
+            new_email,
+            player.username(),
+            new_token.id(),
+            player.id(),
+            player.preferred_language(),
+            player.pronoun(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Refresh a players authentication tokens. Parse the token to find the ID and secret; find
     /// the refresh token in the database matching the id; verify that the secrets match; confirm
-    /// that the token is unexpired; find the associated player account; make a new access token;
+    /// that the token is unexpired; find the associated player account, and ensure that the token
+    /// was issued after the player's sessions were last invalidated; make a new access token;
     /// replace the old refresh token in the database with a new one.
     ///
     /// ### Arguments
     /// - `players`: The Player repository
     /// - `tokens`: The RefreshToken repository
-    /// - `cookie_value`: The value of the refresh_token cookie (should be like `"{id}:{secret}"`)
+    /// - `token_value`: The refresh token, from either the refresh_token cookie or the request body
+    ///   (should be like `"{id}:{secret}"`)
     ///
     /// ### Errors
-    /// - `InvalidToken` if the cookie value cannot be parsed into an id and a secret
-    /// - `AuthenticationFailure` if the token cannot be found, or if the secret does not match
+    /// - `InvalidToken` if the token value cannot be parsed into an id and a secret
+    /// - `AuthenticationFailure` if the token cannot be found, if the secret does not match, or if
+    ///   the associated player account no longer exists or is scheduled for deletion
     /// - `TokenExpired` if the token is found but is older than 30 days
     /// - `InternalConflict` if the token has been revoked
-    /// - `MissingDocument` if the associated player account cannot be found, or if midway through,
-    ///   the old token cannot be found in order to replace it
+    /// - `MissingDocument` if midway through, the old token cannot be found in order to replace it
+    /// - `TokenPremature` if the token was issued before the player's sessions were invalidated
     /// - `AdapterError` if any database query should fail, or if the secret could not be verified,
     ///   or if the new token cannot be created, or if the new secret could not be hashed.
     pub async fn refresh_authn_tokens(
         players: &Repository<Player>,
         tokens: &Repository<RefreshToken>,
-        cookie_value: &str,
+        token_value: &str,
     ) -> DBoResult<LoginTokenInfo> {
-        let regex = Regex::new(r"([^:]+):([^:]+)").unwrap();
-
-        let (token_id, secret) = match regex.captures(cookie_value) {
-            Some(caps) => (caps[1].to_string(), caps[2].to_string()),
+        let (token_id, secret) = match RefreshToken::split_value(token_value) {
+            Some(parts) => parts,
             None => return Err(DBoError::InvalidToken),
         };
 
-        let token = match tokens.find_by_id(&token_id).await? {
+        let token = match tokens.find_by_id(token_id).await? {
             Some(t) => t,
-            None => return Err(DBoError::AuthenticationFailure),
+            None => {
+                return Err(DBoError::AuthenticationFailure(
+                    AuthnFailureReason::UnknownRefreshToken,
+                ));
+            }
         };
 
         if token.expired() {
@@ -424,17 +905,33 @@ impl PlayerService {
             return Err(DBoError::InternalConflict);
         }
 
-        if !verify_secret(&secret, token.secret())? {
-            return Err(DBoError::AuthenticationFailure);
+        if !verify_secret(secret, token.secret())? {
+            return Err(DBoError::AuthenticationFailure(
+                AuthnFailureReason::WrongRefreshSecret,
+            ));
         }
 
         let player = match players.find_by_id(token.player_id()).await? {
             Some(p) => p,
             None => {
-                return Err(DBoError::missing_document(Player::collection_name()));
+                return Err(DBoError::AuthenticationFailure(
+                    AuthnFailureReason::PlayerNotFound,
+                ));
             }
         };
 
+        // Refresh tokens are deleted whenever a player's sessions are invalidated; this also
+        // rejects any which survived, so that they cannot mint access tokens for the new session.
+        if token.predates_session(player.valid_after()) {
+            return Err(DBoError::TokenPremature);
+        }
+
+        if player.deleted_at().is_some() {
+            return Err(DBoError::AuthenticationFailure(
+                AuthnFailureReason::PendingDeletion,
+            ));
+        }
+
         let access_token = generate_access_token(player.id())?;
         let new_secret = generate_secret();
         let new_refresh_token = RefreshToken::new(player.id(), &new_secret)?;
@@ -445,102 +942,502 @@ impl PlayerService {
             &access_token,
             new_refresh_token.id(),
             &new_secret,
+            None,
         ))
     }
 
-    /// Delete a player's account. This requires that they have a valid access token to identify
-    /// them, and they must also provide their password to further verify their identity. Find the
-    /// player by the token, delete the document if the password matches, and increment the counter.
+    /// End a player's session by revoking its refresh token. Parse the token value into an id and
+    /// a secret, and revoke the matching token if the secret is correct. Logging out is idempotent:
+    /// if the value cannot be parsed, the token cannot be found or has already been revoked, or the
+    /// secret does not match, nothing happens.
     ///
     /// ### Arguments
-    /// - `players`: The Player Repository
-    /// - `counters`: The Counter Repository
-    /// - `jwt`: The player's access JWT
-    /// - `password`: The player's password
+    /// - `tokens`: The RefreshToken repository
+    /// - `token_value`: The refresh token, from the refresh_token cookie (should be like
+    ///   `"{id}:{secret}"`)
     ///
     /// ### Errors
-    /// - `TokenExpired` if the access token is expired.
-    /// - `TokenPremature` if the token was created before the player's sessions became invalidated.
-    /// - `InvalidToken` if the token cannot be decoded because it is bad.
-    /// - `MissingDocument` if the player cannot be identified by the token.
-    /// - `AuthenticationFailure` if the password does not match the database.
-    /// - `AdapterError` if a database query fails, or if the token cannot be decoded due to a
-    ///   server-side error.
-    pub async fn delete_player_account(
-        players: &Repository<Player>,
-        counters: &Repository<Counter>,
-        jwt: &str,
-        password: &str,
-    ) -> DBoResult<()> {
-        let player = players.find_by_token(jwt).await?;
-
-        if !verify_secret(password, player.password())? {
-            return Err(DBoError::AuthenticationFailure);
-        }
-
-        players.delete(player.id()).await?;
-        counters
-            .increment_counter(CounterId::AccountsDeleted)
-            .await?;
-
-        Ok(())
+    /// - `AdapterError` if any database query should fail, or if the secret could not be verified
+    pub async fn logout(tokens: &Repository<RefreshToken>, token_value: &str) -> DBoResult<()> {
+        end_session(tokens, token_value).await
     }
 
-    /// Change a player's username in the database. Find the player using their access token, verify
-    /// that their password is correct, update the username, invalidate all player sessions, and
-    /// send an email to the player informing them of this change.
+    /// End every one of a player's sessions at once, such as after a suspected compromise. This
+    /// requires that they have a valid access token to identify them, and they must also provide
+    /// their password to further verify their identity. Find the player by the token, and if the
+    /// password matches, invalidate their access tokens by changing the "session_valid_after"
+    /// field, and delete all of their refresh tokens.
     ///
     /// ### Arguments
     /// - `players`: The Player repository
     /// - `tokens`: The Refresh Token repository
     /// - `jwt`: The player's access token
     /// - `password`: The player's password
-    /// - `new_username`: The player's new username.
     ///
     /// ### Errors
-    /// - `TokenExpired` if the jwt is expired
-    /// - `TokenPremature` if the jwt was created before the player's sessions were invalidated
-    /// - `InvalidToken` if the jwt cannot be decoded because it is bad
-    /// - `MissingDocument` if the player cannot be found
+    /// - `TokenExpired` if the access token is expired
+    /// - `TokenPremature` if the token was created before the player's sessions were invalidated
+    /// - `InvalidToken` if the token cannot be decoded because it is bad
+    /// - `AuthenticationFailure` if the player no longer exists
     /// - `AuthenticationFailure` if the password does not match the database
-    /// - `InvalidPlayerInfo` if the new username is not valid
-    /// - `UniquenessViolation` if the new username is not case-insensitively unique
-    /// - `InvalidEmailAddress` if the email cannot be sent because a player's stored email address
-    ///   cannot be parsed into a Mailbox
+    /// - `MissingDocument` if the player is deleted before their sessions can be invalidated
     /// - `AdapterError` if a database query fails, or if the token cannot be decoded due to a
-    ///   server-side error, or if the player's stored hash could not be parsed, or if the
-    ///   notification email cannot be sent due to a server-side error.
-    pub async fn change_username(
+    ///   server-side error, or if the player's stored hash could not be parsed
+    pub async fn logout_all_sessions(
         players: &Repository<Player>,
         tokens: &Repository<RefreshToken>,
         jwt: &str,
         password: &str,
-        new_username: &str,
     ) -> DBoResult<()> {
         let player = players.find_by_token(jwt).await?;
 
-        if !verify_secret(password, player.password())? {
-            return Err(DBoError::AuthenticationFailure);
-        }
+        reauthenticate(&player, password)?;
 
-        players.update_username(player.id(), new_username).await?;
+        players.invalidate_sessions(player.id()).await?;
         tokens.delete_player_tokens(player.id()).await?;
 
-        send_change_username_email(
-            player.email(),
-            player.username(),
-            new_username,
-            player.preferred_language(),
-            player.gender(),
-        )
+        Ok(())
+    }
+
+    /// Schedule a player's account for deletion. This requires that they have a valid access token
+    /// to identify them, and they must also provide their password to further verify their
+    /// identity. Find the player by the token, and if the password matches, schedule the account to
+    /// be deleted once the grace period has ended. Create a new undo token, and send an email to
+    /// the player allowing them to cancel the deletion.
+    ///
+    /// Until the deletion is cancelled, the player can neither log in nor use their existing
+    /// sessions, so the emailed link is their only way back into the account. If the undo token
+    /// cannot be stored or the email cannot be sent, the deletion is cancelled again. The account
+    /// itself is deleted later by the maintenance tasks.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player Repository
+    /// - `undo_tokens`: The Undo Token Repository
+    /// - `jwt`: The player's access JWT
+    /// - `password`: The player's password
+    ///
+    /// ### Errors
+    /// - `TokenExpired` if the access token is expired.
+    /// - `TokenPremature` if the token was created before the player's sessions became invalidated.
+    /// - `InvalidToken` if the token cannot be decoded because it is bad.
+    /// - `AuthenticationFailure` if the player no longer exists.
+    /// - `AuthenticationFailure` if the password does not match the database.
+    /// - `InternalConflict` if the account is already scheduled for deletion.
+    /// - `InvalidEmailAddress` if the email cannot be sent because a player's stored email address
+    ///   cannot be parsed into a Mailbox
+    /// - `AdapterError` if a database query fails, or if the token cannot be decoded due to a
+    ///   server-side error, or if the email cannot be sent due to a server-side error.
+    pub async fn delete_player_account(
+        players: &Repository<Player>,
+        undo_tokens: &Repository<UndoToken>,
+        jwt: &str,
+        password: &str,
+    ) -> DBoResult<()> {
+        let player = players.find_by_token(jwt).await?;
+
+        reauthenticate(&player, password)?;
+
+        let deleted_at =
+            DateTime::from_chrono(Utc::now() + ChronoDuration::days(ENV.deletion_grace_days));
+
+        players.schedule_deletion(player.id(), &deleted_at).await?;
+
+        let undo_token = UndoToken::new(player.id(), &UndoTokenType::Deletion);
+
+        let notified = async {
+            undo_tokens.insert(&undo_token).await?;
+            send_account_deletion_email(
+                player.email(),
+                player.username(),
+                player.id(),
+                undo_token.id(),
+                player.preferred_language(),
+            )
+            .await
+        }
+        .await;
+
+        // The player could not cancel a deletion which they were never told how to cancel.
+        if let Err(e) = notified {
+            if let Err(e) = players.cancel_deletion(player.id()).await {
+                eprintln!("A player's account deletion could not be cancelled!");
+                eprintln!("{:?}", e);
+            }
+            if let Err(e) = undo_tokens.delete(undo_token.id()).await {
+                eprintln!("An unused deletion undo token could not be deleted!");
+                eprintln!("{:?}", e);
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Cancel the scheduled deletion of a player's account. Find the player and the undo token by
+    /// their ids, and ensure that they match each other, that the token is meant for cancelling a
+    /// deletion, and that it is unexpired. Cancel the deletion, and delete the undo token.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player Repository
+    /// - `undo_tokens`: The Undo Token Repository
+    /// - `player_id`: The player's unique identifier
+    /// - `token_id`: The undo token's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player or the undo token cannot be found
+    /// - `RelationalConflict` if the token does not match the player, or is not a deletion token
+    /// - `TokenExpired` if the grace period has already ended
+    /// - `InternalConflict` if the account is not scheduled for deletion
+    /// - `AdapterError` if a database query fails
+    pub async fn cancel_deletion(
+        players: &Repository<Player>,
+        undo_tokens: &Repository<UndoToken>,
+        player_id: &str,
+        token_id: &str,
+    ) -> DBoResult<()> {
+        let player = match players.find_by_id(player_id).await? {
+            Some(p) => p,
+            None => return Err(DBoError::missing_document(Player::collection_name())),
+        };
+
+        let token = match undo_tokens.find_by_id(token_id).await? {
+            Some(t) => t,
+            None => return Err(DBoError::missing_document(UndoToken::collection_name())),
+        };
+
+        if token.player_id() != player.id() || !matches!(token.function(), UndoTokenType::Deletion)
+        {
+            return Err(DBoError::RelationalConflict);
+        }
+
+        if token.expired() {
+            return Err(DBoError::TokenExpired);
+        }
+
+        players.cancel_deletion(player.id()).await?;
+        undo_tokens.delete(token.id()).await?;
+
+        Ok(())
+    }
+
+    /// Undo a player's most recent password change. Find the player and the undo token by their
+    /// ids, and ensure that they match each other, that the token was sent after a password change,
+    /// and that it is unexpired. Consume the undo token, then restore the player's previous
+    /// password, and invalidate all of their sessions (both by changing the "session_valid_after"
+    /// field and by deleting their refresh tokens).
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `refresh_tokens`: The Refresh Token repository
+    /// - `undo_tokens`: The Undo Token repository
+    /// - `player_id`: The player's unique identifier
+    /// - `token_id`: The undo token's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player or the undo token cannot be found, or if the token was
+    ///   consumed by another redemption
+    /// - `RelationalConflict` if the token does not match the player, or is not a password token
+    /// - `TokenExpired` if the token has expired
+    /// - `InternalConflict` if the player has no previous password to restore, or if their
+    ///   password was changed while the token was being redeemed
+    /// - `AdapterError` if a database query fails
+    pub async fn undo_password_change(
+        players: &Repository<Player>,
+        refresh_tokens: &Repository<RefreshToken>,
+        undo_tokens: &Repository<UndoToken>,
+        player_id: &str,
+        token_id: &str,
+    ) -> DBoResult<()> {
+        let (player, token) = find_undo_token(
+            players,
+            undo_tokens,
+            player_id,
+            token_id,
+            &UndoTokenType::Password,
+        )
+        .await?;
+
+        // The token is consumed first, so that only one redemption can ever undo the change.
+        if !undo_tokens.consume(&token).await? {
+            return Err(DBoError::missing_document(UndoToken::collection_name()));
+        }
+
+        players.restore_previous_password(player.id()).await?;
+        refresh_tokens.delete_player_tokens(player.id()).await?;
+
+        Ok(())
+    }
+
+    /// Undo a player's pending email change. Find the player and the undo token by their ids, and
+    /// ensure that they match each other, that the token was sent after an email change, and that
+    /// it is unexpired. Consume the undo token, then withdraw the player's proposed email address,
+    /// and invalidate all of their sessions (both by changing the "session_valid_after" field and
+    /// by deleting their refresh tokens).
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `refresh_tokens`: The Refresh Token repository
+    /// - `undo_tokens`: The Undo Token repository
+    /// - `player_id`: The player's unique identifier
+    /// - `token_id`: The undo token's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player or the undo token cannot be found, or if the token was
+    ///   consumed by another redemption
+    /// - `RelationalConflict` if the token does not match the player, or is not an email token
+    /// - `TokenExpired` if the token has expired
+    /// - `InternalConflict` if the player does not have a proposed email address, or if it was
+    ///   changed while the token was being redeemed
+    /// - `AdapterError` if a database query fails
+    pub async fn undo_email_change(
+        players: &Repository<Player>,
+        refresh_tokens: &Repository<RefreshToken>,
+        undo_tokens: &Repository<UndoToken>,
+        player_id: &str,
+        token_id: &str,
+    ) -> DBoResult<()> {
+        let (player, token) = find_undo_token(
+            players,
+            undo_tokens,
+            player_id,
+            token_id,
+            &UndoTokenType::Email,
+        )
         .await?;
 
+        // The token is consumed first, so that only one redemption can ever undo the change.
+        if !undo_tokens.consume(&token).await? {
+            return Err(DBoError::missing_document(UndoToken::collection_name()));
+        }
+
+        players.withdraw_proposed_email(player.id()).await?;
+        refresh_tokens.delete_player_tokens(player.id()).await?;
+
         Ok(())
     }
 
+    /// Redeem an undo token, undoing whichever operation it was sent for: a password change, an
+    /// email change, or an account deletion. Tokens sent upon a request to reset a forgotten
+    /// password cannot be redeemed this way, since they require a new password.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `refresh_tokens`: The Refresh Token repository
+    /// - `undo_tokens`: The Undo Token repository
+    /// - `player_id`: The player's unique identifier
+    /// - `token_id`: The undo token's unique identifier
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player or the undo token cannot be found
+    /// - `RelationalConflict` if the token does not match the player, or cannot be redeemed
+    /// - `TokenExpired` if the token has expired
+    /// - `InternalConflict` if there is nothing left to undo
+    /// - `AdapterError` if a database query fails
+    pub async fn redeem_undo_token(
+        players: &Repository<Player>,
+        refresh_tokens: &Repository<RefreshToken>,
+        undo_tokens: &Repository<UndoToken>,
+        player_id: &str,
+        token_id: &str,
+    ) -> DBoResult<()> {
+        let token = match undo_tokens.find_by_id(token_id).await? {
+            Some(t) => t,
+            None => return Err(DBoError::missing_document(UndoToken::collection_name())),
+        };
+
+        match token.function() {
+            UndoTokenType::Password => {
+                Self::undo_password_change(
+                    players,
+                    refresh_tokens,
+                    undo_tokens,
+                    player_id,
+                    token_id,
+                )
+                .await
+            }
+            UndoTokenType::Email => {
+                Self::undo_email_change(players, refresh_tokens, undo_tokens, player_id, token_id)
+                    .await
+            }
+            UndoTokenType::Deletion => {
+                Self::cancel_deletion(players, undo_tokens, player_id, token_id).await
+            }
+            UndoTokenType::PasswordReset => Err(DBoError::RelationalConflict),
+        }
+    }
+
+    /// Change a player's username in the database. Find the player using their access token, verify
+    /// that their password is correct, ensure that the new username differs from the current one
+    /// (otherwise, nothing is changed and no email is sent), update the username, invalidate all
+    /// player sessions (both by changing the "session_valid_after" field and by deleting their
+    /// refresh tokens), and send an email to the player informing them of this change. The change
+    /// stands even if the email cannot be sent; the receipt reports whether it was.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `tokens`: The Refresh Token repository
+    /// - `jwt`: The player's access token
+    /// - `password`: The player's password
+    /// - `new_username`: The player's new username.
+    ///
+    /// ### Returns
+    /// A receipt describing the change
+    ///
+    /// ### Errors
+    /// - `TokenExpired` if the jwt is expired
+    /// - `TokenPremature` if the jwt was created before the player's sessions were invalidated
+    /// - `InvalidToken` if the jwt cannot be decoded because it is bad
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AccountUnconfirmed` if the player's account has never been confirmed
+    /// - `AuthenticationFailure` if the password does not match the database
+    /// - `NoChange` if the new username case-insensitively matches the current one
+    /// - `InvalidPlayerInfo` if the new username is not valid
+    /// - `UniquenessViolation` if the new username is not case-insensitively unique
+    /// - `AdapterError` if a database query fails, or if the token cannot be decoded due to a
+    ///   server-side error, or if the player's stored hash could not be parsed.
+    pub async fn change_username(
+        players: &Repository<Player>,
+        tokens: &Repository<RefreshToken>,
+        jwt: &str,
+        password: &str,
+        new_username: &str,
+    ) -> DBoResult<ChangeReceiptResponse> {
+        let player = players.find_by_token_confirmed(jwt).await?;
+
+        reauthenticate(&player, password)?;
+
+        if is_same_identifier(player.username(), new_username) {
+            return Err(DBoError::NoChange);
+        }
+
+        // Admins may claim reserved usernames for their staff accounts.
+        let change = CredentialChange::Username {
+            value: new_username,
+            allow_reserved: player.admin(),
+        };
+        change_credentials(players, tokens, player.id(), change).await?;
+
+        let notified = send_change_username_email(
+            player.email(),
+            player.username(),
+            new_username,
+            player.preferred_language(),
+            player.gender(),
+        )
+        .await;
+
+        if let Err(e) = &notified {
+            eprintln!("A username change email could not be sent!");
+            eprintln!("{:?}", e);
+        }
+
+        Ok(ChangeReceiptResponse::new(
+            &["username"],
+            true,
+            notified.is_ok(),
+        ))
+    }
+
+    /// Change a player's avatar. Find the player in the database by their access token, and update
+    /// their avatar. As the avatar is public, this does not require the player's password.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `jwt`: The player's access token
+    /// - `avatar`: The player's new avatar, or None to remove their avatar
+    ///
+    /// ### Errors
+    /// - `TokenExpired` if the jwt is expired
+    /// - `TokenPremature` if the jwt was created before the player's sessions were invalidated
+    /// - `InvalidToken` if the jwt cannot be decoded because it is bad
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AccountUnconfirmed` if the player's account has never been confirmed
+    /// - `NoChange` if the new avatar is the same as the current one
+    /// - `InvalidPlayerInfo` if the new avatar is not valid
+    /// - `AdapterError` if a database query fails, or if the token cannot be decoded due to a
+    ///   server-side error
+    pub async fn change_avatar(
+        players: &Repository<Player>,
+        jwt: &str,
+        avatar: Option<&AvatarRef>,
+    ) -> DBoResult<()> {
+        let player = players.find_by_token_confirmed(jwt).await?;
+
+        if player.avatar() == avatar {
+            return Err(DBoError::NoChange);
+        }
+
+        players.update_avatar(player.id(), avatar).await
+    }
+
+    /// Create a presigned URL with which a player may upload an avatar image directly to object
+    /// storage. The image is stored under the player's own key, which the player should then set
+    /// as their avatar once the upload succeeds.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `storage`: The object storage holding uploaded avatars
+    /// - `jwt`: The player's access token
+    /// - `content_type`: The MIME type of the image
+    /// - `content_length`: The size of the image, in bytes
+    ///
+    /// ### Errors
+    /// - `TokenExpired` if the jwt is expired
+    /// - `TokenPremature` if the jwt was created before the player's sessions were invalidated
+    /// - `InvalidToken` if the jwt cannot be decoded because it is bad
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AccountUnconfirmed` if the player's account has never been confirmed
+    /// - `InvalidPlayerInfo` if the image is in an unsupported format, or is too large
+    /// - `AdapterError` if a database query fails, or if the token cannot be decoded due to a
+    ///   server-side error
+    pub async fn avatar_upload_url(
+        players: &Repository<Player>,
+        storage: &ObjectStorage,
+        jwt: &str,
+        content_type: &str,
+        content_length: u64,
+    ) -> DBoResult<AvatarUploadResponse> {
+        let player = players.find_by_token_confirmed(jwt).await?;
+
+        let problems = validate_avatar_upload(content_type, content_length, ENV.avatar_max_bytes);
+        let extension = match (problems, avatar_extension(content_type)) {
+            (None, Some(e)) => e,
+            (problems, _) => {
+                return Err(DBoError::invalid_player_info(
+                    PlayerInvalidFieldsResponse::new(None, None, None, None, None, problems),
+                ));
+            }
+        };
+
+        let key = format!(
+            "{}{}/{}.{}",
+            ENV.avatar_storage_prefix.as_deref().unwrap_or_default(),
+            player.id(),
+            Uuid::new_v4(),
+            extension
+        );
+
+        let now = Utc::now();
+        let upload_url = storage.presign_put(
+            &key,
+            content_type,
+            content_length,
+            AVATAR_UPLOAD_LIFETIME.num_seconds() as u64,
+            now,
+        );
+
+        Ok(AvatarUploadResponse::new(
+            upload_url,
+            key,
+            &(now + AVATAR_UPLOAD_LIFETIME),
+        ))
+    }
+
     /// Change a player's proposed email address. Find the player in the database by their access
-    /// token. Confirm that their password matches the database. Validate the new email address, and
-    /// ensure that it is case-insensitively unique. Update the player's "proposed_email" field.
+    /// token. Confirm that their password matches the database. Ensure that the new email address
+    /// differs from the current one. Validate the new email address, and ensure that it is
+    /// case-insensitively unique. Update the player's "proposed_email" field.
     /// Create a new undo token and a new confirmation token, and insert both into the database.
     /// Send a warning email to the player's current email address, and send a confirmation email to
     /// their new one.
@@ -553,13 +1450,18 @@ impl PlayerService {
     /// - `password`: The player's password
     /// - `new_email`: The player's new proposed email address
     ///
+    /// ### Returns
+    /// A receipt describing the change
+    ///
     /// ### Errors
     /// - `TokenExpired` if the jwt is expired
     /// - `TokenPremature` if the jwt was created before the player's sessions were invalidated
     /// - `InvalidToken` if the jwt cannot be decoded because it is bad
-    /// - `MissingDocument` if the player cannot be found
+    /// - `AuthenticationFailure` if the player no longer exists
     /// - `AuthenticationFailure` if the password does not match the database
-    /// - `InvalidPlayerInfo` if the new email is not valid
+    /// - `NoChange` if the new email case-insensitively matches the current one
+    /// - `InvalidPlayerInfo` if the new email is not valid, or its domain is disposable while
+    ///   `ENV.block_disposable_email` is enabled
     /// - `UniquenessViolation` if the new email is not case-insensitively unique
     /// - `InvalidEmailAddress` if either the *new* email address **or** the currently stored email
     ///   address cannot be parsed into a Mailbox
@@ -573,11 +1475,13 @@ impl PlayerService {
         jwt: &str,
         password: &str,
         new_email: &str,
-    ) -> DBoResult<()> {
+    ) -> DBoResult<ChangeReceiptResponse> {
         let player = players.find_by_token(jwt).await?;
 
-        if !verify_secret(password, player.password())? {
-            return Err(DBoError::AuthenticationFailure);
+        reauthenticate(&player, password)?;
+
+        if is_same_identifier(player.email(), new_email) {
+            return Err(DBoError::NoChange);
         }
 
         players
@@ -590,7 +1494,7 @@ impl PlayerService {
         let conf_token = ConfirmationToken::new(player.id());
         conf_tokens.insert(&conf_token).await?;
 
-        send_change_email_warning_email(
+        let notified = send_change_email_warning_email(
             player.username(),
             player.email(),
             new_email,
@@ -598,7 +1502,12 @@ impl PlayerService {
             undo_token.id(),
             player.preferred_language(),
         )
-        .await?;
+        .await;
+
+        if let Err(e) = &notified {
+            eprintln!("An email change warning could not be sent!");
+            eprintln!("{:?}", e);
+        }
 
         send_change_email_confirmation_email(
             player.username(),
@@ -612,7 +1521,11 @@ impl PlayerService {
         )
         .await?;
 
-        Ok(())
+        Ok(ChangeReceiptResponse::new(
+            &["proposed_email"],
+            false,
+            notified.is_ok(),
+        ))
     }
 
     /// Confirm a player's proposed email address. Find the player and the confirmation token by
@@ -620,12 +1533,14 @@ impl PlayerService {
     /// Confirm the player's proposed email address, validating it and ensuring that it is still
     /// unique. Change the "email" field to the proposed email, change the "proposed_email" field
     /// back to none, and invalidate a player's access tokens by setting the "session_valid_after"
-    /// field. Delete the confirmation token from the database, as well as the undo token that was
-    /// created when the new email address was proposed.
+    /// field, and delete all of their refresh tokens. Delete the confirmation token from the
+    /// database, as well as the undo token that was created when the new email address was
+    /// proposed.
     ///
     /// ### Arguments
     /// - `players`: The Player repository
     /// - `conf_tokens`: The Confirmation Token repository
+    /// - `refresh_tokens`: The Refresh Token repository
     /// - `undo_tokens`: The Undo Token repository
     /// - `player_id`: The player's unique identifier
     /// - `token_id`: The confirmation token's unique identifier
@@ -641,6 +1556,7 @@ impl PlayerService {
     pub async fn confirm_proposed_email(
         players: &Repository<Player>,
         conf_tokens: &Repository<ConfirmationToken>,
+        refresh_tokens: &Repository<RefreshToken>,
         undo_tokens: &Repository<UndoToken>,
         player_id: &str,
         token_id: &str,
@@ -667,7 +1583,8 @@ impl PlayerService {
             return Err(DBoError::RelationalConflict);
         }
 
-        players.confirm_proposed_email(player.id()).await?;
+        let change = CredentialChange::ProposedEmail;
+        change_credentials(players, refresh_tokens, player.id(), change).await?;
         conf_tokens.delete(token.id()).await?;
         undo_tokens
             .delete_by_player_and_func(player.id(), &UndoTokenType::Email)
@@ -680,46 +1597,51 @@ impl PlayerService {
     /// password is the same as is stored in the database. Update the player's password, ensuring
     /// that it is valid and that it does not match their last 5 passwords - update their
     /// "last_passwords" as well. Invalidate the player's access tokens by changing the
-    /// "session_valid_after" field. Create a new undo token and store it in the database. Send an
-    /// email to the player informing them of this change.
+    /// "session_valid_after" field, and delete all of their refresh tokens. Create a new undo token
+    /// and store it in the database. Send an email to the player informing them of this change; the
+    /// change stands even if the email cannot be sent, and the receipt reports whether it was.
     ///
     /// ### Arguments
     /// - `players`: The Player repository
-    /// - `tokens`: The Undo Token repository
+    /// - `refresh_tokens`: The Refresh Token repository
+    /// - `undo_tokens`: The Undo Token repository
     /// - `jwt`: The player's access token
     /// - `old_password`: The player's current password
     /// - `new_password`: The player's new password to be set
     ///
+    /// ### Returns
+    /// A receipt describing the change
+    ///
     /// ### Errors
     /// - `TokenExpired` if the access token is expired
     /// - `TokenPremature` if the token was created before invalidating the player's sessions
     /// - `InvalidToken` if the token cannot be decoded because it is bad
-    /// - `MissingDocument` if the player cannot be found
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AccountUnconfirmed` if the player's account has never been confirmed
     /// - `InvalidPlayerInfo` if the password is not valid
     /// - `InternalConflict` if the new password matches any of the player's last five passwords
-    /// - `InvalidEmailAddress` if the player's email address cannot be parsed into a Mailbox
     /// - `AdapterError` if a database query fails, or if the access token cannot be decoded due to
     ///   a server-side error, or if any of the player's stored hashes cannot be decoded, or if the
-    ///   new password cannot be hashed, or if the email cannot be sent due to a server-side error.
+    ///   new password cannot be hashed.
     pub async fn change_password(
         players: &Repository<Player>,
-        tokens: &Repository<UndoToken>,
+        refresh_tokens: &Repository<RefreshToken>,
+        undo_tokens: &Repository<UndoToken>,
         jwt: &str,
         old_password: &str,
         new_password: &str,
-    ) -> DBoResult<()> {
-        let player = players.find_by_token(jwt).await?;
+    ) -> DBoResult<ChangeReceiptResponse> {
+        let player = players.find_by_token_confirmed(jwt).await?;
 
-        if !verify_secret(old_password, player.password())? {
-            return Err(DBoError::AuthenticationFailure);
-        }
+        reauthenticate(&player, old_password)?;
 
-        players.update_password(player.id(), new_password).await?;
+        let change = CredentialChange::Password(new_password);
+        change_credentials(players, refresh_tokens, player.id(), change).await?;
 
         let token = UndoToken::new(player.id(), &UndoTokenType::Password);
-        tokens.insert(&token).await?;
+        undo_tokens.insert(&token).await?;
 
-        send_change_password_email(
+        let notified = send_change_password_email(
             player.email(),
             player.username(),
             player.id(),
@@ -727,8 +1649,415 @@ impl PlayerService {
             player.preferred_language(),
             player.pronoun(),
         )
-        .await?;
+        .await;
+
+        if let Err(e) = &notified {
+            eprintln!("A password change email could not be sent!");
+            eprintln!("{:?}", e);
+        }
+
+        Ok(ChangeReceiptResponse::new(
+            &["password"],
+            true,
+            notified.is_ok(),
+        ))
+    }
+
+    /// Find the authenticated player, so that a client can render their profile.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `jwt`: The player's access token
+    ///
+    /// ### Returns
+    /// The player's safe information
+    ///
+    /// ### Errors
+    /// - `TokenExpired` if the access token is expired
+    /// - `TokenPremature` if the token was created before invalidating the player's sessions
+    /// - `InvalidToken` if the token cannot be decoded because it is bad
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AdapterError` if a database query fails, or if the access token cannot be decoded due to
+    ///   a server-side error
+    pub async fn current_player(
+        players: &Repository<Player>,
+        jwt: &str,
+    ) -> DBoResult<SafePlayerResponse> {
+        let player = players.find_by_token(jwt).await?;
+
+        Ok(SafePlayerResponse::from(&player))
+    }
+
+    /// Find where the authenticated player ranks among all confirmed players.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `jwt`: The player's access token
+    ///
+    /// ### Returns
+    /// The player's rank, alongside the stats on which it is based
+    ///
+    /// ### Errors
+    /// - `TokenExpired` if the access token is expired
+    /// - `TokenPremature` if the token was created before invalidating the player's sessions
+    /// - `InvalidToken` if the token cannot be decoded because it is bad
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AccountUnconfirmed` if the player's account has never been confirmed
+    /// - `AdapterError` if a database query fails, or if the access token cannot be decoded due to
+    ///   a server-side error
+    pub async fn player_rank(
+        players: &Repository<Player>,
+        jwt: &str,
+    ) -> DBoResult<PlayerRankResponse> {
+        let player = players.find_by_token_confirmed(jwt).await?;
+
+        match players.player_rank(player.id()).await? {
+            Some(rank) => Ok(PlayerRankResponse::new(rank, player.stats())),
+            None => Err(DBoError::missing_document(Player::collection_name())),
+        }
+    }
+
+    /// Return the authenticated player's stats from every past season in which they were confirmed.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `season_archives`: The Season Archive repository
+    /// - `jwt`: The player's access token
+    ///
+    /// ### Errors
+    /// - `InvalidToken` if the access token is invalid
+    /// - `TokenExpired` if the access token has expired
+    /// - `TokenPremature` if the access token was issued before the player's sessions were reset
+    /// - `AuthenticationFailure` if the player no longer exists
+    /// - `AccountUnconfirmed` if the player has not confirmed their account
+    /// - `AdapterError` if a database query fails
+    pub async fn season_history(
+        players: &Repository<Player>,
+        season_archives: &Repository<SeasonArchive>,
+        jwt: &str,
+    ) -> DBoResult<SeasonHistoryResponse> {
+        let player = players.find_by_token_confirmed(jwt).await?;
+        let archives = season_archives.find_player_archives(player.id()).await?;
+
+        Ok(SeasonHistoryResponse::new(&archives))
+    }
+
+    /// Return the public profiles of several players at once, so that lists of players (such as a
+    /// leaderboard) can be rendered with a single request. Only confirmed players are included;
+    /// any other username is left out of the response.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `usernames`: The usernames of the players to find (case-insensitive)
+    ///
+    /// ### Errors
+    /// - `AdapterError` if the database query fails
+    pub async fn public_profiles(
+        players: &Repository<Player>,
+        usernames: &[String],
+    ) -> DBoResult<PublicPlayerListResponse> {
+        let found = players.find_confirmed_by_usernames(usernames).await?;
+
+        Ok(PublicPlayerListResponse::new(&found))
+    }
+
+    /// Remind a player of their username. Find the player by their email address, and if their
+    /// account is confirmed, send them an email containing their username.
+    ///
+    /// To avoid revealing which email addresses are registered, the lookup and the email both
+    /// happen in the background, so that the caller can respond at once whether or not a matching
+    /// account exists. Any failure is only logged.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `email`: The email address to send the reminder to
+    pub fn recover_username(players: &Repository<Player>, email: &str) {
+        let players = players.clone();
+        let email = String::from(email);
+
+        tokio::spawn(async move {
+            let player = match players.find_by_email(&email).await {
+                Ok(Some(p)) if p.confirmed() => p,
+                Ok(_) => return,
+                Err(e) => {
+                    eprintln!("A player could not be found to remind them of their username!");
+                    eprintln!("{:?}", e);
+                    return;
+                }
+            };
+
+            if let Err(e) = send_username_reminder_email(
+                player.email(),
+                player.username(),
+                player.preferred_language(),
+            )
+            .await
+            {
+                eprintln!("A username reminder email could not be sent!");
+                eprintln!("{:?}", e);
+            }
+        });
+    }
+
+    /// Begin resetting a forgotten password. Find the player by their username or email address,
+    /// and if their account is confirmed and protected by a password, create a password reset
+    /// token and send them an email containing a link to choose a new password. The link expires
+    /// after one day, alongside the token.
+    ///
+    /// To avoid revealing which accounts exist, this succeeds whether or not a matching account
+    /// exists, and a failure to send the email is only logged.
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `undo_tokens`: The Undo Token repository
+    /// - `username_or_email`: The username or email address of the player
+    ///
+    /// ### Errors
+    /// - `AdapterError` if a database query fails
+    pub async fn request_password_reset(
+        players: &Repository<Player>,
+        undo_tokens: &Repository<UndoToken>,
+        username_or_email: &str,
+    ) -> DBoResult<()> {
+        let player = match players.find_by_username_or_email(username_or_email).await? {
+            Some(p) if p.confirmed() && matches!(p.auth_method(), AuthMethod::Password) => p,
+            _ => return Ok(()),
+        };
+
+        let token = UndoToken::new(player.id(), &UndoTokenType::PasswordReset);
+        undo_tokens.insert(&token).await?;
+
+        if let Err(e) = send_password_reset_email(
+            player.email(),
+            player.username(),
+            player.id(),
+            token.id(),
+            player.preferred_language(),
+        )
+        .await
+        {
+            eprintln!("A password reset email could not be sent!");
+            eprintln!("{:?}", e);
+        }
 
         Ok(())
     }
+
+    /// Set a new password for a player who cannot provide their current one. Find the player and
+    /// the undo token by their ids, and ensure that they match each other, that the token was sent
+    /// upon a request to reset a forgotten password, and that it is unexpired. Once the new
+    /// password passes validation, consume the token, so that only one redemption can ever set a
+    /// password. Then update the player's password, invalidating all of their sessions (both by
+    /// changing the "session_valid_after" field and by deleting their refresh tokens).
+    ///
+    /// ### Arguments
+    /// - `players`: The Player repository
+    /// - `refresh_tokens`: The Refresh Token repository
+    /// - `undo_tokens`: The Undo Token repository
+    /// - `player_id`: The player's unique identifier
+    /// - `token_id`: The undo token's unique identifier
+    /// - `new_password`: The player's new password to be set
+    ///
+    /// ### Errors
+    /// - `MissingDocument` if the player or the undo token cannot be found, or if the token was
+    ///   consumed by another redemption
+    /// - `RelationalConflict` if the token does not match the player, or is not a password reset
+    ///   token
+    /// - `TokenExpired` if the token has expired
+    /// - `InvalidPlayerInfo` if the new password is not valid
+    /// - `InternalConflict` if the new password matches the current password, or any of the
+    ///   player's last four passwords
+    /// - `AdapterError` if a database query fails, or if any of the player's stored hashes cannot
+    ///   be decoded, or if the new password cannot be hashed
+    pub async fn complete_password_reset(
+        players: &Repository<Player>,
+        refresh_tokens: &Repository<RefreshToken>,
+        undo_tokens: &Repository<UndoToken>,
+        player_id: &str,
+        token_id: &str,
+        new_password: &str,
+    ) -> DBoResult<()> {
+        let (player, token) = find_undo_token(
+            players,
+            undo_tokens,
+            player_id,
+            token_id,
+            &UndoTokenType::PasswordReset,
+        )
+        .await?;
+
+        // An invalid password should not use up the player's only reset link.
+        if let Some(problems) = validate_password(new_password) {
+            return Err(DBoError::invalid_player_info(
+                PlayerInvalidFieldsResponse::new(None, Some(problems), None, None, None, None),
+            ));
+        }
+
+        // The token is consumed first, so that only one redemption can ever set a password.
+        if !undo_tokens.consume(&token).await? {
+            return Err(DBoError::missing_document(UndoToken::collection_name()));
+        }
+
+        let change = CredentialChange::Password(new_password);
+        change_credentials(players, refresh_tokens, player.id(), change).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A store which records every operation, and optionally fails to apply changes.
+    struct RecordingStore {
+        fail: bool,
+        log: Mutex<Vec<String>>,
+        tokens: Mutex<Vec<RefreshToken>>,
+    }
+
+    impl RecordingStore {
+        fn new(fail: bool) -> Self {
+            Self {
+                fail,
+                log: Mutex::new(vec![]),
+                tokens: Mutex::new(vec![]),
+            }
+        }
+
+        /// Store a new refresh token, returning its value as it would be stored in the cookie.
+        fn add_token(&self, secret: &str) -> String {
+            let token = RefreshToken::new("p1", secret).unwrap();
+            let value = format!("{}:{}", token.id(), secret);
+            self.tokens.lock().unwrap().push(token);
+            value
+        }
+
+        fn log(&self) -> Vec<String> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    impl CredentialStore for RecordingStore {
+        async fn apply(&self, player_id: &str, change: &CredentialChange<'_>) -> DBoResult<()> {
+            if self.fail {
+                return Err(DBoError::InternalConflict);
+            }
+            let name = match change {
+                CredentialChange::Username { .. } => "username",
+                CredentialChange::Password(_) => "password",
+                CredentialChange::ProposedEmail => "email",
+            };
+            self.log.lock().unwrap().push(format!("{name} {player_id}"));
+            Ok(())
+        }
+    }
+
+    impl RefreshTokenStore for RecordingStore {
+        async fn find_token(&self, token_id: &str) -> DBoResult<Option<RefreshToken>> {
+            self.log.lock().unwrap().push(format!("find {token_id}"));
+            let tokens = self.tokens.lock().unwrap();
+            Ok(tokens.iter().find(|t| t.id() == token_id).cloned())
+        }
+
+        async fn revoke_token(&self, token_id: &str) -> DBoResult<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("revoke token {token_id}"));
+            let mut tokens = self.tokens.lock().unwrap();
+            let token = match tokens.iter_mut().find(|t| t.id() == token_id) {
+                Some(t) => t,
+                None => return Err(DBoError::missing_document("refresh-tokens")),
+            };
+            let mut document = bson::to_document(token).unwrap();
+            document.insert("revoked", true);
+            *token = bson::from_document(document).unwrap();
+            Ok(())
+        }
+
+        async fn revoke_player_tokens(&self, player_id: &str) -> DBoResult<()> {
+            self.log.lock().unwrap().push(format!("revoke {player_id}"));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credential_changes_revoke_refresh_tokens() {
+        let changes = [
+            CredentialChange::Username {
+                value: "NewName",
+                allow_reserved: false,
+            },
+            CredentialChange::Password("N3w-Passw0rd!"),
+            CredentialChange::ProposedEmail,
+        ];
+
+        for (change, name) in changes.into_iter().zip(["username", "password", "email"]) {
+            let store = RecordingStore::new(false);
+            change_credentials(&store, &store, "p1", change)
+                .await
+                .unwrap();
+            assert_eq!(
+                store.log(),
+                vec![format!("{name} p1"), String::from("revoke p1")]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_credential_change_keeps_refresh_tokens() {
+        let store = RecordingStore::new(true);
+
+        let result =
+            change_credentials(&store, &store, "p1", CredentialChange::ProposedEmail).await;
+
+        assert!(matches!(result, Err(DBoError::InternalConflict)));
+        assert!(store.log().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_logout_parses_token_value() {
+        let store = RecordingStore::new(false);
+        let value = store.add_token("secret");
+        let (id, _) = value.split_once(':').unwrap();
+
+        // Values which cannot be parsed never reach the store.
+        for malformed in ["", "no-separator", ":secret", &format!("{id}:")] {
+            end_session(&store, malformed).await.unwrap();
+        }
+        assert!(store.log().is_empty());
+
+        // A wrong secret is ignored.
+        end_session(&store, &format!("{id}:wrong")).await.unwrap();
+        assert_eq!(store.log(), vec![format!("find {id}")]);
+
+        end_session(&store, &value).await.unwrap();
+        assert_eq!(
+            store.log()[1..],
+            [format!("find {id}"), format!("revoke token {id}")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logout_is_idempotent() {
+        let store = RecordingStore::new(false);
+        let value = store.add_token("secret");
+        let (id, _) = value.split_once(':').unwrap();
+
+        end_session(&store, &value).await.unwrap();
+        end_session(&store, &value).await.unwrap();
+        end_session(&store, "unknown:secret").await.unwrap();
+
+        // The token is only revoked once, and logging out again or with an unknown token succeeds.
+        assert_eq!(
+            store.log(),
+            vec![
+                format!("find {id}"),
+                format!("revoke token {id}"),
+                format!("find {id}"),
+                String::from("find unknown"),
+            ]
+        );
+    }
 }
@@ -1,15 +1,39 @@
+use chrono::{DateTime, Utc};
+
 pub struct LoginTokenInfo {
     pub access_token: String,
     pub refresh_token_id: String,
     pub refresh_token_secret: String,
+    pub security_notice: Option<SecurityNotice>,
 }
 
 impl LoginTokenInfo {
-    pub fn new(access_token: &str, refresh_token_id: &str, refresh_token_secret: &str) -> Self {
+    pub fn new(
+        access_token: &str,
+        refresh_token_id: &str,
+        refresh_token_secret: &str,
+        security_notice: Option<SecurityNotice>,
+    ) -> Self {
         Self {
             access_token: String::from(access_token),
             refresh_token_id: String::from(refresh_token_id),
             refresh_token_secret: String::from(refresh_token_secret),
+            security_notice,
+        }
+    }
+}
+
+/// Tells a player who has just logged in that their account was recently locked.
+pub struct SecurityNotice {
+    pub lockout_cleared_at: DateTime<Utc>,
+    pub failed_logins: u8,
+}
+
+impl SecurityNotice {
+    pub fn new(lockout_cleared_at: DateTime<Utc>, failed_logins: u8) -> Self {
+        Self {
+            lockout_cleared_at,
+            failed_logins,
         }
     }
 }
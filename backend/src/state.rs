@@ -0,0 +1,57 @@
+//! This module defines the state shared by every handler of the axum router. Alongside the
+//! repositories, it holds the few settings which admins may change at runtime.
+//!
+//! Runtime settings are only held in memory; they are not shared between instances of the
+//! application, and are reset to their environment defaults whenever the server restarts.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use axum::extract::FromRef;
+
+use crate::adapters::repositories::Repositories;
+
+/// The state of the axum router. Handlers which only need the repositories may extract
+/// `State<Repositories>` directly.
+#[derive(Clone)]
+pub struct AppState {
+    /// The repositories used by the handlers.
+    repos: Repositories,
+    /// Whether new player accounts may currently be registered.
+    registration_open: Arc<AtomicBool>,
+}
+
+impl AppState {
+    /// Construct a new AppState
+    ///
+    /// ### Arguments
+    /// - `repos`: The repositories used by the handlers
+    /// - `registration_open`: Whether new player accounts may initially be registered
+    pub fn new(repos: Repositories, registration_open: bool) -> Self {
+        Self {
+            repos,
+            registration_open: Arc::new(AtomicBool::new(registration_open)),
+        }
+    }
+
+    /// Determine whether new player accounts may currently be registered.
+    pub fn registration_open(&self) -> bool {
+        self.registration_open.load(Ordering::Relaxed)
+    }
+
+    /// Open or close the registration of new player accounts.
+    ///
+    /// ### Arguments
+    /// - `open`: Whether new player accounts may be registered
+    pub fn set_registration_open(&self, open: bool) {
+        self.registration_open.store(open, Ordering::Relaxed);
+    }
+}
+
+impl FromRef<AppState> for Repositories {
+    fn from_ref(state: &AppState) -> Self {
+        state.repos.clone()
+    }
+}